@@ -0,0 +1,104 @@
+//! The `odyssey import-op-state` command.
+
+use clap::Args;
+use eyre::WrapErr;
+use odyssey_node::{chainspec::OdysseyChainSpecParser, node::OdysseyNode};
+use reth_cli_commands::common::{AccessRights, Environment, EnvironmentArgs};
+use reth_db_common::init::init_from_state_dump;
+use reth_provider::{BlockNumReader, HeaderProvider, StateProviderFactory, StateRootProvider};
+use reth_trie_common::HashedPostState;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::PathBuf,
+};
+
+/// The percentage of the state dump between progress reports.
+const PROGRESS_STEP: u64 = 5;
+
+/// Imports the trusted state snapshot of a chain whose genesis has an empty alloc, like Odyssey
+/// mainnet, at its first block.
+///
+/// The state root of the snapshot is verified against the state root of the first block before
+/// the import is committed. An interrupted import leaves the database untouched, so it can simply
+/// be run again, and a completed import is detected and skipped.
+#[derive(Debug, Args)]
+pub(crate) struct ImportOpStateCommand {
+    #[command(flatten)]
+    env: EnvironmentArgs<OdysseyChainSpecParser>,
+    /// The JSONL state dump to import.
+    ///
+    /// The first line is the state root as `{"root": "0x..."}`, followed by one account per
+    /// line, like the dumps of `geth dump`.
+    #[arg(value_name = "STATE_DUMP_FILE")]
+    state: PathBuf,
+}
+
+impl ImportOpStateCommand {
+    /// Imports the state, and fails if its state root does not match the first block.
+    pub(crate) fn execute(self) -> eyre::Result<()> {
+        let Environment { config, provider_factory, .. } =
+            self.env.init::<OdysseyNode>(AccessRights::RW)?;
+
+        let tip = provider_factory.best_block_number()?;
+        if tip != 0 {
+            eyre::bail!(
+                "the state can only be imported at the first block, the database is at {tip}"
+            );
+        }
+        let expected_root = provider_factory
+            .header_by_number(0)?
+            .ok_or_else(|| eyre::eyre!("first block not found"))?
+            .state_root;
+        if provider_factory.latest()?.state_root(HashedPostState::default())? == expected_root {
+            println!("State with root {expected_root} is already imported");
+            return Ok(());
+        }
+
+        let file = File::open(&self.state)
+            .wrap_err_with(|| format!("failed to open {}", self.state.display()))?;
+        let size = file.metadata()?.len();
+        println!("Importing state with root {expected_root} from {}", self.state.display());
+
+        let provider_rw = provider_factory.provider_rw()?;
+        let hash = init_from_state_dump(
+            BufReader::new(ProgressReader::new(file, size)),
+            &provider_rw,
+            config.stages.etl,
+        )?;
+        provider_rw.commit()?;
+        println!("Imported state of block {hash}");
+        Ok(())
+    }
+}
+
+/// Reads a state dump, reporting the progress every [`PROGRESS_STEP`] percent.
+#[derive(Debug)]
+struct ProgressReader<R> {
+    inner: R,
+    /// The number of bytes read so far.
+    read: u64,
+    /// The size of the dump in bytes.
+    size: u64,
+    /// The percentage that was last reported.
+    reported: u64,
+}
+
+impl<R> ProgressReader<R> {
+    const fn new(inner: R, size: u64) -> Self {
+        Self { inner, read: 0, size, reported: 0 }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read += read as u64;
+        let percent = (self.read * 100).checked_div(self.size).unwrap_or(100).min(100);
+        if percent >= self.reported + PROGRESS_STEP {
+            self.reported = percent - percent % PROGRESS_STEP;
+            println!("Read {}% of the state dump", self.reported);
+        }
+        Ok(read)
+    }
+}
@@ -23,27 +23,81 @@
 //! - `min-debug-logs`: Disables all logs below `debug` level.
 //! - `min-trace-logs`: Disables all logs below `trace` level.
 
-use alloy_network::{Ethereum, EthereumWallet, NetworkWallet};
 use alloy_signer_local::PrivateKeySigner;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Subcommand};
 use eyre::Context;
+use jsonrpsee::Methods;
+use odyssey_common::SystemClock;
 use odyssey_node::{
+    activity::DelegatedActivityFeed,
+    args::{OdysseyArgs, WalletMode},
     broadcaster::periodic_broadcaster,
-    chainspec::OdysseyChainSpecParser,
-    delayed_resolve::{DelayedResolver, MAX_DELAY_INTO_SLOT},
+    chainspec::{chain_parameters, withdrawal_contract, OdysseyChainSpecParser},
+    config::OdysseyConfig,
+    delayed_resolve::DelayedResolver,
+    deposits::DepositIndex,
+    events::{event_stream, EventCheckpoint, WebhookSink, EVENT_STREAM_EXEX_ID},
+    extra_data::ExperimentTag,
     forwarder::forward_raw_transactions,
+    heads::HeadNotifier,
+    mine::{DevMiner, DevMinerApiServer},
     node::OdysseyNode,
-    rpc::{EthApiExt, EthApiOverrideServer},
+    proof_history::{StateHistory, WithdrawalProofHistory},
+    reorgs::{ReorgJournal, DEFAULT_REORG_JOURNAL_SIZE},
+    rpc::{EthApiExt, OdysseyApiExt, OdysseyApiServer},
+    self_check::{
+        check_forks, check_sequencer, check_sponsor, check_withdrawal_contract, SelfCheckReport,
+    },
 };
-use odyssey_wallet::{OdysseyWallet, OdysseyWalletApiServer, RethUpstream};
-use odyssey_walltime::{OdysseyWallTime, OdysseyWallTimeRpcApiServer};
+use odyssey_wallet::{
+    layer_methods, validated_methods, AccessControl, BudgetConfig, DelegationCacheConfig,
+    DeterministicConfig, ExperimentGateLayer, ExperimentGates, LoadShedConfig, LoadShedLayer,
+    MethodGate, OdysseyWallet, OdysseyWalletAdmin, OdysseyWalletAdminApiServer,
+    OdysseyWalletApiServer, OdysseyWalletConfig, OdysseyWalletForwarder, RequestValidationLayer,
+    RethUpstream, RpcDebugLogConfig, SelectorRule, WalletServerConfig, WildcardAuthorizationPolicy,
+    DEFAULT_ACCESS_LISTS_RELOAD_INTERVAL, DEFAULT_REDACTED_FIELDS,
+};
+use odyssey_walltime::{OdysseyWallTime, OdysseyWallTimeRpcApiServer, WallTimeConfig};
 use reth_node_builder::{engine_tree_config::TreeConfig, EngineNodeLauncher, NodeComponents};
-use reth_optimism_cli::Cli;
-use reth_optimism_node::{args::RollupArgs, node::OpAddOnsBuilder};
-use reth_provider::{providers::BlockchainProvider2, CanonStateSubscriptions};
-use std::time::Duration;
+use reth_optimism_cli::{commands::Commands, Cli};
+use reth_optimism_node::node::OpAddOnsBuilder;
+use reth_provider::{
+    providers::BlockchainProvider2, BlockReaderIdExt, CanonStateSubscriptions, StateProviderFactory,
+};
+use reth_rpc_eth_api::EthApiServer;
+use reth_rpc_server_types::RethRpcModule;
+use std::{net::SocketAddr, sync::Arc};
 use tracing::{info, warn};
 
+mod import;
+use import::ImportOpStateCommand;
+
+mod replay;
+use replay::ReplayCommand;
+
+mod vectors;
+use vectors::Gen7702VectorsCommand;
+
+/// Commands of the `odyssey` binary that are not commands of the reth CLI.
+#[derive(Debug, Subcommand)]
+enum OdysseyCommands {
+    Replay(ReplayCommand),
+    ImportOpState(ImportOpStateCommand),
+    #[command(name = "gen-7702-vectors")]
+    Gen7702Vectors(Gen7702VectorsCommand),
+}
+
+impl OdysseyCommands {
+    /// Executes the command.
+    fn execute(self) -> eyre::Result<()> {
+        match self {
+            Self::Replay(command) => command.execute(),
+            Self::ImportOpState(command) => command.execute(),
+            Self::Gen7702Vectors(command) => command.execute(),
+        }
+    }
+}
+
 #[global_allocator]
 static ALLOC: reth_cli_util::allocator::Allocator = reth_cli_util::allocator::new_allocator();
 
@@ -56,116 +110,464 @@ fn main() {
         std::env::set_var("RUST_BACKTRACE", "1");
     }
 
-    if let Err(err) =
-        Cli::<OdysseyChainSpecParser, RollupArgs>::parse().run(|builder, rollup_args| async move {
-            let wallet = sponsor()?;
-            let address = wallet
-                .as_ref()
-                .map(<EthereumWallet as NetworkWallet<Ethereum>>::default_signer_address);
-
-            let handle = builder
-                .with_types_and_provider::<OdysseyNode, BlockchainProvider2<_>>()
-                .with_components(OdysseyNode::components(&rollup_args))
-                .with_add_ons(
-                    OpAddOnsBuilder::default().with_sequencer(rollup_args.sequencer_http).build(),
-                )
-                .on_component_initialized(move |ctx| {
-                    if let Some(address) = address {
-                        ctx.task_executor.spawn(async move {
-                            periodic_broadcaster(
-                                address,
-                                ctx.components.pool(),
-                                ctx.components
-                                    .network
-                                    .transactions_handle()
-                                    .await
-                                    .expect("transactions_handle should be initialized"),
-                            )
-                            .await
-                        });
+    // the Odyssey commands are registered next to the commands of the reth CLI
+    let matches =
+        OdysseyCommands::augment_subcommands(Cli::<OdysseyChainSpecParser, OdysseyArgs>::command())
+            .get_matches();
+    if matches.subcommand_name().is_some_and(OdysseyCommands::has_subcommand) {
+        let command = OdysseyCommands::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+        if let Err(err) = command.execute() {
+            eprintln!("Error: {err:?}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut cli = Cli::<OdysseyChainSpecParser, OdysseyArgs>::from_arg_matches(&matches)
+        .unwrap_or_else(|err| err.exit());
+
+    // apply the chain spec overrides before the node is configured with the chain spec
+    if let Commands::Node(command) = &mut cli.command {
+        let chain_spec = Arc::make_mut(&mut command.chain);
+        if let Err(err) = command.ext.dev.apply(chain_spec, command.dev.dev) {
+            eprintln!("Error: {err:?}");
+            std::process::exit(1);
+        }
+        for fork_override in &command.ext.fork_overrides {
+            fork_override.apply(chain_spec);
+        }
+
+        // tag built blocks with the experiments scheduled in the final chain spec
+        if command.ext.experiment_tag {
+            match ExperimentTag::new(chain_spec).encode() {
+                Ok(tag) => command.builder.extradata = tag,
+                Err(err) => {
+                    eprintln!("Error: {err:?}");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    if let Err(err) = cli.run(|builder, args| async move {
+        let OdysseyArgs {
+            config,
+            rollup: rollup_args,
+            rpc: rpc_args,
+            wallet: wallet_args,
+            events: events_args,
+            ..
+        } = args;
+        let mut config = OdysseyConfig::load(config.as_deref())?;
+        config.apply_chain_parameters(&chain_parameters(&builder.config().chain)?);
+        let wallet = sponsor(&config)?;
+        let address = wallet.as_ref().map(PrivateKeySigner::address);
+        let broadcast_interval = config.broadcaster.interval();
+
+        // run the self-checks that do not need the database, the remaining checks run once the
+        // database is available
+        let mut report = SelfCheckReport::default();
+        check_forks(&mut report, &builder.config().chain);
+        check_sequencer(
+            &mut report,
+            rollup_args.sequencer_http.as_deref(),
+            builder.config().chain.chain().id(),
+        )
+        .await;
+
+        let head_notifier = (!events_args.head_webhook_urls.is_empty()).then(|| {
+            HeadNotifier::new(
+                events_args.head_webhook_urls.clone(),
+                events_args.head_webhook_secret.clone().map(String::into_bytes),
+            )
+        });
+
+        let handle = builder
+            .with_types_and_provider::<OdysseyNode, BlockchainProvider2<_>>()
+            .with_components(OdysseyNode::components(&rollup_args))
+            .with_add_ons(
+                OpAddOnsBuilder::default().with_sequencer(rollup_args.sequencer_http).build(),
+            )
+            .on_component_initialized(move |ctx| {
+                if let Some(address) = address {
+                    ctx.task_executor.spawn(async move {
+                        periodic_broadcaster(
+                            address,
+                            ctx.components.pool(),
+                            ctx.components
+                                .network
+                                .transactions_handle()
+                                .await
+                                .expect("transactions_handle should be initialized"),
+                            broadcast_interval,
+                            Arc::new(SystemClock),
+                        )
+                        .await
+                    });
+                }
+
+                Ok(())
+            })
+            .install_exex_if(
+                events_args.webhook_url.is_some(),
+                EVENT_STREAM_EXEX_ID,
+                move |ctx| async move {
+                    let url = events_args.webhook_url.unwrap_or_default();
+                    info!(target: "reth::cli", %url, "Publishing canonical chain events");
+                    let checkpoint = EventCheckpoint::new(
+                        ctx.config.datadir().data_dir().join("events-checkpoint.json"),
+                    );
+                    Ok(event_stream(ctx, WebhookSink::new(url), checkpoint, address))
+                },
+            )
+            .extend_rpc_modules(move |ctx| {
+                // finish the self-checks against the latest state, before serving any requests
+                let state = ctx.provider().latest()?;
+                let balance = address
+                    .map(|address| state.account_balance(address))
+                    .transpose()?
+                    .flatten()
+                    .unwrap_or_default();
+                check_sponsor(&mut report, address, balance);
+                let withdrawal_contract = withdrawal_contract(&ctx.config().chain)?;
+                let has_code =
+                    state.account_code(withdrawal_contract)?.is_some_and(|code| !code.is_empty());
+                drop(state);
+                let eth_overrides = rpc_args.eth_overrides
+                    && config.rpc.eth_overrides
+                    && check_withdrawal_contract(&mut report, withdrawal_contract, has_code);
+                report.finish()?;
+
+                // shed calls to the odyssey extensions by priority under overload, sharing the
+                // capacity between all namespaces
+                let shed = LoadShedLayer::new(LoadShedConfig {
+                    max_in_flight: rpc_args.shed_max_in_flight,
+                    max_latency: rpc_args.shed_max_latency,
+                    send_deadline: rpc_args.shed_send_deadline,
+                });
+                // reject calls to methods of experiments that are not active
+                let experiment_gates = rpc_args
+                    .experiment_gates
+                    .iter()
+                    .map(|gate| gate.parse::<MethodGate>())
+                    .collect::<Result<ExperimentGates, _>>()
+                    .map_err(|err| eyre::eyre!(err))?;
+                let gates = ExperimentGateLayer::new(experiment_gates.clone());
+                let layer = |methods: Methods| {
+                    layer_methods(layer_methods(methods, shed.clone()), gates.clone())
+                };
+
+                // override eth namespace
+                if eth_overrides {
+                    let prune_modes = ctx
+                        .config()
+                        .prune_config()
+                        .map(|config| config.segments)
+                        .unwrap_or_default();
+                    let mut eth_api_ext =
+                        EthApiExt::new(ctx.registry.eth_api().clone(), withdrawal_contract)
+                            .with_state_history(StateHistory::new(&prune_modes));
+                    if rpc_args.withdrawal_proof_history > 0 {
+                        info!(
+                            target: "reth::cli",
+                            blocks = rpc_args.withdrawal_proof_history,
+                            "Retaining withdrawal proof history"
+                        );
+                        let history = WithdrawalProofHistory::new(
+                            withdrawal_contract,
+                            rpc_args.withdrawal_proof_history,
+                        );
+                        history.clone().spawn(ctx.provider().canonical_state_stream());
+                        eth_api_ext = eth_api_ext.with_withdrawal_history(history);
                     }
+                    eth_api_ext.install(ctx.modules)?;
+                }
+
+                // validate the transaction requests of the eth methods that take one
+                let eth_api = EthApiServer::into_rpc(ctx.registry.eth_api().clone());
+                let eth_methods = layer(validated_methods(eth_api));
+                for method in eth_methods.method_names() {
+                    ctx.modules.remove_method_from_configured(method);
+                }
+                ctx.modules.merge_if_module_configured(RethRpcModule::Eth, eth_methods)?;
+
+                if let Some(head_notifier) = head_notifier {
+                    info!(target: "reth::cli", "Posting canonical heads to webhooks");
+                    head_notifier.spawn(ctx.provider().canonical_state_stream());
+                }
 
-                    Ok(())
-                })
-                .extend_rpc_modules(move |ctx| {
-                    // override eth namespace
-                    ctx.modules.replace_configured(
-                        EthApiExt::new(ctx.registry.eth_api().clone()).into_rpc(),
+                // register odyssey namespace
+                if config.rpc.odyssey {
+                    let deposits = DepositIndex::default();
+                    deposits.clone().spawn(ctx.provider().canonical_state_stream());
+                    let serves_wallet = wallet_args.mode != WalletMode::Disabled
+                        && (wallet_args.forward_url.is_some() || wallet.is_some());
+                    let sponsors: Vec<_> =
+                        address.into_iter().chain(rpc_args.sponsor_addresses.clone()).collect();
+                    let reorgs = ReorgJournal::new(DEFAULT_REORG_JOURNAL_SIZE, sponsors.clone());
+                    reorgs.clone().spawn(ctx.provider().canonical_state_stream());
+                    let activity = DelegatedActivityFeed::default();
+                    activity
+                        .clone()
+                        .spawn(ctx.provider().clone(), ctx.provider().canonical_state_stream());
+                    let odyssey_api = OdysseyApiExt::new(
+                        ctx.registry.eth_api().clone(),
+                        deposits,
+                        ctx.config().chain.clone(),
+                    )
+                    .with_wallet(serves_wallet)
+                    .with_payload_config(config.payload.clone())
+                    .with_sponsors(sponsors)
+                    .with_reorg_journal(reorgs)
+                    .with_withdrawal_contract(withdrawal_contract)
+                    .with_delegated_activity(activity)
+                    .into_rpc();
+                    let odyssey_api = layer_methods(odyssey_api, RequestValidationLayer);
+                    ctx.modules.merge_configured(layer(odyssey_api))?;
+                }
+
+                // mine blocks on demand on development chains
+                if ctx.config().dev.dev {
+                    let miner = DevMiner::new(
+                        ctx.provider().clone(),
+                        ctx.config().chain.clone(),
+                        ctx.auth_module.module_mut().clone(),
+                        ctx.node().payload_builder().clone(),
+                    );
+                    ctx.modules.merge_configured(miner.into_rpc())?;
+                    info!(target: "reth::cli", "Mining blocks on demand with odyssey_mine");
+                }
+
+                // register odyssey wallet namespace
+                let wallet_rpc: Option<Methods> = if wallet_args.mode == WalletMode::Disabled {
+                    None
+                } else if let Some(url) = &wallet_args.forward_url {
+                    // forward the namespace, so there is only a single signer
+                    let forwarder = OdysseyWalletForwarder::new(
+                        url,
+                        wallet_args.forward_timeout,
+                        wallet_args.forward_retries,
                     )?;
+                    info!(target: "reth::cli", %url, "Forwarding wallet namespace");
+                    Some(forwarder.into_rpc().into())
+                } else if let Some(wallet) = wallet {
+                    let deterministic = if wallet_args.deterministic {
+                        let nonce = ctx
+                            .provider()
+                            .latest()?
+                            .account_nonce(wallet.address())?
+                            .unwrap_or_default();
+                        warn!(
+                            target: "reth::cli",
+                            nonce,
+                            "Sponsoring transactions deterministically"
+                        );
+                        Some(DeterministicConfig { nonce, ..Default::default() })
+                    } else {
+                        None
+                    };
+                    let access = match &wallet_args.access_lists {
+                        Some(path) => {
+                            let access = AccessControl::load(path)?;
+                            access.spawn_reload(DEFAULT_ACCESS_LISTS_RELOAD_INTERVAL);
+                            info!(target: "reth::cli", ?path, "Loaded wallet access lists");
+                            access
+                        }
+                        None => AccessControl::default(),
+                    };
+                    let mut wallet_config = OdysseyWalletConfig {
+                        deterministic,
+                        legacy_compatibility: wallet_args
+                            .legacy_compatibility
+                            .parse()
+                            .map_err(|err: String| eyre::eyre!(err))?,
+                        selectors: wallet_args
+                            .allowed_selectors
+                            .iter()
+                            .map(|rule| rule.parse::<SelectorRule>())
+                            .collect::<Result<_, _>>()
+                            .map_err(|err| eyre::eyre!(err))?,
+                        budget: wallet_args
+                            .max_sponsored_gas_per_block
+                            .or(config.wallet.max_sponsored_gas_per_block)
+                            .map(|max_gas_per_block| BudgetConfig {
+                                max_gas_per_block,
+                                ..Default::default()
+                            }),
+                        access: access.clone(),
+                        estimate_block: wallet_args.estimate_block,
+                        wildcard_authorizations: WildcardAuthorizationPolicy::new(
+                            wallet_args
+                                .wildcard_authorizations
+                                .parse()
+                                .map_err(|err: String| eyre::eyre!(err))?,
+                            wallet_args.wildcard_implementations.iter().copied(),
+                        ),
+                        tracked_implementations: wallet_args
+                            .tracked_implementations
+                            .iter()
+                            .copied()
+                            .collect(),
+                        delegation_cache: wallet_args
+                            .delegation_cache
+                            .then(DelegationCacheConfig::default),
+                        ..Default::default()
+                    };
+                    if let Some(max_pending) = config.wallet.max_pending_per_account {
+                        wallet_config.max_pending_per_account = max_pending;
+                    }
+                    if let Some(max_cost) = config.wallet.max_sponsored_cost {
+                        wallet_config.max_sponsored_cost = max_cost;
+                    }
+                    let wallet = OdysseyWallet::with_config(
+                        RethUpstream::new(
+                            ctx.provider().clone(),
+                            ctx.registry.eth_api().clone(),
+                            wallet,
+                        ),
+                        ctx.config().chain.chain().id(),
+                        wallet_config,
+                    );
 
-                    // register odyssey wallet namespace
-                    if let Some(wallet) = wallet {
-                        ctx.modules.merge_configured(
-                            OdysseyWallet::new(
-                                RethUpstream::new(
-                                    ctx.provider().clone(),
-                                    ctx.registry.eth_api().clone(),
-                                    wallet,
-                                ),
-                                ctx.config().chain.chain().id(),
-                            )
+                    // only expose the wallet admin namespace if the admin namespace is enabled
+                    ctx.modules.merge_if_module_configured(
+                        RethRpcModule::Admin,
+                        OdysseyWalletAdmin::new(wallet.reputation().clone())
+                            .with_access_control(access)
                             .into_rpc(),
-                        )?;
+                    )?;
+                    wallet
+                        .inclusion_tracker()
+                        .clone()
+                        .spawn(ctx.provider().canonical_state_stream());
+                    if let Some(budget) = wallet.budget() {
+                        budget.clone().spawn(ctx.provider().canonical_state_stream());
+                    }
+                    if let Some(cache) = wallet.delegation_cache() {
+                        cache.clone().spawn(ctx.provider().canonical_state_stream());
                     }
+                    wallet.spawn_expiry();
+                    Some(wallet.into_rpc().into())
+                } else {
+                    None
+                };
 
-                    let walltime = OdysseyWallTime::spawn(ctx.provider().canonical_state_stream());
-                    ctx.modules.merge_configured(walltime.into_rpc())?;
-                    info!(target: "reth::cli", "Walltime configured");
+                if let Some(wallet_rpc) = wallet_rpc {
+                    if wallet_args.mode == WalletMode::External {
+                        let config = WalletServerConfig {
+                            addr: SocketAddr::new(wallet_args.http_addr, wallet_args.http_port),
+                            cors_domains: wallet_args.http_cors_domains,
+                            load_shed: LoadShedConfig {
+                                max_in_flight: wallet_args.http_max_in_flight,
+                                max_latency: wallet_args.http_max_latency,
+                                send_deadline: wallet_args.http_send_deadline,
+                            },
+                            debug_log: RpcDebugLogConfig {
+                                namespaces: wallet_args.debug_log_namespaces,
+                                redacted_fields: DEFAULT_REDACTED_FIELDS
+                                    .iter()
+                                    .map(|field| field.to_string())
+                                    .chain(wallet_args.debug_log_redacted_fields)
+                                    .collect(),
+                                sample_every: wallet_args.debug_log_sample,
+                            },
+                            experiments: experiment_gates.clone(),
+                            ..Default::default()
+                        };
+                        // bind before spawning, so the node fails to start if the address is taken
+                        let handle =
+                            config.start(wallet_rpc).wrap_err("failed to start wallet server")?;
+                        ctx.node()
+                            .task_executor()
+                            .spawn_critical("wallet server", handle.stopped());
+                    } else {
+                        let wallet_rpc = layer_methods(wallet_rpc, RequestValidationLayer);
+                        ctx.modules.merge_configured(layer(wallet_rpc))?;
+                    }
+                }
 
-                    // wrap the getPayloadV3 method in a delay
-                    let engine_module = ctx.auth_module.module_mut().clone();
-                    let delay_into_slot = std::env::var("MAX_PAYLOAD_DELAY")
-                        .ok()
-                        .and_then(|val| val.parse::<u64>().map(Duration::from_millis).ok())
-                        .unwrap_or(MAX_DELAY_INTO_SLOT);
-
-                    let delayed_payload = DelayedResolver::new(engine_module, delay_into_slot);
-                    delayed_payload.clone().spawn(ctx.provider().canonical_state_stream());
-                    ctx.auth_module.replace_auth_methods(delayed_payload.into_rpc_module())?;
-                    info!(target: "reth::cli", "Configured payload delay");
-
-                    Ok(())
-                })
-                .launch_with_fn(|builder| {
-                    let engine_tree_config = TreeConfig::default()
-                        .with_persistence_threshold(rollup_args.persistence_threshold)
-                        .with_memory_block_buffer_target(rollup_args.memory_block_buffer_target);
-                    let launcher = EngineNodeLauncher::new(
-                        builder.task_executor().clone(),
-                        builder.config().datadir(),
-                        engine_tree_config,
+                if rpc_args.walltime && config.rpc.walltime {
+                    // initialize the walltime from the latest block, so it is available after
+                    // a restart before the next block arrives
+                    let walltime = OdysseyWallTime::spawn_with_config(
+                        ctx.provider().canonical_state_stream(),
+                        WallTimeConfig {
+                            latest_block_timestamp: ctx
+                                .provider()
+                                .latest_header()
+                                .ok()
+                                .flatten()
+                                .map(|header| header.timestamp),
+                            persistence_path: Some(
+                                ctx.config().datadir().data_dir().join("walltime.json"),
+                            ),
+                            dependencies: rpc_args
+                                .walltime_dependencies
+                                .iter()
+                                .map(|dependency| dependency.parse())
+                                .collect::<Result<_, _>>()
+                                .map_err(|err: String| eyre::eyre!(err))?,
+                            ..Default::default()
+                        },
                     );
-                    builder.launch_with(launcher)
-                })
-                .await?;
+                    ctx.modules.merge_configured(layer(walltime.into_rpc().into()))?;
+                    info!(target: "reth::cli", "Walltime configured");
+                }
 
-            // spawn raw transaction forwarding
-            let txhandle = handle.node.network.transactions_handle().await.unwrap();
-            let raw_txs =
-                handle.node.add_ons_handle.eth_api().eth_api().subscribe_to_raw_transactions();
-            handle.node.task_executor.spawn(Box::pin(forward_raw_transactions(txhandle, raw_txs)));
+                // wrap the getPayloadV3 method in a delay
+                let engine_module = ctx.auth_module.module_mut().clone();
+                let delayed_payload =
+                    DelayedResolver::new(engine_module, config.payload.max_delay())
+                        .with_payload_progress(
+                            Arc::new(ctx.node().payload_builder().clone()),
+                            config.payload.settle_time(),
+                        );
+                delayed_payload.clone().spawn(ctx.provider().canonical_state_stream());
+                // engine API calls pass the load shedding, but are never shed
+                ctx.auth_module
+                    .replace_auth_methods(layer_methods(delayed_payload.into_rpc_module(), shed))?;
+                info!(target: "reth::cli", "Configured payload delay");
 
-            handle.wait_for_node_exit().await
-        })
-    {
+                Ok(())
+            })
+            .launch_with_fn(|builder| {
+                let engine_tree_config = TreeConfig::default()
+                    .with_persistence_threshold(rollup_args.persistence_threshold)
+                    .with_memory_block_buffer_target(rollup_args.memory_block_buffer_target);
+                let launcher = EngineNodeLauncher::new(
+                    builder.task_executor().clone(),
+                    builder.config().datadir(),
+                    engine_tree_config,
+                );
+                builder.launch_with(launcher)
+            })
+            .await?;
+
+        // spawn raw transaction forwarding
+        let txhandle = handle.node.network.transactions_handle().await.unwrap();
+        let raw_txs =
+            handle.node.add_ons_handle.eth_api().eth_api().subscribe_to_raw_transactions();
+        handle.node.task_executor.spawn(Box::pin(forward_raw_transactions(txhandle, raw_txs)));
+
+        handle.wait_for_node_exit().await
+    }) {
         eprintln!("Error: {err:?}");
         std::process::exit(1);
     }
 }
 
-/// Returns a [`EthereumWallet`] with the sponsor private key.
-fn sponsor() -> eyre::Result<Option<EthereumWallet>> {
-    std::env::var("EXP1_SK")
-        .ok()
+/// Returns a [`PrivateKeySigner`] with the sponsor private key, read from `EXP1_SK` or the
+/// configured key file.
+fn sponsor(config: &OdysseyConfig) -> eyre::Result<Option<PrivateKeySigner>> {
+    config
+        .sponsor
+        .secret_key()?
         .or_else(|| {
             warn!(target: "reth::cli", "EXP0001 wallet not configured");
             None
         })
         .map(|sk| {
-            let wallet = sk
-                .parse::<PrivateKeySigner>()
-                .map(EthereumWallet::from)
-                .wrap_err("Invalid EXP0001 secret key.")?;
+            let wallet = sk.parse::<PrivateKeySigner>().wrap_err("Invalid EXP0001 secret key.")?;
             info!(target: "reth::cli", "EXP0001 wallet configured");
             Ok::<_, eyre::Report>(wallet)
         })
@@ -0,0 +1,60 @@
+//! The `odyssey replay` command.
+
+use clap::Args;
+use odyssey_node::{
+    chainspec::OdysseyChainSpecParser, forks::ForkOverride, node::OdysseyNode,
+    replay::BlockReplayer,
+};
+use reth_cli_commands::common::{AccessRights, Environment, EnvironmentArgs};
+use reth_provider::BlockNumReader;
+use std::sync::Arc;
+
+/// Re-executes a range of stored blocks with the current Odyssey EVM configuration, and reports
+/// the blocks whose gas used, receipts or state root differ from the stored ones.
+#[derive(Debug, Args)]
+pub(crate) struct ReplayCommand {
+    #[command(flatten)]
+    env: EnvironmentArgs<OdysseyChainSpecParser>,
+    /// The first block to replay.
+    #[arg(long, value_name = "BLOCK", default_value_t = 1)]
+    from: u64,
+    /// The last block to replay, the latest stored block by default.
+    #[arg(long, value_name = "BLOCK")]
+    to: Option<u64>,
+    /// Overrides the activation timestamp of an Odyssey hardfork, like the `node` command.
+    ///
+    /// Pass the same overrides the node ran with, or blocks after the override diverge.
+    #[arg(long = "override.fork", value_name = "FORK=TIMESTAMP")]
+    fork_overrides: Vec<ForkOverride>,
+}
+
+impl ReplayCommand {
+    /// Replays the blocks, and fails if any block diverged.
+    pub(crate) fn execute(mut self) -> eyre::Result<()> {
+        let chain_spec = Arc::make_mut(&mut self.env.chain);
+        for fork_override in &self.fork_overrides {
+            fork_override.apply(chain_spec);
+        }
+
+        let Environment { provider_factory, .. } =
+            self.env.init::<OdysseyNode>(AccessRights::RO)?;
+        let to = match self.to {
+            Some(to) => to,
+            None => provider_factory.best_block_number()?,
+        };
+        let replayer = BlockReplayer::new(provider_factory, self.env.chain.clone());
+
+        println!("Replaying blocks {} to {to}", self.from);
+        let report = replayer.replay_range(self.from..=to)?;
+        for (number, divergences) in &report.diverged {
+            for divergence in divergences {
+                println!("block {number}: {divergence}");
+            }
+        }
+        if !report.is_ok() {
+            eyre::bail!("{} of {} replayed blocks diverged", report.diverged.len(), report.blocks);
+        }
+        println!("All {} replayed blocks match", report.blocks);
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+//! The `odyssey gen-7702-vectors` command.
+
+use alloy_primitives::{Address, B256};
+use alloy_provider::{Provider, ProviderBuilder};
+use alloy_signer_local::PrivateKeySigner;
+use clap::Args;
+use eyre::WrapErr;
+use odyssey_node::vectors::{
+    TestVector, VectorGenerator, VectorNonces, DEFAULT_MAX_AUTHORIZATIONS,
+};
+use std::{collections::HashMap, path::PathBuf};
+
+/// Generates signed EIP-7702 transactions covering edge cases, with their expected outcomes.
+///
+/// The vectors are written as JSON. With `--rpc-url`, the chain id and the nonces are read from
+/// the node, and the vectors are submitted in order and checked against their expected outcomes.
+#[derive(Debug, Args)]
+pub(crate) struct Gen7702VectorsCommand {
+    /// The private key of the account that sends the vectors.
+    #[arg(long, value_name = "KEY", env = "SENDER_KEY")]
+    sender_key: B256,
+    /// The private key of the account that delegates.
+    #[arg(long, value_name = "KEY", env = "AUTHORITY_KEY")]
+    authority_key: B256,
+    /// The private key of the account the authority delegates to in the delegation chain, a
+    /// random key by default.
+    #[arg(long, value_name = "KEY")]
+    chained_key: Option<B256>,
+    /// The contract the accounts delegate to.
+    #[arg(long, value_name = "ADDRESS")]
+    delegation: Address,
+    /// The chain id of the vectors, read from the node with `--rpc-url`.
+    #[arg(long, value_name = "CHAIN_ID", required_unless_present = "rpc_url")]
+    chain_id: Option<u64>,
+    /// The nonce of the sender before the first vector, read from the node with `--rpc-url`.
+    #[arg(long, value_name = "NONCE")]
+    sender_nonce: Option<u64>,
+    /// The nonce of the authority before the first vector, read from the node with `--rpc-url`.
+    #[arg(long, value_name = "NONCE")]
+    authority_nonce: Option<u64>,
+    /// The maximum fee per gas of the vectors, in wei.
+    #[arg(long, value_name = "WEI", default_value_t = 1_000_000_000)]
+    max_fee_per_gas: u128,
+    /// The maximum priority fee per gas of the vectors, in wei.
+    #[arg(long, value_name = "WEI", default_value_t = 1_000_000)]
+    max_priority_fee_per_gas: u128,
+    /// The number of authorizations in the vector with the longest authorization list.
+    #[arg(long, value_name = "COUNT", default_value_t = DEFAULT_MAX_AUTHORIZATIONS)]
+    max_authorizations: usize,
+    /// The RPC endpoint of a devnet to submit the vectors to.
+    #[arg(long, value_name = "URL")]
+    rpc_url: Option<String>,
+    /// The file the vectors are written to, stdout by default.
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+}
+
+impl Gen7702VectorsCommand {
+    /// Generates the vectors, and submits them if an RPC endpoint is given.
+    pub(crate) fn execute(self) -> eyre::Result<()> {
+        tokio::runtime::Runtime::new()?.block_on(self.run())
+    }
+
+    async fn run(self) -> eyre::Result<()> {
+        let sender = PrivateKeySigner::from_bytes(&self.sender_key)?;
+        let authority = PrivateKeySigner::from_bytes(&self.authority_key)?;
+        let chained = match self.chained_key {
+            Some(key) => PrivateKeySigner::from_bytes(&key)?,
+            None => PrivateKeySigner::random(),
+        };
+
+        let provider = match &self.rpc_url {
+            Some(url) => Some(ProviderBuilder::new().on_http(url.parse()?)),
+            None => None,
+        };
+        let (chain_id, nonces) = match &provider {
+            Some(provider) => (
+                self.chain_id.unwrap_or(provider.get_chain_id().await?),
+                VectorNonces {
+                    sender: match self.sender_nonce {
+                        Some(nonce) => nonce,
+                        None => provider.get_transaction_count(sender.address()).await?,
+                    },
+                    authority: match self.authority_nonce {
+                        Some(nonce) => nonce,
+                        None => provider.get_transaction_count(authority.address()).await?,
+                    },
+                    chained: provider.get_transaction_count(chained.address()).await?,
+                },
+            ),
+            None => (
+                self.chain_id.unwrap_or_default(),
+                VectorNonces {
+                    sender: self.sender_nonce.unwrap_or_default(),
+                    authority: self.authority_nonce.unwrap_or_default(),
+                    chained: 0,
+                },
+            ),
+        };
+
+        let vectors = VectorGenerator::new(chain_id, self.delegation, sender, authority, chained)
+            .with_fees(self.max_fee_per_gas, self.max_priority_fee_per_gas)
+            .with_max_authorizations(self.max_authorizations)
+            .generate(nonces)?;
+        let json = serde_json::to_string_pretty(&vectors)?;
+        match &self.out {
+            Some(out) => std::fs::write(out, json)
+                .wrap_err_with(|| format!("failed to write {}", out.display()))?,
+            None => println!("{json}"),
+        }
+
+        let Some(provider) = provider else { return Ok(()) };
+        let mut failed = 0;
+        for vector in &vectors {
+            let mismatches = submit(&provider, vector).await?;
+            if mismatches.is_empty() {
+                eprintln!("{}: ok", vector.name);
+            } else {
+                failed += 1;
+                for mismatch in mismatches {
+                    eprintln!("{}: {mismatch}", vector.name);
+                }
+            }
+        }
+        if failed > 0 {
+            eyre::bail!("{failed} of {} vectors did not have the expected outcome", vectors.len());
+        }
+        Ok(())
+    }
+}
+
+/// Submits the vector, waits for its receipt, and compares its outcome with the expected outcome.
+async fn submit(provider: &impl Provider, vector: &TestVector) -> eyre::Result<Vec<String>> {
+    let receipt = provider
+        .send_raw_transaction(&vector.raw)
+        .await
+        .wrap_err_with(|| format!("failed to submit {}", vector.name))?
+        .get_receipt()
+        .await?;
+    let mut codes = HashMap::new();
+    for authority in vector.expected.delegations.keys() {
+        codes.insert(*authority, provider.get_code_at(*authority).await?);
+    }
+    Ok(vector.expected.mismatches(receipt.status(), &codes))
+}
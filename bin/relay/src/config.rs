@@ -0,0 +1,197 @@
+//! Validation and printing of the relay configuration.
+
+use crate::Args;
+use alloy_primitives::U256;
+use alloy_provider::{Provider, ProviderBuilder};
+use alloy_rpc_client::RpcClient;
+use clap::{parser::ValueSource, ArgMatches, CommandFactory};
+use odyssey_wallet::WildcardAuthorizationMode;
+
+/// The results of the checks of a configuration.
+#[derive(Debug, Default)]
+struct Checks {
+    /// The number of failed checks.
+    failed: usize,
+}
+
+impl Checks {
+    /// Records a passed check.
+    fn ok(&mut self, message: impl AsRef<str>) {
+        println!("ok       {}", message.as_ref());
+    }
+
+    /// Records a check that passed with a warning.
+    fn warn(&mut self, message: impl AsRef<str>) {
+        println!("warning  {}", message.as_ref());
+    }
+
+    /// Records a failed check.
+    fn fail(&mut self, message: impl AsRef<str>) {
+        println!("error    {}", message.as_ref());
+        self.failed += 1;
+    }
+
+    /// Records a check that fails if the condition does not hold.
+    fn ensure(&mut self, condition: bool, message: impl AsRef<str>) {
+        if condition {
+            self.ok(message)
+        } else {
+            self.fail(message)
+        }
+    }
+}
+
+impl Args {
+    /// Prints the effective configuration, and where each value comes from.
+    ///
+    /// The secret key is not printed, only the address of the sponsor.
+    pub(crate) fn print_config(&self, matches: &ArgMatches) -> eyre::Result<()> {
+        for arg in Self::command().get_arguments() {
+            let (Some(long), id) = (arg.get_long(), arg.get_id().as_str()) else { continue };
+            let source = match matches.value_source(id) {
+                Some(ValueSource::CommandLine) => "command line",
+                Some(ValueSource::EnvVariable) => "environment",
+                Some(ValueSource::DefaultValue) => "default",
+                _ => "unset",
+            };
+            let value = if id == "secret_key" {
+                self.signer().map_or_else(
+                    |_| "<invalid>".to_string(),
+                    |signer| format!("<redacted, sponsor {}>", signer.address()),
+                )
+            } else {
+                matches
+                    .get_raw(id)
+                    .map(|values| {
+                        values.map(|value| value.to_string_lossy()).collect::<Vec<_>>().join(",")
+                    })
+                    .unwrap_or_default()
+            };
+            println!("--{long} = {value} ({source})");
+        }
+        Ok(())
+    }
+
+    /// Checks the signer, caps, allowlists and upstream of the configuration.
+    ///
+    /// Connects to the upstream to verify the chain and the balance of the sponsor, and fails if
+    /// any check failed.
+    pub(crate) async fn check_config(&self) -> eyre::Result<()> {
+        let mut checks = Checks::default();
+
+        // signer
+        let signer = match self.signer() {
+            Ok(signer) => {
+                checks.ok(format!("sponsor is {}", signer.address()));
+                Some(signer)
+            }
+            Err(err) => {
+                checks.fail(format!("secret key is invalid: {err}"));
+                None
+            }
+        };
+
+        // caps
+        checks.ensure(self.max_sponsored_cost > U256::ZERO, "maximum sponsored cost is positive");
+        checks.ensure(
+            self.max_pending_per_account > 0,
+            "maximum pending requests per account is positive",
+        );
+        checks.ensure(
+            self.max_gas_per_block != Some(0),
+            "maximum sponsored gas per block is positive or unset",
+        );
+        checks.ensure(
+            (0.0..=1.0).contains(&self.max_revert_rate),
+            "maximum revert rate is between 0 and 1",
+        );
+        checks.ensure(
+            [
+                self.expiry_interval,
+                self.prepared_calls_ttl,
+                self.quote_ttl,
+                self.pending_transaction_ttl,
+                self.idempotency_key_ttl,
+                self.health_check_interval,
+                self.probe_interval,
+                self.access_lists_reload_interval,
+            ]
+            .iter()
+            .all(|ms| *ms > 0),
+            "intervals and expiry times are positive",
+        );
+
+        checks.ensure(
+            self.unavailable_threshold > 0,
+            "upstream unavailability threshold is positive",
+        );
+        checks.ensure(self.debug_log_sample > 0, "debug log sample rate is positive");
+
+        if self.token_fee_token.is_some() {
+            checks.ensure(
+                self.token_fee_price > U256::ZERO || self.token_fee_min_amount > U256::ZERO,
+                "token fee is positive",
+            );
+        }
+
+        // allowlists
+        if self.allowed_selectors.is_empty() {
+            checks.warn("no selectors are allowed, calls to any implementation are sponsored");
+        } else {
+            checks.ok(format!("{} selector rules are allowed", self.allowed_selectors.len()));
+        }
+        match (self.wildcard_authorizations, self.wildcard_implementations.is_empty()) {
+            (WildcardAuthorizationMode::Restrict, true) => checks.warn(
+                "wildcard authorizations are restricted to no implementations, they are rejected",
+            ),
+            (WildcardAuthorizationMode::Restrict, false) | (_, true) => {
+                checks.ok("wildcard authorization policy is consistent")
+            }
+            (_, false) => checks.warn(
+                "wildcard implementations are ignored unless wildcard authorizations are restricted",
+            ),
+        }
+        if !self.debug_log_namespaces.is_empty() {
+            checks.warn(format!(
+                "raw requests and responses of the {} namespaces are logged",
+                self.debug_log_namespaces.join(", ")
+            ));
+        }
+        match (&self.access_lists, self.access_control()) {
+            (None, _) => checks.ok("no access lists are configured"),
+            (Some(path), Ok(_)) => checks.ok(format!("access lists load from {}", path.display())),
+            (Some(path), Err(err)) => {
+                checks.fail(format!("access lists fail to load from {}: {err}", path.display()))
+            }
+        }
+
+        // upstream
+        let upstream = match self.transport() {
+            Ok(transport) => transport.check_chain_id().await.map(|chain_id| (transport, chain_id)),
+            Err(err) => Err(err),
+        };
+        match upstream {
+            Ok((transport, chain_id)) => {
+                checks.ok(format!("upstreams serve chain {chain_id}"));
+                if let Some(signer) = signer {
+                    let provider =
+                        ProviderBuilder::new().on_client(RpcClient::new(transport, false));
+                    match provider.get_balance(signer.address()).await {
+                        Ok(balance) if balance.is_zero() => checks.fail("sponsor has no balance"),
+                        Ok(balance) if balance < self.max_sponsored_cost => checks.warn(format!(
+                            "sponsor balance of {balance} wei is below the maximum sponsored cost"
+                        )),
+                        Ok(balance) => checks.ok(format!("sponsor balance is {balance} wei")),
+                        Err(err) => checks.fail(format!("sponsor balance is unavailable: {err}")),
+                    }
+                }
+            }
+            Err(err) => checks.fail(format!("upstream check failed: {err}")),
+        }
+
+        if checks.failed > 0 {
+            eyre::bail!("{} configuration checks failed", checks.failed);
+        }
+        Ok(())
+    }
+}
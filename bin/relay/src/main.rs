@@ -2,72 +2,445 @@
 //!
 //! A relay service that sponsors transactions for EIP-7702 accounts.
 
-use alloy_provider::{network::EthereumWallet, Provider, ProviderBuilder};
+use alloy_eips::BlockId;
+use alloy_primitives::{Address, U256};
+use alloy_provider::{network::EthereumWallet, ProviderBuilder};
 use alloy_rpc_client::RpcClient;
 use alloy_signer_local::PrivateKeySigner;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use eyre::Context;
-use hyper::Method;
 use jsonrpsee::server::Server;
-use odyssey_wallet::{AlloyUpstream, OdysseyWallet, OdysseyWalletApiServer};
-use reth_tracing::Tracer;
-use std::net::{IpAddr, Ipv4Addr};
-use tower::ServiceBuilder;
-use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use odyssey_wallet::{
+    otlp_layer, AccessControl, AlloyUpstream, AvailabilityConfig, BudgetConfig,
+    EstimationFallbackConfig, ExpiryConfig, FailoverConfig, FailoverTransport, LegacyCompatibility,
+    LoadShedConfig, MethodGate, OdysseyWallet, OdysseyWalletAdmin, OdysseyWalletAdminApiServer,
+    OdysseyWalletApiServer, OdysseyWalletConfig, ReputationConfig, RpcDebugLogConfig, SelectorRule,
+    TokenFeeConfig, WalletServerConfig, WildcardAuthorizationMode, WildcardAuthorizationPolicy,
+    DEFAULT_ACCESS_LISTS_RELOAD_INTERVAL, DEFAULT_AVAILABILITY_PROBE_INTERVAL,
+    DEFAULT_ESTIMATION_BACKOFF, DEFAULT_EXPIRY_INTERVAL, DEFAULT_HEALTH_CHECK_INTERVAL,
+    DEFAULT_IDEMPOTENCY_KEY_TTL, DEFAULT_MAX_AUTHORIZATIONS, DEFAULT_MAX_CONNECTIONS,
+    DEFAULT_MAX_IN_FLIGHT, DEFAULT_MAX_LATENCY, DEFAULT_MAX_PENDING_PER_ACCOUNT,
+    DEFAULT_MAX_REQUEST_SIZE_MB, DEFAULT_MAX_RESPONSE_SIZE_MB, DEFAULT_MAX_REVERT_RATE,
+    DEFAULT_MAX_SPONSORED_COST, DEFAULT_MIN_SAMPLES, DEFAULT_PENDING_TRANSACTION_TTL,
+    DEFAULT_PREPARED_CALLS_TTL, DEFAULT_QUOTE_TTL, DEFAULT_REDACTED_FIELDS, DEFAULT_SEND_DEADLINE,
+    DEFAULT_UNAVAILABLE_THRESHOLD, DEFAULT_UPSTREAM_RETRIES, DEFAULT_WALLET_HTTP_PORT,
+};
+use reth_tracing::{Layers, Tracer};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+use tracing::{info, warn};
 use url::Url;
 
+mod config;
+
 /// The Odyssey relayer service sponsors transactions for EIP-7702 accounts.
 #[derive(Debug, Parser)]
 #[command(author, about = "Relay", long_about = None)]
 struct Args {
+    /// Runs a command instead of the relayer service.
+    #[command(subcommand)]
+    command: Option<Command>,
     /// The address to serve the RPC on.
     #[arg(long = "http.addr", value_name = "ADDR", default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
     address: IpAddr,
     /// The port to serve the RPC on.
-    #[arg(long = "http.port", value_name = "PORT", default_value_t = 9119)]
+    #[arg(long = "http.port", value_name = "PORT", default_value_t = DEFAULT_WALLET_HTTP_PORT)]
     port: u16,
-    /// The RPC endpoint of the chain to send transactions to.
-    /// Must be a valid HTTP or HTTPS URL pointing to an Ethereum JSON-RPC endpoint.
-    #[arg(long, value_name = "RPC_ENDPOINT")]
-    upstream: Url,
+    /// Comma separated list of origins allowed to make cross-origin requests, or `*` for any.
+    #[arg(long = "http.corsdomain", value_name = "DOMAINS", default_value = "*")]
+    cors_domains: String,
+    /// The maximum size of a request body in megabytes.
+    #[arg(long = "http.max-request-size", value_name = "MB", default_value_t = DEFAULT_MAX_REQUEST_SIZE_MB)]
+    max_request_size: u32,
+    /// The maximum size of a response body in megabytes.
+    #[arg(long = "http.max-response-size", value_name = "MB", default_value_t = DEFAULT_MAX_RESPONSE_SIZE_MB)]
+    max_response_size: u32,
+    /// The maximum number of concurrent connections.
+    #[arg(long = "http.max-connections", value_name = "COUNT", default_value_t = DEFAULT_MAX_CONNECTIONS)]
+    max_connections: u32,
+    /// Disables gzip and brotli compression of responses.
+    #[arg(long = "http.disable-compression")]
+    disable_compression: bool,
+    /// A namespace whose raw requests and responses are logged for debugging, e.g. `wallet`.
+    ///
+    /// Can be passed multiple times. The values of sensitive fields are redacted.
+    #[arg(long = "http.debug-log", value_name = "NAMESPACE")]
+    debug_log_namespaces: Vec<String>,
+    /// Only one of every `N` calls to the namespaces of `--http.debug-log` is logged.
+    #[arg(long = "http.debug-log-sample", value_name = "N", default_value_t = 1)]
+    debug_log_sample: u64,
+    /// A field whose values are redacted in the debug logs, in addition to signatures, keys,
+    /// secrets, passwords and API keys.
+    ///
+    /// Can be passed multiple times.
+    #[arg(long = "http.debug-log-redact", value_name = "FIELD")]
+    debug_log_redacted_fields: Vec<String>,
+    /// Accepts WebSocket connections on the same port as HTTP.
+    ///
+    /// This is required to subscribe to status updates of sponsored transactions with
+    /// `wallet_subscribeMyTransactions`.
+    #[arg(long)]
+    ws: bool,
+    /// The maximum number of calls that are processed at once, before calls are shed.
+    #[arg(long = "shed.max-in-flight", value_name = "COUNT", default_value_t = DEFAULT_MAX_IN_FLIGHT)]
+    max_in_flight: usize,
+    /// The average latency in milliseconds above which read-only calls are shed.
+    #[arg(long = "shed.max-latency", value_name = "MS", default_value_t = DEFAULT_MAX_LATENCY.as_millis() as u64)]
+    max_latency: u64,
+    /// The time in milliseconds calls that send transactions wait for capacity.
+    #[arg(long = "shed.send-deadline", value_name = "MS", default_value_t = DEFAULT_SEND_DEADLINE.as_millis() as u64)]
+    send_deadline: u64,
+    /// Gates a method behind the flag of an experiment, e.g. `wallet_getQuote=quotes:1700000000`.
+    ///
+    /// Calls fail with an "experiment not enabled" error until the experiment is active. The
+    /// activation is `enabled`, `disabled` or a unix timestamp in seconds, and a whole namespace
+    /// is gated by its prefix, e.g. `wallet_`. Can be passed multiple times.
+    #[arg(long = "experiment.gate", value_name = "METHOD=EXPERIMENT:ACTIVATION")]
+    experiment_gates: Vec<MethodGate>,
+    /// The RPC endpoints of the chain to send transactions to, in order of preference.
+    /// Must be valid HTTP or HTTPS URLs pointing to Ethereum JSON-RPC endpoints.
+    ///
+    /// Can be passed multiple times. Requests fail over to the next healthy endpoint if an
+    /// endpoint is unreachable, and endpoints that serve another chain are not used.
+    #[arg(long, value_name = "RPC_ENDPOINT", required = true, value_delimiter = ',')]
+    upstream: Vec<Url>,
+    /// The number of times a request is retried on another upstream endpoint.
+    #[arg(long = "upstream.retries", value_name = "COUNT", default_value_t = DEFAULT_UPSTREAM_RETRIES)]
+    upstream_retries: usize,
+    /// The interval in milliseconds between health checks of the upstream endpoints.
+    #[arg(long = "upstream.health-check-interval", value_name = "MS", value_parser = clap::value_parser!(u64).range(1..), default_value_t = DEFAULT_HEALTH_CHECK_INTERVAL.as_millis() as u64)]
+    health_check_interval: u64,
+    /// The number of consecutive calls that have to fail because the upstream is unreachable,
+    /// before new requests are rejected and queued requests wait for the upstream to recover.
+    #[arg(long = "upstream.unavailable-threshold", value_name = "COUNT", default_value_t = DEFAULT_UNAVAILABLE_THRESHOLD)]
+    unavailable_threshold: usize,
+    /// The interval in milliseconds at which an unavailable upstream is probed.
+    #[arg(long = "upstream.probe-interval", value_name = "MS", default_value_t = DEFAULT_AVAILABILITY_PROBE_INTERVAL.as_millis() as u64)]
+    probe_interval: u64,
     /// The secret key to sponsor transactions with.
     #[arg(long, value_name = "SECRET_KEY", env = "RELAY_SK")]
     secret_key: String,
+    /// The address to serve the admin RPC on.
+    #[arg(long = "admin.addr", value_name = "ADDR", default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    admin_address: IpAddr,
+    /// The port to serve the admin RPC on.
+    ///
+    /// The admin RPC is disabled if this is not set.
+    #[arg(long = "admin.port", value_name = "PORT")]
+    admin_port: Option<u16>,
+    /// The minimum number of sponsored transactions of an account before it can be banned.
+    #[arg(long = "reputation.min-samples", value_name = "COUNT", default_value_t = DEFAULT_MIN_SAMPLES)]
+    min_samples: u64,
+    /// The maximum ratio of reverted sponsored transactions of an account before it is banned.
+    #[arg(long = "reputation.max-revert-rate", value_name = "RATE", default_value_t = DEFAULT_MAX_REVERT_RATE)]
+    max_revert_rate: f64,
+    /// The maximum number of outstanding requests per account.
+    #[arg(long = "queue.max-pending-per-account", value_name = "COUNT", default_value_t = DEFAULT_MAX_PENDING_PER_ACCOUNT)]
+    max_pending_per_account: usize,
+    /// The maximum cost of a sponsored transaction in wei, including the L1 data fee.
+    #[arg(long = "sponsor.max-cost", value_name = "WEI", default_value_t = DEFAULT_MAX_SPONSORED_COST)]
+    max_sponsored_cost: U256,
+    /// The maximum number of authorizations of a sponsored transaction.
+    ///
+    /// Every authorization raises the gas limit of the transaction by 25,000 gas.
+    #[arg(long = "sponsor.max-authorizations", value_name = "COUNT", default_value_t = DEFAULT_MAX_AUTHORIZATIONS)]
+    max_authorizations: usize,
+    /// The maximum gas of sponsored transactions that wait for inclusion at once.
+    ///
+    /// New sponsorships wait for pending sponsored transactions to be included if the budget is
+    /// exhausted. There is no limit if this is not set.
+    #[arg(long = "sponsor.max-gas-per-block", value_name = "GAS")]
+    max_gas_per_block: Option<u64>,
+    /// The ERC-20 token sponsored transactions have to pay the sponsor with (experimental).
+    ///
+    /// If this is set, a sponsored call is only sent if it transfers the price of the transaction
+    /// in the token to the sponsor in simulation. Sponsored transactions are free if this is not
+    /// set.
+    #[arg(long = "token-fee.token", value_name = "ADDRESS")]
+    token_fee_token: Option<Address>,
+    /// The amount of the token, in its smallest unit, charged per ETH of the maximum cost of a
+    /// sponsored transaction.
+    #[arg(long = "token-fee.price", value_name = "AMOUNT", default_value_t = U256::ZERO)]
+    token_fee_price: U256,
+    /// The minimum amount of the token, in its smallest unit, charged per sponsored transaction.
+    #[arg(long = "token-fee.min-amount", value_name = "AMOUNT", default_value_t = U256::ZERO)]
+    token_fee_min_amount: U256,
+    /// The number of times gas and fee estimations are retried if the upstream is unreachable.
+    ///
+    /// Requests are rejected as soon as the estimation fails if this is zero and
+    /// `--estimation.max-cache-age` is not set.
+    #[arg(long = "estimation.retries", value_name = "COUNT", default_value_t = 0)]
+    estimation_retries: usize,
+    /// The delay in milliseconds before the first retry of an estimation, which is doubled on
+    /// every retry.
+    #[arg(long = "estimation.backoff", value_name = "MS", default_value_t = DEFAULT_ESTIMATION_BACKOFF.as_millis() as u64)]
+    estimation_backoff: u64,
+    /// The maximum age in milliseconds of the fee estimate that requests are estimated from if the
+    /// upstream is unreachable after all retries.
+    ///
+    /// The gas limit is the highest recent estimate of the destination and selector plus 20%.
+    /// Cached estimates are not used if this is not set.
+    #[arg(long = "estimation.max-cache-age", value_name = "MS")]
+    estimation_max_cache_age: Option<u64>,
+    /// How requests for legacy and EIP-2930 transactions are sponsored: `disabled` rejects them,
+    /// `upgrade` sponsors them as EIP-1559 transactions, and `preserve` sponsors them as
+    /// requested.
+    #[arg(long = "sponsor.legacy-compatibility", value_name = "MODE", default_value = "disabled")]
+    legacy_compatibility: LegacyCompatibility,
+    /// The block whose state sponsored transactions are estimated against, e.g. `latest` or
+    /// `pending`.
+    ///
+    /// Estimating against `pending` accounts for transactions that are waiting in the transaction
+    /// pool of the upstream, such as earlier transactions of the same account.
+    #[arg(long = "sponsor.estimate-block", value_name = "BLOCK", default_value = "latest")]
+    estimate_block: BlockId,
+    /// How authorizations with a chain ID of 0, which are valid on every chain, are sponsored:
+    /// `accept` sponsors them, `reject` rejects them, and `restrict` only sponsors them if they
+    /// delegate to an implementation passed with `--sponsor.wildcard-implementation`.
+    #[arg(long = "sponsor.wildcard-authorizations", value_name = "MODE", default_value = "accept")]
+    wildcard_authorizations: WildcardAuthorizationMode,
+    /// An implementation that authorizations with a chain ID of 0 may delegate to in `restrict`
+    /// mode.
+    ///
+    /// Can be passed multiple times.
+    #[arg(long = "sponsor.wildcard-implementation", value_name = "ADDRESS")]
+    wildcard_implementations: Vec<Address>,
+    /// A function that is sponsored for an implementation, in the form
+    /// `<implementation>:<selector>`, where the selector is the 4-byte selector, a function
+    /// signature such as `execute(bytes32,bytes)`, or `*` to allow any call.
+    ///
+    /// Can be passed multiple times. If this is set, only calls to accounts delegating to one of
+    /// the implementations with an allowed selector are sponsored.
+    #[arg(long = "sponsor.allowed-selector", value_name = "IMPLEMENTATION:SELECTOR")]
+    allowed_selectors: Vec<SelectorRule>,
+    /// An implementation whose sponsored transactions and gas are exported as metrics, labeled by
+    /// the implementation.
+    ///
+    /// Can be passed multiple times.
+    #[arg(long = "sponsor.tracked-implementation", value_name = "ADDRESS")]
+    tracked_implementations: Vec<Address>,
+    /// The JSON file of the allow and deny lists of authorities, destinations, implementations
+    /// and selectors.
+    ///
+    /// The file is created on the first change through the admin RPC, and reloaded when it is
+    /// modified. No requests are denied if this is not set.
+    #[arg(long = "access-lists", value_name = "PATH")]
+    access_lists: Option<PathBuf>,
+    /// The interval in milliseconds at which the file of the access lists is checked for changes.
+    #[arg(long = "access-lists.reload-interval", value_name = "MS", value_parser = clap::value_parser!(u64).range(1..), default_value_t = DEFAULT_ACCESS_LISTS_RELOAD_INTERVAL.as_millis() as u64)]
+    access_lists_reload_interval: u64,
+    /// Signs and discards sponsored transactions instead of sending them.
+    ///
+    /// Requests are validated and estimated against the upstream as usual, and the hash of the
+    /// signed transaction is returned. The discarded transactions can be listed with
+    /// `admin_getDryRunTransactions`.
+    #[arg(long = "dry-run")]
+    dry_run: bool,
+    /// The interval in milliseconds at which expired prepared transactions, quotes, pending
+    /// transactions and idempotency keys are removed.
+    #[arg(long = "expiry.interval", value_name = "MS", value_parser = clap::value_parser!(u64).range(1..), default_value_t = DEFAULT_EXPIRY_INTERVAL.as_millis() as u64)]
+    expiry_interval: u64,
+    /// The time in milliseconds a transaction prepared with `wallet_prepareCalls` can be sent for.
+    #[arg(long = "expiry.prepared-calls-ttl", value_name = "MS", default_value_t = DEFAULT_PREPARED_CALLS_TTL.as_millis() as u64)]
+    prepared_calls_ttl: u64,
+    /// The time in milliseconds a quote of `wallet_getQuote` is valid for.
+    #[arg(long = "expiry.quote-ttl", value_name = "MS", default_value_t = DEFAULT_QUOTE_TTL.as_millis() as u64)]
+    quote_ttl: u64,
+    /// The time in milliseconds the inclusion of a sponsored transaction is tracked for.
+    #[arg(long = "expiry.pending-transaction-ttl", value_name = "MS", default_value_t = DEFAULT_PENDING_TRANSACTION_TTL.as_millis() as u64)]
+    pending_transaction_ttl: u64,
+    /// The time in milliseconds the transaction sponsored for an idempotency key of
+    /// `odyssey_sendTransaction` is returned for repeated calls.
+    #[arg(long = "expiry.idempotency-key-ttl", value_name = "MS", default_value_t = DEFAULT_IDEMPOTENCY_KEY_TTL.as_millis() as u64)]
+    idempotency_key_ttl: u64,
+    /// The OTLP/HTTP endpoint to export traces to, e.g. `http://localhost:4318/v1/traces`.
+    ///
+    /// Traces are not exported if this is not set.
+    #[arg(long = "tracing.otlp-endpoint", value_name = "URL")]
+    otlp_endpoint: Option<String>,
+}
+
+/// Commands of the relay.
+///
+/// The commands take the same options as the relayer service, which are passed before the
+/// command, e.g. `relay --upstream <URL> check-config`.
+#[derive(Debug, Clone, Copy, Subcommand)]
+enum Command {
+    /// Validates the configuration, connecting to the upstream to verify the chain and the balance
+    /// of the sponsor, and exits with a non-zero status if there are problems.
+    CheckConfig,
+    /// Prints the effective configuration, merged from the options, the environment and the
+    /// defaults.
+    PrintConfig,
 }
 
 impl Args {
+    /// Returns the signer that sponsors transactions.
+    fn signer(&self) -> eyre::Result<PrivateKeySigner> {
+        self.secret_key.parse().wrap_err("Invalid signing key")
+    }
+
+    /// Returns the transport to the upstream endpoints.
+    fn transport(&self) -> eyre::Result<FailoverTransport> {
+        FailoverTransport::http(
+            &self.upstream,
+            FailoverConfig {
+                retries: self.upstream_retries,
+                health_check_interval: Duration::from_millis(self.health_check_interval),
+            },
+        )
+    }
+
+    /// Loads the access lists, if configured.
+    fn access_control(&self) -> eyre::Result<AccessControl> {
+        match &self.access_lists {
+            Some(path) => AccessControl::load(path),
+            None => Ok(AccessControl::default()),
+        }
+    }
+
+    /// Returns the configuration of the wallet with the given access lists.
+    fn wallet_config(&self, access: AccessControl) -> OdysseyWalletConfig {
+        OdysseyWalletConfig {
+            reputation: ReputationConfig {
+                min_samples: self.min_samples,
+                max_revert_rate: self.max_revert_rate,
+            },
+            max_pending_per_account: self.max_pending_per_account,
+            max_sponsored_cost: self.max_sponsored_cost,
+            max_authorizations: self.max_authorizations,
+            dry_run: self.dry_run,
+            legacy_compatibility: self.legacy_compatibility,
+            selectors: self.allowed_selectors.iter().copied().collect(),
+            access,
+            estimate_block: self.estimate_block,
+            wildcard_authorizations: WildcardAuthorizationPolicy::new(
+                self.wildcard_authorizations,
+                self.wildcard_implementations.iter().copied(),
+            ),
+            tracked_implementations: self.tracked_implementations.iter().copied().collect(),
+            budget: self
+                .max_gas_per_block
+                .map(|max_gas_per_block| BudgetConfig { max_gas_per_block, ..Default::default() }),
+            token_fee: self.token_fee_token.map(|token| TokenFeeConfig {
+                token,
+                price: self.token_fee_price,
+                min_amount: self.token_fee_min_amount,
+            }),
+            estimation_fallback: (self.estimation_retries > 0
+                || self.estimation_max_cache_age.is_some())
+            .then(|| EstimationFallbackConfig {
+                retries: self.estimation_retries,
+                backoff: Duration::from_millis(self.estimation_backoff),
+                max_cache_age: self.estimation_max_cache_age.map(Duration::from_millis),
+            }),
+            availability: AvailabilityConfig {
+                failure_threshold: self.unavailable_threshold,
+                probe_interval: Duration::from_millis(self.probe_interval),
+            },
+            expiry: ExpiryConfig {
+                interval: Duration::from_millis(self.expiry_interval),
+                prepared_calls_ttl: Duration::from_millis(self.prepared_calls_ttl),
+                quote_ttl: Duration::from_millis(self.quote_ttl),
+                pending_transaction_ttl: Duration::from_millis(self.pending_transaction_ttl),
+                idempotency_key_ttl: Duration::from_millis(self.idempotency_key_ttl),
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Returns the configuration of the wallet server.
+    fn server_config(&self) -> WalletServerConfig {
+        WalletServerConfig {
+            addr: SocketAddr::new(self.address, self.port),
+            cors_domains: self.cors_domains.clone(),
+            max_request_size_mb: self.max_request_size,
+            max_response_size_mb: self.max_response_size,
+            max_connections: self.max_connections,
+            compression: !self.disable_compression,
+            load_shed: LoadShedConfig {
+                max_in_flight: self.max_in_flight,
+                max_latency: Duration::from_millis(self.max_latency),
+                send_deadline: Duration::from_millis(self.send_deadline),
+            },
+            ws: self.ws,
+            experiments: self.experiment_gates.iter().cloned().collect(),
+            debug_log: RpcDebugLogConfig {
+                namespaces: self.debug_log_namespaces.clone(),
+                redacted_fields: DEFAULT_REDACTED_FIELDS
+                    .iter()
+                    .map(|field| field.to_string())
+                    .chain(self.debug_log_redacted_fields.iter().cloned())
+                    .collect(),
+                sample_every: self.debug_log_sample,
+            },
+        }
+    }
+
     /// Run the relayer service.
     async fn run(self) -> eyre::Result<()> {
-        let _guard = reth_tracing::RethTracer::new().init()?;
+        let mut layers = Layers::new();
+        if let Some(endpoint) = &self.otlp_endpoint {
+            layers.add_layer(otlp_layer(endpoint, "odyssey-relay")?);
+        }
+        let _guard = reth_tracing::RethTracer::new().init_with_layers(layers)?;
 
         // construct provider
-        let signer: PrivateKeySigner = self.secret_key.parse().wrap_err("Invalid signing key")?;
-        let wallet = EthereumWallet::from(signer);
-        let rpc_client = RpcClient::new_http(self.upstream).boxed();
+        let signer = self.signer()?;
+        let wallet = EthereumWallet::from(signer.clone());
+        let transport = self.transport()?;
+
+        // get chain id, making sure all upstreams serve the same chain
+        let chain_id = transport.check_chain_id().await?;
+        transport.spawn_health_checks(chain_id);
+
+        let rpc_client = RpcClient::new(transport, false).boxed();
         let provider =
             ProviderBuilder::new().with_recommended_fillers().wallet(wallet).on_client(rpc_client);
 
-        // get chain id
-        let chain_id = provider.get_chain_id().await?;
+        // load access lists
+        let access = self.access_control()?;
+        if let Some(path) = &self.access_lists {
+            access.spawn_reload(Duration::from_millis(self.access_lists_reload_interval));
+            info!(?path, "Loaded access lists");
+        }
 
         // construct rpc module
-        let rpc = OdysseyWallet::new(AlloyUpstream::new(provider), chain_id).into_rpc();
+        let config = self.wallet_config(access.clone());
+        let wallet =
+            OdysseyWallet::with_config(AlloyUpstream::new(provider, signer), chain_id, config);
+        let mut admin =
+            OdysseyWalletAdmin::new(wallet.reputation().clone()).with_access_control(access);
+        if let Some(log) = wallet.dry_run_log() {
+            warn!("Dry-run mode is enabled, sponsored transactions are not sent");
+            admin = admin.with_dry_run_log(log.clone());
+        }
+        wallet.spawn_expiry();
+        let rpc = wallet.into_rpc();
 
         // start server
-        let cors = CorsLayer::new()
-            .allow_methods([Method::POST])
-            .allow_origin(Any)
-            .allow_headers([hyper::header::CONTENT_TYPE]);
-        let server = Server::builder()
-            .http_only()
-            .set_http_middleware(ServiceBuilder::new().layer(cors))
-            .build((self.address, self.port))
-            .await?;
-        info!(addr = ?server.local_addr().unwrap(), "Started relay service");
-
-        let handle = server.start(rpc);
+        let handle = self.server_config().start(rpc)?;
+
+        // start admin server
+        let admin_handle = if let Some(admin_port) = self.admin_port {
+            let admin_server =
+                Server::builder().http_only().build((self.admin_address, admin_port)).await?;
+            info!(addr = ?admin_server.local_addr().unwrap(), "Started relay admin service");
+            Some(admin_server.start(admin.into_rpc()))
+        } else {
+            None
+        };
+
         handle.stopped().await;
+        if let Some(admin_handle) = admin_handle {
+            let _ = admin_handle.stop();
+        }
+
+        // flush pending spans
+        opentelemetry::global::shutdown_tracer_provider();
 
         Ok(())
     }
@@ -81,8 +454,14 @@ async fn main() {
         std::env::set_var("RUST_BACKTRACE", "1");
     }
 
-    let args = Args::parse();
-    if let Err(err) = args.run().await {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let res = match args.command {
+        Some(Command::CheckConfig) => args.check_config().await,
+        Some(Command::PrintConfig) => args.print_config(&matches),
+        None => args.run().await,
+    };
+    if let Err(err) = res {
         eprintln!("Error: {err:?}");
         std::process::exit(1);
     }
@@ -0,0 +1,39 @@
+//! # Odyssey client
+//!
+//! Typed clients of the RPC extensions of Odyssey, generated from the same traits the servers
+//! implement, so the method names, parameters and responses can not drift apart:
+//!
+//! - [`OdysseyWalletApiClient`] for the `wallet_` namespace of the node and the relay.
+//! - [`OdysseyWalletAdminApiClient`] for the `admin_` namespace of the relay.
+//! - [`OdysseyApiClient`], [`OdysseyWallTimeRpcApiClient`] and [`DevMinerApiClient`] for the
+//!   `odyssey_` namespace of the node.
+//! - [`EthApiOverrideClient`] for the `eth_` methods overridden by the node.
+//!
+//! The traits are implemented for any `jsonrpsee` client, e.g. an [`HttpClient`] or a
+//! [`WsClient`]. Subscriptions such as `wallet_subscribeMyTransactions` require a [`WsClient`].
+//!
+//! ```no_run
+//! use odyssey_client::{HttpClientBuilder, OdysseyWallTimeRpcApiClient};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = HttpClientBuilder::default().build("http://localhost:8545")?;
+//! let walltime = client.get_timedata().await?;
+//! println!("last block timestamp: {}", walltime.last_block_timestamp());
+//! # Ok(())
+//! # }
+//! ```
+
+#![cfg_attr(not(test), warn(unused_crate_dependencies))]
+#![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+
+pub use jsonrpsee::{
+    core::ClientError,
+    http_client::{HttpClient, HttpClientBuilder},
+    ws_client::{WsClient, WsClientBuilder},
+};
+pub use odyssey_node::{
+    mine::DevMinerApiClient,
+    rpc::{EthApiOverrideClient, OdysseyApiClient},
+};
+pub use odyssey_wallet::{OdysseyWalletAdminApiClient, OdysseyWalletApiClient};
+pub use odyssey_walltime::OdysseyWallTimeRpcApiClient;
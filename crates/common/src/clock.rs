@@ -0,0 +1,127 @@
+//! Clock abstraction.
+//!
+//! Time-dependent components take a [`Clock`] instead of reading the system clocks directly, so
+//! their behaviour can be tested deterministically with a [`MockClock`].
+
+use parking_lot::Mutex;
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// A source of monotonic and wall-clock time.
+pub trait Clock: Debug + Send + Sync + 'static {
+    /// Returns the current monotonic time.
+    ///
+    /// This should be used to measure elapsed time.
+    fn now(&self) -> Instant;
+
+    /// Returns the current wall-clock time.
+    ///
+    /// This should be used for timestamps that are reported to users.
+    fn system_time(&self) -> SystemTime;
+
+    /// Returns the current wall-clock time as milliseconds since the unix epoch.
+    ///
+    /// Returns 0 if the wall-clock time is before the unix epoch.
+    fn unix_epoch_ms(&self) -> u64 {
+        self.system_time().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+    }
+}
+
+/// A [`Clock`] backed by the system clocks.
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A [`Clock`] that only advances when told to.
+#[derive(Debug)]
+pub struct MockClock {
+    instant: Instant,
+    system_time: SystemTime,
+    elapsed: Mutex<Duration>,
+}
+
+impl MockClock {
+    /// Creates a new mock clock whose wall-clock time starts at the given milliseconds since the
+    /// unix epoch.
+    pub fn new(unix_epoch_ms: u64) -> Self {
+        Self {
+            instant: Instant::now(),
+            system_time: UNIX_EPOCH + Duration::from_millis(unix_epoch_ms),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Advances the clock by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock() += duration;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.instant + *self.elapsed.lock()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.system_time + *self.elapsed.lock()
+    }
+}
+
+/// Returns the current unix epoch in milliseconds according to the [`SystemClock`].
+///
+/// Returns 0 if the system clock is before the unix epoch.
+pub fn unix_epoch_ms() -> u64 {
+    SystemClock.unix_epoch_ms()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock_advances() {
+        let clock = MockClock::new(1_000);
+        let start = clock.now();
+        assert_eq!(clock.unix_epoch_ms(), 1_000);
+
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(clock.now().duration_since(start), Duration::from_millis(500));
+        assert_eq!(clock.unix_epoch_ms(), 1_500);
+    }
+
+    #[test]
+    fn system_time_before_epoch_does_not_panic() {
+        #[derive(Debug)]
+        struct BrokenClock;
+
+        impl Clock for BrokenClock {
+            fn now(&self) -> Instant {
+                Instant::now()
+            }
+
+            fn system_time(&self) -> SystemTime {
+                UNIX_EPOCH - Duration::from_secs(1)
+            }
+        }
+
+        assert_eq!(BrokenClock.unix_epoch_ms(), 0);
+    }
+}
@@ -0,0 +1,47 @@
+//! [EIP-7702][eip-7702] delegation designator helpers.
+//!
+//! [eip-7702]: https://eips.ethereum.org/EIPS/eip-7702
+
+use alloy_primitives::{Address, Bytes};
+
+/// The prefix of an [EIP-7702][eip-7702] delegation designator.
+///
+/// [eip-7702]: https://eips.ethereum.org/EIPS/eip-7702
+pub const DELEGATION_DESIGNATOR_PREFIX: [u8; 3] = [0xef, 0x01, 0x00];
+
+/// Returns the delegation designator code for an account delegating to `address`.
+pub fn delegation_designator(address: Address) -> Bytes {
+    [DELEGATION_DESIGNATOR_PREFIX.as_slice(), address.as_slice()].concat().into()
+}
+
+/// Returns the address the code delegates to, if it is a delegation designator.
+///
+/// Note that a designator delegating to the zero address is a cleared delegation.
+pub fn parse_delegation_designator(code: &[u8]) -> Option<Address> {
+    match code {
+        [0xef, 0x01, 0x00, address @ ..] if address.len() == Address::len_bytes() => {
+            Some(Address::from_slice(address))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn designator_roundtrip() {
+        let address = Address::with_last_byte(0x42);
+        let code = delegation_designator(address);
+        assert_eq!(code.len(), 23);
+        assert_eq!(parse_delegation_designator(&code), Some(address));
+    }
+
+    #[test]
+    fn rejects_other_code() {
+        assert_eq!(parse_delegation_designator(&[]), None);
+        assert_eq!(parse_delegation_designator(&[0xef, 0x01, 0x00]), None);
+        assert_eq!(parse_delegation_designator(&[0x60, 0x80, 0x60, 0x40]), None);
+    }
+}
@@ -3,5 +3,13 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 #![warn(unused_crate_dependencies)]
 
+mod clock;
+pub use clock::{unix_epoch_ms, Clock, MockClock, SystemClock};
+
 mod constants;
 pub use constants::WITHDRAWAL_CONTRACT;
+
+mod delegation;
+pub use delegation::{
+    delegation_designator, parse_delegation_designator, DELEGATION_DESIGNATOR_PREFIX,
+};
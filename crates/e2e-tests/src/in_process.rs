@@ -0,0 +1,225 @@
+//! Tests of the Odyssey extensions working together on an in-process dev node.
+//!
+//! Unlike the tests against the replica and sequencer of a deployment, these tests boot their own
+//! node with the wallet, walltime, `eth_` override and dev miner namespaces installed like in the
+//! `odyssey` binary, so they do not depend on external infrastructure.
+
+use alloy::{
+    eips::eip7702::Authorization,
+    primitives::{b256, Address, B256, U256, U64},
+    providers::{PendingTransactionBuilder, Provider, ProviderBuilder},
+    signers::SignerSync,
+};
+use alloy_network::{TransactionBuilder, TransactionBuilder7702};
+use alloy_rpc_types::{serde_helpers::JsonStorageKey, BlockNumberOrTag, TransactionRequest};
+use alloy_signer_local::PrivateKeySigner;
+use odyssey_client::{
+    ClientError, DevMinerApiClient, EthApiOverrideClient, HttpClientBuilder,
+    OdysseyWallTimeRpcApiClient, OdysseyWalletApiClient,
+};
+use odyssey_common::WITHDRAWAL_CONTRACT;
+use odyssey_node::{
+    chainspec::ODYSSEY_DEV,
+    mine::{DevMiner, DevMinerApiServer},
+    node::OdysseyNode,
+    rpc::EthApiExt,
+};
+use odyssey_wallet::{
+    layer_methods, ExperimentGateLayer, ExperimentGates, ExperimentNotEnabled, ExperimentStatus,
+    MethodGate, OdysseyWallet, OdysseyWalletApiServer, RethUpstream, EXPERIMENT_NOT_ENABLED_CODE,
+};
+use odyssey_walltime::{OdysseyWallTime, OdysseyWallTimeRpcApiServer};
+use reth_node_builder::{
+    engine_tree_config::TreeConfig, EngineNodeLauncher, NodeBuilder, NodeConfig,
+};
+use reth_node_core::args::{DevArgs, RpcServerArgs};
+use reth_optimism_node::{args::RollupArgs, node::OpAddOnsBuilder};
+use reth_provider::providers::BlockchainProvider2;
+use reth_tasks::TaskManager;
+use reth_trie_common::StorageProof;
+use std::time::Duration;
+
+/// The sponsor of the in-process node, the first account funded by the dev genesis.
+const SPONSOR_KEY: B256 = b256!("ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80");
+
+/// The implementation sponsored accounts delegate to.
+const DELEGATION_ADDRESS: Address = Address::with_last_byte(0x42);
+
+/// Sponsors a delegation, calls the delegated account, proves the withdrawal contract storage and
+/// checks the walltime data against the chain, all on the same node.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_in_process_sponsorship_flow() -> Result<(), Box<dyn std::error::Error>> {
+    let tasks = TaskManager::current();
+    let config = NodeConfig::new(ODYSSEY_DEV.clone())
+        .with_dev(DevArgs { dev: true, ..Default::default() })
+        .with_rpc(RpcServerArgs::default().with_unused_ports().with_http());
+    let sponsor = PrivateKeySigner::from_bytes(&SPONSOR_KEY)?;
+    let sponsor_address = sponsor.address();
+
+    let handle = NodeBuilder::new(config)
+        .testing_node(tasks.executor())
+        .with_types_and_provider::<OdysseyNode, BlockchainProvider2<_>>()
+        .with_components(OdysseyNode::components(&RollupArgs::default()))
+        .with_add_ons(OpAddOnsBuilder::default().build())
+        .extend_rpc_modules(move |ctx| {
+            let eth_api = ctx.registry.eth_api().clone();
+            EthApiExt::new(eth_api.clone(), WITHDRAWAL_CONTRACT).install(ctx.modules)?;
+
+            let wallet = OdysseyWallet::new(
+                RethUpstream::new(ctx.provider().clone(), eth_api, sponsor),
+                ctx.config().chain.chain().id(),
+            );
+            wallet.inclusion_tracker().clone().spawn(ctx.provider().canonical_state_stream());
+            ctx.modules.merge_configured(wallet.into_rpc())?;
+
+            let walltime = OdysseyWallTime::spawn(ctx.provider().canonical_state_stream());
+            ctx.modules.merge_configured(walltime.into_rpc())?;
+
+            let miner = DevMiner::new(
+                ctx.provider().clone(),
+                ctx.config().chain.clone(),
+                ctx.auth_module.module_mut().clone(),
+                ctx.node().payload_builder().clone(),
+            );
+            ctx.modules.merge_configured(miner.into_rpc())?;
+            Ok(())
+        })
+        .launch_with_fn(|builder| {
+            let launcher = EngineNodeLauncher::new(
+                builder.task_executor().clone(),
+                builder.config().datadir(),
+                TreeConfig::default(),
+            );
+            builder.launch_with(launcher)
+        })
+        .await?;
+    let url = handle.node.rpc_server_handle().http_url().ok_or("http server not started")?;
+    let provider = ProviderBuilder::new().on_http(url.parse()?);
+    let client = HttpClientBuilder::default().build(&url)?;
+
+    // the `eth_` methods that are not overridden are still served next to the override
+    assert_eq!(provider.get_chain_id().await?, ODYSSEY_DEV.chain.id());
+    assert!(provider.get_balance(sponsor_address).await? > U256::ZERO);
+    assert_eq!(provider.get_transaction_count(sponsor_address).await?, 0);
+    let proof = provider.get_proof(sponsor_address, vec![]).await?;
+    assert!(!proof.account_proof.is_empty());
+
+    // sponsor a delegation of a fresh account
+    let account = PrivateKeySigner::random();
+    let auth = Authorization {
+        chain_id: provider.get_chain_id().await?,
+        address: DELEGATION_ADDRESS,
+        nonce: 0,
+    };
+    let signature = account.sign_hash_sync(&auth.signature_hash())?;
+    let tx = TransactionRequest::default()
+        .with_authorization_list(vec![auth.into_signed(signature)])
+        .with_to(account.address());
+    let tx_hash = client.send_transaction(tx, None, None).await?;
+    let receipt = PendingTransactionBuilder::new(provider.clone(), tx_hash).get_receipt().await?;
+    assert!(receipt.status(), "Delegation failed");
+
+    let code = provider.get_code_at(account.address()).await?;
+    assert_eq!(code[..3], [0xef, 0x01, 0x00]);
+    assert_eq!(Address::from_slice(&code[3..]), DELEGATION_ADDRESS);
+
+    // the delegated account can now be called with sponsorship
+    let tx = TransactionRequest::default().with_to(account.address());
+    let tx_hash = client.send_transaction(tx, None, None).await?;
+    let receipt = PendingTransactionBuilder::new(provider.clone(), tx_hash).get_receipt().await?;
+    assert!(receipt.status(), "Call of the delegated account failed");
+
+    // the withdrawal contract storage is proven against the state of the latest block
+    let block_number = BlockNumberOrTag::Number(provider.get_block_number().await?);
+    let proof = client
+        .get_proof(
+            WITHDRAWAL_CONTRACT,
+            vec![JsonStorageKey::from(B256::ZERO)],
+            Some(block_number.into()),
+            None,
+        )
+        .await?;
+    assert!(proof.account_proof.is_empty());
+    assert_eq!(proof.storage_proof.len(), 1);
+    for storage in proof.storage_proof {
+        StorageProof::new(storage.key.as_b256())
+            .with_proof(storage.proof)
+            .verify(proof.storage_hash)?;
+    }
+
+    // the walltime tracks the blocks mined on demand
+    let mined = client.mine(Some(U64::from(1)), None).await?;
+    let block = provider
+        .get_block_by_number(BlockNumberOrTag::Latest, false.into())
+        .await?
+        .ok_or("latest block not found")?;
+    assert_eq!(mined, vec![block.header.hash]);
+
+    // the canonical block is observed by the walltime tracker asynchronously
+    let mut walltime = client.get_timedata().await?;
+    for _ in 0..50 {
+        if walltime.last_block_timestamp() == block.header.timestamp {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        walltime = client.get_timedata().await?;
+    }
+    assert_eq!(walltime.last_block_timestamp(), block.header.timestamp);
+    assert!(walltime.current_wall_time_ms() >= walltime.last_block_wall_time_ms());
+
+    Ok(())
+}
+
+/// Rejects calls to methods gated behind an experiment that is not active, like the `odyssey`
+/// binary does for `--rpc.experiment-gate`.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_in_process_experiment_gates() -> Result<(), Box<dyn std::error::Error>> {
+    let tasks = TaskManager::current();
+    let config = NodeConfig::new(ODYSSEY_DEV.clone())
+        .with_dev(DevArgs { dev: true, ..Default::default() })
+        .with_rpc(RpcServerArgs::default().with_unused_ports().with_http());
+    let gates = ["odyssey_getWallTimeData=walltime:disabled"]
+        .into_iter()
+        .map(str::parse::<MethodGate>)
+        .collect::<Result<ExperimentGates, _>>()?;
+
+    let handle = NodeBuilder::new(config)
+        .testing_node(tasks.executor())
+        .with_types_and_provider::<OdysseyNode, BlockchainProvider2<_>>()
+        .with_components(OdysseyNode::components(&RollupArgs::default()))
+        .with_add_ons(OpAddOnsBuilder::default().build())
+        .extend_rpc_modules(move |ctx| {
+            let walltime = OdysseyWallTime::spawn(ctx.provider().canonical_state_stream());
+            ctx.modules.merge_configured(layer_methods(
+                walltime.into_rpc(),
+                ExperimentGateLayer::new(gates),
+            ))?;
+            Ok(())
+        })
+        .launch_with_fn(|builder| {
+            let launcher = EngineNodeLauncher::new(
+                builder.task_executor().clone(),
+                builder.config().datadir(),
+                TreeConfig::default(),
+            );
+            builder.launch_with(launcher)
+        })
+        .await?;
+    let url = handle.node.rpc_server_handle().http_url().ok_or("http server not started")?;
+    let provider = ProviderBuilder::new().on_http(url.parse()?);
+    let client = HttpClientBuilder::default().build(&url)?;
+
+    // the gated method is rejected with the flag of the experiment
+    let Err(ClientError::Call(err)) = client.get_timedata().await else {
+        return Err("gated method was not rejected".into());
+    };
+    assert_eq!(err.code(), EXPERIMENT_NOT_ENABLED_CODE);
+    let reason: ExperimentNotEnabled = serde_json::from_str(err.data().ok_or("no data")?.get())?;
+    assert_eq!(reason.experiment, "walltime");
+    assert_eq!(reason.status, ExperimentStatus::Disabled);
+
+    // methods that are not gated are served as usual
+    assert_eq!(provider.get_chain_id().await?, ODYSSEY_DEV.chain.id());
+
+    Ok(())
+}
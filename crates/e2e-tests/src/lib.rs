@@ -2,5 +2,7 @@
 
 #![warn(unused_crate_dependencies)]
 
+#[cfg(test)]
+mod in_process;
 #[cfg(test)]
 mod tests;
@@ -2,7 +2,7 @@ use std::{str::FromStr, sync::LazyLock};
 
 use alloy::{
     eips::eip7702::Authorization,
-    primitives::{b256, Address, B256},
+    primitives::{b256, Address, B256, U256},
     providers::{PendingTransactionBuilder, Provider, ProviderBuilder},
     signers::SignerSync,
 };
@@ -36,6 +36,9 @@ const TEST_PRIVATE_KEY: B256 =
 /// Default delegation address for testing
 const DEFAULT_DELEGATION_ADDRESS: &str = "0x90f79bf6eb2c4f870365e785982e1f101e93b906";
 
+/// The JSON-RPC error code of requests rejected by the wallet
+const INVALID_PARAMS_CODE: i64 = -32602;
+
 /// Tests if the chain is advancing by checking block numbers
 #[tokio::test]
 async fn assert_chain_advances() -> Result<(), Box<dyn std::error::Error>> {
@@ -174,6 +177,18 @@ async fn test_withdrawal_proof_with_fallback() -> Result<(), Box<dyn std::error:
         StorageProof::new(proof.key.as_b256()).with_proof(proof.proof).verify(storage_root)?
     }
 
+    // Light clients can request the account proof of the withdrawal contract as well
+    let full_response: EIP1186AccountProofResponse = provider
+        .client()
+        .request(
+            "eth_getProof",
+            (odyssey_common::WITHDRAWAL_CONTRACT, vec![B256::ZERO], block_number, true),
+        )
+        .await?;
+
+    assert!(!full_response.account_proof.is_empty());
+    assert_eq!(full_response.storage_hash, storage_root);
+
     // If not targeting the withdrawal contract, it defaults back to the standard getProof
     // implementation
     let signer = PrivateKeySigner::from_bytes(&b256!(
@@ -201,3 +216,92 @@ async fn test_withdrawal_proof_with_fallback() -> Result<(), Box<dyn std::error:
 
     Ok(())
 }
+
+/// Sponsors a delegation of the signer to the given address, and waits for it to be included
+async fn sponsor_delegation(
+    signer: &PrivateKeySigner,
+    address: Address,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let provider = ProviderBuilder::new().on_http(REPLICA_RPC.clone());
+
+    let auth = Authorization {
+        chain_id: provider.get_chain_id().await?,
+        address,
+        nonce: provider.get_transaction_count(signer.address()).await?,
+    };
+    let signature = signer.sign_hash_sync(&auth.signature_hash())?;
+    let auth = auth.into_signed(signature);
+
+    let tx =
+        TransactionRequest::default().with_authorization_list(vec![auth]).with_to(signer.address());
+    let tx_hash: B256 = provider.client().request("odyssey_sendTransaction", vec![tx]).await?;
+    let receipt = PendingTransactionBuilder::new(provider, tx_hash).get_receipt().await?;
+    assert!(receipt.status(), "Delegation failed");
+
+    Ok(())
+}
+
+/// Asserts that the wallet rejects the transaction request with an error containing the message
+async fn assert_rejected(tx: TransactionRequest, message: &str) {
+    let provider = ProviderBuilder::new().on_http(REPLICA_RPC.clone());
+
+    let err = provider
+        .client()
+        .request::<_, B256>("odyssey_sendTransaction", vec![tx])
+        .await
+        .expect_err("request should be rejected");
+    let payload = err.as_error_resp().expect("rejection should be an error response");
+
+    assert_eq!(payload.code, INVALID_PARAMS_CODE);
+    assert!(payload.message.contains(message), "unexpected error: {}", payload.message);
+}
+
+/// Tests that the wallet rejects requests it should not sponsor
+#[tokio::test]
+async fn test_wallet_rejections() -> Result<(), Box<dyn std::error::Error>> {
+    if !ci_info::is_ci() {
+        return Ok(());
+    }
+
+    let delegation_address = Address::from_str(
+        &std::env::var("DELEGATION_ADDRESS")
+            .unwrap_or_else(|_| DEFAULT_DELEGATION_ADDRESS.to_string()),
+    )?;
+
+    // use a fresh delegated account, so the rejections do not depend on other tests
+    let account = PrivateKeySigner::random();
+    sponsor_delegation(&account, delegation_address).await?;
+
+    // transactions with value would drain the sponsor
+    assert_rejected(
+        TransactionRequest::default().with_to(account.address()).with_value(U256::from(1)),
+        "tx value not zero",
+    )
+    .await;
+
+    // accounts without a delegation can not be called
+    assert_rejected(
+        TransactionRequest::default().with_to(PrivateKeySigner::random().address()),
+        "not a delegated account",
+    )
+    .await;
+
+    // accounts whose delegation was cleared can not be called either
+    let cleared = PrivateKeySigner::random();
+    sponsor_delegation(&cleared, delegation_address).await?;
+    sponsor_delegation(&cleared, Address::ZERO).await?;
+    assert_rejected(
+        TransactionRequest::default().with_to(cleared.address()),
+        "not a delegated account",
+    )
+    .await;
+
+    // the calldata alone costs more than the gas cap
+    assert_rejected(
+        TransactionRequest::default().with_to(account.address()).with_input(vec![0xff; 25_000]),
+        "request would use too much gas",
+    )
+    .await;
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+//! Embeds the git commit the node is built from as `ODYSSEY_GIT_SHA`.
+//!
+//! The commit is omitted if the source is not a git checkout, unless `ODYSSEY_GIT_SHA` is set
+//! explicitly, e.g. when building from a source archive.
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=ODYSSEY_GIT_SHA");
+    println!("cargo:rerun-if-changed=../../.git/HEAD");
+    println!("cargo:rerun-if-changed=../../.git/refs/heads");
+
+    if std::env::var_os("ODYSSEY_GIT_SHA").is_some() {
+        return;
+    }
+    let sha = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(sha) = sha {
+        println!("cargo:rustc-env=ODYSSEY_GIT_SHA={}", sha.trim());
+    }
+}
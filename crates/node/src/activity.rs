@@ -0,0 +1,261 @@
+//! Activity feed of delegated accounts.
+//!
+//! Every transaction of a canonical block whose sender or destination delegates to a contract
+//! using [EIP-7702](https://eips.ethereum.org/EIPS/eip-7702) is published along with the
+//! implementation the account resolves to, the called selector and whether the transaction
+//! succeeded. Delegations are resolved against the state after the block, when the block is
+//! imported. Teams running delegation experiments subscribe to the activity of their
+//! implementations with `odyssey_subscribeDelegatedActivity` over WebSocket, following at most
+//! [`MAX_SUBSCRIBED_IMPLEMENTATIONS`] implementations per subscription.
+
+use crate::rpc::resolve_delegation;
+use alloy_consensus::Transaction as _;
+use alloy_primitives::{Address, Selector, TxHash, B256};
+use futures::{Stream, StreamExt};
+use jsonrpsee::{
+    core::SubscriptionResult,
+    types::{error::INVALID_PARAMS_CODE, ErrorObject},
+    PendingSubscriptionSink, SubscriptionMessage,
+};
+use reth_chain_state::CanonStateNotification;
+use reth_storage_api::StateProviderFactory;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::debug;
+
+/// The default number of activities that are buffered for slow subscribers.
+pub const DEFAULT_ACTIVITY_CHANNEL_CAPACITY: usize = 1_024;
+
+/// The maximum number of implementations a subscription can follow.
+pub const MAX_SUBSCRIBED_IMPLEMENTATIONS: usize = 64;
+
+/// A transaction that touched a delegated account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DelegatedActivity {
+    /// The number of the block the transaction was included in.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub block_number: u64,
+    /// The hash of the block the transaction was included in.
+    pub block_hash: B256,
+    /// The hash of the transaction.
+    pub transaction_hash: TxHash,
+    /// The delegated account, the sender or the destination of the transaction.
+    pub account: Address,
+    /// The address the account delegates to.
+    pub designator: Address,
+    /// The address of the code that executes for the account.
+    ///
+    /// See [`AccountBundle::implementation`](crate::rpc::AccountBundle::implementation).
+    pub implementation: Address,
+    /// The selector of the called function, if the input has one.
+    pub selector: Option<Selector>,
+    /// Whether the transaction succeeded.
+    pub success: bool,
+}
+
+impl DelegatedActivity {
+    /// Returns `true` if the account delegates to one of the implementations, either directly or
+    /// through a proxy.
+    pub fn matches(&self, implementations: &HashSet<Address>) -> bool {
+        implementations.contains(&self.designator) || implementations.contains(&self.implementation)
+    }
+}
+
+/// Broadcasts the activity of delegated accounts to subscribers.
+#[derive(Debug, Clone)]
+pub struct DelegatedActivityFeed {
+    sender: broadcast::Sender<DelegatedActivity>,
+}
+
+impl Default for DelegatedActivityFeed {
+    fn default() -> Self {
+        Self::new(DEFAULT_ACTIVITY_CHANNEL_CAPACITY)
+    }
+}
+
+impl DelegatedActivityFeed {
+    /// Creates a new feed that buffers the given number of activities for slow subscribers.
+    pub fn new(capacity: usize) -> Self {
+        Self { sender: broadcast::channel(capacity).0 }
+    }
+
+    /// Publishes an activity to all subscribers.
+    pub fn publish(&self, activity: DelegatedActivity) {
+        // sending only fails if there are no subscribers
+        let _ = self.sender.send(activity);
+    }
+
+    /// Subscribes to the activity of all delegated accounts.
+    pub fn subscribe(&self) -> broadcast::Receiver<DelegatedActivity> {
+        self.sender.subscribe()
+    }
+
+    /// Accepts the subscription, and sends the activity of accounts delegating to one of the
+    /// implementations to the subscriber until it unsubscribes.
+    ///
+    /// The subscription is rejected if it follows more than [`MAX_SUBSCRIBED_IMPLEMENTATIONS`]
+    /// implementations.
+    pub(crate) async fn pipe(
+        &self,
+        pending: PendingSubscriptionSink,
+        implementations: Vec<Address>,
+    ) -> SubscriptionResult {
+        if implementations.len() > MAX_SUBSCRIBED_IMPLEMENTATIONS {
+            pending
+                .reject(ErrorObject::owned(
+                    INVALID_PARAMS_CODE,
+                    format!(
+                        "at most {MAX_SUBSCRIBED_IMPLEMENTATIONS} implementations can be followed"
+                    ),
+                    None::<()>,
+                ))
+                .await;
+            return Ok(());
+        }
+        let implementations: HashSet<_> = implementations.into_iter().collect();
+
+        // subscribe before accepting, so no activity is missed
+        let mut activities = self.subscribe();
+        let sink = pending.accept().await?;
+
+        loop {
+            tokio::select! {
+                _ = sink.closed() => break,
+                activity = activities.recv() => match activity {
+                    Ok(activity) if activity.matches(&implementations) => {
+                        sink.send(SubscriptionMessage::from_json(&activity)?).await?;
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        debug!(target: "rpc::odyssey", skipped, "Subscriber lagged behind");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spawns a task that publishes the activity of delegated accounts in the blocks of canonical
+    /// state notifications.
+    ///
+    /// Blocks are only inspected while there are subscribers.
+    pub fn spawn<St, Provider>(self, provider: Provider, mut st: St)
+    where
+        St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
+        Provider: StateProviderFactory + Send + 'static,
+    {
+        tokio::task::spawn(async move {
+            while let Some(notification) = st.next().await {
+                if self.sender.receiver_count() == 0 {
+                    continue;
+                }
+                for (block, receipts) in notification.committed().blocks_and_receipts() {
+                    let state = match provider.history_by_block_hash(block.hash()) {
+                        Ok(state) => state,
+                        Err(err) => {
+                            debug!(
+                                target: "odyssey::activity",
+                                %err,
+                                block = %block.hash(),
+                                "Failed to resolve delegations"
+                            );
+                            continue;
+                        }
+                    };
+                    for ((sender, tx), receipt) in block.transactions_with_sender().zip(receipts) {
+                        let success = receipt.as_ref().is_some_and(|receipt| receipt.success);
+                        let selector = tx.input().get(..4).map(Selector::from_slice);
+                        let mut accounts = vec![*sender];
+                        accounts.extend(tx.to().filter(|to| to != sender));
+                        for account in accounts {
+                            let Ok(Some((designator, implementation))) =
+                                resolve_delegation(&*state, account)
+                            else {
+                                continue;
+                            };
+                            self.publish(DelegatedActivity {
+                                block_number: block.number,
+                                block_hash: block.hash(),
+                                transaction_hash: tx.hash(),
+                                account,
+                                designator,
+                                implementation,
+                                selector,
+                                success,
+                            });
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpsee::{MethodsError, RpcModule};
+
+    fn activity() -> DelegatedActivity {
+        DelegatedActivity {
+            block_number: 1,
+            block_hash: B256::with_last_byte(1),
+            transaction_hash: TxHash::with_last_byte(2),
+            account: Address::with_last_byte(3),
+            designator: Address::with_last_byte(4),
+            implementation: Address::with_last_byte(5),
+            selector: Some(Selector::new([0xa9, 0x05, 0x9c, 0xbb])),
+            success: true,
+        }
+    }
+
+    #[test]
+    fn matches_designator_and_implementation() {
+        let activity = activity();
+        assert!(activity.matches(&HashSet::from([Address::with_last_byte(4)])));
+        assert!(activity.matches(&HashSet::from([Address::ZERO, Address::with_last_byte(5)])));
+        assert!(!activity.matches(&HashSet::from([Address::with_last_byte(3)])));
+        assert!(!activity.matches(&HashSet::new()));
+    }
+
+    #[tokio::test]
+    async fn publishes_to_subscribers() {
+        let feed = DelegatedActivityFeed::new(4);
+        // publishing without subscribers does not fail
+        feed.publish(activity());
+
+        let mut activities = feed.subscribe();
+        feed.publish(activity());
+        assert_eq!(activities.recv().await.unwrap(), activity());
+
+        let json = serde_json::to_value(activity()).unwrap();
+        assert_eq!(json["blockNumber"], "0x1");
+        assert_eq!(json["selector"], "0xa9059cbb");
+    }
+
+    #[tokio::test]
+    async fn limits_followed_implementations() {
+        let mut module = RpcModule::new(DelegatedActivityFeed::default());
+        module
+            .register_subscription(
+                "subscribe",
+                "activity",
+                "unsubscribe",
+                |params, pending, feed, _| async move {
+                    feed.pipe(pending, params.one().unwrap()).await
+                },
+            )
+            .unwrap();
+
+        let implementations = vec![Address::ZERO; MAX_SUBSCRIBED_IMPLEMENTATIONS + 1];
+        let err = module.subscribe_unbounded("subscribe", [implementations]).await.unwrap_err();
+        assert!(matches!(err, MethodsError::JsonRpc(err) if err.code() == INVALID_PARAMS_CODE));
+
+        let implementations = vec![Address::ZERO; MAX_SUBSCRIBED_IMPLEMENTATIONS];
+        assert!(module.subscribe_unbounded("subscribe", [implementations]).await.is_ok());
+    }
+}
@@ -0,0 +1,487 @@
+//! Odyssey command line arguments.
+
+use crate::{chainspec::ODYSSEY_DEV, forks::ForkOverride};
+use alloy_primitives::Address;
+use alloy_rpc_types_eth::BlockId;
+use clap::{ArgAction, Args, ValueEnum};
+use reth_chainspec::{once_cell_set, BaseFeeParams, BaseFeeParamsKind};
+use reth_optimism_chainspec::OpChainSpec;
+use reth_optimism_node::args::RollupArgs;
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+    time::Duration,
+};
+
+/// Odyssey command line arguments, extending the [`RollupArgs`].
+#[derive(Debug, Clone, Args)]
+pub struct OdysseyArgs {
+    /// The Odyssey configuration file, see [`OdysseyConfig`](crate::config::OdysseyConfig).
+    #[arg(long = "odyssey.config", value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// The rollup arguments.
+    #[command(flatten)]
+    pub rollup: RollupArgs,
+    /// The arguments for the Odyssey RPC extensions.
+    #[command(flatten)]
+    pub rpc: OdysseyRpcArgs,
+    /// The arguments for the wallet service.
+    #[command(flatten)]
+    pub wallet: OdysseyWalletArgs,
+    /// The arguments for the canonical event stream.
+    #[command(flatten)]
+    pub events: OdysseyEventsArgs,
+    /// The arguments for development chains.
+    #[command(flatten)]
+    pub dev: OdysseyDevArgs,
+    /// Overrides the activation timestamp of an Odyssey hardfork, e.g. `exp1=1700000000`.
+    ///
+    /// This is intended for devnets, and can be passed multiple times.
+    #[arg(long = "override.fork", value_name = "FORK=TIMESTAMP")]
+    pub fork_overrides: Vec<ForkOverride>,
+    /// Tags the `extraData` of built blocks with the Odyssey version and the scheduled
+    /// experiments, replacing `--builder.extradata`.
+    #[arg(long = "builder.experiment-tag")]
+    pub experiment_tag: bool,
+}
+
+/// Arguments controlling which Odyssey RPC extensions are registered.
+///
+/// All extensions are enabled by default.
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+#[command(next_help_heading = "Odyssey RPC")]
+pub struct OdysseyRpcArgs {
+    /// Whether to enable the `odyssey_getWallTimeData` endpoint.
+    #[arg(long = "rpc.odyssey-walltime", value_name = "BOOL", default_value_t = true, action = ArgAction::Set)]
+    pub walltime: bool,
+    /// Whether to enable the Odyssey overrides of the `eth_` namespace.
+    #[arg(long = "rpc.eth-overrides", value_name = "BOOL", default_value_t = true, action = ArgAction::Set)]
+    pub eth_overrides: bool,
+    /// The number of blocks for which the storage of the withdrawal contract can be proven with
+    /// `eth_getProof`, even if the state is pruned. Disabled if zero.
+    #[arg(long = "rpc.withdrawal-proof-history", value_name = "BLOCKS", default_value_t = 0)]
+    pub withdrawal_proof_history: u64,
+    /// An address whose transactions `odyssey_getBlockReceipts` annotates as sponsored, in
+    /// addition to the sponsor of the node.
+    ///
+    /// Can be passed multiple times, e.g. on nodes that forward the `wallet_` namespace.
+    #[arg(long = "rpc.sponsor-address", value_name = "ADDRESS")]
+    pub sponsor_addresses: Vec<Address>,
+    /// A chain the node depends on, whose tip `odyssey_getChainTips` reports, e.g.
+    /// `op=http://localhost:8545`.
+    ///
+    /// Can be passed multiple times, e.g. for interop experiments with multiple chains.
+    #[arg(long = "rpc.walltime-dependency", value_name = "NAME=URL")]
+    pub walltime_dependencies: Vec<String>,
+    /// The maximum number of calls to the Odyssey extensions of the RPC server that are processed
+    /// at once, before calls are shed. Engine API calls are never shed.
+    #[arg(long = "rpc.shed-max-in-flight", value_name = "COUNT", default_value_t = 512)]
+    pub shed_max_in_flight: usize,
+    /// The average latency in milliseconds above which read-only calls to the Odyssey extensions
+    /// of the RPC server are shed.
+    #[arg(long = "rpc.shed-max-latency", value_name = "MS", value_parser = parse_duration_ms, default_value = "2000")]
+    pub shed_max_latency: Duration,
+    /// The time in milliseconds calls that send transactions through the RPC server wait for
+    /// capacity.
+    #[arg(long = "rpc.shed-send-deadline", value_name = "MS", value_parser = parse_duration_ms, default_value = "5000")]
+    pub shed_send_deadline: Duration,
+    /// Gates a method of the Odyssey extensions behind the flag of an experiment, e.g.
+    /// `wallet_getQuote=quotes:1700000000`.
+    ///
+    /// Calls fail with an "experiment not enabled" error until the experiment is active. The
+    /// activation is `enabled`, `disabled` or a unix timestamp in seconds, and a whole namespace
+    /// is gated by its prefix, e.g. `wallet_`. Can be passed multiple times.
+    #[arg(long = "rpc.experiment-gate", value_name = "METHOD=EXPERIMENT:ACTIVATION")]
+    pub experiment_gates: Vec<String>,
+}
+
+impl Default for OdysseyRpcArgs {
+    fn default() -> Self {
+        Self {
+            walltime: true,
+            eth_overrides: true,
+            withdrawal_proof_history: 0,
+            sponsor_addresses: Vec::new(),
+            walltime_dependencies: Vec::new(),
+            shed_max_in_flight: 512,
+            shed_max_latency: Duration::from_secs(2),
+            shed_send_deadline: Duration::from_secs(5),
+            experiment_gates: Vec::new(),
+        }
+    }
+}
+
+/// How the `wallet_` namespace is served.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum WalletMode {
+    /// Serve the namespace on the node's RPC server.
+    #[default]
+    Embedded,
+    /// Serve the namespace on a separate server.
+    External,
+    /// Do not serve the namespace.
+    Disabled,
+}
+
+/// Arguments for the wallet service.
+///
+/// The wallet service is only enabled if a sponsor key is configured.
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+#[command(next_help_heading = "Wallet")]
+pub struct OdysseyWalletArgs {
+    /// How to serve the `wallet_` namespace.
+    #[arg(long = "wallet.mode", value_name = "MODE", value_enum, default_value_t = WalletMode::Embedded)]
+    pub mode: WalletMode,
+    /// The address of the separate wallet server.
+    ///
+    /// Only used if the mode is `external`.
+    #[arg(long = "wallet.http.addr", value_name = "ADDR", default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    pub http_addr: IpAddr,
+    /// The port of the separate wallet server.
+    ///
+    /// Only used if the mode is `external`.
+    #[arg(long = "wallet.http.port", value_name = "PORT", default_value_t = 9119)]
+    pub http_port: u16,
+    /// Comma separated list of origins allowed to make cross-origin requests to the separate
+    /// wallet server, or `*` for any.
+    ///
+    /// Only used if the mode is `external`.
+    #[arg(long = "wallet.http.corsdomain", value_name = "DOMAINS", default_value = "*")]
+    pub http_cors_domains: String,
+    /// The maximum number of requests the separate wallet server processes at once, before
+    /// shedding calls.
+    ///
+    /// Only used if the mode is `external`.
+    #[arg(long = "wallet.http.max-in-flight", value_name = "COUNT", default_value_t = 512)]
+    pub http_max_in_flight: usize,
+    /// The average latency in milliseconds above which the separate wallet server sheds read-only
+    /// calls.
+    ///
+    /// Only used if the mode is `external`.
+    #[arg(long = "wallet.http.max-latency", value_name = "MS", value_parser = parse_duration_ms, default_value = "2000")]
+    pub http_max_latency: Duration,
+    /// The time in milliseconds calls that send transactions wait for capacity on the separate
+    /// wallet server.
+    ///
+    /// Only used if the mode is `external`.
+    #[arg(long = "wallet.http.send-deadline", value_name = "MS", value_parser = parse_duration_ms, default_value = "5000")]
+    pub http_send_deadline: Duration,
+    /// A namespace whose raw requests and responses are logged for debugging, e.g. `wallet`.
+    ///
+    /// Can be passed multiple times. The values of sensitive fields are redacted.
+    ///
+    /// Only used if the mode is `external`.
+    #[arg(long = "wallet.http.debug-log", value_name = "NAMESPACE")]
+    pub debug_log_namespaces: Vec<String>,
+    /// Only one of every `N` calls to the namespaces of `--wallet.http.debug-log` is logged.
+    ///
+    /// Only used if the mode is `external`.
+    #[arg(long = "wallet.http.debug-log-sample", value_name = "N", default_value_t = 1)]
+    pub debug_log_sample: u64,
+    /// A field whose values are redacted in the debug logs, in addition to signatures, keys,
+    /// secrets, passwords and API keys.
+    ///
+    /// Can be passed multiple times.
+    ///
+    /// Only used if the mode is `external`.
+    #[arg(long = "wallet.http.debug-log-redact", value_name = "FIELD")]
+    pub debug_log_redacted_fields: Vec<String>,
+    /// Sponsors transactions deterministically, so that the hashes of sponsored transactions are
+    /// reproducible: fees are fixed, nonces are counted from the nonce of the sponsor at startup,
+    /// and quotes expire relative to a clock frozen at the unix epoch.
+    ///
+    /// This is only intended for tests and devnets.
+    #[arg(long = "wallet.deterministic")]
+    pub deterministic: bool,
+    /// The maximum gas of sponsored transactions that wait for inclusion at once.
+    ///
+    /// New sponsorships wait for pending sponsored transactions to be included if the budget is
+    /// exhausted. There is no limit if this is not set.
+    #[arg(long = "wallet.max-sponsored-gas-per-block", value_name = "GAS")]
+    pub max_sponsored_gas_per_block: Option<u64>,
+    /// How requests for legacy and EIP-2930 transactions are sponsored: `disabled` rejects them,
+    /// `upgrade` sponsors them as EIP-1559 transactions, and `preserve` sponsors them as
+    /// requested.
+    #[arg(long = "wallet.legacy-compatibility", value_name = "MODE", value_parser = ["disabled", "upgrade", "preserve"], default_value = "disabled")]
+    pub legacy_compatibility: String,
+    /// The block whose state sponsored transactions are estimated against, e.g. `latest` or
+    /// `pending`.
+    ///
+    /// Estimating against `pending` accounts for transactions that are waiting in the transaction
+    /// pool, such as earlier transactions of the same account.
+    #[arg(long = "wallet.estimate-block", value_name = "BLOCK", default_value = "latest")]
+    pub estimate_block: BlockId,
+    /// How authorizations with a chain ID of 0, which are valid on every chain, are sponsored:
+    /// `accept` sponsors them, `reject` rejects them, and `restrict` only sponsors them if they
+    /// delegate to an implementation passed with `--wallet.wildcard-implementation`.
+    #[arg(long = "wallet.wildcard-authorizations", value_name = "MODE", value_parser = ["accept", "reject", "restrict"], default_value = "accept")]
+    pub wildcard_authorizations: String,
+    /// An implementation that authorizations with a chain ID of 0 may delegate to in `restrict`
+    /// mode.
+    ///
+    /// Can be passed multiple times.
+    #[arg(long = "wallet.wildcard-implementation", value_name = "ADDRESS")]
+    pub wildcard_implementations: Vec<Address>,
+    /// A function that is sponsored for an implementation, in the form
+    /// `<implementation>:<selector>`, where the selector is the 4-byte selector, a function
+    /// signature such as `execute(bytes32,bytes)`, or `*` to allow any call.
+    ///
+    /// Can be passed multiple times. If this is set, only calls to accounts delegating to one of
+    /// the implementations with an allowed selector are sponsored.
+    #[arg(long = "wallet.allowed-selector", value_name = "IMPLEMENTATION:SELECTOR")]
+    pub allowed_selectors: Vec<String>,
+    /// An implementation whose sponsored transactions and gas are exported as metrics, labeled by
+    /// the implementation.
+    ///
+    /// Can be passed multiple times.
+    #[arg(long = "wallet.tracked-implementation", value_name = "ADDRESS")]
+    pub tracked_implementations: Vec<Address>,
+    /// Cache the delegations of sponsored destinations until a canonical block changes their code,
+    /// instead of reading the code of the destination for every request.
+    #[arg(long = "wallet.delegation-cache")]
+    pub delegation_cache: bool,
+    /// The JSON file of the allow and deny lists of authorities, destinations, implementations
+    /// and selectors.
+    ///
+    /// The file is created on the first change through the `admin_` namespace, and reloaded when
+    /// it is modified. No requests are denied if this is not set.
+    #[arg(long = "wallet.access-lists", value_name = "PATH")]
+    pub access_lists: Option<PathBuf>,
+    /// The URL of a wallet service to forward the `wallet_` namespace to, e.g. the sequencer or a
+    /// relay.
+    ///
+    /// If this is set, the node does not sponsor transactions itself.
+    #[arg(long = "wallet.forward-url", value_name = "URL")]
+    pub forward_url: Option<String>,
+    /// The timeout of forwarded requests in milliseconds.
+    #[arg(long = "wallet.forward-timeout", value_name = "MS", value_parser = parse_duration_ms, default_value = "10000")]
+    pub forward_timeout: Duration,
+    /// The number of times a forwarded request is retried if the wallet service is unreachable.
+    #[arg(long = "wallet.forward-retries", value_name = "COUNT", default_value_t = 3)]
+    pub forward_retries: usize,
+}
+
+/// Arguments for the canonical event stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Args)]
+#[command(next_help_heading = "Event stream")]
+pub struct OdysseyEventsArgs {
+    /// The URL to post canonical chain events to, e.g. a webhook that feeds a message queue.
+    ///
+    /// If this is not set, the event stream is disabled.
+    #[arg(long = "events.webhook-url", value_name = "URL")]
+    pub webhook_url: Option<String>,
+    /// A URL to post new canonical heads to.
+    ///
+    /// Can be passed multiple times. Unlike the event stream, delivery is best-effort.
+    #[arg(long = "events.head-webhook-url", value_name = "URL")]
+    pub head_webhook_urls: Vec<String>,
+    /// The secret to sign head updates with, using HMAC-SHA256.
+    #[arg(
+        long = "events.head-webhook-secret",
+        value_name = "SECRET",
+        requires = "head_webhook_urls"
+    )]
+    pub head_webhook_secret: Option<String>,
+}
+
+/// Arguments overriding the chain spec of development chains.
+///
+/// The block time of development chains is configured with `--dev.block-time`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Args)]
+#[command(next_help_heading = "Dev")]
+pub struct OdysseyDevArgs {
+    /// The gas limit of the genesis block, which is inherited by all following blocks.
+    #[arg(long = "dev.block-gas-limit", value_name = "GAS")]
+    pub block_gas_limit: Option<u64>,
+    /// The base fee parameters, in the form `<max change denominator>,<elasticity multiplier>`,
+    /// e.g. `50,6` for the OP Stack parameters.
+    #[arg(long = "dev.base-fee-params", value_name = "DENOMINATOR,ELASTICITY", value_parser = parse_base_fee_params)]
+    pub base_fee_params: Option<BaseFeeParams>,
+}
+
+impl OdysseyDevArgs {
+    /// Applies the overrides to the given chain spec.
+    ///
+    /// The overrides change the genesis of the chain, so they are rejected unless the node runs in
+    /// dev mode or on the dev chain.
+    pub fn apply(&self, chain_spec: &mut OpChainSpec, dev: bool) -> eyre::Result<()> {
+        if *self == Self::default() {
+            return Ok(());
+        }
+        if !dev && chain_spec.genesis_hash() != ODYSSEY_DEV.genesis_hash() {
+            eyre::bail!("the --dev.* overrides require --dev or the dev chain");
+        }
+        if let Some(gas_limit) = self.block_gas_limit {
+            let mut header = chain_spec.genesis_header().clone();
+            header.gas_limit = gas_limit;
+            chain_spec.inner.genesis.gas_limit = gas_limit;
+            chain_spec.inner.genesis_header = once_cell_set(header);
+            // the genesis hash is derived from the genesis header
+            chain_spec.inner.genesis_hash = Default::default();
+        }
+        if let Some(base_fee_params) = self.base_fee_params {
+            chain_spec.inner.base_fee_params = BaseFeeParamsKind::Constant(base_fee_params);
+        }
+        Ok(())
+    }
+}
+
+/// Parses base fee parameters in the form `<max change denominator>,<elasticity multiplier>`.
+fn parse_base_fee_params(arg: &str) -> Result<BaseFeeParams, String> {
+    let (denominator, elasticity) = arg
+        .split_once(',')
+        .ok_or_else(|| format!("expected <denominator>,<elasticity>, got {arg}"))?;
+    let parse = |value: &str| value.trim().parse::<u128>().map_err(|err| format!("{value}: {err}"));
+    Ok(BaseFeeParams::new(parse(denominator)?, parse(elasticity)?))
+}
+
+/// Parses a duration in milliseconds.
+fn parse_duration_ms(arg: &str) -> Result<Duration, std::num::ParseIntError> {
+    arg.parse().map(Duration::from_millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct CommandParser<T: Args> {
+        #[command(flatten)]
+        args: T,
+    }
+
+    #[test]
+    fn rpc_args_default() {
+        let args = CommandParser::<OdysseyRpcArgs>::parse_from(["odyssey"]).args;
+        assert_eq!(args, OdysseyRpcArgs::default());
+    }
+
+    #[test]
+    fn rpc_args_disable() {
+        let args = CommandParser::<OdysseyRpcArgs>::parse_from([
+            "odyssey",
+            "--rpc.eth-overrides",
+            "false",
+        ])
+        .args;
+        assert_eq!(args, OdysseyRpcArgs { eth_overrides: false, ..Default::default() });
+
+        let args = CommandParser::<OdysseyRpcArgs>::parse_from([
+            "odyssey",
+            "--rpc.sponsor-address",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc.sponsor-address",
+            "0x0000000000000000000000000000000000000002",
+        ])
+        .args;
+        assert_eq!(
+            args.sponsor_addresses,
+            vec![Address::with_last_byte(1), Address::with_last_byte(2)]
+        );
+    }
+
+    #[test]
+    fn wallet_args_mode() {
+        let args = CommandParser::<OdysseyWalletArgs>::parse_from(["odyssey"]).args;
+        assert_eq!(args.mode, WalletMode::Embedded);
+
+        let args = CommandParser::<OdysseyWalletArgs>::parse_from([
+            "odyssey",
+            "--wallet.mode",
+            "external",
+            "--wallet.http.port",
+            "9120",
+        ])
+        .args;
+        assert_eq!(args.mode, WalletMode::External);
+        assert_eq!(args.http_port, 9120);
+        assert_eq!(args.http_max_in_flight, 512);
+        assert_eq!(args.http_send_deadline, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn wallet_args_forward() {
+        let args = CommandParser::<OdysseyWalletArgs>::parse_from([
+            "odyssey",
+            "--wallet.forward-url",
+            "http://sequencer:8545",
+            "--wallet.forward-timeout",
+            "500",
+        ])
+        .args;
+        assert_eq!(args.forward_url.as_deref(), Some("http://sequencer:8545"));
+        assert_eq!(args.forward_timeout, Duration::from_millis(500));
+        assert_eq!(args.forward_retries, 3);
+    }
+
+    #[test]
+    fn events_args() {
+        let args = CommandParser::<OdysseyEventsArgs>::parse_from(["odyssey"]).args;
+        assert_eq!(args, OdysseyEventsArgs::default());
+
+        let args = CommandParser::<OdysseyEventsArgs>::parse_from([
+            "odyssey",
+            "--events.webhook-url",
+            "http://localhost:8080/events",
+        ])
+        .args;
+        assert_eq!(args.webhook_url.as_deref(), Some("http://localhost:8080/events"));
+
+        let args = CommandParser::<OdysseyEventsArgs>::parse_from([
+            "odyssey",
+            "--events.head-webhook-url",
+            "http://localhost:8080/a",
+            "--events.head-webhook-url",
+            "http://localhost:8080/b",
+            "--events.head-webhook-secret",
+            "secret",
+        ])
+        .args;
+        assert_eq!(args.head_webhook_urls.len(), 2);
+        assert_eq!(args.head_webhook_secret.as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn dev_args() {
+        let args = CommandParser::<OdysseyDevArgs>::parse_from([
+            "odyssey",
+            "--dev.block-gas-limit",
+            "100000000",
+            "--dev.base-fee-params",
+            "50,6",
+        ])
+        .args;
+        assert_eq!(
+            args,
+            OdysseyDevArgs {
+                block_gas_limit: Some(100_000_000),
+                base_fee_params: Some(BaseFeeParams::new(50, 6)),
+            }
+        );
+
+        assert!(CommandParser::<OdysseyDevArgs>::try_parse_from([
+            "odyssey",
+            "--dev.base-fee-params",
+            "50"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn dev_args_apply() {
+        let mut chain_spec = (**ODYSSEY_DEV).clone();
+        let genesis_hash = chain_spec.genesis_hash();
+
+        let args = OdysseyDevArgs { block_gas_limit: Some(1_000_000_000), base_fee_params: None };
+        args.apply(&mut chain_spec, false).unwrap();
+        assert_eq!(chain_spec.genesis_header().gas_limit, 1_000_000_000);
+        assert_ne!(chain_spec.genesis_hash(), genesis_hash);
+
+        // the overrides are rejected on other chains unless the node runs in dev mode
+        let mut chain_spec = (**crate::chainspec::ODYSSEY_MAINNET).clone();
+        assert!(args.apply(&mut chain_spec, false).is_err());
+        assert!(OdysseyDevArgs::default().apply(&mut chain_spec, false).is_ok());
+        assert!(args.apply(&mut chain_spec, true).is_ok());
+    }
+}
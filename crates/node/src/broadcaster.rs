@@ -1,31 +1,77 @@
 //! Sponsor periodic broadcaster
 
 use alloy_primitives::Address;
+use odyssey_common::Clock;
 use reth_network::{transactions::TransactionsHandle, NetworkPrimitives};
 use reth_transaction_pool::TransactionPool;
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 /// Periodically broadcasts sponsored transactions from the transaction pool.
 ///
 /// `p2p` broadcasting can potentially be flaky, and due to the p2p rules, some txs may never make
 /// it to the sequencer, this can happen if a message is dropped internally when channel bounds are
-/// enforced for example. So, we re-broadcast them every 10 minutes.
+/// enforced for example. So, we re-broadcast them at the given interval, as measured by the given
+/// [`Clock`].
 pub async fn periodic_broadcaster<P, N>(
     address: Address,
     pool: P,
     transactions_handle: TransactionsHandle<N>,
+    interval: Duration,
+    clock: Arc<dyn Clock>,
 ) where
     P: TransactionPool,
     N: NetworkPrimitives,
 {
-    let mut interval_timer = tokio::time::interval(Duration::from_secs(60));
-
-    loop {
+    run_every(&*clock, interval, || {
         let transactions =
             pool.get_transactions_by_sender(address).into_iter().map(|tx| *tx.hash()).collect();
 
         transactions_handle.propagate_transactions(transactions);
+    })
+    .await
+}
+
+/// Calls the function immediately, and then whenever the interval has elapsed on the clock.
+async fn run_every(clock: &dyn Clock, interval: Duration, mut f: impl FnMut()) {
+    let mut next = clock.now();
+    loop {
+        let now = clock.now();
+        if now >= next {
+            f();
+            next = now + interval;
+        }
+        tokio::time::sleep(next.saturating_duration_since(now)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odyssey_common::MockClock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn broadcasts_at_the_clock_interval() {
+        let interval = Duration::from_secs(10);
+        let clock = Arc::new(MockClock::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+        tokio::spawn({
+            let clock = clock.clone();
+            let calls = calls.clone();
+            async move {
+                run_every(&*clock, interval, || {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                })
+                .await
+            }
+        });
+
+        // the first broadcast is immediate, the next one waits for the clock
+        tokio::time::sleep(interval * 3).await;
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
 
-        interval_timer.tick().await;
+        clock.advance(interval);
+        tokio::time::sleep(interval).await;
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
     }
 }
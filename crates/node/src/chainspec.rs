@@ -1,5 +1,7 @@
 //! Odyssey chainspec parsing logic.
-use alloy_primitives::{b256, U256};
+use crate::forks::OdysseyHardfork;
+use alloy_primitives::{b256, Address, U256};
+use odyssey_common::WITHDRAWAL_CONTRACT;
 use reth_chainspec::{
     once_cell_set, BaseFeeParams, BaseFeeParamsKind, Chain, ChainHardforks, ChainSpec,
     EthereumHardfork, ForkCondition, NamedChain,
@@ -7,6 +9,7 @@ use reth_chainspec::{
 use reth_cli::chainspec::{parse_genesis, ChainSpecParser};
 use reth_optimism_chainspec::OpChainSpec;
 use reth_optimism_forks::OpHardfork;
+use serde::Deserialize;
 use std::sync::{Arc, LazyLock};
 
 /// Odyssey forks.
@@ -34,6 +37,7 @@ pub static ODYSSEY_FORKS: LazyLock<ChainHardforks> = LazyLock::new(|| {
         (OpHardfork::Ecotone.boxed(), ForkCondition::Timestamp(0)),
         (OpHardfork::Canyon.boxed(), ForkCondition::Timestamp(0)),
         (EthereumHardfork::Prague.boxed(), ForkCondition::Timestamp(0)),
+        (OdysseyHardfork::Exp1.boxed(), ForkCondition::Timestamp(0)),
     ])
 });
 
@@ -44,7 +48,13 @@ pub static ODYSSEY_DEV: LazyLock<Arc<OpChainSpec>> = LazyLock::new(|| {
         genesis: serde_json::from_str(include_str!("../../../etc/dev-genesis.json"))
             .expect("Can't deserialize odyssey genesis json"),
         paris_block_and_final_difficulty: Some((0, U256::ZERO)),
-        hardforks: ODYSSEY_FORKS.clone(),
+        hardforks: {
+            // the dev chain starts from scratch, so it runs the experiments that are not scheduled
+            // on Odyssey mainnet from genesis as well
+            let mut hardforks = ODYSSEY_FORKS.clone();
+            hardforks.insert(OdysseyHardfork::Exp2, ForkCondition::Timestamp(0));
+            hardforks
+        },
         base_fee_params: BaseFeeParamsKind::Constant(BaseFeeParams::ethereum()),
         deposit_contract: None,
         ..Default::default()
@@ -78,6 +88,67 @@ pub static ODYSSEY_MAINNET: LazyLock<Arc<OpChainSpec>> = LazyLock::new(|| {
     .into()
 });
 
+/// The Odyssey specific fields of the genesis config, under the `odyssey` key.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OdysseyGenesisInfo {
+    /// The address of the withdrawal contract.
+    withdrawal_contract: Option<Address>,
+    /// The defaults of the Odyssey subsystems on this chain.
+    #[serde(default)]
+    parameters: OdysseyChainParameters,
+}
+
+impl OdysseyGenesisInfo {
+    /// Reads the Odyssey specific fields from the genesis config of the chain.
+    fn from_chain_spec(chain_spec: &OpChainSpec) -> eyre::Result<Self> {
+        Ok(chain_spec
+            .inner
+            .genesis
+            .config
+            .extra_fields
+            .get_deserialized::<Self>("odyssey")
+            .transpose()?
+            .unwrap_or_default())
+    }
+}
+
+/// The defaults of the Odyssey subsystems on a chain, under the `odyssey.parameters` key of the
+/// genesis config.
+///
+/// This lets a chain, e.g. a devnet, use different defaults than Odyssey mainnet. The parameters
+/// only apply to settings that are not set in the configuration file, the environment or on the
+/// command line.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct OdysseyChainParameters {
+    /// The maximum delay of `engine_getPayload` into the slot in milliseconds.
+    pub payload_max_delay_ms: Option<u64>,
+    /// The time in milliseconds the best payload must not change for before `engine_getPayload`
+    /// is resolved early.
+    pub payload_settle_ms: Option<u64>,
+    /// The maximum gas of sponsored transactions that wait for inclusion at once.
+    pub max_sponsored_gas_per_block: Option<u64>,
+    /// The maximum number of outstanding wallet requests per account.
+    pub max_pending_per_account: Option<usize>,
+    /// The maximum cost of a sponsored transaction in wei, including the L1 data fee.
+    pub max_sponsored_cost: Option<U256>,
+}
+
+/// Returns the address of the withdrawal contract of the chain.
+///
+/// Custom deployments can set the address in the `odyssey.withdrawalContract` field of the genesis
+/// config. Otherwise, this is the [`WITHDRAWAL_CONTRACT`] predeploy.
+pub fn withdrawal_contract(chain_spec: &OpChainSpec) -> eyre::Result<Address> {
+    let info = OdysseyGenesisInfo::from_chain_spec(chain_spec)?;
+    Ok(info.withdrawal_contract.unwrap_or(WITHDRAWAL_CONTRACT))
+}
+
+/// Returns the defaults of the Odyssey subsystems on the chain, see [`OdysseyChainParameters`].
+pub fn chain_parameters(chain_spec: &OpChainSpec) -> eyre::Result<OdysseyChainParameters> {
+    Ok(OdysseyGenesisInfo::from_chain_spec(chain_spec)?.parameters)
+}
+
 /// Odyssey chain specification parser.
 #[derive(Debug, Clone, Default)]
 pub struct OdysseyChainSpecParser;
@@ -102,6 +173,13 @@ impl ChainSpecParser for OdysseyChainSpecParser {
                     .hardforks
                     .insert(EthereumHardfork::Prague, ForkCondition::Timestamp(0));
 
+                // the first Odyssey experiments are active from genesis, unless overridden on the
+                // CLI, later experiments have to be scheduled with `--override.fork`
+                chainspec
+                    .inner
+                    .hardforks
+                    .insert(OdysseyHardfork::Exp1, ForkCondition::Timestamp(0));
+
                 // NOTE(onbjerg): op-node will fetch the genesis block and check that the hash
                 // matches whatever is in the L2 rollup config, which it will not when we activate
                 // Prague, since the autogenerated genesis header will include a requests hash of
@@ -121,8 +199,14 @@ impl ChainSpecParser for OdysseyChainSpecParser {
 mod tests {
     use std::path::PathBuf;
 
-    use super::OdysseyChainSpecParser;
-    use reth_chainspec::EthereumHardforks;
+    use super::{
+        chain_parameters, withdrawal_contract, OdysseyChainParameters, OdysseyChainSpecParser,
+        ODYSSEY_DEV,
+    };
+    use crate::forks::OdysseyHardfork;
+    use alloy_primitives::{address, U256};
+    use odyssey_common::WITHDRAWAL_CONTRACT;
+    use reth_chainspec::{EthereumHardforks, ForkCondition, Hardforks};
     use reth_cli::chainspec::ChainSpecParser;
     use reth_optimism_forks::OpHardforks;
 
@@ -139,5 +223,49 @@ mod tests {
             chain_spec.is_prague_active_at_timestamp(0),
             "prague should be active at timestamp 0"
         );
+        assert!(
+            chain_spec.fork(OdysseyHardfork::Exp1).active_at_timestamp(0),
+            "exp1 should be active at timestamp 0"
+        );
+        assert_eq!(
+            chain_spec.fork(OdysseyHardfork::Exp2),
+            ForkCondition::Never,
+            "exp2 should not be scheduled"
+        );
+    }
+
+    #[test]
+    fn withdrawal_contract_from_genesis() {
+        assert_eq!(withdrawal_contract(&ODYSSEY_DEV).unwrap(), WITHDRAWAL_CONTRACT);
+
+        let mut genesis: serde_json::Value =
+            serde_json::from_str(include_str!("../../../etc/odyssey-genesis.json")).unwrap();
+        let custom = address!("0000000000000000000000000000000000000011");
+        genesis["config"]["odyssey"] = serde_json::json!({ "withdrawalContract": custom });
+
+        let chain_spec = OdysseyChainSpecParser::parse(&genesis.to_string()).unwrap();
+        assert_eq!(withdrawal_contract(&chain_spec).unwrap(), custom);
+    }
+
+    #[test]
+    fn chain_parameters_from_genesis() {
+        assert_eq!(chain_parameters(&ODYSSEY_DEV).unwrap(), OdysseyChainParameters::default());
+
+        let mut genesis: serde_json::Value =
+            serde_json::from_str(include_str!("../../../etc/odyssey-genesis.json")).unwrap();
+        genesis["config"]["odyssey"] = serde_json::json!({
+            "parameters": { "payloadMaxDelayMs": 0, "maxSponsoredCost": "0x3e8" }
+        });
+        let chain_spec = OdysseyChainSpecParser::parse(&genesis.to_string()).unwrap();
+        let parameters = chain_parameters(&chain_spec).unwrap();
+        assert_eq!(parameters.payload_max_delay_ms, Some(0));
+        assert_eq!(parameters.max_sponsored_cost, Some(U256::from(1_000)));
+        assert_eq!(parameters.max_pending_per_account, None);
+        assert_eq!(withdrawal_contract(&chain_spec).unwrap(), WITHDRAWAL_CONTRACT);
+
+        // typos are not silently ignored
+        genesis["config"]["odyssey"] = serde_json::json!({ "parameters": { "maxDelay": 0 } });
+        let chain_spec = OdysseyChainSpecParser::parse(&genesis.to_string()).unwrap();
+        assert!(chain_parameters(&chain_spec).is_err());
     }
 }
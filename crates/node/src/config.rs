@@ -0,0 +1,343 @@
+//! Odyssey configuration file.
+//!
+//! Settings of the Odyssey subsystems that are not part of the reth configuration are read from a
+//! TOML file passed with `--odyssey.config`. Every setting is optional, and the environment
+//! variables below override the file:
+//!
+//! - [`SPONSOR_KEY_ENV`]: the secret key of the sponsor, instead of `sponsor.key_path`.
+//! - [`PAYLOAD_DELAY_ENV`]: `payload.max_delay_ms`.
+//! - [`BROADCAST_INTERVAL_ENV`]: `broadcaster.interval_ms`.
+//!
+//! The payload and wallet settings that are set neither in the file nor in the environment fall
+//! back to the [`OdysseyChainParameters`] of the chain, see
+//! [`OdysseyConfig::apply_chain_parameters`].
+//!
+//! ```toml
+//! [sponsor]
+//! key_path = "/run/secrets/sponsor-key"
+//!
+//! [payload]
+//! max_delay_ms = 500
+//! settle_ms = 100
+//!
+//! [broadcaster]
+//! interval_ms = 60000
+//!
+//! [wallet]
+//! max_sponsored_gas_per_block = 3000000
+//! max_pending_per_account = 8
+//! max_sponsored_cost = "10000000000000000"
+//!
+//! [rpc]
+//! walltime = true
+//! eth_overrides = true
+//! odyssey = true
+//! ```
+
+use crate::{
+    chainspec::OdysseyChainParameters,
+    delayed_resolve::{DEFAULT_PAYLOAD_SETTLE_TIME, MAX_DELAY_INTO_SLOT},
+};
+use alloy_primitives::U256;
+use eyre::Context;
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// The environment variable with the secret key of the sponsor.
+pub const SPONSOR_KEY_ENV: &str = "EXP1_SK";
+
+/// The environment variable with the maximum delay of `engine_getPayload` into the slot in
+/// milliseconds.
+pub const PAYLOAD_DELAY_ENV: &str = "MAX_PAYLOAD_DELAY";
+
+/// The environment variable with the interval at which sponsored transactions are rebroadcast in
+/// milliseconds.
+pub const BROADCAST_INTERVAL_ENV: &str = "BROADCAST_INTERVAL";
+
+/// The default interval at which sponsored transactions are rebroadcast.
+pub const DEFAULT_BROADCAST_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The Odyssey configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OdysseyConfig {
+    /// The sponsor of the wallet service.
+    pub sponsor: SponsorConfig,
+    /// The delay of `engine_getPayload`.
+    pub payload: PayloadConfig,
+    /// The rebroadcasting of sponsored transactions.
+    pub broadcaster: BroadcasterConfig,
+    /// The limits of the wallet service.
+    pub wallet: WalletConfig,
+    /// The Odyssey RPC extensions.
+    pub rpc: RpcConfig,
+}
+
+impl OdysseyConfig {
+    /// Loads the configuration from the given file, or the default configuration if no file is
+    /// given, and applies the overrides of the environment.
+    pub fn load(path: Option<&Path>) -> eyre::Result<Self> {
+        let mut config = match path {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+                toml::from_str(&contents)
+                    .wrap_err_with(|| format!("invalid configuration in {}", path.display()))?
+            }
+            None => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks that the settings are valid.
+    pub fn validate(&self) -> eyre::Result<()> {
+        if self.broadcaster.interval_ms == 0 {
+            eyre::bail!(
+                "broadcaster.interval_ms ({BROADCAST_INTERVAL_ENV}) must be positive, got 0"
+            );
+        }
+        Ok(())
+    }
+
+    /// Applies the overrides of the environment, reading the variables with the given function.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> eyre::Result<()> {
+        if let Some(key) = var(SPONSOR_KEY_ENV) {
+            self.sponsor.key = Some(key);
+        }
+        if let Some(delay) = var(PAYLOAD_DELAY_ENV) {
+            self.payload.max_delay_ms =
+                Some(delay.parse().wrap_err_with(|| format!("invalid {PAYLOAD_DELAY_ENV}"))?);
+        }
+        if let Some(interval) = var(BROADCAST_INTERVAL_ENV) {
+            self.broadcaster.interval_ms =
+                interval.parse().wrap_err_with(|| format!("invalid {BROADCAST_INTERVAL_ENV}"))?;
+        }
+        Ok(())
+    }
+
+    /// Applies the defaults of the chain to the payload and wallet settings that are not set.
+    pub fn apply_chain_parameters(&mut self, parameters: &OdysseyChainParameters) {
+        self.payload.max_delay_ms = self.payload.max_delay_ms.or(parameters.payload_max_delay_ms);
+        self.payload.settle_ms = self.payload.settle_ms.or(parameters.payload_settle_ms);
+        self.wallet.max_sponsored_gas_per_block =
+            self.wallet.max_sponsored_gas_per_block.or(parameters.max_sponsored_gas_per_block);
+        self.wallet.max_pending_per_account =
+            self.wallet.max_pending_per_account.or(parameters.max_pending_per_account);
+        self.wallet.max_sponsored_cost =
+            self.wallet.max_sponsored_cost.or(parameters.max_sponsored_cost);
+    }
+}
+
+/// Configuration of the sponsor of the wallet service.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SponsorConfig {
+    /// The file containing the hex-encoded secret key of the sponsor.
+    pub key_path: Option<PathBuf>,
+    /// The hex-encoded secret key of the sponsor, which takes precedence over the key file.
+    ///
+    /// This is only read from the environment, so the key is not stored in the configuration
+    /// file.
+    #[serde(skip)]
+    pub key: Option<String>,
+}
+
+impl SponsorConfig {
+    /// Returns the hex-encoded secret key of the sponsor, if one is configured.
+    pub fn secret_key(&self) -> eyre::Result<Option<String>> {
+        if let Some(key) = &self.key {
+            return Ok(Some(key.clone()));
+        }
+        self.key_path
+            .as_deref()
+            .map(|path| {
+                std::fs::read_to_string(path)
+                    .map(|key| key.trim().to_string())
+                    .wrap_err_with(|| format!("failed to read sponsor key from {}", path.display()))
+            })
+            .transpose()
+    }
+}
+
+/// Configuration of the delay of `engine_getPayload`.
+///
+/// The defaults of the payload delay apply to unset settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PayloadConfig {
+    /// The maximum delay of `engine_getPayload` into the slot in milliseconds, giving the payload
+    /// builder more time to build the block.
+    pub max_delay_ms: Option<u64>,
+    /// The time in milliseconds the best payload must not change for before `engine_getPayload`
+    /// is resolved early.
+    ///
+    /// A value of zero always delays `engine_getPayload` up to `max_delay_ms`.
+    pub settle_ms: Option<u64>,
+}
+
+impl PayloadConfig {
+    /// Returns the maximum delay of `engine_getPayload` into the slot.
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay_ms.map_or(MAX_DELAY_INTO_SLOT, Duration::from_millis)
+    }
+
+    /// Returns the time the best payload must not change for before it is resolved early.
+    pub fn settle_time(&self) -> Duration {
+        self.settle_ms.map_or(DEFAULT_PAYLOAD_SETTLE_TIME, Duration::from_millis)
+    }
+}
+
+/// Configuration of the rebroadcasting of sponsored transactions.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BroadcasterConfig {
+    /// The interval at which sponsored transactions in the pool are rebroadcast in milliseconds.
+    pub interval_ms: u64,
+}
+
+impl BroadcasterConfig {
+    /// Returns the interval at which sponsored transactions are rebroadcast.
+    pub const fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+}
+
+impl Default for BroadcasterConfig {
+    fn default() -> Self {
+        Self { interval_ms: DEFAULT_BROADCAST_INTERVAL.as_millis() as u64 }
+    }
+}
+
+/// Configuration of the limits of the wallet service.
+///
+/// The defaults of the wallet service apply to unset limits.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WalletConfig {
+    /// The maximum gas of sponsored transactions that wait for inclusion at once.
+    ///
+    /// `--wallet.max-sponsored-gas-per-block` takes precedence over this.
+    pub max_sponsored_gas_per_block: Option<u64>,
+    /// The maximum number of outstanding requests per account.
+    pub max_pending_per_account: Option<usize>,
+    /// The maximum cost of a sponsored transaction in wei, including the L1 data fee.
+    pub max_sponsored_cost: Option<U256>,
+}
+
+/// Configuration of the Odyssey RPC extensions.
+///
+/// An extension is only registered if it is enabled both here and on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfig {
+    /// Whether to enable the `odyssey_getWallTimeData` endpoint.
+    pub walltime: bool,
+    /// Whether to enable the Odyssey overrides of the `eth_` namespace.
+    pub eth_overrides: bool,
+    /// Whether to enable the `odyssey_` namespace.
+    pub odyssey: bool,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self { walltime: true, eth_overrides: true, odyssey: true }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_config() {
+        let config: OdysseyConfig = toml::from_str(
+            r#"
+            [payload]
+            max_delay_ms = 250
+            settle_ms = 50
+
+            [wallet]
+            max_sponsored_gas_per_block = 3000000
+            max_sponsored_cost = "1000"
+
+            [rpc]
+            walltime = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.payload.max_delay(), Duration::from_millis(250));
+        assert_eq!(config.payload.settle_time(), Duration::from_millis(50));
+        assert_eq!(config.broadcaster, BroadcasterConfig::default());
+        assert_eq!(config.wallet.max_sponsored_gas_per_block, Some(3_000_000));
+        assert_eq!(config.wallet.max_sponsored_cost, Some(U256::from(1_000)));
+        assert_eq!(config.wallet.max_pending_per_account, None);
+        assert!(!config.rpc.walltime);
+        assert!(config.rpc.eth_overrides);
+
+        // typos are not silently ignored
+        assert!(toml::from_str::<OdysseyConfig>("[payload]\nmax_delay = 250").is_err());
+    }
+
+    #[test]
+    fn environment_overrides_file() {
+        let mut config = OdysseyConfig::default();
+        config.sponsor.key_path = Some(PathBuf::from("/nonexistent"));
+        config
+            .apply_env(|name| match name {
+                SPONSOR_KEY_ENV => Some("0x01".to_string()),
+                PAYLOAD_DELAY_ENV => Some("100".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(config.sponsor.secret_key().unwrap().as_deref(), Some("0x01"));
+        assert_eq!(config.payload.max_delay(), Duration::from_millis(100));
+        assert_eq!(config.broadcaster.interval(), DEFAULT_BROADCAST_INTERVAL);
+
+        assert!(config.apply_env(|_| Some("soon".to_string())).is_err());
+    }
+
+    #[test]
+    fn rejects_zero_broadcast_interval() {
+        assert!(OdysseyConfig::default().validate().is_ok());
+
+        let mut config: OdysseyConfig = toml::from_str("[broadcaster]\ninterval_ms = 0").unwrap();
+        assert!(config.validate().is_err());
+
+        config
+            .apply_env(|name| (name == BROADCAST_INTERVAL_ENV).then(|| "1000".to_string()))
+            .unwrap();
+        assert!(config.validate().is_ok());
+        config.apply_env(|name| (name == BROADCAST_INTERVAL_ENV).then(|| "0".to_string())).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn chain_parameters_fill_unset_settings() {
+        let mut config: OdysseyConfig = toml::from_str(
+            "[payload]\nmax_delay_ms = 250\n\n[wallet]\nmax_pending_per_account = 4",
+        )
+        .unwrap();
+        config.apply_chain_parameters(&OdysseyChainParameters {
+            payload_max_delay_ms: Some(0),
+            payload_settle_ms: Some(0),
+            max_pending_per_account: Some(16),
+            max_sponsored_cost: Some(U256::from(1_000)),
+            ..Default::default()
+        });
+        assert_eq!(config.payload.max_delay(), Duration::from_millis(250));
+        assert_eq!(config.payload.settle_time(), Duration::ZERO);
+        assert_eq!(config.wallet.max_pending_per_account, Some(4));
+        assert_eq!(config.wallet.max_sponsored_cost, Some(U256::from(1_000)));
+        assert_eq!(config.wallet.max_sponsored_gas_per_block, None);
+
+        let mut config = OdysseyConfig::default();
+        config.apply_chain_parameters(&OdysseyChainParameters::default());
+        assert_eq!(config.payload.max_delay(), MAX_DELAY_INTO_SLOT);
+        assert_eq!(config.payload.settle_time(), DEFAULT_PAYLOAD_SETTLE_TIME);
+    }
+}
@@ -1,23 +1,70 @@
 //! Helper that delays resolving the payload
+//!
+//! `engine_getPayload` is delayed up to a maximum delay into the slot, to give the payload builder
+//! more time to build the block. If the progress of payload jobs can be observed, the best payload
+//! of the requested job is polled while the call is delayed, and the call is resolved early once
+//! the payload stopped improving for the settle time.
 
-use futures::{Stream, StreamExt};
+use alloy_primitives::U256;
+use alloy_rpc_types_engine::PayloadId;
+use futures::{future::BoxFuture, Stream, StreamExt};
 use jsonrpsee::{
     core::traits::ToRpcParams,
     types::{error::INVALID_PARAMS_CODE, ErrorObject, Params},
     MethodsError, RpcModule,
 };
+use odyssey_common::{Clock, SystemClock};
 use parking_lot::Mutex;
 use reth_chain_state::CanonStateNotification;
+use reth_optimism_node::OpEngineTypes;
+use reth_payload_builder::PayloadBuilderHandle;
 use serde::de::Error;
 use serde_json::value::RawValue;
 use std::{
+    collections::HashMap,
+    fmt::Debug,
     sync::Arc,
     time::{Duration, Instant},
 };
+use tracing::{debug, instrument};
 
 /// Delay into the slot
 pub const MAX_DELAY_INTO_SLOT: Duration = Duration::from_millis(500);
 
+/// The default time the best payload must not change for before the payload is resolved early.
+pub const DEFAULT_PAYLOAD_SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// The interval at which the best payload of a delayed job is polled.
+const PAYLOAD_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The progress of a payload job, as observed through its best payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadSnapshot {
+    /// The fees paid to the fee recipient by the best payload.
+    pub fees: U256,
+    /// The number of transactions of the best payload.
+    pub transactions: usize,
+}
+
+/// A source of the progress of payload jobs.
+pub trait PayloadProgress: Debug + Send + Sync + 'static {
+    /// Returns the progress of the job with the given id, or `None` if there is no such job or it
+    /// did not build a payload yet.
+    fn progress(&self, id: PayloadId) -> BoxFuture<'_, Option<PayloadSnapshot>>;
+}
+
+impl PayloadProgress for PayloadBuilderHandle<OpEngineTypes> {
+    fn progress(&self, id: PayloadId) -> BoxFuture<'_, Option<PayloadSnapshot>> {
+        Box::pin(async move {
+            let payload = self.best_payload(id).await?.ok()?;
+            Some(PayloadSnapshot {
+                fees: payload.fees(),
+                transactions: payload.block().body.transactions.len(),
+            })
+        })
+    }
+}
+
 /// The getpayload fn we want to delay
 pub const GET_PAYLOAD_V3: &str = "engine_getPayloadV3";
 
@@ -31,47 +78,143 @@ pub struct DelayedResolver {
 impl DelayedResolver {
     /// Creates a new instance with the engine module and the duration we should target
     pub fn new(engine_module: RpcModule<()>, max_delay_into_slot: Duration) -> Self {
+        Self::with_clock(engine_module, max_delay_into_slot, Arc::new(SystemClock))
+    }
+
+    /// Creates a new instance with the engine module and the duration we should target, that
+    /// reads the time from the given [`Clock`].
+    pub fn with_clock(
+        engine_module: RpcModule<()>,
+        max_delay_into_slot: Duration,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
         Self {
             inner: Arc::new(DelayedResolverInner {
-                last_block_time: Mutex::new(Instant::now()),
+                last_block_time: Mutex::new(clock.now()),
                 engine_module,
                 max_delay_into_slot,
+                clock,
+                progress: None,
+                settle_time: DEFAULT_PAYLOAD_SETTLE_TIME,
+                jobs: Default::default(),
+            }),
+        }
+    }
+
+    /// Resolves delayed calls early once the best payload of the requested job, as observed
+    /// through the given [`PayloadProgress`], did not change for the settle time.
+    ///
+    /// A zero settle time always delays calls until the maximum delay into the slot.
+    pub fn with_payload_progress(
+        self,
+        progress: Arc<dyn PayloadProgress>,
+        settle_time: Duration,
+    ) -> Self {
+        let inner = &self.inner;
+        Self {
+            inner: Arc::new(DelayedResolverInner {
+                last_block_time: Mutex::new(*inner.last_block_time.lock()),
+                engine_module: inner.engine_module.clone(),
+                max_delay_into_slot: inner.max_delay_into_slot,
+                clock: inner.clock.clone(),
+                progress: (!settle_time.is_zero()).then_some(progress),
+                settle_time,
+                jobs: Default::default(),
             }),
         }
     }
 
     /// Listen for new blocks and track the local timestamp.
+    ///
+    /// On a reorg, the slot starts when the tip of the new chain arrives, even if the new tip is
+    /// not ahead of the reorged tip.
     pub fn spawn<St>(self, mut st: St)
     where
         St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
     {
         tokio::task::spawn(async move {
-            while st.next().await.is_some() {
-                *self.inner.last_block_time.lock() = Instant::now();
+            while let Some(notification) = st.next().await {
+                let now = self.inner.clock.now();
+                match &notification {
+                    CanonStateNotification::Commit { .. } => {}
+                    CanonStateNotification::Reorg { old, new } => debug!(
+                        target: "rpc::engine",
+                        reverted = old.len(),
+                        old_tip = old.tip().number,
+                        new_tip = new.tip().number,
+                        "Restarting slot at the new canonical tip"
+                    ),
+                }
+                *self.inner.last_block_time.lock() = now;
+                // jobs of the previous slot are not resolved anymore
+                self.inner.jobs.lock().clear();
             }
         });
     }
 
+    #[instrument(target = "rpc::engine", skip_all)]
     async fn call(&self, params: Params<'static>) -> Result<serde_json::Value, MethodsError> {
         let last = *self.inner.last_block_time.lock();
-        let now = Instant::now();
+        let now = self.inner.clock.now();
         // how far we're into the slot
         let offset = now.duration_since(last);
 
+        let params = params
+            .as_str()
+            .ok_or_else(|| MethodsError::Parse(serde_json::Error::missing_field("payload id")))?;
+
         if offset < self.inner.max_delay_into_slot {
             // if we received the request before the max delay exceeded we can delay the request to
             // give the payload builder more time to build the payload.
             let delay = self.inner.max_delay_into_slot.saturating_sub(offset);
-            tokio::time::sleep(delay).await;
+            let id = serde_json::from_str::<(PayloadId,)>(params).ok().map(|(id,)| id);
+            match (&self.inner.progress, id) {
+                (Some(progress), Some(id)) => {
+                    self.delay_until_settled(&**progress, id, delay).await
+                }
+                _ => tokio::time::sleep(delay).await,
+            }
         }
 
-        let params = params
-            .as_str()
-            .ok_or_else(|| MethodsError::Parse(serde_json::Error::missing_field("payload id")))?;
-
         self.inner.engine_module.call(GET_PAYLOAD_V3, PayloadParam(params.to_string())).await
     }
 
+    /// Delays up to the given delay, until the best payload of the job did not change for the
+    /// settle time.
+    async fn delay_until_settled(
+        &self,
+        progress: &dyn PayloadProgress,
+        id: PayloadId,
+        delay: Duration,
+    ) {
+        let deadline = tokio::time::Instant::now() + delay;
+        loop {
+            let snapshot = progress.progress(id).await;
+            let now = tokio::time::Instant::now();
+            let unchanged = snapshot.map(|snapshot| {
+                let mut jobs = self.inner.jobs.lock();
+                let job = jobs.entry(id).or_insert(JobProgress { snapshot, changed_at: now });
+                if job.snapshot != snapshot {
+                    *job = JobProgress { snapshot, changed_at: now };
+                }
+                now.duration_since(job.changed_at)
+            });
+            if unchanged.is_some_and(|unchanged| unchanged >= self.inner.settle_time) {
+                debug!(
+                    target: "rpc::engine",
+                    %id,
+                    remaining = ?deadline.saturating_duration_since(now),
+                    "Resolving settled payload early"
+                );
+                return;
+            }
+            if now >= deadline {
+                return;
+            }
+            tokio::time::sleep_until(deadline.min(now + PAYLOAD_POLL_INTERVAL)).await;
+        }
+    }
+
     /// Converts this type into a new [`RpcModule`] that delegates the get payload call.
     pub fn into_rpc_module(self) -> RpcModule<()> {
         let mut module = RpcModule::new(());
@@ -102,6 +245,23 @@ struct DelayedResolverInner {
     engine_module: RpcModule<()>,
     /// By how much we want to delay getPayload into the slot
     max_delay_into_slot: Duration,
+    /// The clock used to measure how far we're into the slot
+    clock: Arc<dyn Clock>,
+    /// Observes the progress of payload jobs, if calls are resolved once the payload settled
+    progress: Option<Arc<dyn PayloadProgress>>,
+    /// How long the best payload must not change for before it is resolved early
+    settle_time: Duration,
+    /// The observed progress of the jobs of the current slot
+    jobs: Mutex<HashMap<PayloadId, JobProgress>>,
+}
+
+/// The observed progress of a payload job.
+#[derive(Debug, Clone, Copy)]
+struct JobProgress {
+    /// The last observed progress.
+    snapshot: PayloadSnapshot,
+    /// When the progress last changed.
+    changed_at: tokio::time::Instant,
 }
 
 struct PayloadParam(String);
@@ -143,4 +303,89 @@ mod tests {
         let delayer = DelayedResolver::new(module, MAX_DELAY_INTO_SLOT).into_rpc_module();
         let _echo: Payload = delayer.call(GET_PAYLOAD_V3, [id]).await.unwrap();
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delay_depends_on_slot_offset() {
+        use jsonrpsee::{core::RpcResult, RpcModule};
+        use odyssey_common::MockClock;
+
+        let mut module = RpcModule::new(());
+        module
+            .register_method::<RpcResult<Payload>, _>(GET_PAYLOAD_V3, |params, _, _| {
+                params.one::<PayloadId>()?;
+                Ok(Payload::default())
+            })
+            .unwrap();
+
+        let clock = Arc::new(MockClock::default());
+        let delayer = DelayedResolver::with_clock(module, MAX_DELAY_INTO_SLOT, clock.clone())
+            .into_rpc_module();
+        let id = PayloadId::default();
+
+        // 100ms into the slot, the call is delayed until the max delay into the slot
+        clock.advance(Duration::from_millis(100));
+        let start = tokio::time::Instant::now();
+        let _echo: Payload = delayer.call(GET_PAYLOAD_V3, [id]).await.unwrap();
+        assert_eq!(start.elapsed(), MAX_DELAY_INTO_SLOT - Duration::from_millis(100));
+
+        // past the max delay into the slot, the call is resolved immediately
+        clock.advance(MAX_DELAY_INTO_SLOT);
+        let start = tokio::time::Instant::now();
+        let _echo: Payload = delayer.call(GET_PAYLOAD_V3, [id]).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    /// A payload that improves until it was built for the given time.
+    #[derive(Debug)]
+    struct BuildingPayload {
+        started: tokio::time::Instant,
+        building: Duration,
+    }
+
+    impl PayloadProgress for BuildingPayload {
+        fn progress(&self, _: PayloadId) -> BoxFuture<'_, Option<PayloadSnapshot>> {
+            let built = self.started.elapsed().min(self.building);
+            let snapshot = PayloadSnapshot { fees: U256::from(built.as_millis()), transactions: 1 };
+            Box::pin(futures::future::ready(Some(snapshot)))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_resolves_settled_payload_early() {
+        use jsonrpsee::{core::RpcResult, RpcModule};
+        use odyssey_common::MockClock;
+
+        let mut module = RpcModule::new(());
+        module
+            .register_method::<RpcResult<Payload>, _>(GET_PAYLOAD_V3, |params, _, _| {
+                params.one::<PayloadId>()?;
+                Ok(Payload::default())
+            })
+            .unwrap();
+        let delayer = |building| {
+            let progress =
+                Arc::new(BuildingPayload { started: tokio::time::Instant::now(), building });
+            DelayedResolver::with_clock(
+                module.clone(),
+                MAX_DELAY_INTO_SLOT,
+                Arc::new(MockClock::default()),
+            )
+            .with_payload_progress(progress, DEFAULT_PAYLOAD_SETTLE_TIME)
+            .into_rpc_module()
+        };
+        let id = PayloadId::default();
+
+        // the payload stops improving after 100ms, and is resolved once it did not change for the
+        // settle time
+        let start = tokio::time::Instant::now();
+        let _echo: Payload =
+            delayer(Duration::from_millis(100)).call(GET_PAYLOAD_V3, [id]).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(100) + DEFAULT_PAYLOAD_SETTLE_TIME);
+
+        // a payload that keeps improving is delayed until the max delay into the slot
+        let start = tokio::time::Instant::now();
+        let _echo: Payload =
+            delayer(Duration::from_secs(1)).call(GET_PAYLOAD_V3, [id]).await.unwrap();
+        assert_eq!(start.elapsed(), MAX_DELAY_INTO_SLOT);
+    }
 }
@@ -0,0 +1,259 @@
+//! Index of OP Stack deposit transactions.
+//!
+//! Deposit transactions are derived from L1 and never enter the transaction pool, so they are
+//! indexed from canonical state notifications as they are included. The index only covers the
+//! most recent blocks since the node started.
+
+use alloy_primitives::{Address, TxHash, B256, U256};
+use futures::{Stream, StreamExt};
+use parking_lot::RwLock;
+use reth_chain_state::CanonStateNotification;
+use reth_primitives::{SealedBlockWithSenders, Transaction};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+/// The default number of recent blocks whose deposits are indexed.
+pub const DEFAULT_DEPOSIT_INDEX_BLOCKS: u64 = 100_000;
+
+/// A deposit transaction and its inclusion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DepositInfo {
+    /// The hash of the deposit transaction.
+    pub transaction_hash: TxHash,
+    /// The hash that uniquely identifies the source of the deposit.
+    pub source_hash: B256,
+    /// The address of the sender on L1.
+    pub from: Address,
+    /// The recipient, or `None` if the deposit creates a contract.
+    pub to: Option<Address>,
+    /// The ETH minted on L2.
+    pub mint: U256,
+    /// The ETH transferred to the recipient.
+    pub value: U256,
+    /// The gas limit of the deposit on L2.
+    pub gas_limit: u64,
+    /// Whether this is a system transaction.
+    pub is_system_transaction: bool,
+    /// The number of the block the deposit was included in.
+    pub block_number: u64,
+    /// The hash of the block the deposit was included in.
+    pub block_hash: B256,
+    /// The index of the deposit in the block.
+    pub transaction_index: u64,
+    /// The L1 block the including block was derived from, if it could be decoded from the L1
+    /// attributes transaction of the block.
+    pub l1_origin: Option<L1Origin>,
+}
+
+/// An L1 block an L2 block was derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L1Origin {
+    /// The number of the L1 block.
+    pub number: u64,
+    /// The hash of the L1 block.
+    pub hash: B256,
+}
+
+/// Index of the deposit transactions in recent canonical blocks.
+#[derive(Debug, Clone)]
+pub struct DepositIndex {
+    inner: Arc<RwLock<DepositIndexInner>>,
+}
+
+#[derive(Debug, Default)]
+struct DepositIndexInner {
+    /// The indexed deposits.
+    deposits: HashMap<TxHash, DepositInfo>,
+    /// The hashes of the deposits in each indexed block.
+    blocks: BTreeMap<u64, Vec<TxHash>>,
+    /// The number of recent blocks to index.
+    max_blocks: u64,
+}
+
+impl DepositIndex {
+    /// Creates a new index that covers the given number of recent blocks.
+    pub fn new(max_blocks: u64) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(DepositIndexInner { max_blocks, ..Default::default() })),
+        }
+    }
+
+    /// Returns the deposit with the given hash, if it is indexed.
+    pub fn get(&self, tx_hash: &TxHash) -> Option<DepositInfo> {
+        self.inner.read().deposits.get(tx_hash).cloned()
+    }
+
+    /// Indexes the deposits of a block, evicting blocks that are too old.
+    pub fn insert(&self, block_number: u64, deposits: Vec<DepositInfo>) {
+        let inner = &mut *self.inner.write();
+        let hashes = deposits.iter().map(|deposit| deposit.transaction_hash).collect();
+        inner
+            .deposits
+            .extend(deposits.into_iter().map(|deposit| (deposit.transaction_hash, deposit)));
+        inner.blocks.insert(block_number, hashes);
+
+        while let Some(entry) = inner.blocks.first_entry() {
+            if entry.key().saturating_add(inner.max_blocks) > block_number {
+                break;
+            }
+            for tx_hash in entry.remove() {
+                inner.deposits.remove(&tx_hash);
+            }
+        }
+    }
+
+    /// Removes the deposits of a block that is no longer canonical.
+    pub fn remove(&self, block_number: u64) {
+        let mut inner = self.inner.write();
+        for tx_hash in inner.blocks.remove(&block_number).unwrap_or_default() {
+            inner.deposits.remove(&tx_hash);
+        }
+    }
+
+    /// Spawns a task that indexes the deposits of canonical blocks.
+    pub fn spawn<St>(self, mut st: St)
+    where
+        St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
+    {
+        tokio::task::spawn(async move {
+            while let Some(notification) = st.next().await {
+                if let Some(reverted) = notification.reverted() {
+                    for block in reverted.blocks_iter() {
+                        self.remove(block.number);
+                    }
+                }
+                for block in notification.committed().blocks_iter() {
+                    self.insert(block.number, block_deposits(block));
+                }
+            }
+        });
+    }
+}
+
+impl Default for DepositIndex {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEPOSIT_INDEX_BLOCKS)
+    }
+}
+
+/// Returns the deposit transactions of a block.
+fn block_deposits(block: &SealedBlockWithSenders) -> Vec<DepositInfo> {
+    // the first transaction of every block is the L1 attributes deposit
+    let l1_origin = block.body.transactions.first().and_then(|tx| match &tx.transaction {
+        Transaction::Deposit(deposit) => l1_origin(&deposit.input),
+        _ => None,
+    });
+
+    block
+        .body
+        .transactions
+        .iter()
+        .enumerate()
+        .filter_map(|(index, tx)| {
+            let Transaction::Deposit(deposit) = &tx.transaction else { return None };
+            Some(DepositInfo {
+                transaction_hash: tx.hash(),
+                source_hash: deposit.source_hash,
+                from: deposit.from,
+                to: deposit.to.to().copied(),
+                mint: U256::from(deposit.mint.unwrap_or_default()),
+                value: deposit.value,
+                gas_limit: deposit.gas_limit,
+                is_system_transaction: deposit.is_system_transaction,
+                block_number: block.number,
+                block_hash: block.hash(),
+                transaction_index: index as u64,
+                l1_origin,
+            })
+        })
+        .collect()
+}
+
+/// Decodes the L1 origin from the calldata of an L1 attributes deposit.
+///
+/// The number and hash of the L1 block are at the same offsets in the calldata of both
+/// `setL1BlockValues` (Bedrock) and `setL1BlockValuesEcotone` (Ecotone).
+fn l1_origin(input: &[u8]) -> Option<L1Origin> {
+    let number = input.get(28..36)?;
+    let hash = input.get(100..132)?;
+    Some(L1Origin {
+        number: u64::from_be_bytes(number.try_into().ok()?),
+        hash: B256::from_slice(hash),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::hex;
+
+    #[test]
+    fn decodes_l1_origin() {
+        // `setL1BlockValuesEcotone` calldata
+        let input = hex!("440a5e200000146b000f79c500000000000000040000000066d052e700000000013ad8a3000000000000000000000000000000000000000000000000000000003ef1278700000000000000000000000000000000000000000000000000000000000000014f98b83baf52c498b49bfff33e59965b27da7febbea9a2fcc4719d06dc06932a000000000000000000000000e0e9f07ce7b4c80b53e2a0c0d7fad8a8e8c20b62");
+        assert_eq!(
+            l1_origin(&input),
+            Some(L1Origin {
+                number: 20_633_763,
+                hash: B256::from(hex!(
+                    "4f98b83baf52c498b49bfff33e59965b27da7febbea9a2fcc4719d06dc06932a"
+                )),
+            })
+        );
+        assert_eq!(l1_origin(&input[..100]), None);
+    }
+
+    fn deposit(i: u8) -> DepositInfo {
+        DepositInfo {
+            transaction_hash: TxHash::with_last_byte(i),
+            source_hash: B256::ZERO,
+            from: Address::ZERO,
+            to: None,
+            mint: U256::ZERO,
+            value: U256::ZERO,
+            gas_limit: 0,
+            is_system_transaction: false,
+            block_number: i as u64,
+            block_hash: B256::ZERO,
+            transaction_index: 0,
+            l1_origin: None,
+        }
+    }
+
+    #[test]
+    fn indexes_recent_blocks() {
+        let index = DepositIndex::new(2);
+
+        index.insert(1, vec![deposit(1)]);
+        index.insert(2, vec![deposit(2)]);
+        assert!(index.get(&TxHash::with_last_byte(1)).is_some());
+
+        // block 1 is evicted
+        index.insert(3, vec![deposit(3)]);
+        assert!(index.get(&TxHash::with_last_byte(1)).is_none());
+        assert!(index.get(&TxHash::with_last_byte(2)).is_some());
+
+        // reverted blocks are removed
+        index.remove(3);
+        assert!(index.get(&TxHash::with_last_byte(3)).is_none());
+    }
+
+    #[test]
+    fn serializes_deposit_info() {
+        let origin = L1Origin { number: 1, hash: B256::with_last_byte(1) };
+        let info = DepositInfo { l1_origin: Some(origin), ..deposit(1) };
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["transactionHash"], serde_json::to_value(info.transaction_hash).unwrap());
+        assert_eq!(json["isSystemTransaction"], false);
+        assert_eq!(json["l1Origin"]["hash"], serde_json::to_value(origin.hash).unwrap());
+        // deposits that create a contract have no recipient
+        assert!(json["to"].is_null());
+        assert_eq!(serde_json::from_value::<DepositInfo>(json).unwrap(), info);
+    }
+}
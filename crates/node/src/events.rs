@@ -0,0 +1,350 @@
+//! Canonical event stream.
+//!
+//! An execution extension that publishes canonical headers, receipts and Odyssey specific events
+//! to an external [`EventSink`], e.g. a webhook that feeds a message queue.
+//!
+//! Delivery is at-least-once: a batch of events is retried until the sink accepts it, and only
+//! then is the block checkpointed and the ExEx marked as finished up to that block. After a
+//! restart, the stream resumes from the last checkpoint, so consumers may receive the events of
+//! the checkpointed block again and should deduplicate by block hash.
+
+use alloy_consensus::{Header, Transaction as _};
+use alloy_eips::BlockNumHash;
+use alloy_primitives::{Address, Log, TxHash, B256};
+use futures::TryStreamExt;
+use reth_exex::{ExExContext, ExExEvent, ExExHead, ExExNotification};
+use reth_node_api::{FullNodeComponents, NodeTypes};
+use reth_optimism_primitives::OpPrimitives;
+use reth_primitives::{Receipt, SealedBlockWithSenders};
+use serde::{Deserialize, Serialize};
+use std::{future::Future, path::PathBuf, time::Duration};
+use tracing::{debug, info, warn};
+
+/// The identifier of the event stream ExEx.
+pub const EVENT_STREAM_EXEX_ID: &str = "odyssey-events";
+
+/// The initial delay between retries of a batch that was not accepted by the sink.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The maximum delay between retries of a batch that was not accepted by the sink.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// An event published by the event stream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum OdysseyEvent {
+    /// A block was added to the canonical chain.
+    BlockCommitted {
+        /// The hash of the block.
+        hash: B256,
+        /// The header of the block.
+        header: Header,
+        /// The receipts of the transactions in the block.
+        receipts: Vec<ReceiptEvent>,
+    },
+    /// A block was removed from the canonical chain by a reorg.
+    BlockReverted {
+        /// The number of the block.
+        number: u64,
+        /// The hash of the block.
+        hash: B256,
+    },
+    /// A transaction sent by the sponsor was included in a canonical block.
+    SponsoredTransactionIncluded {
+        /// The number of the block.
+        block_number: u64,
+        /// The hash of the block.
+        block_hash: B256,
+        /// The hash of the transaction.
+        transaction_hash: TxHash,
+    },
+    /// An EIP-7702 authorization changed the delegate of an account.
+    DelegationChanged {
+        /// The number of the block.
+        block_number: u64,
+        /// The hash of the block.
+        block_hash: B256,
+        /// The hash of the transaction carrying the authorization.
+        transaction_hash: TxHash,
+        /// The account that signed the authorization.
+        authority: Address,
+        /// The address the account delegates to.
+        delegate: Address,
+    },
+}
+
+/// The receipt of a transaction in a committed block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptEvent {
+    /// The hash of the transaction.
+    pub transaction_hash: TxHash,
+    /// Whether the transaction was successful.
+    pub success: bool,
+    /// The gas used in the block up until and including this transaction.
+    pub cumulative_gas_used: u64,
+    /// The logs emitted by the transaction.
+    pub logs: Vec<Log>,
+}
+
+/// A destination for the events of the event stream.
+pub trait EventSink: Send + Sync + 'static {
+    /// Publishes a batch of events.
+    ///
+    /// The batch is retried until this returns `Ok`, so implementations should not return `Ok`
+    /// before the events are durably accepted.
+    fn publish(&self, events: &[OdysseyEvent]) -> impl Future<Output = eyre::Result<()>> + Send;
+}
+
+/// An [`EventSink`] that posts each batch of events as a JSON array to a URL.
+#[derive(Debug, Clone)]
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    /// Creates a new sink that posts to the given URL.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { client: reqwest::Client::new(), url: url.into() }
+    }
+}
+
+impl EventSink for WebhookSink {
+    async fn publish(&self, events: &[OdysseyEvent]) -> eyre::Result<()> {
+        self.client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(events)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// The last block whose events were accepted by the sink, persisted to a file.
+#[derive(Debug, Clone)]
+pub struct EventCheckpoint {
+    path: PathBuf,
+}
+
+impl EventCheckpoint {
+    /// Creates a new checkpoint stored at the given path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Loads the checkpoint, if any.
+    pub async fn load(&self) -> eyre::Result<Option<BlockNumHash>> {
+        match tokio::fs::read(&self.path).await {
+            Ok(data) => {
+                let Checkpoint { number, hash } = serde_json::from_slice(&data)?;
+                Ok(Some(BlockNumHash::new(number, hash)))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Stores the checkpoint.
+    ///
+    /// The checkpoint is written to a temporary file first, so it is never left partially written.
+    pub async fn store(&self, block: BlockNumHash) -> eyre::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        let data = serde_json::to_vec(&Checkpoint { number: block.number, hash: block.hash })?;
+        tokio::fs::write(&tmp, data).await?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+        Ok(())
+    }
+}
+
+/// The serialized form of an [`EventCheckpoint`].
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    number: u64,
+    hash: B256,
+}
+
+/// The event stream ExEx.
+///
+/// Publishes the events of every canonical state change to the sink. If a sponsor address is
+/// given, inclusions of transactions sent by the sponsor are published as well.
+pub async fn event_stream<Node, S>(
+    mut ctx: ExExContext<Node>,
+    sink: S,
+    checkpoint: EventCheckpoint,
+    sponsor: Option<Address>,
+) -> eyre::Result<()>
+where
+    Node: FullNodeComponents<Types: NodeTypes<Primitives = OpPrimitives>>,
+    S: EventSink,
+{
+    if let Some(head) = checkpoint.load().await? {
+        info!(
+            target: "odyssey::events",
+            number = head.number,
+            hash = %head.hash,
+            "Resuming event stream"
+        );
+        ctx.notifications.set_with_head(ExExHead { block: head });
+    }
+
+    while let Some(notification) = ctx.notifications.try_next().await? {
+        let events = notification_events(&notification, sponsor);
+        if !events.is_empty() {
+            publish(&sink, &events).await;
+        }
+
+        if let Some(committed) = notification.committed_chain() {
+            let tip = committed.tip().num_hash();
+            checkpoint.store(tip).await?;
+            ctx.events.send(ExExEvent::FinishedHeight(tip))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Publishes the events, retrying with exponential backoff until the sink accepts them.
+async fn publish<S: EventSink>(sink: &S, events: &[OdysseyEvent]) {
+    let mut delay = INITIAL_RETRY_DELAY;
+    loop {
+        match sink.publish(events).await {
+            Ok(()) => {
+                debug!(target: "odyssey::events", count = events.len(), "Published events");
+                return;
+            }
+            Err(err) => {
+                warn!(
+                    target: "odyssey::events",
+                    %err,
+                    retry_in = ?delay,
+                    "Failed to publish events"
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+        }
+    }
+}
+
+/// Returns the events of a canonical state change.
+///
+/// Reverted blocks are reported before committed blocks, in descending order.
+fn notification_events(
+    notification: &ExExNotification<OpPrimitives>,
+    sponsor: Option<Address>,
+) -> Vec<OdysseyEvent> {
+    let mut events = Vec::new();
+
+    if let Some(reverted) = notification.reverted_chain() {
+        events.extend(
+            reverted.blocks_iter().rev().map(|block| OdysseyEvent::BlockReverted {
+                number: block.number,
+                hash: block.hash(),
+            }),
+        );
+    }
+
+    if let Some(committed) = notification.committed_chain() {
+        for (block, receipts) in committed.blocks_and_receipts() {
+            events.extend(block_events(block, receipts, sponsor));
+        }
+    }
+
+    events
+}
+
+/// Returns the events of a committed block.
+fn block_events(
+    block: &SealedBlockWithSenders,
+    receipts: &[Option<Receipt>],
+    sponsor: Option<Address>,
+) -> Vec<OdysseyEvent> {
+    let (block_number, block_hash) = (block.number, block.hash());
+    let mut events = Vec::new();
+
+    for (sender, tx) in block.transactions_with_sender() {
+        let transaction_hash = tx.hash();
+
+        if sponsor == Some(*sender) {
+            events.push(OdysseyEvent::SponsoredTransactionIncluded {
+                block_number,
+                block_hash,
+                transaction_hash,
+            });
+        }
+
+        for auth in tx.authorization_list().unwrap_or_default() {
+            // invalid authorizations are skipped by the EVM, so they do not change the delegate
+            let Ok(authority) = auth.recover_authority() else { continue };
+            events.push(OdysseyEvent::DelegationChanged {
+                block_number,
+                block_hash,
+                transaction_hash,
+                authority,
+                delegate: auth.address,
+            });
+        }
+    }
+
+    let receipts = block
+        .body
+        .transactions
+        .iter()
+        .zip(receipts)
+        .filter_map(|(tx, receipt)| {
+            let receipt = receipt.as_ref()?;
+            Some(ReceiptEvent {
+                transaction_hash: tx.hash(),
+                success: receipt.success,
+                cumulative_gas_used: receipt.cumulative_gas_used,
+                logs: receipt.logs.clone(),
+            })
+        })
+        .collect();
+
+    // the block itself is published first, followed by the events of its transactions
+    events.insert(
+        0,
+        OdysseyEvent::BlockCommitted {
+            hash: block_hash,
+            header: block.header.header().clone(),
+            receipts,
+        },
+    );
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn checkpoint_roundtrip() {
+        let path = std::env::temp_dir().join(format!("odyssey-events-{}.json", std::process::id()));
+        let checkpoint = EventCheckpoint::new(&path);
+
+        assert_eq!(checkpoint.load().await.unwrap(), None);
+
+        let block = BlockNumHash::new(42, B256::with_last_byte(1));
+        checkpoint.store(block).await.unwrap();
+        assert_eq!(checkpoint.load().await.unwrap(), Some(block));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn event_format() {
+        let event = OdysseyEvent::BlockReverted { number: 1, hash: B256::ZERO };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "type": "blockReverted",
+                "number": 1,
+                "hash": B256::ZERO,
+            })
+        );
+    }
+}
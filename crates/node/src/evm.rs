@@ -10,6 +10,7 @@
 //! This currently configures the instructions defined in [EIP3074-instructions](https://github.com/paradigmxyz/eip3074-instructions), and the
 //! precompiles defined by [`revm_precompile`].
 
+use crate::{forks::OdysseyHardfork, rsa::RSA_VERIFY};
 use alloy_consensus::Header;
 use alloy_primitives::{Address, Bytes, TxKind, U256};
 use op_alloy_consensus::EIP1559ParamError;
@@ -20,7 +21,7 @@ use reth_optimism_chainspec::OpChainSpec;
 use reth_optimism_forks::OpHardfork;
 use reth_primitives::{transaction::FillTxEnv, TransactionSigned};
 use reth_revm::{
-    handler::register::EvmHandler,
+    handler::register::{EvmHandler, HandleRegisterBox},
     inspector_handle_register,
     precompile::PrecompileSpecId,
     primitives::{
@@ -34,7 +35,10 @@ use revm_precompile::{
     u64_to_address, PrecompileWithAddress,
 };
 use revm_primitives::{CfgEnvWithHandlerCfg, Precompile, TxEnv};
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
 /// P256 verify precompile address.
 pub const P256VERIFY_ADDRESS: u64 = 0x14;
@@ -55,8 +59,24 @@ impl OdysseyEvmConfig {
         Self { chain_spec }
     }
 
-    fn precompiles() -> impl Iterator<Item = PrecompileWithAddress> {
-        [P256VERIFY, REVM_P256VERIFY].into_iter()
+    /// Returns the additional precompiles that are active at the given timestamp.
+    fn precompiles(
+        chain_spec: &OpChainSpec,
+        timestamp: u64,
+    ) -> impl Iterator<Item = PrecompileWithAddress> {
+        let rsa_verify = chain_spec.fork(OdysseyHardfork::Exp2).active_at_timestamp(timestamp);
+        [P256VERIFY, REVM_P256VERIFY].into_iter().chain(rsa_verify.then_some(RSA_VERIFY))
+    }
+
+    /// Returns the handler register that sets the precompiles of the chain spec, see
+    /// [`Self::set_precompiles`].
+    fn precompiles_register<'a, EXT: 'a, DB: Database + 'a>(
+        &self,
+    ) -> HandleRegisterBox<'a, EXT, DB> {
+        let chain_spec = self.chain_spec.clone();
+        Box::new(move |handler: &mut EvmHandler<'a, EXT, DB>| {
+            Self::set_precompiles(handler, chain_spec.clone())
+        })
     }
 
     /// Sets the precompiles to the EVM handler
@@ -65,19 +85,33 @@ impl OdysseyEvmConfig {
     /// [`ConfigureEvm::evm_with_inspector`]
     ///
     /// This will use the default mainnet precompiles and add additional precompiles.
-    fn set_precompiles<EXT, DB>(handler: &mut EvmHandler<'_, EXT, DB>)
+    ///
+    /// The precompiles of the Odyssey hardforks depend on the timestamp of the block, which is not
+    /// known when the precompiles are loaded. It is recorded when the accounts of the transaction
+    /// are loaded, which happens right before.
+    fn set_precompiles<EXT, DB>(handler: &mut EvmHandler<'_, EXT, DB>, chain_spec: Arc<OpChainSpec>)
     where
         DB: Database,
     {
         // first we need the evm spec id, which determines the precompiles
         let spec_id = handler.cfg.spec_id;
 
+        // record the timestamp of the block
+        let timestamp = Arc::new(AtomicU64::new(0));
+        let load_accounts = handler.pre_execution.load_accounts.clone();
+        let block_timestamp = timestamp.clone();
+        handler.pre_execution.load_accounts = Arc::new(move |ctx| {
+            block_timestamp.store(ctx.evm.env.block.timestamp.saturating_to(), Ordering::Relaxed);
+            load_accounts(ctx)
+        });
+
         // install the precompiles
         handler.pre_execution.load_precompiles = Arc::new(move || {
             let mut loaded_precompiles: ContextPrecompiles<DB> =
                 ContextPrecompiles::new(PrecompileSpecId::from_spec_id(spec_id));
 
-            loaded_precompiles.extend(Self::precompiles());
+            loaded_precompiles
+                .extend(Self::precompiles(&chain_spec, timestamp.load(Ordering::Relaxed)));
 
             loaded_precompiles
         });
@@ -245,7 +279,7 @@ impl ConfigureEvm for OdysseyEvmConfig {
             .with_db(db)
             .optimism()
             // add additional precompiles
-            .append_handler_register(Self::set_precompiles)
+            .append_handler_register_box(self.precompiles_register())
             .build()
     }
 
@@ -259,7 +293,7 @@ impl ConfigureEvm for OdysseyEvmConfig {
             .with_external_context(inspector)
             .optimism()
             // add additional precompiles
-            .append_handler_register(Self::set_precompiles)
+            .append_handler_register_box(self.precompiles_register())
             .append_handler_register(inspector_handle_register)
             .build()
     }
@@ -269,7 +303,7 @@ impl ConfigureEvm for OdysseyEvmConfig {
 
 /// Determine the revm spec ID from the current block and reth chainspec.
 fn revm_spec(chain_spec: &ChainSpec, block: &Head) -> reth_revm::primitives::SpecId {
-    if chain_spec.fork(EthereumHardfork::Prague).active_at_head(block) {
+    if chain_spec.fork(OdysseyHardfork::Exp1).active_at_head(block) {
         reth_revm::primitives::OSAKA
     } else if chain_spec.fork(OpHardfork::Granite).active_at_head(block) {
         reth_revm::primitives::GRANITE
@@ -320,8 +354,10 @@ fn revm_spec(chain_spec: &ChainSpec, block: &Head) -> reth_revm::primitives::Spe
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{chainspec::ODYSSEY_DEV, forks::ForkOverride, rsa::RSA_VERIFY_ADDRESS};
     use reth_chainspec::{Chain, ChainSpecBuilder, EthereumHardfork};
     use reth_primitives::ForkCondition;
+    use reth_revm::db::EmptyDB;
     use revm_primitives::{BlockEnv, CfgEnv, SpecId};
 
     #[test]
@@ -350,12 +386,7 @@ mod tests {
 
     #[test]
     fn test_p256verify_precompile_availability() {
-        let evm = EvmBuilder::default()
-            .with_empty_db()
-            .optimism()
-            // add additional precompiles
-            .append_handler_register(OdysseyEvmConfig::set_precompiles)
-            .build();
+        let evm = OdysseyEvmConfig::new(ODYSSEY_DEV.clone()).evm(EmptyDB::default());
 
         // loading the precompiles from pre execution instead of the evm context directly, as they
         // are only set pre-execution in the context
@@ -363,4 +394,25 @@ mod tests {
         assert!(precompiles.contains(&u64_to_address(0x14)));
         assert!(precompiles.contains(&u64_to_address(0x100)));
     }
+
+    #[test]
+    fn test_rsa_verify_precompile_activation() {
+        let mut chain_spec = (**ODYSSEY_DEV).clone();
+        ForkOverride { fork: OdysseyHardfork::Exp2, timestamp: 100 }.apply(&mut chain_spec);
+        let evm_config = OdysseyEvmConfig::new(Arc::new(chain_spec));
+
+        let precompiles_at = |timestamp: u64| {
+            let mut evm = evm_config.evm(EmptyDB::default());
+            evm.context.evm.env.block.timestamp = U256::from(timestamp);
+            let pre_execution = evm.handler.pre_execution();
+            pre_execution.load_accounts(&mut evm.context).unwrap();
+            pre_execution.load_precompiles()
+        };
+
+        let rsa_verify = u64_to_address(RSA_VERIFY_ADDRESS);
+        let before = precompiles_at(99);
+        assert!(!before.contains(&rsa_verify));
+        assert!(before.contains(&u64_to_address(0x14)));
+        assert!(precompiles_at(100).contains(&rsa_verify));
+    }
 }
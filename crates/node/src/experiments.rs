@@ -0,0 +1,177 @@
+//! Status of the Odyssey experiments.
+//!
+//! `odyssey_getExperimentStatus` lists the experiments of the node, whether they are enabled and
+//! active at the current head, the hardfork and timestamp they activate at, if they are scheduled
+//! in the chain spec, and their key parameters. Users can look up which experiments an endpoint
+//! runs, instead of finding out by trial and error.
+
+use crate::{config::PayloadConfig, evm::P256VERIFY, forks::OdysseyHardfork, rsa::RSA_VERIFY};
+use reth_chainspec::{ForkCondition, Hardfork, Hardforks, Head};
+use reth_optimism_chainspec::OpChainSpec;
+use revm_precompile::secp256r1::P256VERIFY as REVM_P256VERIFY;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// The settings of the node that determine the status of the experiments that are not scheduled
+/// in the chain spec.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExperimentSettings {
+    /// Whether the `wallet_` namespace is served.
+    pub wallet: bool,
+    /// The delay of `engine_getPayload`.
+    pub payload: PayloadConfig,
+}
+
+/// The status of an experiment, as returned by `odyssey_getExperimentStatus`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExperimentStatus {
+    /// The name of the experiment.
+    pub name: String,
+    /// Whether the experiment is enabled on this node, including experiments scheduled to
+    /// activate later.
+    pub enabled: bool,
+    /// Whether the experiment is active at the current head.
+    pub active: bool,
+    /// The hardfork that activates the experiment, if it is scheduled in the chain spec.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fork: Option<String>,
+    /// The timestamp the experiment activates at, if it is scheduled at a timestamp.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "alloy_rpc_types::serde_helpers::quantity::opt"
+    )]
+    pub activation_timestamp: Option<u64>,
+    /// The key parameters of the experiment.
+    pub parameters: Map<String, Value>,
+}
+
+impl ExperimentStatus {
+    /// Returns the status of an experiment that is not scheduled in the chain spec.
+    fn unscheduled(name: &str, enabled: bool, parameters: Value) -> Self {
+        Self {
+            name: name.to_string(),
+            enabled,
+            active: enabled,
+            fork: None,
+            activation_timestamp: None,
+            parameters: into_map(parameters),
+        }
+    }
+
+    /// Returns the status of the experiment activated by an Odyssey hardfork.
+    fn scheduled(chain_spec: &OpChainSpec, head: &Head, fork: OdysseyHardfork) -> Self {
+        let condition = chain_spec.fork(fork);
+        let parameters = match fork {
+            OdysseyHardfork::Exp1 => json!({ "evmSpec": "OSAKA" }),
+            OdysseyHardfork::Exp2 => json!({ "rsaVerify": RSA_VERIFY.0 }),
+        };
+        Self {
+            name: fork.name().to_string(),
+            enabled: condition != ForkCondition::Never,
+            active: condition.active_at_head(head),
+            fork: Some(fork.name().to_string()),
+            activation_timestamp: match condition {
+                ForkCondition::Timestamp(timestamp) => Some(timestamp),
+                _ => None,
+            },
+            parameters: into_map(parameters),
+        }
+    }
+}
+
+/// Returns the status of the experiments of a node with the given chain spec and settings, at the
+/// given head.
+pub fn experiment_status(
+    chain_spec: &OpChainSpec,
+    head: &Head,
+    settings: &ExperimentSettings,
+) -> Vec<ExperimentStatus> {
+    let mut experiments: Vec<_> = OdysseyHardfork::ALL
+        .into_iter()
+        .map(|fork| ExperimentStatus::scheduled(chain_spec, head, fork))
+        .collect();
+
+    // the P256 precompiles are installed regardless of the hardforks
+    experiments.push(ExperimentStatus::unscheduled(
+        "precompiles",
+        true,
+        json!({
+            "p256Verify": P256VERIFY.0,
+            "rip7212P256Verify": REVM_P256VERIFY.0,
+        }),
+    ));
+    experiments.push(ExperimentStatus::unscheduled(
+        "walletSponsorship",
+        settings.wallet,
+        json!({}),
+    ));
+    experiments.push(ExperimentStatus::unscheduled(
+        "payloadDelay",
+        !settings.payload.max_delay().is_zero(),
+        json!({
+            "maxDelayMs": settings.payload.max_delay().as_millis() as u64,
+            "settleMs": settings.payload.settle_time().as_millis() as u64,
+        }),
+    ));
+    experiments
+}
+
+/// Returns the fields of a JSON object.
+fn into_map(value: Value) -> Map<String, Value> {
+    match value {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chainspec::ODYSSEY_DEV, forks::ForkOverride};
+
+    #[test]
+    fn reports_experiments() {
+        let mut chain_spec = (**ODYSSEY_DEV).clone();
+        ForkOverride { fork: OdysseyHardfork::Exp1, timestamp: 100 }.apply(&mut chain_spec);
+        let settings = ExperimentSettings {
+            wallet: true,
+            payload: PayloadConfig { max_delay_ms: Some(0), settle_ms: Some(50) },
+        };
+
+        let experiments = experiment_status(
+            &chain_spec,
+            &Head { timestamp: 99, ..Default::default() },
+            &settings,
+        );
+        let exp1 = &experiments[0];
+        assert_eq!(exp1.name, "Exp1");
+        assert!(exp1.enabled && !exp1.active);
+        assert_eq!(exp1.activation_timestamp, Some(100));
+
+        let wallet = experiments.iter().find(|e| e.name == "walletSponsorship").unwrap();
+        assert!(wallet.enabled && wallet.active);
+        let payload = experiments.iter().find(|e| e.name == "payloadDelay").unwrap();
+        assert!(!payload.enabled);
+        assert_eq!(payload.parameters["settleMs"], 50);
+
+        let json = serde_json::to_value(exp1).unwrap();
+        assert_eq!(json["activationTimestamp"], "0x64");
+        assert_eq!(json["parameters"]["evmSpec"], "OSAKA");
+        let exp2 = serde_json::to_value(&experiments[1]).unwrap();
+        assert_eq!(exp2["active"], true);
+        assert_eq!(exp2["parameters"]["rsaVerify"], "0x0000000000000000000000000000000000000015");
+        let json = serde_json::to_value(&experiments[2]).unwrap();
+        assert_eq!(json["name"], "precompiles");
+        assert!(json.get("fork").is_none());
+
+        let experiments = experiment_status(
+            &chain_spec,
+            &Head { timestamp: 100, ..Default::default() },
+            &ExperimentSettings::default(),
+        );
+        assert!(experiments[0].active);
+        assert!(experiments.iter().find(|e| e.name == "payloadDelay").unwrap().enabled);
+    }
+}
@@ -0,0 +1,141 @@
+//! Experiment metadata in the `extraData` of blocks.
+//!
+//! If enabled with `--builder.experiment-tag`, the `extraData` of blocks built by the node is set
+//! to an [`ExperimentTag`] of the form `odyssey/<version>/<experiments>`, where `<experiments>` is
+//! a hex-encoded bitmap of the Odyssey experiments that are scheduled in the chain spec of the
+//! builder, indexed by their position in [`OdysseyHardfork::ALL`].
+//!
+//! `odyssey_getBlockExperiments` decodes the tag of a block, and returns which of the tagged
+//! experiments were active when the block was produced.
+
+use crate::forks::OdysseyHardfork;
+use reth_chainspec::{ForkCondition, Hardfork, Hardforks};
+use reth_optimism_chainspec::OpChainSpec;
+use serde::{Deserialize, Serialize};
+
+/// The prefix of the experiment tag.
+pub const EXPERIMENT_TAG_PREFIX: &str = "odyssey";
+
+/// The maximum size of the `extraData` of a block.
+const MAX_EXTRA_DATA_SIZE: usize = 32;
+
+/// The Odyssey version and the experiments scheduled on the node that built a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExperimentTag {
+    /// The version of Odyssey.
+    pub version: String,
+    /// The bitmap of the scheduled experiments, indexed by their position in
+    /// [`OdysseyHardfork::ALL`].
+    pub experiments: u64,
+}
+
+impl ExperimentTag {
+    /// Creates the tag of this version of Odyssey, with the experiments scheduled in the chain
+    /// spec.
+    pub fn new(chain_spec: &OpChainSpec) -> Self {
+        let experiments = OdysseyHardfork::ALL
+            .into_iter()
+            .enumerate()
+            .filter(|(_, fork)| chain_spec.fork(*fork) != ForkCondition::Never)
+            .fold(0, |bitmap, (index, _)| bitmap | (1 << index));
+        Self { version: env!("CARGO_PKG_VERSION").to_string(), experiments }
+    }
+
+    /// Returns whether the experiment is scheduled.
+    pub fn contains(&self, fork: OdysseyHardfork) -> bool {
+        OdysseyHardfork::ALL
+            .iter()
+            .position(|experiment| *experiment == fork)
+            .is_some_and(|index| self.experiments & (1 << index) != 0)
+    }
+
+    /// Encodes the tag as the `extraData` of a block.
+    ///
+    /// Returns an error if the tag does not fit into the `extraData`.
+    pub fn encode(&self) -> eyre::Result<String> {
+        let tag = format!("{EXPERIMENT_TAG_PREFIX}/{}/{:x}", self.version, self.experiments);
+        if tag.len() > MAX_EXTRA_DATA_SIZE {
+            eyre::bail!("experiment tag {tag} exceeds {MAX_EXTRA_DATA_SIZE} bytes");
+        }
+        Ok(tag)
+    }
+
+    /// Decodes the tag from the `extraData` of a block, if the block is tagged.
+    pub fn decode(extra_data: &[u8]) -> Option<Self> {
+        let mut parts = std::str::from_utf8(extra_data).ok()?.split('/');
+        if parts.next()? != EXPERIMENT_TAG_PREFIX {
+            return None;
+        }
+        let version = parts.next()?.to_string();
+        let experiments = u64::from_str_radix(parts.next()?, 16).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self { version, experiments })
+    }
+}
+
+/// The experiments of a block, as returned by `odyssey_getBlockExperiments`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockExperiments {
+    /// The version of Odyssey that built the block.
+    pub version: String,
+    /// The experiments that were scheduled on the node that built the block.
+    pub experiments: Vec<String>,
+    /// The scheduled experiments that were active at the timestamp of the block.
+    pub active_experiments: Vec<String>,
+}
+
+impl BlockExperiments {
+    /// Returns the experiments of a block with the given tag and timestamp.
+    ///
+    /// The activation of the experiments is read from the given chain spec.
+    pub fn new(tag: ExperimentTag, timestamp: u64, chain_spec: &OpChainSpec) -> Self {
+        let experiments: Vec<_> =
+            OdysseyHardfork::ALL.into_iter().filter(|fork| tag.contains(*fork)).collect();
+        Self {
+            version: tag.version,
+            active_experiments: experiments
+                .iter()
+                .filter(|fork| chain_spec.fork(**fork).active_at_timestamp(timestamp))
+                .map(|fork| fork.name().to_string())
+                .collect(),
+            experiments: experiments.iter().map(|fork| fork.name().to_string()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chainspec::ODYSSEY_DEV, forks::ForkOverride};
+
+    #[test]
+    fn roundtrips_tag() {
+        let tag = ExperimentTag::new(&ODYSSEY_DEV);
+        assert!(tag.contains(OdysseyHardfork::Exp1));
+        assert!(tag.contains(OdysseyHardfork::Exp2));
+
+        let encoded = tag.encode().unwrap();
+        assert_eq!(encoded, format!("odyssey/{}/3", env!("CARGO_PKG_VERSION")));
+        assert_eq!(ExperimentTag::decode(encoded.as_bytes()), Some(tag));
+
+        assert_eq!(ExperimentTag::decode(b"reth/v1.1.4/linux"), None);
+        assert_eq!(ExperimentTag::decode(&[0xff; 32]), None);
+    }
+
+    #[test]
+    fn reports_active_experiments() {
+        let mut chain_spec = (**ODYSSEY_DEV).clone();
+        ForkOverride { fork: OdysseyHardfork::Exp1, timestamp: 100 }.apply(&mut chain_spec);
+        let tag = ExperimentTag::new(&chain_spec);
+
+        let before = BlockExperiments::new(tag.clone(), 99, &chain_spec);
+        assert_eq!(before.experiments, vec!["Exp1", "Exp2"]);
+        assert_eq!(before.active_experiments, vec!["Exp2"]);
+
+        let after = BlockExperiments::new(tag, 100, &chain_spec);
+        assert_eq!(after.active_experiments, vec!["Exp1", "Exp2"]);
+    }
+}
@@ -0,0 +1,113 @@
+//! Odyssey hardforks.
+//!
+//! Odyssey experiments are activated by their own hardforks, so they can be scheduled
+//! independently of the upstream Ethereum and OP Stack hardforks.
+
+use reth_chainspec::{ForkCondition, Hardfork};
+use reth_optimism_chainspec::OpChainSpec;
+use std::{fmt, str::FromStr};
+
+/// The Odyssey hardforks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OdysseyHardfork {
+    /// The first set of Odyssey experiments, activating the `OSAKA` EVM spec.
+    Exp1,
+    /// The second set of Odyssey experiments, installing the RSA verification precompile at
+    /// `0x15`.
+    Exp2,
+}
+
+impl OdysseyHardfork {
+    /// All Odyssey hardforks, in order of activation.
+    pub const ALL: [Self; 2] = [Self::Exp1, Self::Exp2];
+}
+
+impl Hardfork for OdysseyHardfork {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Exp1 => "Exp1",
+            Self::Exp2 => "Exp2",
+        }
+    }
+}
+
+impl fmt::Display for OdysseyHardfork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for OdysseyHardfork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|fork| fork.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown Odyssey hardfork: {s}"))
+    }
+}
+
+/// An override of the activation timestamp of an Odyssey hardfork, in the form
+/// `<name>=<timestamp>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForkOverride {
+    /// The hardfork to schedule.
+    pub fork: OdysseyHardfork,
+    /// The timestamp the hardfork activates at.
+    pub timestamp: u64,
+}
+
+impl ForkOverride {
+    /// Schedules the hardfork in the given chain spec, replacing its existing activation.
+    pub fn apply(&self, chain_spec: &mut OpChainSpec) {
+        chain_spec.inner.hardforks.insert(self.fork, ForkCondition::Timestamp(self.timestamp));
+    }
+}
+
+impl FromStr for ForkOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (fork, timestamp) =
+            s.split_once('=').ok_or_else(|| format!("expected <name>=<timestamp>, got {s}"))?;
+        Ok(Self {
+            fork: fork.trim().parse()?,
+            timestamp: timestamp
+                .trim()
+                .parse()
+                .map_err(|err| format!("invalid timestamp {timestamp}: {err}"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainspec::ODYSSEY_DEV;
+    use reth_chainspec::Hardforks;
+
+    #[test]
+    fn parses_fork_override() {
+        assert_eq!(
+            "exp1=1700000000".parse::<ForkOverride>(),
+            Ok(ForkOverride { fork: OdysseyHardfork::Exp1, timestamp: 1_700_000_000 })
+        );
+        assert_eq!(
+            "Exp2=0".parse::<ForkOverride>(),
+            Ok(ForkOverride { fork: OdysseyHardfork::Exp2, timestamp: 0 })
+        );
+        assert!("exp1".parse::<ForkOverride>().is_err());
+        assert!("exp9=0".parse::<ForkOverride>().is_err());
+        assert!("exp1=soon".parse::<ForkOverride>().is_err());
+    }
+
+    #[test]
+    fn applies_fork_override() {
+        let mut chain_spec = (**ODYSSEY_DEV).clone();
+        assert_eq!(chain_spec.fork(OdysseyHardfork::Exp1), ForkCondition::Timestamp(0));
+
+        ForkOverride { fork: OdysseyHardfork::Exp1, timestamp: 100 }.apply(&mut chain_spec);
+        assert_eq!(chain_spec.fork(OdysseyHardfork::Exp1), ForkCondition::Timestamp(100));
+    }
+}
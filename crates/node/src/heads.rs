@@ -0,0 +1,185 @@
+//! Chain head webhook notifier.
+//!
+//! Posts every new canonical head to a set of webhooks, so downstream services learn about new
+//! blocks without polling the RPC. Unlike the [event stream](crate::events), delivery is
+//! best-effort: an update is retried a few times and then dropped, and nothing is replayed after
+//! a restart.
+//!
+//! If a secret is configured, the body of every request is signed with HMAC-SHA256 and the
+//! hex-encoded signature is sent in the [`SIGNATURE_HEADER`] header, so webhooks can
+//! authenticate the updates.
+
+use alloy_primitives::{hex, B256};
+use futures::{Stream, StreamExt};
+use hmac::{Hmac, Mac};
+use reth_chain_state::CanonStateNotification;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{sync::Arc, time::Duration};
+use tracing::{debug, warn};
+
+/// The header carrying the HMAC-SHA256 signature of the body.
+pub const SIGNATURE_HEADER: &str = "x-odyssey-signature";
+
+/// The default number of times a head update is retried per webhook.
+pub const DEFAULT_HEAD_WEBHOOK_RETRIES: usize = 3;
+
+/// The timeout of a single request to a webhook.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The initial delay between retries of an update, doubled after every attempt.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// A new canonical head, as posted to the webhooks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeadUpdate {
+    /// The hash of the block.
+    pub hash: B256,
+    /// The number of the block.
+    pub number: u64,
+    /// The timestamp of the block.
+    pub timestamp: u64,
+    /// The number of transactions in the block.
+    pub transaction_count: usize,
+    /// The gas used by the block.
+    pub gas_used: u64,
+}
+
+/// Posts canonical head updates to webhooks.
+#[derive(Debug, Clone)]
+pub struct HeadNotifier {
+    inner: Arc<HeadNotifierInner>,
+}
+
+#[derive(Debug)]
+struct HeadNotifierInner {
+    client: reqwest::Client,
+    urls: Vec<String>,
+    secret: Option<Vec<u8>>,
+    retries: usize,
+}
+
+impl HeadNotifier {
+    /// Creates a new notifier that posts to the given URLs, signing the updates with the secret
+    /// if one is given.
+    pub fn new(urls: Vec<String>, secret: Option<Vec<u8>>) -> Self {
+        Self {
+            inner: Arc::new(HeadNotifierInner {
+                client: reqwest::Client::new(),
+                urls,
+                secret,
+                retries: DEFAULT_HEAD_WEBHOOK_RETRIES,
+            }),
+        }
+    }
+
+    /// Spawns a task that posts the tip of every canonical state notification.
+    ///
+    /// Every webhook is posted to independently, so a slow or failing webhook does not delay the
+    /// others. Updates may arrive out of order if they are retried, so webhooks should order them
+    /// by block number.
+    pub fn spawn<St>(self, mut st: St)
+    where
+        St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
+    {
+        tokio::task::spawn(async move {
+            while let Some(notification) = st.next().await {
+                let tip = notification.tip();
+                let update = HeadUpdate {
+                    hash: tip.hash(),
+                    number: tip.number,
+                    timestamp: tip.timestamp,
+                    transaction_count: tip.body.transactions.len(),
+                    gas_used: tip.gas_used,
+                };
+                let body = match serde_json::to_vec(&update) {
+                    Ok(body) => body,
+                    Err(err) => {
+                        warn!(target: "odyssey::heads", %err, "Failed to serialize head update");
+                        continue;
+                    }
+                };
+                let signature = self.inner.secret.as_deref().map(|secret| sign(secret, &body));
+                let number = update.number;
+
+                for url in &self.inner.urls {
+                    let this = self.clone();
+                    let url = url.clone();
+                    let body = body.clone();
+                    let signature = signature.clone();
+                    tokio::task::spawn(
+                        async move { this.deliver(&url, body, signature, number).await },
+                    );
+                }
+            }
+        });
+    }
+
+    /// Posts an update to a webhook, retrying with exponential backoff.
+    async fn deliver(&self, url: &str, body: Vec<u8>, signature: Option<String>, number: u64) {
+        let mut delay = INITIAL_RETRY_DELAY;
+        for attempt in 0..=self.inner.retries {
+            let mut request = self
+                .inner
+                .client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .timeout(REQUEST_TIMEOUT)
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                request = request.header(SIGNATURE_HEADER, signature);
+            }
+
+            match request.send().await.and_then(|response| response.error_for_status()) {
+                Ok(_) => return,
+                Err(err) => debug!(
+                    target: "odyssey::heads",
+                    %url,
+                    number,
+                    attempt,
+                    %err,
+                    "Failed to post head update"
+                ),
+            }
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+        warn!(target: "odyssey::heads", %url, number, "Dropping head update after retries");
+    }
+}
+
+/// Returns the hex-encoded HMAC-SHA256 signature of the body.
+fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC can take a key of any size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_body() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn serializes_update() {
+        let update = HeadUpdate {
+            hash: B256::ZERO,
+            number: 1,
+            timestamp: 2,
+            transaction_count: 3,
+            gas_used: 4,
+        };
+        let json = serde_json::to_value(&update).unwrap();
+        assert_eq!(json["transactionCount"], 3);
+        assert_eq!(json["gasUsed"], 4);
+    }
+}
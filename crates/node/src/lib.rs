@@ -15,10 +15,29 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 #![warn(unused_crate_dependencies)]
 
+pub mod activity;
+pub mod args;
 pub mod broadcaster;
 pub mod chainspec;
+pub mod config;
 pub mod delayed_resolve;
+pub mod deposits;
+pub mod events;
 pub mod evm;
+pub mod experiments;
+pub mod extra_data;
+pub mod forks;
 pub mod forwarder;
+pub mod heads;
+pub mod mine;
 pub mod node;
+pub mod proof_history;
+pub mod reorgs;
+pub mod replay;
 pub mod rpc;
+pub mod rsa;
+pub mod self_check;
+pub mod semantics;
+pub mod vectors;
+pub mod version;
+pub mod withdrawals;
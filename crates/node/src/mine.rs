@@ -0,0 +1,190 @@
+//! Manual block production on development chains.
+//!
+//! On a development chain (`--dev`), `odyssey_mine(blocks, interval)` builds the given number of
+//! blocks on top of the canonical head through the engine API of the node, so tests and local
+//! development can advance the chain on demand instead of waiting for a timer.
+//!
+//! The timestamp of every block is `interval` seconds after its parent, and the fee recipient and
+//! `prevRandao` are zero, so mining the same transactions on the same chain always produces the
+//! same blocks.
+
+use alloy_eips::BlockId;
+use alloy_primitives::{Address, B256, U64};
+use alloy_rpc_types_engine::{ForkchoiceState, ForkchoiceUpdated, PayloadStatus};
+use eyre::{eyre, OptionExt};
+use jsonrpsee::{
+    core::{async_trait, params::ArrayParams, RpcResult},
+    proc_macros::rpc,
+    types::{error::INTERNAL_ERROR_CODE, ErrorObject},
+    RpcModule,
+};
+use op_alloy_rpc_types_engine::{OpExecutionPayloadEnvelopeV3, OpPayloadAttributes};
+use reth_engine_local::LocalPayloadAttributesBuilder;
+use reth_optimism_chainspec::OpChainSpec;
+use reth_optimism_node::OpEngineTypes;
+use reth_payload_builder::PayloadBuilderHandle;
+use reth_payload_primitives::{PayloadAttributesBuilder, PayloadKind};
+use reth_storage_api::BlockReaderIdExt;
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, trace};
+
+/// The default number of seconds between the timestamps of mined blocks.
+pub const DEFAULT_MINE_INTERVAL: u64 = 1;
+
+/// The maximum number of blocks mined in one call.
+pub const MAX_MINE_BLOCKS: u64 = 1_000;
+
+const FORKCHOICE_UPDATED_V3: &str = "engine_forkchoiceUpdatedV3";
+const NEW_PAYLOAD_V3: &str = "engine_newPayloadV3";
+
+/// Odyssey `odyssey_` RPC namespace for development chains.
+#[cfg_attr(not(any(test, feature = "client")), rpc(server, namespace = "odyssey"))]
+#[cfg_attr(any(test, feature = "client"), rpc(server, client, namespace = "odyssey"))]
+pub trait DevMinerApi {
+    /// Mines `blocks` blocks (one by default) on top of the canonical head, `interval` seconds
+    /// apart, and returns their hashes.
+    #[method(name = "mine")]
+    async fn mine(&self, blocks: Option<U64>, interval: Option<U64>) -> RpcResult<Vec<B256>>;
+}
+
+/// Mines blocks on demand through the engine API of the node.
+#[derive(Debug, Clone)]
+pub struct DevMiner<Provider> {
+    inner: Arc<DevMinerInner<Provider>>,
+}
+
+#[derive(Debug)]
+struct DevMinerInner<Provider> {
+    provider: Provider,
+    chain_spec: Arc<OpChainSpec>,
+    engine_module: RpcModule<()>,
+    payload_builder: PayloadBuilderHandle<OpEngineTypes>,
+    /// Serializes calls, so every block is built on top of the previous one.
+    lock: Mutex<()>,
+}
+
+impl<Provider> DevMiner<Provider>
+where
+    Provider: BlockReaderIdExt,
+{
+    /// Creates a new miner that builds payloads with the given payload builder, and imports them
+    /// through the given engine API module.
+    pub fn new(
+        provider: Provider,
+        chain_spec: Arc<OpChainSpec>,
+        engine_module: RpcModule<()>,
+        payload_builder: PayloadBuilderHandle<OpEngineTypes>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(DevMinerInner {
+                provider,
+                chain_spec,
+                engine_module,
+                payload_builder,
+                lock: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Mines a block on top of the canonical head, `interval` seconds after it, and makes it the
+    /// new canonical head.
+    async fn mine_block(&self, interval: u64) -> eyre::Result<B256> {
+        let head = self
+            .inner
+            .provider
+            .sealed_header_by_id(BlockId::latest())?
+            .ok_or_eyre("canonical head not found")?;
+
+        // start building a payload on top of the head
+        let mut attributes: OpPayloadAttributes =
+            LocalPayloadAttributesBuilder::new(self.inner.chain_spec.clone())
+                .build(head.timestamp + interval);
+        attributes.payload_attributes.prev_randao = B256::ZERO;
+        attributes.payload_attributes.suggested_fee_recipient = Address::ZERO;
+        let updated: ForkchoiceUpdated = self
+            .call(FORKCHOICE_UPDATED_V3, (forkchoice_state(head.hash()), Some(attributes)))
+            .await?;
+        let payload_id = updated
+            .payload_id
+            .ok_or_else(|| eyre!("payload was not started: {:?}", updated.payload_status))?;
+
+        // wait for the payload to include the pending transactions, and import it
+        let payload = self
+            .inner
+            .payload_builder
+            .resolve_kind(payload_id, PayloadKind::WaitForPending)
+            .await
+            .ok_or_eyre("payload job not found")??;
+        let envelope = OpExecutionPayloadEnvelopeV3::from(payload);
+        let hash = envelope.execution_payload.payload_inner.payload_inner.block_hash;
+        let status: PayloadStatus = self
+            .call(
+                NEW_PAYLOAD_V3,
+                (envelope.execution_payload, Vec::<B256>::new(), envelope.parent_beacon_block_root),
+            )
+            .await?;
+        if !status.is_valid() {
+            eyre::bail!("mined block {hash} is invalid: {}", status.status);
+        }
+
+        // make the block canonical
+        let updated: ForkchoiceUpdated =
+            self.call(FORKCHOICE_UPDATED_V3, (forkchoice_state(hash), None::<()>)).await?;
+        if !updated.is_valid() {
+            eyre::bail!("mined block {hash} was not made canonical: {}", updated.payload_status);
+        }
+
+        debug!(target: "rpc::odyssey", %hash, number = head.number + 1, "Mined block");
+        Ok(hash)
+    }
+
+    /// Calls a method of the engine API module with the given positional parameters.
+    async fn call<P, R>(&self, method: &str, params: P) -> eyre::Result<R>
+    where
+        P: Serialize,
+        R: DeserializeOwned + Clone,
+    {
+        let mut array = ArrayParams::new();
+        for param in serde_json::to_value(params)?.as_array().into_iter().flatten() {
+            array.insert(param)?;
+        }
+        Ok(self.inner.engine_module.call(method, array).await?)
+    }
+}
+
+#[async_trait]
+impl<Provider> DevMinerApiServer for DevMiner<Provider>
+where
+    Provider: BlockReaderIdExt + 'static,
+{
+    async fn mine(&self, blocks: Option<U64>, interval: Option<U64>) -> RpcResult<Vec<B256>> {
+        trace!(target: "rpc::odyssey", ?blocks, ?interval, "Serving odyssey_mine");
+
+        let blocks = blocks.map_or(1, |blocks| blocks.to());
+        if blocks > MAX_MINE_BLOCKS {
+            return Err(ErrorObject::owned(
+                jsonrpsee::types::error::INVALID_PARAMS_CODE,
+                format!("too many blocks, at most {MAX_MINE_BLOCKS} are mined at once"),
+                None::<()>,
+            ));
+        }
+        let interval = interval.map_or(DEFAULT_MINE_INTERVAL, |interval| interval.to());
+
+        let _guard = self.inner.lock.lock().await;
+        let mut hashes = Vec::with_capacity(blocks as usize);
+        for _ in 0..blocks {
+            let hash = self.mine_block(interval).await.map_err(|err| {
+                ErrorObject::owned(INTERNAL_ERROR_CODE, err.to_string(), None::<()>)
+            })?;
+            hashes.push(hash);
+        }
+        Ok(hashes)
+    }
+}
+
+/// Returns the fork choice state with the given block as head, safe and finalized block.
+const fn forkchoice_state(hash: B256) -> ForkchoiceState {
+    ForkchoiceState { head_block_hash: hash, safe_block_hash: hash, finalized_block_hash: hash }
+}
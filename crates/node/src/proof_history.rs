@@ -0,0 +1,247 @@
+//! History of the state available for `eth_getProof`.
+//!
+//! Proofs of historical state need the changesets of all blocks after the requested block, which
+//! are removed by pruning. [`StateHistory`] derives the earliest block that can still be proven
+//! from the prune configuration of the node, so requests for pruned state fail with a
+//! [`StatePruned`] error naming the earliest available block instead of an internal error.
+//!
+//! Withdrawals are proven against the storage of the withdrawal contract at the block of an output
+//! root, which may be older than the general state history. [`WithdrawalProofHistory`] records the
+//! previous values of the storage slots of the withdrawal contract for a configurable number of
+//! blocks, so its storage at those blocks can be proven by reverting the latest storage.
+
+use alloy_primitives::{keccak256, Address, B256, U256};
+use futures::{Stream, StreamExt};
+use jsonrpsee::types::ErrorObject;
+use parking_lot::RwLock;
+use reth_chain_state::CanonStateNotification;
+use reth_prune_types::{PruneMode, PruneModes};
+use reth_trie_common::HashedStorage;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+/// The error code of requests for state that is pruned.
+pub const STATE_PRUNED_CODE: i32 = -32021;
+
+/// The data of the error of requests for state that is pruned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatePruned {
+    /// The requested block.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub block_number: u64,
+    /// The earliest block whose state is available.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub earliest_block: u64,
+}
+
+impl From<StatePruned> for ErrorObject<'static> {
+    fn from(error: StatePruned) -> Self {
+        ErrorObject::owned(
+            STATE_PRUNED_CODE,
+            format!(
+                "state at block {} is pruned, the earliest available block is {}",
+                error.block_number, error.earliest_block
+            ),
+            Some(error),
+        )
+    }
+}
+
+/// The state history retained by the prune configuration of the node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateHistory {
+    account_history: Option<PruneMode>,
+    storage_history: Option<PruneMode>,
+}
+
+impl StateHistory {
+    /// Creates the state history of the given prune configuration.
+    pub const fn new(modes: &PruneModes) -> Self {
+        Self { account_history: modes.account_history, storage_history: modes.storage_history }
+    }
+
+    /// Returns the earliest block whose state is available, given the current tip.
+    ///
+    /// This is conservative, since the pruner may not have caught up with the tip yet.
+    pub fn earliest_block(&self, tip: u64) -> u64 {
+        let earliest = |mode: Option<PruneMode>| match mode {
+            None => 0,
+            Some(PruneMode::Full) => tip,
+            Some(PruneMode::Distance(distance)) => tip.saturating_sub(distance),
+            Some(PruneMode::Before(block)) => block.min(tip),
+        };
+        earliest(self.account_history).max(earliest(self.storage_history))
+    }
+}
+
+/// The previous values of the storage slots of the withdrawal contract changed in recent blocks.
+#[derive(Debug, Clone)]
+pub struct WithdrawalProofHistory {
+    inner: Arc<WithdrawalProofHistoryInner>,
+}
+
+#[derive(Debug)]
+struct WithdrawalProofHistoryInner {
+    /// The address of the withdrawal contract.
+    withdrawal_contract: Address,
+    /// The number of blocks the history is retained for.
+    retention: u64,
+    /// The recorded blocks, by number.
+    blocks: RwLock<BTreeMap<u64, BlockStorageChanges>>,
+}
+
+/// The storage changes of the withdrawal contract in a block.
+#[derive(Debug, Clone, Default)]
+struct BlockStorageChanges {
+    /// The hash of the block.
+    hash: B256,
+    /// The values of the changed slots before the block.
+    previous: HashMap<B256, U256>,
+}
+
+impl WithdrawalProofHistory {
+    /// Creates a new history of the storage of the withdrawal contract, retained for the given
+    /// number of blocks.
+    pub fn new(withdrawal_contract: Address, retention: u64) -> Self {
+        Self {
+            inner: Arc::new(WithdrawalProofHistoryInner {
+                withdrawal_contract,
+                retention,
+                blocks: Default::default(),
+            }),
+        }
+    }
+
+    /// Records the previous values of the slots changed in a canonical block.
+    pub fn record(&self, number: u64, hash: B256, previous: HashMap<B256, U256>) {
+        let mut blocks = self.inner.blocks.write();
+        blocks.insert(number, BlockStorageChanges { hash, previous });
+        while blocks.len() as u64 > self.inner.retention {
+            blocks.pop_first();
+        }
+    }
+
+    /// Removes the blocks from the given block number on, which were reorged out.
+    pub fn revert(&self, first_reverted: u64) {
+        self.inner.blocks.write().split_off(&first_reverted);
+    }
+
+    /// Returns the earliest block whose storage can be proven, if any block was recorded.
+    pub fn earliest_block(&self) -> Option<u64> {
+        self.inner.blocks.read().first_key_value().map(|(number, _)| number.saturating_sub(1))
+    }
+
+    /// Returns the hash of the latest recorded block, and the storage overlay that reverts the
+    /// storage of the withdrawal contract at that block to the given block.
+    ///
+    /// Returns `None` if the block is not retained, or is not older than the latest recorded
+    /// block.
+    pub fn revert_to(&self, block_number: u64) -> Option<(B256, HashedStorage)> {
+        let blocks = self.inner.blocks.read();
+        let (first, _) = blocks.first_key_value()?;
+        let (last, latest) = blocks.last_key_value()?;
+        if block_number < first.saturating_sub(1) || block_number >= *last {
+            return None;
+        }
+
+        // the value of a slot at the block is its value before the first later change
+        let mut storage = HashMap::new();
+        for (_, changes) in blocks.range(block_number + 1..).rev() {
+            storage.extend(changes.previous.iter().map(|(slot, value)| (*slot, *value)));
+        }
+
+        Some((
+            latest.hash,
+            HashedStorage::from_iter(
+                false,
+                storage.into_iter().map(|(slot, value)| (keccak256(slot), value)),
+            ),
+        ))
+    }
+
+    /// Spawns a task that records the storage changes of the withdrawal contract in canonical
+    /// blocks.
+    pub fn spawn<St>(self, mut st: St)
+    where
+        St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
+    {
+        tokio::task::spawn(async move {
+            while let Some(notification) = st.next().await {
+                if let Some(reverted) = notification.reverted() {
+                    self.revert(reverted.first().number);
+                }
+
+                let committed = notification.committed();
+                let reverts = &committed.execution_outcome().bundle.reverts;
+                for (block, reverts) in committed.blocks_iter().zip(reverts.iter()) {
+                    let previous = reverts
+                        .iter()
+                        .find(|(address, _)| *address == self.inner.withdrawal_contract)
+                        .map(|(_, revert)| {
+                            revert
+                                .storage
+                                .iter()
+                                .map(|(slot, value)| (B256::from(*slot), value.to_previous_value()))
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    self.record(block.number, block.hash(), previous);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn earliest_block_of_prune_modes() {
+        assert_eq!(StateHistory::default().earliest_block(1_000), 0);
+
+        let history = StateHistory::new(&PruneModes {
+            account_history: Some(PruneMode::Distance(100)),
+            storage_history: Some(PruneMode::Before(950)),
+            ..Default::default()
+        });
+        assert_eq!(history.earliest_block(1_000), 950);
+        assert_eq!(history.earliest_block(1_100), 1_000);
+        assert_eq!(history.earliest_block(10), 10);
+    }
+
+    #[test]
+    fn reverts_storage() {
+        let history = WithdrawalProofHistory::new(Address::ZERO, 3);
+        let slot = B256::with_last_byte(1);
+        let changed = |value: u64| HashMap::from([(slot, U256::from(value))]);
+
+        history.record(1, B256::with_last_byte(1), changed(0));
+        history.record(2, B256::with_last_byte(2), HashMap::new());
+        history.record(3, B256::with_last_byte(3), changed(1));
+        history.record(4, B256::with_last_byte(4), changed(2));
+
+        // block 1 is no longer retained
+        assert_eq!(history.earliest_block(), Some(1));
+        assert!(history.revert_to(0).is_none());
+        // the latest block is not reverted
+        assert!(history.revert_to(4).is_none());
+
+        // the slot had the value before the change in block 3
+        let (tip, overlay) = history.revert_to(1).unwrap();
+        assert_eq!(tip, B256::with_last_byte(4));
+        assert_eq!(overlay.storage.get(&keccak256(slot)), Some(&U256::from(1)));
+
+        let (_, overlay) = history.revert_to(3).unwrap();
+        assert_eq!(overlay.storage.get(&keccak256(slot)), Some(&U256::from(2)));
+
+        // reorged blocks are removed
+        history.revert(4);
+        let (tip, _) = history.revert_to(2).unwrap();
+        assert_eq!(tip, B256::with_last_byte(3));
+    }
+}
@@ -0,0 +1,181 @@
+//! Journal of observed chain reorgs.
+//!
+//! Every reorg observed through canonical state notifications is recorded with its depth, the
+//! replaced and new tips, and the sponsored transactions that were reorged out and not included
+//! again by the new chain. The journal keeps the most recent reorgs since the node started, and is
+//! served by `odyssey_getReorgHistory`.
+
+use alloy_primitives::{Address, TxHash, B256};
+use futures::{Stream, StreamExt};
+use odyssey_common::unix_epoch_ms;
+use parking_lot::RwLock;
+use reth_chain_state::CanonStateNotification;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+};
+use tracing::info;
+
+/// The default number of recent reorgs kept in the journal.
+pub const DEFAULT_REORG_JOURNAL_SIZE: usize = 256;
+
+/// A reorg of the canonical chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReorgRecord {
+    /// The number of blocks that were removed from the canonical chain.
+    pub depth: u64,
+    /// The number of the tip that was replaced.
+    pub old_tip_number: u64,
+    /// The hash of the tip that was replaced.
+    pub old_tip_hash: B256,
+    /// The number of the new tip.
+    pub new_tip_number: u64,
+    /// The hash of the new tip.
+    pub new_tip_hash: B256,
+    /// The sponsored transactions that were reorged out and not included by the new chain.
+    pub sponsored_transactions: Vec<TxHash>,
+    /// The wall time the reorg was observed at, in milliseconds since the unix epoch.
+    pub observed_at_ms: u64,
+}
+
+impl ReorgRecord {
+    /// Returns the record of the reorg of the notification, observed at the given wall time, or
+    /// `None` if the notification is not a reorg.
+    fn new(
+        notification: &CanonStateNotification,
+        sponsors: &[Address],
+        observed_at_ms: u64,
+    ) -> Option<Self> {
+        let reverted = notification.reverted()?;
+        let committed = notification.committed();
+        let (old_tip, new_tip) = (reverted.tip(), committed.tip());
+        let included: HashSet<TxHash> = committed
+            .blocks_iter()
+            .flat_map(|block| block.body.transactions.iter())
+            .map(|tx| tx.hash())
+            .collect();
+        let sponsored_transactions = reverted
+            .blocks_iter()
+            .flat_map(|block| block.transactions_with_sender())
+            .filter(|(sender, tx)| sponsors.contains(*sender) && !included.contains(&tx.hash()))
+            .map(|(_, tx)| tx.hash())
+            .collect();
+        Some(Self {
+            depth: reverted.len() as u64,
+            old_tip_number: old_tip.number,
+            old_tip_hash: old_tip.hash(),
+            new_tip_number: new_tip.number,
+            new_tip_hash: new_tip.hash(),
+            sponsored_transactions,
+            observed_at_ms,
+        })
+    }
+}
+
+/// Bounded journal of the most recent reorgs.
+#[derive(Debug, Clone)]
+pub struct ReorgJournal {
+    inner: Arc<ReorgJournalInner>,
+}
+
+#[derive(Debug)]
+struct ReorgJournalInner {
+    /// The recorded reorgs, oldest first.
+    records: RwLock<VecDeque<ReorgRecord>>,
+    /// The maximum number of recorded reorgs.
+    capacity: usize,
+    /// The senders whose reorged transactions are recorded.
+    sponsors: Vec<Address>,
+}
+
+impl ReorgJournal {
+    /// Creates a new journal that keeps the given number of recent reorgs, and records the
+    /// reorged transactions of the given sponsors.
+    pub fn new(capacity: usize, sponsors: Vec<Address>) -> Self {
+        Self {
+            inner: Arc::new(ReorgJournalInner {
+                records: RwLock::new(VecDeque::with_capacity(capacity)),
+                capacity,
+                sponsors,
+            }),
+        }
+    }
+
+    /// Records a reorg, evicting the oldest record if the journal is full.
+    pub fn record(&self, record: ReorgRecord) {
+        let mut records = self.inner.records.write();
+        if records.len() >= self.inner.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Returns up to `limit` of the most recent reorgs, newest first.
+    pub fn history(&self, limit: usize) -> Vec<ReorgRecord> {
+        self.inner.records.read().iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Spawns a task that records the reorgs of canonical state notifications.
+    pub fn spawn<St>(self, mut st: St)
+    where
+        St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
+    {
+        tokio::task::spawn(async move {
+            while let Some(notification) = st.next().await {
+                let Some(record) =
+                    ReorgRecord::new(&notification, &self.inner.sponsors, unix_epoch_ms())
+                else {
+                    continue;
+                };
+                info!(
+                    target: "odyssey::reorgs",
+                    depth = record.depth,
+                    old_tip = %record.old_tip_hash,
+                    new_tip = %record.new_tip_hash,
+                    sponsored = record.sponsored_transactions.len(),
+                    "Chain reorged"
+                );
+                self.record(record);
+            }
+        });
+    }
+}
+
+impl Default for ReorgJournal {
+    fn default() -> Self {
+        Self::new(DEFAULT_REORG_JOURNAL_SIZE, Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(depth: u64) -> ReorgRecord {
+        ReorgRecord {
+            depth,
+            old_tip_number: 10,
+            old_tip_hash: B256::with_last_byte(1),
+            new_tip_number: 10,
+            new_tip_hash: B256::with_last_byte(2),
+            sponsored_transactions: Vec::new(),
+            observed_at_ms: 0,
+        }
+    }
+
+    #[test]
+    fn keeps_recent_reorgs() {
+        let journal = ReorgJournal::new(2, Vec::new());
+        assert!(journal.history(10).is_empty());
+
+        journal.record(record(1));
+        journal.record(record(2));
+        journal.record(record(3));
+
+        let history = journal.history(10);
+        assert_eq!(history.iter().map(|record| record.depth).collect::<Vec<_>>(), vec![3, 2]);
+        assert_eq!(journal.history(1), vec![record(3)]);
+    }
+}
@@ -0,0 +1,223 @@
+//! Re-execution of historical blocks.
+//!
+//! Changes to the execution of experiments, e.g. to precompiles or instructions of the
+//! [`OdysseyEvmConfig`], can change the outcome of blocks that are already part of the chain.
+//! Replaying a range of stored blocks with the current EVM configuration and comparing the gas
+//! used, receipts and state roots with the stored ones shows whether a change diverges from the
+//! execution of the chain.
+
+use crate::evm::OdysseyEvmConfig;
+use alloy_primitives::{BlockNumber, B256, U256};
+use reth_evm::execute::{BasicBlockExecutorProvider, BlockExecutorProvider, Executor};
+use reth_optimism_chainspec::OpChainSpec;
+use reth_optimism_node::OpExecutionStrategyFactory;
+use reth_primitives::{Block, Receipt};
+use reth_revm::database::StateProviderDatabase;
+use reth_storage_api::{
+    BlockReader, ReceiptProvider, StateProviderFactory, StateRootProvider, TransactionVariant,
+};
+use reth_trie_common::{HashedPostState, KeccakKeyHasher};
+use std::{fmt, ops::RangeInclusive, sync::Arc};
+
+/// A difference between the stored outcome of a block and the outcome of re-executing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The block could not be re-executed.
+    Execution(String),
+    /// The gas used by the block differs.
+    GasUsed {
+        /// The gas used according to the stored header.
+        stored: u64,
+        /// The gas used by the re-executed block.
+        replayed: u64,
+    },
+    /// The number of receipts differs.
+    ReceiptCount {
+        /// The number of stored receipts.
+        stored: usize,
+        /// The number of receipts of the re-executed block.
+        replayed: usize,
+    },
+    /// The receipt of a transaction differs.
+    Receipt {
+        /// The index of the transaction in the block.
+        index: usize,
+    },
+    /// The state root differs.
+    StateRoot {
+        /// The state root of the stored header.
+        stored: B256,
+        /// The state root after re-executing the block.
+        replayed: B256,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Execution(err) => write!(f, "execution failed: {err}"),
+            Self::GasUsed { stored, replayed } => {
+                write!(f, "gas used is {replayed}, stored {stored}")
+            }
+            Self::ReceiptCount { stored, replayed } => {
+                write!(f, "{replayed} receipts, stored {stored}")
+            }
+            Self::Receipt { index } => write!(f, "receipt of transaction {index} differs"),
+            Self::StateRoot { stored, replayed } => {
+                write!(f, "state root is {replayed}, stored {stored}")
+            }
+        }
+    }
+}
+
+/// The outcome of replaying a range of blocks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// The number of blocks that were replayed.
+    pub blocks: u64,
+    /// The blocks whose outcome diverged, with their divergences.
+    pub diverged: Vec<(BlockNumber, Vec<Divergence>)>,
+}
+
+impl ReplayReport {
+    /// Returns whether every replayed block matched its stored outcome.
+    pub fn is_ok(&self) -> bool {
+        self.diverged.is_empty()
+    }
+}
+
+/// Compares the stored receipts of a block with the receipts of re-executing it, returning the
+/// first divergence.
+pub fn compare_receipts(stored: &[Receipt], replayed: &[Receipt]) -> Option<Divergence> {
+    if stored.len() != replayed.len() {
+        return Some(Divergence::ReceiptCount { stored: stored.len(), replayed: replayed.len() });
+    }
+    stored
+        .iter()
+        .zip(replayed)
+        .position(|(stored, replayed)| {
+            stored.success != replayed.success
+                || stored.cumulative_gas_used != replayed.cumulative_gas_used
+                || stored.logs != replayed.logs
+        })
+        .map(|index| Divergence::Receipt { index })
+}
+
+/// Re-executes historical blocks with the [`OdysseyEvmConfig`].
+#[derive(Debug)]
+pub struct BlockReplayer<Provider> {
+    provider: Provider,
+    executor: BasicBlockExecutorProvider<OpExecutionStrategyFactory<OdysseyEvmConfig>>,
+}
+
+impl<Provider> BlockReplayer<Provider>
+where
+    Provider:
+        StateProviderFactory + BlockReader<Block = Block> + ReceiptProvider<Receipt = Receipt>,
+{
+    /// Creates a new replayer of the blocks of the provider on the given chain.
+    pub fn new(provider: Provider, chain_spec: Arc<OpChainSpec>) -> Self {
+        let evm_config = OdysseyEvmConfig::new(chain_spec.clone());
+        let executor = BasicBlockExecutorProvider::new(OpExecutionStrategyFactory::new(
+            chain_spec, evm_config,
+        ));
+        Self { provider, executor }
+    }
+
+    /// Replays the blocks of the range, and reports the blocks whose outcome diverged.
+    pub fn replay_range(&self, range: RangeInclusive<BlockNumber>) -> eyre::Result<ReplayReport> {
+        let mut report = ReplayReport::default();
+        for number in range {
+            let divergences = self.replay_block(number)?;
+            report.blocks += 1;
+            if !divergences.is_empty() {
+                report.diverged.push((number, divergences));
+            }
+        }
+        Ok(report)
+    }
+
+    /// Re-executes a block on top of the state of its parent, and returns the differences to its
+    /// stored outcome.
+    ///
+    /// Receipts are only compared if they are stored, i.e. if they were not pruned. The genesis
+    /// block can not be replayed, since there is no state before it.
+    pub fn replay_block(&self, number: BlockNumber) -> eyre::Result<Vec<Divergence>> {
+        let parent = number
+            .checked_sub(1)
+            .ok_or_else(|| eyre::eyre!("the genesis block can not be replayed"))?;
+        let block = self
+            .provider
+            .block_with_senders(number.into(), TransactionVariant::WithHash)?
+            .ok_or_else(|| eyre::eyre!("block {number} not found"))?;
+        let state = self.provider.history_by_block_number(parent)?;
+
+        let output = match self
+            .executor
+            .executor(StateProviderDatabase::new(&state))
+            .execute((&block, U256::ZERO).into())
+        {
+            Ok(output) => output,
+            Err(err) => return Ok(vec![Divergence::Execution(err.to_string())]),
+        };
+
+        let mut divergences = Vec::new();
+        if output.gas_used != block.header.gas_used {
+            divergences.push(Divergence::GasUsed {
+                stored: block.header.gas_used,
+                replayed: output.gas_used,
+            });
+        }
+        if let Some(stored) = self.provider.receipts_by_block(number.into())? {
+            divergences.extend(compare_receipts(&stored, &output.receipts));
+        }
+        let state_root = state.state_root(
+            HashedPostState::from_bundle_state::<KeccakKeyHasher>(&output.state.state),
+        )?;
+        if state_root != block.header.state_root {
+            divergences.push(Divergence::StateRoot {
+                stored: block.header.state_root,
+                replayed: state_root,
+            });
+        }
+        Ok(divergences)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{Address, Log};
+
+    fn receipt(cumulative_gas_used: u64, logs: usize) -> Receipt {
+        Receipt {
+            success: true,
+            cumulative_gas_used,
+            logs: vec![Log::empty(); logs],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compares_receipts() {
+        let stored = [receipt(21_000, 0), receipt(50_000, 1)];
+        assert_eq!(compare_receipts(&stored, &stored), None);
+
+        assert_eq!(
+            compare_receipts(&stored, &stored[..1]),
+            Some(Divergence::ReceiptCount { stored: 2, replayed: 1 })
+        );
+        assert_eq!(
+            compare_receipts(&stored, &[receipt(21_000, 0), receipt(60_000, 1)]),
+            Some(Divergence::Receipt { index: 1 })
+        );
+
+        let mut reverted = stored.clone();
+        reverted[0].success = false;
+        assert_eq!(compare_receipts(&stored, &reverted), Some(Divergence::Receipt { index: 0 }));
+
+        let mut other_logs = stored.clone();
+        other_logs[1].logs[0].address = Address::with_last_byte(1);
+        assert_eq!(compare_receipts(&stored, &other_logs), Some(Divergence::Receipt { index: 1 }));
+    }
+}
@@ -1,40 +1,154 @@
 //! Odyssey rpc logic.
 //!
-//! `eth_` namespace overrides:
+//! `eth_` namespace overrides, installed next to the `eth_` methods of reth with
+//! [`EthApiExt::install`]:
 //!
 //! - `eth_getProof` will _ONLY_ return the storage proofs _WITHOUT_ an account proof _IF_ targeting
-//!   the withdrawal contract. Otherwise, it fallbacks to default behaviour.
+//!   the withdrawal contract. Otherwise, it fallbacks to default behaviour. The withdrawal contract
+//!   is configured per chain, see [`withdrawal_contract`](crate::chainspec::withdrawal_contract).
+//!   Light clients that verify the storage root against the state root request the account proof as
+//!   well by passing `true` as the optional fourth parameter. Requests for pruned state fail with a
+//!   [`StatePruned`] error naming the earliest available block, see
+//!   [`proof_history`](crate::proof_history).
+//!
+//! `odyssey_` namespace:
+//!
+//! - `odyssey_callAsDelegated` executes an `eth_call` as if the caller had delegated to the given
+//!   address using [EIP-7702](https://eips.ethereum.org/EIPS/eip-7702).
+//! - `odyssey_getDepositInfo` returns the decoded fields and the inclusion of a deposit
+//!   transaction.
+//! - `odyssey_getAccountBundle` returns the state of an account, including its delegation, in one
+//!   call.
+//! - `odyssey_getBlockExperiments` decodes the experiment tag of a block, see
+//!   [`extra_data`](crate::extra_data).
+//! - `odyssey_getBlockReceipts` returns the receipts of a block, annotated with whether each
+//!   transaction was sent by a sponsor and the delegation of its destination.
+//! - `odyssey_getVersionInfo` returns the build and experiments of the node, see
+//!   [`version`](crate::version).
+//! - `odyssey_getExperimentStatus` returns whether each experiment is enabled and active, and its
+//!   parameters, see [`experiments`](crate::experiments).
+//! - `odyssey_buildWithdrawalTx` builds the transaction that initiates a withdrawal to L1, see
+//!   [`withdrawals`](crate::withdrawals).
+//! - `odyssey_verifyDelegationSemantics` compares the code of an account returned by `eth_getCode`
+//!   with the code read by the `EXTCODE*` instructions, see [`semantics`](crate::semantics).
+//! - `odyssey_subscribeDelegatedActivity` streams the transactions that touch accounts delegating
+//!   to the given implementations, see [`activity`](crate::activity).
 
+use crate::{
+    activity::{DelegatedActivity, DelegatedActivityFeed},
+    config::PayloadConfig,
+    deposits::{DepositIndex, DepositInfo},
+    experiments::{experiment_status, ExperimentSettings, ExperimentStatus},
+    extra_data::{BlockExperiments, ExperimentTag},
+    proof_history::{StateHistory, StatePruned, WithdrawalProofHistory},
+    reorgs::{ReorgJournal, ReorgRecord, DEFAULT_REORG_JOURNAL_SIZE},
+    semantics::{extcode_request, extcode_state_override, DelegationSemantics},
+    version::VersionInfo,
+    withdrawals::{L2ToL1MessagePasser, Withdrawal, WithdrawalTx, DEFAULT_WITHDRAWAL_GAS_LIMIT},
+};
 use alloy_eips::BlockId;
-use alloy_primitives::{Address, B256};
+use alloy_network::ReceiptResponse;
+use alloy_primitives::{b256, Address, Bytes, TxHash, B256, U256, U64};
 use alloy_rpc_types::serde_helpers::JsonStorageKey;
-use alloy_rpc_types_eth::EIP1186AccountProofResponse;
+use alloy_rpc_types_eth::{
+    state::{EvmOverrides, StateOverride},
+    EIP1186AccountProofResponse, TransactionRequest,
+};
+use alloy_sol_types::SolCall;
 use jsonrpsee::{
-    core::{async_trait, RpcResult},
+    core::{async_trait, RegisterMethodError, RpcResult, SubscriptionResult},
     proc_macros::rpc,
+    PendingSubscriptionSink,
 };
-use odyssey_common::WITHDRAWAL_CONTRACT;
+use odyssey_common::{delegation_designator, parse_delegation_designator, WITHDRAWAL_CONTRACT};
+use reth_chainspec::Head;
 use reth_errors::RethError;
+use reth_optimism_chainspec::OpChainSpec;
+use reth_rpc_builder::{RethRpcModule, TransportRpcModules};
 use reth_rpc_eth_api::{
-    helpers::{EthState, FullEthApi},
-    FromEthApiError,
+    helpers::{EthBlocks, EthCall, EthState, FullEthApi},
+    FromEthApiError, RpcNodeCore,
 };
 use reth_rpc_eth_types::EthApiError;
-use reth_trie_common::AccountProof;
-use tracing::trace;
+use reth_storage_api::{BlockIdReader, BlockNumReader, BlockReaderIdExt, StateProvider};
+use reth_trie_common::{AccountProof, HashedStorage};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tracing::{debug, instrument, trace};
+
+/// The maximum number of storage slots that can be requested with `odyssey_getAccountBundle`.
+pub const MAX_ACCOUNT_BUNDLE_SLOTS: usize = 256;
+
+/// The [ERC-1967] storage slot of the implementation of a proxy.
+///
+/// [ERC-1967]: https://eips.ethereum.org/EIPS/eip-1967
+const ERC1967_IMPLEMENTATION_SLOT: B256 =
+    b256!("360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc");
+
+/// The state of an account, as returned by `odyssey_getAccountBundle`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountBundle {
+    /// The balance of the account.
+    pub balance: U256,
+    /// The nonce of the account.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub nonce: u64,
+    /// The hash of the code of the account.
+    pub code_hash: B256,
+    /// The address the account delegates to using [EIP-7702], if any.
+    ///
+    /// [EIP-7702]: https://eips.ethereum.org/EIPS/eip-7702
+    pub designator: Option<Address>,
+    /// The address of the code that executes for the account, if it is delegated.
+    ///
+    /// If the account delegates to an [ERC-1967] proxy, this is the implementation stored in the
+    /// account's storage, otherwise this is the designator.
+    ///
+    /// [ERC-1967]: https://eips.ethereum.org/EIPS/eip-1967
+    pub implementation: Option<Address>,
+    /// The hash of the code of the implementation, if any.
+    pub implementation_code_hash: Option<B256>,
+    /// The values of the requested storage slots of the account.
+    pub storage: BTreeMap<B256, B256>,
+}
+
+/// A receipt annotated with the sponsorship of its transaction and the delegation of its
+/// destination, as returned by `odyssey_getBlockReceipts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotatedReceipt {
+    /// The fields of the receipt, as returned by `eth_getBlockReceipts`.
+    #[serde(flatten)]
+    pub receipt: serde_json::Map<String, serde_json::Value>,
+    /// Whether the transaction was sent by a sponsor.
+    pub sponsored: bool,
+    /// Whether the destination of the transaction is a delegated account.
+    pub delegated: bool,
+    /// The address of the code that executed for the destination, if it is delegated.
+    ///
+    /// See [`AccountBundle::implementation`].
+    pub implementation: Option<Address>,
+}
 
 /// Odyssey `eth_` RPC namespace overrides.
-#[cfg_attr(not(test), rpc(server, namespace = "eth"))]
-#[cfg_attr(test, rpc(server, client, namespace = "eth"))]
+#[cfg_attr(not(any(test, feature = "client")), rpc(server, namespace = "eth"))]
+#[cfg_attr(any(test, feature = "client"), rpc(server, client, namespace = "eth"))]
 pub trait EthApiOverride {
     /// Returns the account and storage values of the specified account including the Merkle-proof.
     /// This call can be used to verify that the data you are pulling from is not tampered with.
+    ///
+    /// The account proof of the withdrawal contract is only included if `full_proof` is `true`.
     #[method(name = "getProof")]
     async fn get_proof(
         &self,
         address: Address,
         keys: Vec<JsonStorageKey>,
         block_number: Option<BlockId>,
+        full_proof: Option<bool>,
     ) -> RpcResult<EIP1186AccountProofResponse>;
 }
 
@@ -42,12 +156,106 @@ pub trait EthApiOverride {
 #[derive(Debug)]
 pub struct EthApiExt<Eth> {
     eth_api: Eth,
+    withdrawal_contract: Address,
+    state_history: StateHistory,
+    withdrawal_history: Option<WithdrawalProofHistory>,
 }
 
 impl<E> EthApiExt<E> {
-    /// Create a new `EthApiExt` module.
-    pub const fn new(eth_api: E) -> Self {
-        Self { eth_api }
+    /// Create a new `EthApiExt` module for a chain with the given withdrawal contract.
+    pub fn new(eth_api: E, withdrawal_contract: Address) -> Self {
+        Self {
+            eth_api,
+            withdrawal_contract,
+            state_history: StateHistory::default(),
+            withdrawal_history: None,
+        }
+    }
+
+    /// Sets the state history retained by the prune configuration of the node.
+    pub const fn with_state_history(mut self, state_history: StateHistory) -> Self {
+        self.state_history = state_history;
+        self
+    }
+
+    /// Proves the storage of the withdrawal contract at blocks whose state is pruned from the
+    /// given history.
+    pub fn with_withdrawal_history(mut self, withdrawal_history: WithdrawalProofHistory) -> Self {
+        self.withdrawal_history = Some(withdrawal_history);
+        self
+    }
+
+    /// Returns whether only the storage of the account is proven, which is the case for the
+    /// withdrawal contract unless its account proof is requested with `full_proof`.
+    fn proves_storage_only(&self, address: Address, full_proof: Option<bool>) -> bool {
+        address == self.withdrawal_contract && !full_proof.unwrap_or_default()
+    }
+
+    /// Ensures that the state of the requested block can be proven, given the tip and the proof
+    /// window of the node.
+    ///
+    /// Proofs of the storage of the withdrawal contract are not limited by the proof window, and
+    /// its storage at pruned blocks is proven from the withdrawal history, in which case the
+    /// latest recorded block and the storage reverted to the requested block are returned.
+    fn check_pruned(
+        &self,
+        requested: u64,
+        tip: u64,
+        proof_window: u64,
+        withdrawal_storage: bool,
+    ) -> Result<Option<(B256, HashedStorage)>, StatePruned> {
+        let mut earliest = self.state_history.earliest_block(tip);
+        if !withdrawal_storage {
+            earliest = earliest.max(tip.saturating_sub(proof_window));
+        }
+        if requested >= earliest {
+            return Ok(None);
+        }
+
+        let history = self.withdrawal_history.as_ref().filter(|_| withdrawal_storage);
+        if let Some(reverted) = history.and_then(|history| history.revert_to(requested)) {
+            return Ok(Some(reverted));
+        }
+        let earliest_block = history
+            .and_then(WithdrawalProofHistory::earliest_block)
+            .map_or(earliest, |retained| retained.min(earliest));
+        Err(StatePruned { block_number: requested, earliest_block })
+    }
+}
+
+impl<Eth> EthApiExt<Eth>
+where
+    Eth: FullEthApi + Send + Sync + 'static,
+{
+    /// Installs the overrides on the transports that serve the `eth` namespace.
+    ///
+    /// Only the overridden methods are replaced, every other `eth_` method is still served by reth,
+    /// and transports that do not serve the `eth` namespace are left untouched.
+    pub fn install(self, modules: &mut TransportRpcModules) -> Result<(), RegisterMethodError> {
+        let overrides = self.into_rpc();
+        for method in overrides.method_names() {
+            if modules.remove_method_from_configured(method) {
+                debug!(target: "rpc::eth", method, "Overriding eth method");
+            }
+        }
+        modules.merge_if_module_configured(RethRpcModule::Eth, overrides)
+    }
+
+    /// Returns the number of the requested block and the number of the tip, or `None` if the
+    /// block does not exist.
+    async fn resolve_block(&self, block_id: BlockId) -> Result<Option<(u64, u64)>, Eth::Error> {
+        self.eth_api
+            .spawn_blocking_io(move |this| {
+                let provider = this.provider();
+                let Some(number) =
+                    provider.block_number_for_id(block_id).map_err(EthApiError::from_eth_err)?
+                else {
+                    return Ok(None);
+                };
+                let tip = provider.best_block_number().map_err(EthApiError::from_eth_err)?;
+                Ok(Some((number, tip)))
+            })
+            .await
     }
 }
 
@@ -56,17 +264,43 @@ impl<Eth> EthApiOverrideServer for EthApiExt<Eth>
 where
     Eth: FullEthApi + Send + Sync + 'static,
 {
+    #[instrument(target = "rpc::eth", skip_all, fields(%address))]
     async fn get_proof(
         &self,
         address: Address,
         keys: Vec<JsonStorageKey>,
         block_number: Option<BlockId>,
+        full_proof: Option<bool>,
     ) -> RpcResult<EIP1186AccountProofResponse> {
-        trace!(target: "rpc::eth", ?address, ?keys, ?block_number, "Serving eth_getProof");
+        trace!(
+            target: "rpc::eth",
+            ?address,
+            ?keys,
+            ?block_number,
+            ?full_proof,
+            "Serving eth_getProof"
+        );
+
+        let withdrawal_storage = self.proves_storage_only(address, full_proof);
+
+        // reject requests for pruned state, unless the storage of the withdrawal contract can be
+        // proven from its history
+        let mut reverted_storage = None;
+        if let Some((requested, tip)) =
+            self.resolve_block(block_number.unwrap_or_default()).await.map_err(Into::into)?
+        {
+            let proof_window = self.eth_api.max_proof_window();
+            reverted_storage = self
+                .check_pruned(requested, tip, proof_window, withdrawal_storage)
+                .inspect_err(|err| {
+                    let earliest_block = err.earliest_block;
+                    trace!(target: "rpc::eth", requested, earliest_block, "State is pruned");
+                })?;
+        }
 
         // If we are targeting the withdrawal contract, then we only need to provide the storage
-        // proofs for withdrawal.
-        if address == WITHDRAWAL_CONTRACT {
+        // proofs for withdrawal, unless the full proof was requested.
+        if withdrawal_storage {
             let _permit = self
                 .eth_api
                 .acquire_owned()
@@ -78,10 +312,18 @@ where
                 .eth_api
                 .spawn_blocking_io(move |this| {
                     let b256_keys: Vec<B256> = keys.iter().map(|k| k.as_b256()).collect();
-                    let state = this.state_at_block_id(block_number.unwrap_or_default())?;
+                    // pruned storage is proven by reverting the storage at the latest recorded
+                    // block
+                    let (state, storage) = match reverted_storage {
+                        Some((tip, storage)) => (this.state_at_block_id(tip.into())?, storage),
+                        None => (
+                            this.state_at_block_id(block_number.unwrap_or_default())?,
+                            Default::default(),
+                        ),
+                    };
 
                     let proofs = state
-                        .storage_multiproof(WITHDRAWAL_CONTRACT, &b256_keys, Default::default())
+                        .storage_multiproof(address, &b256_keys, storage)
                         .map_err(EthApiError::from_eth_err)?;
 
                     let account_proof = AccountProof {
@@ -107,3 +349,894 @@ where
             .map_err(Into::into)
     }
 }
+
+/// Odyssey `odyssey_` RPC namespace.
+#[cfg_attr(not(any(test, feature = "client")), rpc(server, namespace = "odyssey"))]
+#[cfg_attr(any(test, feature = "client"), rpc(server, client, namespace = "odyssey"))]
+pub trait OdysseyApi {
+    /// Executes a call as if the `from` account of the request had delegated to `delegation` using
+    /// [EIP-7702](https://eips.ethereum.org/EIPS/eip-7702).
+    ///
+    /// This allows wallets to preview what a delegation would do before signing the
+    /// authorization. Additional state overrides are applied before the delegation designator is
+    /// injected.
+    #[method(name = "callAsDelegated")]
+    async fn call_as_delegated(
+        &self,
+        request: TransactionRequest,
+        delegation: Address,
+        block_number: Option<BlockId>,
+        state_overrides: Option<StateOverride>,
+    ) -> RpcResult<Bytes>;
+
+    /// Returns the decoded fields of a deposit transaction, along with the block it was included
+    /// in and the L1 block that block was derived from.
+    ///
+    /// Returns `null` if the transaction is not a deposit in a recent canonical block.
+    #[method(name = "getDepositInfo")]
+    async fn get_deposit_info(&self, tx_hash: TxHash) -> RpcResult<Option<DepositInfo>>;
+
+    /// Returns the balance, nonce, code hash, [EIP-7702](https://eips.ethereum.org/EIPS/eip-7702)
+    /// delegation and the given storage slots of an account in one call.
+    ///
+    /// At most [`MAX_ACCOUNT_BUNDLE_SLOTS`] storage slots can be requested.
+    #[method(name = "getAccountBundle")]
+    async fn get_account_bundle(
+        &self,
+        address: Address,
+        slots: Option<Vec<JsonStorageKey>>,
+        block_number: Option<BlockId>,
+    ) -> RpcResult<AccountBundle>;
+
+    /// Returns the Odyssey version and experiments a block was built with, decoded from the
+    /// experiment tag in its `extraData`.
+    ///
+    /// Returns `null` if the block does not exist or is not tagged.
+    #[method(name = "getBlockExperiments")]
+    async fn get_block_experiments(&self, block_id: BlockId)
+        -> RpcResult<Option<BlockExperiments>>;
+
+    /// Returns the receipts of a block like `eth_getBlockReceipts`, annotated with whether each
+    /// transaction was sent by a sponsor, whether its destination is a delegated account, and the
+    /// implementation the destination resolved to.
+    ///
+    /// Delegations are resolved against the state before the block, or against the state after
+    /// the block for accounts that were delegated in the block.
+    ///
+    /// Returns `null` if the block does not exist.
+    #[method(name = "getBlockReceipts")]
+    async fn get_block_receipts(
+        &self,
+        block_id: BlockId,
+    ) -> RpcResult<Option<Vec<AnnotatedReceipt>>>;
+
+    /// Returns the version and git commit of the node, the scheduled Odyssey experiments, whether
+    /// the wallet service is enabled, and the hardforks that are active at the current head.
+    #[method(name = "getVersionInfo")]
+    async fn get_version_info(&self) -> RpcResult<VersionInfo>;
+
+    /// Returns the experiments of the node, whether they are enabled and active at the current
+    /// head, the hardfork and timestamp that activate them, and their key parameters.
+    #[method(name = "getExperimentStatus")]
+    async fn get_experiment_status(&self) -> RpcResult<Vec<ExperimentStatus>>;
+
+    /// Returns up to `limit` of the most recent reorgs observed since the node started, newest
+    /// first, with their depth, the replaced and new tips, and the sponsored transactions that
+    /// were reorged out.
+    ///
+    /// At most [`DEFAULT_REORG_JOURNAL_SIZE`] reorgs are kept, all of them are returned by default.
+    #[method(name = "getReorgHistory")]
+    async fn get_reorg_history(&self, limit: Option<U64>) -> RpcResult<Vec<ReorgRecord>>;
+
+    /// Builds the unsigned transaction with which `from` initiates a withdrawal of `value` to L1,
+    /// calling `target` with `data` and at least `gas_limit` gas on L1.
+    ///
+    /// The transaction calls the withdrawal contract with its gas limit estimated, and is returned
+    /// with the withdrawal it initiates and its expected hash, computed from the current message
+    /// nonce of the contract. The L1 gas limit defaults to [`DEFAULT_WITHDRAWAL_GAS_LIMIT`].
+    #[method(name = "buildWithdrawalTx")]
+    async fn build_withdrawal_tx(
+        &self,
+        from: Address,
+        target: Address,
+        value: U256,
+        data: Option<Bytes>,
+        gas_limit: Option<U64>,
+    ) -> RpcResult<WithdrawalTx>;
+
+    /// Reads the code of an account with `eth_getCode` and with the `EXTCODESIZE`, `EXTCODEHASH`
+    /// and `EXTCODECOPY` instructions in an `eth_call`, and reports whether they disagree.
+    ///
+    /// Both are expected to return the delegation designator of a delegated account.
+    #[method(name = "verifyDelegationSemantics")]
+    async fn verify_delegation_semantics(
+        &self,
+        address: Address,
+        block_number: Option<BlockId>,
+    ) -> RpcResult<DelegationSemantics>;
+
+    /// Subscribes to the transactions of canonical blocks whose sender or destination delegates to
+    /// one of the given implementations, directly or through a proxy.
+    ///
+    /// Every activity contains the delegated account, the implementation it resolved to, the
+    /// selector of the called function and whether the transaction succeeded. This is only
+    /// available over WebSocket, and follows at most
+    /// [`MAX_SUBSCRIBED_IMPLEMENTATIONS`](crate::activity::MAX_SUBSCRIBED_IMPLEMENTATIONS)
+    /// implementations.
+    #[subscription(
+        name = "subscribeDelegatedActivity",
+        unsubscribe = "unsubscribeDelegatedActivity",
+        item = DelegatedActivity
+    )]
+    async fn subscribe_delegated_activity(
+        &self,
+        implementations: Vec<Address>,
+    ) -> SubscriptionResult;
+}
+
+/// Implementation of the `odyssey_` namespace.
+#[derive(Debug)]
+pub struct OdysseyApiExt<Eth> {
+    eth_api: Eth,
+    deposits: DepositIndex,
+    chain_spec: Arc<OpChainSpec>,
+    wallet: bool,
+    payload: PayloadConfig,
+    sponsors: Vec<Address>,
+    reorgs: ReorgJournal,
+    withdrawal_contract: Address,
+    activity: DelegatedActivityFeed,
+}
+
+impl<E> OdysseyApiExt<E> {
+    /// Create a new `OdysseyApiExt` module, serving deposits from the given index.
+    pub fn new(eth_api: E, deposits: DepositIndex, chain_spec: Arc<OpChainSpec>) -> Self {
+        Self {
+            eth_api,
+            deposits,
+            chain_spec,
+            wallet: false,
+            payload: PayloadConfig::default(),
+            sponsors: Vec::new(),
+            reorgs: ReorgJournal::default(),
+            withdrawal_contract: WITHDRAWAL_CONTRACT,
+            activity: DelegatedActivityFeed::default(),
+        }
+    }
+
+    /// Sets the feed `odyssey_subscribeDelegatedActivity` streams the activity of delegated
+    /// accounts from.
+    pub fn with_delegated_activity(mut self, activity: DelegatedActivityFeed) -> Self {
+        self.activity = activity;
+        self
+    }
+
+    /// Sets the payload delay reported by `odyssey_getExperimentStatus`.
+    pub fn with_payload_config(mut self, payload: PayloadConfig) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Sets the journal the reorgs returned by `odyssey_getReorgHistory` are recorded in.
+    pub fn with_reorg_journal(mut self, reorgs: ReorgJournal) -> Self {
+        self.reorgs = reorgs;
+        self
+    }
+
+    /// Sets the sponsors whose transactions are annotated as sponsored by
+    /// `odyssey_getBlockReceipts`.
+    pub fn with_sponsors(mut self, sponsors: Vec<Address>) -> Self {
+        self.sponsors = sponsors;
+        self
+    }
+
+    /// Sets whether the `wallet_` namespace is served, as reported by `odyssey_getVersionInfo`.
+    pub const fn with_wallet(mut self, wallet: bool) -> Self {
+        self.wallet = wallet;
+        self
+    }
+
+    /// Sets the withdrawal contract `odyssey_buildWithdrawalTx` initiates withdrawals through.
+    pub const fn with_withdrawal_contract(mut self, withdrawal_contract: Address) -> Self {
+        self.withdrawal_contract = withdrawal_contract;
+        self
+    }
+}
+
+impl<Eth> OdysseyApiExt<Eth>
+where
+    Eth: FullEthApi + Send + Sync + 'static,
+{
+    /// Returns the head of the latest block.
+    async fn latest_head(&self) -> Result<Head, Eth::Error> {
+        self.eth_api
+            .spawn_blocking_io(move |this| {
+                let header = this
+                    .provider()
+                    .sealed_header_by_id(BlockId::latest())
+                    .map_err(EthApiError::from_eth_err)?
+                    .ok_or(EthApiError::HeaderNotFound(BlockId::latest()))?;
+                Ok(Head {
+                    number: header.number,
+                    hash: header.hash(),
+                    difficulty: header.difficulty,
+                    timestamp: header.timestamp,
+                    ..Default::default()
+                })
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl<Eth> OdysseyApiServer for OdysseyApiExt<Eth>
+where
+    Eth: FullEthApi + Send + Sync + 'static,
+{
+    async fn call_as_delegated(
+        &self,
+        request: TransactionRequest,
+        delegation: Address,
+        block_number: Option<BlockId>,
+        state_overrides: Option<StateOverride>,
+    ) -> RpcResult<Bytes> {
+        trace!(
+            target: "rpc::odyssey",
+            ?request,
+            %delegation,
+            ?block_number,
+            "Serving odyssey_callAsDelegated"
+        );
+
+        let state_overrides = delegated_call_overrides(&request, delegation, state_overrides)?;
+        EthCall::call(
+            &self.eth_api,
+            request,
+            block_number,
+            EvmOverrides::state(Some(state_overrides)),
+        )
+        .await
+        .map_err(Into::into)
+    }
+
+    async fn get_deposit_info(&self, tx_hash: TxHash) -> RpcResult<Option<DepositInfo>> {
+        trace!(target: "rpc::odyssey", %tx_hash, "Serving odyssey_getDepositInfo");
+        Ok(self.deposits.get(&tx_hash))
+    }
+
+    #[instrument(target = "rpc::odyssey", skip_all, fields(%address))]
+    async fn get_account_bundle(
+        &self,
+        address: Address,
+        slots: Option<Vec<JsonStorageKey>>,
+        block_number: Option<BlockId>,
+    ) -> RpcResult<AccountBundle> {
+        trace!(
+            target: "rpc::odyssey",
+            %address,
+            ?slots,
+            ?block_number,
+            "Serving odyssey_getAccountBundle"
+        );
+
+        let slots = account_bundle_slots(slots)?;
+        self.eth_api
+            .spawn_blocking_io(move |this| {
+                let state = this.state_at_block_id(block_number.unwrap_or_default())?;
+                let storage = |slot: B256| {
+                    state
+                        .storage(address, slot)
+                        .map(|value| B256::from(value.unwrap_or_default()))
+                        .map_err(EthApiError::from_eth_err)
+                };
+                let code_hash = |address: Address| {
+                    state
+                        .basic_account(address)
+                        .map(|account| account.and_then(|account| account.bytecode_hash))
+                        .map_err(EthApiError::from_eth_err)
+                };
+
+                let account = state
+                    .basic_account(address)
+                    .map_err(EthApiError::from_eth_err)?
+                    .unwrap_or_default();
+                let delegation = resolve_delegation(&state, address)?;
+                let designator = delegation.map(|(designator, _)| designator);
+                let implementation = delegation.map(|(_, implementation)| implementation);
+                let implementation_code_hash = match implementation {
+                    Some(implementation) => code_hash(implementation)?,
+                    None => None,
+                };
+
+                Ok(AccountBundle {
+                    balance: account.balance,
+                    nonce: account.nonce,
+                    code_hash: account.get_bytecode_hash(),
+                    designator,
+                    implementation,
+                    implementation_code_hash,
+                    storage: slots
+                        .into_iter()
+                        .map(|slot| {
+                            let slot = slot.as_b256();
+                            Ok((slot, storage(slot)?))
+                        })
+                        .collect::<Result<_, EthApiError>>()?,
+                })
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_block_experiments(
+        &self,
+        block_id: BlockId,
+    ) -> RpcResult<Option<BlockExperiments>> {
+        trace!(target: "rpc::odyssey", ?block_id, "Serving odyssey_getBlockExperiments");
+
+        let chain_spec = self.chain_spec.clone();
+        self.eth_api
+            .spawn_blocking_io(move |this| {
+                let header =
+                    this.provider().header_by_id(block_id).map_err(EthApiError::from_eth_err)?;
+                Ok(header.and_then(|header| {
+                    ExperimentTag::decode(&header.extra_data)
+                        .map(|tag| BlockExperiments::new(tag, header.timestamp, &chain_spec))
+                }))
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_block_receipts(
+        &self,
+        block_id: BlockId,
+    ) -> RpcResult<Option<Vec<AnnotatedReceipt>>> {
+        trace!(target: "rpc::odyssey", ?block_id, "Serving odyssey_getBlockReceipts");
+
+        let Some(receipts) =
+            EthBlocks::block_receipts(&self.eth_api, block_id).await.map_err(Into::into)?
+        else {
+            return Ok(None);
+        };
+        // resolve the block by the hash of the receipts, in case the tag moved since
+        let Some(block_hash) = receipts.first().and_then(|receipt| receipt.block_hash()) else {
+            return Ok(Some(Vec::new()));
+        };
+
+        let destinations: Vec<_> = receipts.iter().filter_map(|receipt| receipt.to()).collect();
+        let delegations = self
+            .eth_api
+            .spawn_blocking_io(move |this| {
+                let header = this
+                    .provider()
+                    .sealed_header_by_id(block_hash.into())
+                    .map_err(EthApiError::from_eth_err)?
+                    .ok_or(EthApiError::HeaderNotFound(block_hash.into()))?;
+                let before = if header.number == 0 {
+                    None
+                } else {
+                    Some(this.state_at_block_id(header.parent_hash.into())?)
+                };
+                let after = this.state_at_block_id(block_hash.into())?;
+
+                let mut delegations = HashMap::new();
+                for address in destinations {
+                    if delegations.contains_key(&address) {
+                        continue;
+                    }
+                    let delegation = match &before {
+                        Some(before) => resolve_delegation(before, address)?,
+                        None => None,
+                    };
+                    let delegation = match delegation {
+                        Some(delegation) => Some(delegation),
+                        None => resolve_delegation(&after, address)?,
+                    };
+                    delegations.insert(address, delegation);
+                }
+                Ok(delegations)
+            })
+            .await
+            .map_err(Into::into)?;
+
+        receipts
+            .into_iter()
+            .map(|receipt| annotate_receipt(receipt, &self.sponsors, &delegations))
+            .collect::<Result<_, EthApiError>>()
+            .map(Some)
+            .map_err(Into::into)
+    }
+
+    async fn get_version_info(&self) -> RpcResult<VersionInfo> {
+        trace!(target: "rpc::odyssey", "Serving odyssey_getVersionInfo");
+
+        let head = self.latest_head().await.map_err(Into::into)?;
+        Ok(VersionInfo::new(&self.chain_spec, &head, self.wallet))
+    }
+
+    async fn get_experiment_status(&self) -> RpcResult<Vec<ExperimentStatus>> {
+        trace!(target: "rpc::odyssey", "Serving odyssey_getExperimentStatus");
+
+        let head = self.latest_head().await.map_err(Into::into)?;
+        let settings = ExperimentSettings { wallet: self.wallet, payload: self.payload.clone() };
+        Ok(experiment_status(&self.chain_spec, &head, &settings))
+    }
+
+    async fn get_reorg_history(&self, limit: Option<U64>) -> RpcResult<Vec<ReorgRecord>> {
+        trace!(target: "rpc::odyssey", ?limit, "Serving odyssey_getReorgHistory");
+        Ok(self.reorgs.history(limit.map_or(usize::MAX, |limit| limit.saturating_to())))
+    }
+
+    async fn build_withdrawal_tx(
+        &self,
+        from: Address,
+        target: Address,
+        value: U256,
+        data: Option<Bytes>,
+        gas_limit: Option<U64>,
+    ) -> RpcResult<WithdrawalTx> {
+        trace!(
+            target: "rpc::odyssey",
+            %from,
+            %target,
+            %value,
+            ?gas_limit,
+            "Serving odyssey_buildWithdrawalTx"
+        );
+
+        // the nonce of the withdrawal is the nonce of the next message of the contract
+        let nonce_call = TransactionRequest::default()
+            .to(self.withdrawal_contract)
+            .input(Bytes::from(L2ToL1MessagePasser::messageNonceCall {}.abi_encode()).into());
+        let output = EthCall::call(
+            &self.eth_api,
+            nonce_call,
+            Some(BlockId::latest()),
+            EvmOverrides::default(),
+        )
+        .await
+        .map_err(Into::into)?;
+
+        let withdrawal = initiated_withdrawal(&output, from, target, value, data, gas_limit)?;
+        let request = withdrawal.initiate_request(self.withdrawal_contract);
+        let gas = EthCall::estimate_gas_at(&self.eth_api, request.clone(), BlockId::latest(), None)
+            .await
+            .map_err(Into::into)?;
+
+        Ok(WithdrawalTx {
+            request: request.gas_limit(gas.saturating_to()),
+            withdrawal_hash: withdrawal.hash(),
+            withdrawal,
+        })
+    }
+
+    async fn verify_delegation_semantics(
+        &self,
+        address: Address,
+        block_number: Option<BlockId>,
+    ) -> RpcResult<DelegationSemantics> {
+        trace!(
+            target: "rpc::odyssey",
+            %address,
+            ?block_number,
+            "Serving odyssey_verifyDelegationSemantics"
+        );
+
+        // resolve the block once, so both paths read the same state
+        let block_id = block_number.unwrap_or_default();
+        let block_hash = self
+            .eth_api
+            .spawn_blocking_io(move |this| {
+                Ok(this
+                    .provider()
+                    .block_hash_for_id(block_id)
+                    .map_err(EthApiError::from_eth_err)?
+                    .ok_or(EthApiError::HeaderNotFound(block_id))?)
+            })
+            .await
+            .map_err(Into::into)?;
+
+        let code = EthState::get_code(&self.eth_api, address, Some(block_hash.into()))
+            .await
+            .map_err(Into::into)?;
+        let output = EthCall::call(
+            &self.eth_api,
+            extcode_request(address),
+            Some(block_hash.into()),
+            EvmOverrides::state(Some(extcode_state_override())),
+        )
+        .await
+        .map_err(Into::into)?;
+
+        delegation_semantics(code, &output).map_err(Into::into)
+    }
+
+    async fn subscribe_delegated_activity(
+        &self,
+        pending: PendingSubscriptionSink,
+        implementations: Vec<Address>,
+    ) -> SubscriptionResult {
+        trace!(
+            target: "rpc::odyssey",
+            ?implementations,
+            "Serving odyssey_subscribeDelegatedActivity"
+        );
+        self.activity.pipe(pending, implementations).await
+    }
+}
+
+/// Compares the code returned by `eth_getCode` with the output of the `EXTCODE*` helper of
+/// `odyssey_verifyDelegationSemantics`.
+fn delegation_semantics(code: Bytes, output: &[u8]) -> Result<DelegationSemantics, EthApiError> {
+    DelegationSemantics::new(code, output).ok_or_else(|| {
+        EthApiError::Internal(RethError::msg("malformed output of the EXTCODE helper"))
+    })
+}
+
+/// Returns the storage slots requested with `odyssey_getAccountBundle`, rejecting requests for more
+/// than [`MAX_ACCOUNT_BUNDLE_SLOTS`] slots.
+fn account_bundle_slots(
+    slots: Option<Vec<JsonStorageKey>>,
+) -> Result<Vec<JsonStorageKey>, EthApiError> {
+    let slots = slots.unwrap_or_default();
+    if slots.len() > MAX_ACCOUNT_BUNDLE_SLOTS {
+        return Err(EthApiError::InvalidParams(format!(
+            "too many storage slots, at most {MAX_ACCOUNT_BUNDLE_SLOTS} are allowed"
+        )));
+    }
+    Ok(slots)
+}
+
+/// Returns the withdrawal initiated by the transaction of `odyssey_buildWithdrawalTx`, given the
+/// output of the `messageNonce` call of the withdrawal contract.
+fn initiated_withdrawal(
+    nonce_output: &[u8],
+    from: Address,
+    target: Address,
+    value: U256,
+    data: Option<Bytes>,
+    gas_limit: Option<U64>,
+) -> Result<Withdrawal, EthApiError> {
+    let nonce = L2ToL1MessagePasser::messageNonceCall::abi_decode_returns(nonce_output, true)
+        .map_err(|err| EthApiError::Internal(RethError::other(err)))?
+        ._0;
+
+    Ok(Withdrawal {
+        nonce,
+        sender: from,
+        target,
+        value,
+        gas_limit: U256::from(gas_limit.map_or(DEFAULT_WITHDRAWAL_GAS_LIMIT, |limit| limit.to())),
+        data: data.unwrap_or_default(),
+    })
+}
+
+/// Returns the state overrides of `odyssey_callAsDelegated`: the given overrides, with the
+/// delegation designator installed on the caller.
+fn delegated_call_overrides(
+    request: &TransactionRequest,
+    delegation: Address,
+    state_overrides: Option<StateOverride>,
+) -> Result<StateOverride, EthApiError> {
+    let Some(from) = request.from else {
+        return Err(EthApiError::InvalidParams("missing `from` field".to_string()));
+    };
+
+    let mut state_overrides = state_overrides.unwrap_or_default();
+    state_overrides.entry(from).or_default().code = Some(delegation_designator(delegation));
+    Ok(state_overrides)
+}
+
+/// Annotates a receipt with whether its transaction was sent by one of the sponsors, and with the
+/// delegation of its destination, given the delegations resolved for the block.
+fn annotate_receipt<R>(
+    receipt: R,
+    sponsors: &[Address],
+    delegations: &HashMap<Address, Option<(Address, Address)>>,
+) -> Result<AnnotatedReceipt, EthApiError>
+where
+    R: ReceiptResponse + Serialize,
+{
+    let delegation = receipt.to().and_then(|to| delegations.get(&to).copied().flatten());
+    let sponsored = sponsors.contains(&receipt.from());
+    let receipt = serde_json::to_value(receipt)
+        .and_then(serde_json::from_value)
+        .map_err(|err| EthApiError::Internal(RethError::other(err)))?;
+    Ok(AnnotatedReceipt {
+        receipt,
+        sponsored,
+        delegated: delegation.is_some(),
+        implementation: delegation.map(|(_, implementation)| implementation),
+    })
+}
+
+/// Returns the address the account delegates to using
+/// [EIP-7702](https://eips.ethereum.org/EIPS/eip-7702), and the address of the code that executes
+/// for it, if the account is delegated.
+pub(crate) fn resolve_delegation(
+    state: &dyn StateProvider,
+    address: Address,
+) -> Result<Option<(Address, Address)>, EthApiError> {
+    let Some(designator) = state
+        .account_code(address)
+        .map_err(EthApiError::from_eth_err)?
+        .and_then(|code| parse_delegation_designator(code.original_byte_slice()))
+        .filter(|designator| !designator.is_zero())
+    else {
+        return Ok(None);
+    };
+
+    // a delegated proxy keeps its implementation in the storage of the account
+    let slot = state
+        .storage(address, ERC1967_IMPLEMENTATION_SLOT)
+        .map_err(EthApiError::from_eth_err)?
+        .unwrap_or_default();
+    let proxied = Address::from_word(slot.into());
+    Ok(Some((designator, if proxied.is_zero() { designator } else { proxied })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof_history::STATE_PRUNED_CODE;
+    use alloy_primitives::{keccak256, Bloom};
+    use alloy_rpc_types_eth::{state::AccountOverride, TransactionReceipt};
+    use jsonrpsee::{
+        types::{error::INVALID_PARAMS_CODE, ErrorObject},
+        MethodsError,
+    };
+    use parking_lot::Mutex;
+    use reth_prune_types::{PruneMode, PruneModes};
+    use serde_json::{json, Value};
+
+    /// Records the `full_proof` parameter of `eth_getProof` calls.
+    #[derive(Debug, Default, Clone)]
+    struct FullProofRecorder(Arc<Mutex<Vec<Option<bool>>>>);
+
+    #[async_trait]
+    impl EthApiOverrideServer for FullProofRecorder {
+        async fn get_proof(
+            &self,
+            _: Address,
+            _: Vec<JsonStorageKey>,
+            _: Option<BlockId>,
+            full_proof: Option<bool>,
+        ) -> RpcResult<EIP1186AccountProofResponse> {
+            self.0.lock().push(full_proof);
+            Ok(Default::default())
+        }
+    }
+
+    #[test]
+    fn installs_delegation_on_caller() {
+        let (from, delegation, other) =
+            (Address::with_last_byte(1), Address::with_last_byte(2), Address::with_last_byte(3));
+
+        let err = delegated_call_overrides(&TransactionRequest::default(), delegation, None);
+        assert!(matches!(err, Err(EthApiError::InvalidParams(_))));
+
+        // the code of the caller is replaced, other overrides are applied as given
+        let mut overrides = StateOverride::default();
+        overrides.insert(
+            from,
+            AccountOverride {
+                balance: Some(U256::from(1)),
+                code: Some(Bytes::from_static(&[0x00])),
+                ..Default::default()
+            },
+        );
+        overrides.insert(other, AccountOverride { nonce: Some(1), ..Default::default() });
+        let request = TransactionRequest::default().from(from);
+        let overrides = delegated_call_overrides(&request, delegation, Some(overrides)).unwrap();
+        assert_eq!(overrides[&from].code, Some(delegation_designator(delegation)));
+        assert_eq!(overrides[&from].balance, Some(U256::from(1)));
+        assert_eq!(overrides[&other].nonce, Some(1));
+    }
+
+    #[test]
+    fn limits_account_bundle_slots() {
+        assert!(account_bundle_slots(None).unwrap().is_empty());
+
+        let slots = vec![JsonStorageKey::from(B256::ZERO); MAX_ACCOUNT_BUNDLE_SLOTS];
+        assert_eq!(account_bundle_slots(Some(slots.clone())).unwrap().len(), slots.len());
+        let slots = [slots, vec![JsonStorageKey::from(B256::ZERO)]].concat();
+        assert!(matches!(account_bundle_slots(Some(slots)), Err(EthApiError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn serializes_account_bundle() {
+        let bundle = AccountBundle {
+            balance: U256::from(1),
+            nonce: 2,
+            code_hash: B256::with_last_byte(3),
+            designator: Some(Address::with_last_byte(4)),
+            implementation: Some(Address::with_last_byte(4)),
+            implementation_code_hash: None,
+            storage: BTreeMap::from([(B256::ZERO, B256::with_last_byte(5))]),
+        };
+
+        let json = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(json["nonce"], "0x2");
+        assert_eq!(json["designator"], json["implementation"]);
+        assert!(json["implementationCodeHash"].is_null());
+        assert_eq!(json["storage"][B256::ZERO.to_string()], B256::with_last_byte(5).to_string());
+        assert_eq!(serde_json::from_value::<AccountBundle>(json).unwrap(), bundle);
+    }
+
+    #[test]
+    fn annotates_receipts() {
+        let (sponsor, account, other) =
+            (Address::with_last_byte(1), Address::with_last_byte(2), Address::with_last_byte(3));
+        let (designator, implementation) = (Address::with_last_byte(4), Address::with_last_byte(5));
+        let receipt = |from: Address, to: Address| -> TransactionReceipt {
+            serde_json::from_value(json!({
+                "type": "0x2",
+                "status": "0x1",
+                "cumulativeGasUsed": "0x5208",
+                "logs": [],
+                "logsBloom": Bloom::ZERO,
+                "transactionHash": TxHash::with_last_byte(1),
+                "transactionIndex": "0x0",
+                "blockHash": B256::with_last_byte(1),
+                "blockNumber": "0x1",
+                "gasUsed": "0x5208",
+                "effectiveGasPrice": "0x1",
+                "from": from,
+                "to": to,
+                "contractAddress": null,
+            }))
+            .unwrap()
+        };
+        let delegations =
+            HashMap::from([(account, Some((designator, implementation))), (other, None)]);
+
+        let annotated =
+            annotate_receipt(receipt(sponsor, account), &[sponsor], &delegations).unwrap();
+        assert!(annotated.sponsored);
+        assert!(annotated.delegated);
+        assert_eq!(annotated.implementation, Some(implementation));
+        // the fields of the receipt are kept next to the annotations
+        let json = serde_json::to_value(&annotated).unwrap();
+        assert_eq!(json["gasUsed"], "0x5208");
+        assert_eq!(json["sponsored"], true);
+
+        let annotated = annotate_receipt(receipt(other, other), &[sponsor], &delegations).unwrap();
+        assert!(!annotated.sponsored);
+        assert!(!annotated.delegated);
+        assert_eq!(annotated.implementation, None);
+    }
+
+    #[test]
+    fn initiates_withdrawal_with_message_nonce() {
+        let (from, target) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        let nonce = U256::from(7);
+        let output = B256::from(nonce);
+
+        let withdrawal =
+            initiated_withdrawal(output.as_slice(), from, target, U256::from(1), None, None)
+                .unwrap();
+        assert_eq!(withdrawal.nonce, nonce);
+        assert_eq!(withdrawal.sender, from);
+        assert_eq!(withdrawal.gas_limit, U256::from(DEFAULT_WITHDRAWAL_GAS_LIMIT));
+        assert!(withdrawal.data.is_empty());
+
+        let data = Bytes::from_static(&[0xab]);
+        let withdrawal = initiated_withdrawal(
+            output.as_slice(),
+            from,
+            target,
+            U256::ZERO,
+            Some(data.clone()),
+            Some(U64::from(200_000)),
+        )
+        .unwrap();
+        assert_eq!(withdrawal.gas_limit, U256::from(200_000));
+        assert_eq!(withdrawal.data, data);
+
+        // the output of the nonce call is malformed
+        assert!(matches!(
+            initiated_withdrawal(&output[..31], from, target, U256::ZERO, None, None),
+            Err(EthApiError::Internal(_))
+        ));
+    }
+
+    #[test]
+    fn compares_code_with_extcode_output() {
+        let code = delegation_designator(Address::with_last_byte(1));
+        let size = B256::from(U256::from(code.len()));
+        let output = [size.as_slice(), keccak256(&code).as_slice(), &code].concat();
+
+        let semantics = delegation_semantics(code.clone(), &output).unwrap();
+        assert!(semantics.consistent);
+        assert_eq!(semantics.designator, Some(Address::with_last_byte(1)));
+
+        // the helper returned less than the size and hash of the code
+        assert!(matches!(delegation_semantics(code, &[0; 63]), Err(EthApiError::Internal(_))));
+    }
+
+    #[tokio::test]
+    async fn parses_full_proof() {
+        let recorder = FullProofRecorder::default();
+        let module = &recorder.clone().into_rpc();
+        let get_proof = |params: Vec<Value>| async move {
+            module.call::<_, EIP1186AccountProofResponse>("eth_getProof", params).await
+        };
+        let params = || vec![json!(WITHDRAWAL_CONTRACT), json!([]), json!("latest")];
+
+        // the parameter is optional
+        get_proof(params()).await.unwrap();
+        get_proof([params(), vec![Value::Null]].concat()).await.unwrap();
+        get_proof([params(), vec![json!(false)]].concat()).await.unwrap();
+        get_proof([params(), vec![json!(true)]].concat()).await.unwrap();
+        assert_eq!(*recorder.0.lock(), [None, None, Some(false), Some(true)]);
+
+        let err = get_proof([params(), vec![json!("0x1")]].concat()).await.unwrap_err();
+        assert!(matches!(err, MethodsError::JsonRpc(err) if err.code() == INVALID_PARAMS_CODE));
+    }
+
+    #[test]
+    fn proves_withdrawal_storage_unless_full_proof() {
+        let eth = EthApiExt::new((), WITHDRAWAL_CONTRACT);
+        assert!(eth.proves_storage_only(WITHDRAWAL_CONTRACT, None));
+        assert!(eth.proves_storage_only(WITHDRAWAL_CONTRACT, Some(false)));
+        assert!(!eth.proves_storage_only(WITHDRAWAL_CONTRACT, Some(true)));
+        assert!(!eth.proves_storage_only(Address::ZERO, None));
+    }
+
+    #[test]
+    fn rejects_pruned_state() {
+        let eth = EthApiExt::new((), WITHDRAWAL_CONTRACT).with_state_history(StateHistory::new(
+            &PruneModes { account_history: Some(PruneMode::Distance(100)), ..Default::default() },
+        ));
+
+        assert!(eth.check_pruned(900, 1_000, u64::MAX, false).unwrap().is_none());
+        assert_eq!(
+            eth.check_pruned(899, 1_000, u64::MAX, false).unwrap_err(),
+            StatePruned { block_number: 899, earliest_block: 900 }
+        );
+
+        // accounts are only proven within the proof window, unlike the withdrawal storage
+        assert_eq!(
+            eth.check_pruned(950, 1_000, 10, false).unwrap_err(),
+            StatePruned { block_number: 950, earliest_block: 990 }
+        );
+        assert!(eth.check_pruned(950, 1_000, 10, true).unwrap().is_none());
+
+        let err = ErrorObject::from(StatePruned { block_number: 899, earliest_block: 900 });
+        assert_eq!(err.code(), STATE_PRUNED_CODE);
+        assert_eq!(
+            err.data().map(|data| data.get()),
+            Some(r#"{"blockNumber":"0x383","earliestBlock":"0x384"}"#)
+        );
+    }
+
+    #[test]
+    fn proves_pruned_withdrawal_storage_from_history() {
+        let history = WithdrawalProofHistory::new(WITHDRAWAL_CONTRACT, 3);
+        for number in 1..=4 {
+            history.record(number, B256::with_last_byte(number as u8), HashMap::new());
+        }
+        let eth = EthApiExt::new((), WITHDRAWAL_CONTRACT)
+            .with_state_history(StateHistory::new(&PruneModes {
+                account_history: Some(PruneMode::Full),
+                ..Default::default()
+            }))
+            .with_withdrawal_history(history);
+
+        // the storage is reverted from the latest recorded block
+        let (tip, _) = eth.check_pruned(2, 4, u64::MAX, true).unwrap().unwrap();
+        assert_eq!(tip, B256::with_last_byte(4));
+
+        // the earliest block is the earliest retained by the history
+        assert_eq!(
+            eth.check_pruned(0, 4, u64::MAX, true).unwrap_err(),
+            StatePruned { block_number: 0, earliest_block: 1 }
+        );
+        // accounts are not proven from the history
+        assert_eq!(
+            eth.check_pruned(2, 4, u64::MAX, false).unwrap_err(),
+            StatePruned { block_number: 2, earliest_block: 4 }
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! RSA signature verification precompile.
+//!
+//! Verifies RSASSA-PKCS1-v1_5 signatures over SHA-256 digests with 2048 or 4096 bit keys, the
+//! signatures used by most attestation certificates of passkeys. WebAuthn attestation flows can
+//! verify a certificate chain onchain with it, instead of parsing the result of the `MODEXP`
+//! precompile in bytecode.
+//!
+//! The input is the 32 byte digest, the public exponent as a 32 byte word, and the modulus and the
+//! signature with the length of the key each, all big-endian:
+//!
+//! ```text
+//! hash (32) || exponent (32) || modulus (256 or 512) || signature (256 or 512)
+//! ```
+//!
+//! Like [`P256VERIFY`](crate::evm::P256VERIFY), the output is `1` as a 32 byte word if the
+//! signature is valid, and empty otherwise. The exponentiation is priced like `MODEXP`, plus
+//! [`RSA_VERIFY_BASE_GAS`] for decoding the input and checking the encoding of the digest.
+//!
+//! The precompile is installed once [`OdysseyHardfork::Exp2`](crate::forks::OdysseyHardfork::Exp2)
+//! is active.
+
+use alloy_primitives::{hex, Bytes, B256, U256};
+use revm_precompile::{modexp::berlin_run, u64_to_address, PrecompileWithAddress};
+use revm_primitives::{Precompile, PrecompileError, PrecompileOutput, PrecompileResult};
+
+/// RSA verify precompile address.
+pub const RSA_VERIFY_ADDRESS: u64 = 0x15;
+
+/// The gas charged by the RSA verify precompile in addition to the exponentiation.
+pub const RSA_VERIFY_BASE_GAS: u64 = 300;
+
+/// RSASSA-PKCS1-v1_5 SHA-256 signature verification precompile.
+pub const RSA_VERIFY: PrecompileWithAddress =
+    PrecompileWithAddress(u64_to_address(RSA_VERIFY_ADDRESS), Precompile::Standard(rsa_verify));
+
+/// The lengths of the supported moduli in bytes, for 2048 and 4096 bit keys.
+const MODULUS_LENGTHS: [usize; 2] = [256, 512];
+
+/// The DER encoded `DigestInfo` prefix of SHA-256 digests, see
+/// [RFC 8017](https://www.rfc-editor.org/rfc/rfc8017#section-9.2).
+const SHA256_DIGEST_INFO: [u8; 19] = hex!("3031300d060960864801650304020105000420");
+
+/// Verifies an RSASSA-PKCS1-v1_5 signature over a SHA-256 digest.
+///
+/// Returns `1` as a 32 byte word if the signature is valid, and empty output otherwise.
+pub fn rsa_verify(input: &Bytes, gas_limit: u64) -> PrecompileResult {
+    if gas_limit < RSA_VERIFY_BASE_GAS {
+        return Err(PrecompileError::OutOfGas.into());
+    }
+    let invalid = Ok(PrecompileOutput::new(RSA_VERIFY_BASE_GAS, Bytes::new()));
+
+    let Some((hash, exponent, modulus, signature)) = split_input(input) else { return invalid };
+    // the signature representative has to be smaller than the modulus, the slices are of equal
+    // length, so they compare like the numbers
+    if signature >= modulus {
+        return invalid;
+    }
+
+    let len = B256::from(U256::from(modulus.len()));
+    let modexp_input = [len.as_slice(), B256::with_last_byte(32).as_slice(), len.as_slice()]
+        .into_iter()
+        .chain([signature, exponent, modulus])
+        .flatten()
+        .copied()
+        .collect::<Bytes>();
+    let modexp = berlin_run(&modexp_input, gas_limit - RSA_VERIFY_BASE_GAS)?;
+
+    let result = if modexp.bytes[..] == encode_sha256_digest(hash, modulus.len())[..] {
+        B256::with_last_byte(1).into()
+    } else {
+        Bytes::new()
+    };
+    Ok(PrecompileOutput::new(RSA_VERIFY_BASE_GAS + modexp.gas_used, result))
+}
+
+/// Splits the input into the digest, the exponent, the modulus and the signature, if the input has
+/// the length of a supported key.
+fn split_input(input: &[u8]) -> Option<(&[u8], &[u8], &[u8], &[u8])> {
+    let len = input.len().checked_sub(64)? / 2;
+    if !MODULUS_LENGTHS.contains(&len) || input.len() != 64 + 2 * len {
+        return None;
+    }
+    let (hash, rest) = input.split_at(32);
+    let (exponent, rest) = rest.split_at(32);
+    let (modulus, signature) = rest.split_at(len);
+    Some((hash, exponent, modulus, signature))
+}
+
+/// Returns the EMSA-PKCS1-v1_5 encoding of a SHA-256 digest with the given length:
+///
+/// ```text
+/// 0x00 || 0x01 || 0xff.. || 0x00 || DigestInfo || hash
+/// ```
+fn encode_sha256_digest(hash: &[u8], len: usize) -> Vec<u8> {
+    let mut encoded = vec![0xff; len];
+    encoded[0] = 0x00;
+    encoded[1] = 0x01;
+    let digest_info = len - SHA256_DIGEST_INFO.len() - hash.len();
+    encoded[digest_info - 1] = 0x00;
+    encoded[digest_info..len - hash.len()].copy_from_slice(&SHA256_DIGEST_INFO);
+    encoded[len - hash.len()..].copy_from_slice(hash);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA-256 digest of `odyssey`.
+    const HASH: [u8; 32] = hex!("f460554ef1e286f4c7dc66b4d0256dc5cff3dbfb14630f847b2d75b5376a2374");
+
+    /// The modulus of a 2048 bit key with exponent 65537.
+    const MODULUS: [u8; 256] = hex!("f7e367865259f6e95e98b21b2f062bc6357b7f5c7bba2fcc838c2d77bb3bf7508ba598e2269e50751e5ffca0bdd49324fdbb18312905df219f6001d48df90f8388e2b82eae5213241c50093e020ed4ce7960c28de9810a5ca8d7df6e4b2fbb87008830f4d20d88aec52094a1bde85cb9ccbeb7dc1239dc204b6cfd475d4783df99864c2ed66346f06c5582f871111ceaa80f30fa247e43b191c50f8c1d43151718eb0fe0c51bff2eefeb9dfa821e97dc92c498bc21771ee9b8208605460b840c6d4b4f53dc9b7f6759ec3eeb56798e21e23420fb2e00e93189d6119547adb9302be85eb886736432ca850e8729bad1ba2ec243f4e27b1becaf1e2576f1f038e3");
+
+    /// The signature of [`HASH`] with the key of [`MODULUS`].
+    const SIGNATURE: [u8; 256] = hex!("746c530559d646a162c304b262bc7c79efa402bd8e9925418fbf25aa446f5fc3a1af22a4e2f5a6a1aa397ab5e351c36188f4b50114621826777495cd76f1fdbe7880850b891021f306a20b1d832195467051f8d9c914425f32f174a2ec8d5745b3574ae19d31304ca809adb0be50297f4f633c6435ac9844548a216f39ae1b87b1f7edc4f207eb2d122ed5d0966bccf2076f5a1661802430281b04235087f602a8596faa43ed8b1cff17603ebe3c9e515ec5031df4d4690f79c5bd5125570ab3dde786cd3cc02c59974b9b631ddeb70d214d5c7b2dbf7181597cf01e4aeac12266a3e580190f46d5257ac087e8aaa88db5d9b4fbcf845805b815aeb12cc11fe9");
+
+    fn input(hash: &[u8], modulus: &[u8], signature: &[u8]) -> Bytes {
+        let exponent = B256::from(U256::from(65537));
+        [hash, exponent.as_slice(), modulus, signature].concat().into()
+    }
+
+    #[test]
+    fn verifies_signatures() {
+        let output = rsa_verify(&input(&HASH, &MODULUS, &SIGNATURE), u64::MAX).unwrap();
+        assert_eq!(output.bytes, Bytes::from(B256::with_last_byte(1)));
+        // 32 words squared, times 16 iterations for the exponent, divided by 3
+        assert_eq!(output.gas_used, RSA_VERIFY_BASE_GAS + 5_461);
+
+        let mut hash = HASH;
+        hash[31] ^= 1;
+        let output = rsa_verify(&input(&hash, &MODULUS, &SIGNATURE), u64::MAX).unwrap();
+        assert!(output.bytes.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        // the signature is not smaller than the modulus
+        let output = rsa_verify(&input(&HASH, &MODULUS, &MODULUS), u64::MAX).unwrap();
+        assert!(output.bytes.is_empty());
+
+        // unsupported key size
+        let output = rsa_verify(&input(&HASH, &MODULUS[..128], &SIGNATURE[..128]), u64::MAX);
+        assert!(output.unwrap().bytes.is_empty());
+        let output = rsa_verify(&Bytes::from(HASH), u64::MAX).unwrap();
+        assert_eq!(output.gas_used, RSA_VERIFY_BASE_GAS);
+        assert!(output.bytes.is_empty());
+    }
+
+    #[test]
+    fn charges_exponentiation() {
+        let input = input(&HASH, &MODULUS, &SIGNATURE);
+        assert!(rsa_verify(&input, RSA_VERIFY_BASE_GAS - 1).is_err());
+        assert!(rsa_verify(&input, RSA_VERIFY_BASE_GAS + 5_460).is_err());
+        assert!(rsa_verify(&input, RSA_VERIFY_BASE_GAS + 5_461).is_ok());
+    }
+
+    #[test]
+    fn encodes_digests() {
+        let encoded = encode_sha256_digest(&HASH, 256);
+        assert_eq!(encoded.len(), 256);
+        assert_eq!(encoded[..3], [0x00, 0x01, 0xff]);
+        assert_eq!(encoded[203..206], [0xff, 0x00, 0x30]);
+        assert_eq!(encoded[224..], HASH);
+    }
+}
@@ -0,0 +1,254 @@
+//! Startup self-checks.
+//!
+//! Misconfigured deployments usually fail once a feature is first used, with runtime errors that
+//! are hard to trace back to the configuration. The self-checks run before the node serves RPC
+//! requests and log a report of the configuration. Failed checks prevent the node from starting,
+//! while warnings may disable the affected feature.
+
+use crate::forks::OdysseyHardfork;
+use alloy_primitives::{Address, U256, U64};
+use reth_chainspec::{EthereumHardfork, ForkCondition, Hardfork, Hardforks};
+use reth_optimism_chainspec::OpChainSpec;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// The timeout of the request to the sequencer.
+const SEQUENCER_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The outcome of a self-check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    /// The check passed.
+    Passed,
+    /// The check found a problem that does not prevent the node from starting.
+    Warning,
+    /// The check found a problem that prevents the node from starting.
+    Failed,
+}
+
+/// The result of a single self-check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    /// The name of the check.
+    pub name: &'static str,
+    /// The outcome of the check.
+    pub status: CheckStatus,
+    /// A description of the outcome.
+    pub message: String,
+}
+
+/// The results of the startup self-checks.
+#[derive(Debug, Clone, Default)]
+pub struct SelfCheckReport {
+    results: Vec<CheckResult>,
+}
+
+impl SelfCheckReport {
+    /// Records a check with the given outcome.
+    pub fn record(&mut self, name: &'static str, status: CheckStatus, message: impl Into<String>) {
+        self.results.push(CheckResult { name, status, message: message.into() });
+    }
+
+    /// Returns the results of all checks, in the order they ran.
+    pub fn results(&self) -> &[CheckResult] {
+        &self.results
+    }
+
+    /// Returns the result of the check with the given name, if it ran.
+    pub fn get(&self, name: &str) -> Option<&CheckResult> {
+        self.results.iter().find(|result| result.name == name)
+    }
+
+    /// Returns whether any check failed.
+    pub fn has_failures(&self) -> bool {
+        self.results.iter().any(|result| result.status == CheckStatus::Failed)
+    }
+
+    /// Logs the results of all checks, and returns an error if any check failed.
+    pub fn finish(&self) -> eyre::Result<()> {
+        for CheckResult { name, status, message } in &self.results {
+            match status {
+                CheckStatus::Passed => info!(target: "reth::cli", check = name, "{message}"),
+                CheckStatus::Warning => warn!(target: "reth::cli", check = name, "{message}"),
+                CheckStatus::Failed => error!(target: "reth::cli", check = name, "{message}"),
+            }
+        }
+
+        let failed: Vec<_> = self
+            .results
+            .iter()
+            .filter(|result| result.status == CheckStatus::Failed)
+            .map(|result| result.name)
+            .collect();
+        if !failed.is_empty() {
+            eyre::bail!("startup self-checks failed: {}", failed.join(", "));
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the hardforks the Odyssey features rely on are scheduled.
+///
+/// Prague must be active at genesis, since sponsored delegations are EIP-7702 transactions.
+/// Odyssey experiments that are scheduled later, or not at all, are reported as warnings.
+pub fn check_forks(report: &mut SelfCheckReport, chain_spec: &OpChainSpec) {
+    let genesis_timestamp = chain_spec.inner.genesis.timestamp;
+    if chain_spec.fork(EthereumHardfork::Prague).active_at_timestamp(genesis_timestamp) {
+        report.record("prague", CheckStatus::Passed, "Prague is active at genesis");
+    } else {
+        report.record(
+            "prague",
+            CheckStatus::Failed,
+            "Prague is not active at genesis, EIP-7702 transactions would be rejected",
+        );
+    }
+
+    for fork in OdysseyHardfork::ALL {
+        let (status, message) = match chain_spec.fork(fork) {
+            condition if condition.active_at_timestamp(genesis_timestamp) => {
+                (CheckStatus::Passed, format!("{fork} is active at genesis"))
+            }
+            ForkCondition::Timestamp(timestamp) => {
+                (CheckStatus::Warning, format!("{fork} activates at timestamp {timestamp}"))
+            }
+            _ => (CheckStatus::Warning, format!("{fork} is not scheduled")),
+        };
+        report.record(fork.name(), status, message);
+    }
+}
+
+/// Checks that the sponsor key is configured and funded, given the balance of the sponsor in the
+/// latest state.
+pub fn check_sponsor(report: &mut SelfCheckReport, sponsor: Option<Address>, balance: U256) {
+    match sponsor {
+        None => report.record(
+            "sponsor",
+            CheckStatus::Warning,
+            "no sponsor key is configured, the node does not sponsor transactions",
+        ),
+        Some(sponsor) if balance.is_zero() => report.record(
+            "sponsor",
+            CheckStatus::Warning,
+            format!("sponsor {sponsor} is not funded, sponsored transactions will fail"),
+        ),
+        Some(sponsor) => report.record(
+            "sponsor",
+            CheckStatus::Passed,
+            format!("sponsor {sponsor} has a balance of {balance} wei"),
+        ),
+    }
+}
+
+/// Checks that the withdrawal contract is deployed, given whether there is code at its address
+/// in the latest state.
+///
+/// Returns whether the contract is deployed.
+pub fn check_withdrawal_contract(
+    report: &mut SelfCheckReport,
+    withdrawal_contract: Address,
+    has_code: bool,
+) -> bool {
+    if has_code {
+        report.record(
+            "withdrawal-contract",
+            CheckStatus::Passed,
+            format!("withdrawal contract {withdrawal_contract} is deployed"),
+        );
+    } else {
+        report.record(
+            "withdrawal-contract",
+            CheckStatus::Warning,
+            format!(
+                "withdrawal contract {withdrawal_contract} is not deployed, disabling the eth_ \
+                 overrides"
+            ),
+        );
+    }
+    has_code
+}
+
+/// Checks that the sequencer is reachable and serves the chain with the given id.
+///
+/// An unreachable sequencer is only a warning, since it may start after this node.
+pub async fn check_sequencer(report: &mut SelfCheckReport, url: Option<&str>, chain_id: u64) {
+    let Some(url) = url else {
+        report.record("sequencer", CheckStatus::Passed, "no sequencer configured");
+        return;
+    };
+
+    match sequencer_chain_id(url).await {
+        Ok(id) if id == chain_id => {
+            report.record("sequencer", CheckStatus::Passed, format!("sequencer {url} is reachable"))
+        }
+        Ok(id) => report.record(
+            "sequencer",
+            CheckStatus::Failed,
+            format!("sequencer {url} serves chain {id}, expected chain {chain_id}"),
+        ),
+        Err(err) => report.record(
+            "sequencer",
+            CheckStatus::Warning,
+            format!("sequencer {url} is unreachable: {err}"),
+        ),
+    }
+}
+
+/// Queries the chain id of the sequencer.
+async fn sequencer_chain_id(url: &str) -> eyre::Result<u64> {
+    #[derive(Deserialize)]
+    struct Response {
+        result: U64,
+    }
+
+    let response = reqwest::Client::new()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(r#"{"jsonrpc":"2.0","id":1,"method":"eth_chainId","params":[]}"#)
+        .timeout(SEQUENCER_CHECK_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    Ok(serde_json::from_slice::<Response>(&response)?.result.to())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chainspec::ODYSSEY_DEV, forks::ForkOverride};
+
+    #[test]
+    fn checks_forks() {
+        let mut report = SelfCheckReport::default();
+        check_forks(&mut report, &ODYSSEY_DEV);
+        assert!(report.finish().is_ok());
+
+        let mut chain_spec = (**ODYSSEY_DEV).clone();
+        ForkOverride { fork: OdysseyHardfork::Exp1, timestamp: 100 }.apply(&mut chain_spec);
+        chain_spec.inner.hardforks.insert(EthereumHardfork::Prague, ForkCondition::Never);
+        let mut report = SelfCheckReport::default();
+        check_forks(&mut report, &chain_spec);
+        assert_eq!(report.get("prague").unwrap().status, CheckStatus::Failed);
+        assert_eq!(report.get("Exp1").unwrap().status, CheckStatus::Warning);
+        assert!(report.finish().is_err());
+    }
+
+    #[test]
+    fn warnings_do_not_fail() {
+        let mut report = SelfCheckReport::default();
+        check_sponsor(&mut report, Some(Address::ZERO), U256::ZERO);
+        assert!(!check_withdrawal_contract(&mut report, Address::ZERO, false));
+        assert!(report.results().iter().all(|result| result.status == CheckStatus::Warning));
+        assert!(!report.has_failures());
+    }
+
+    #[tokio::test]
+    async fn unreachable_sequencer_is_a_warning() {
+        let mut report = SelfCheckReport::default();
+        // nothing is listening on this port
+        check_sequencer(&mut report, Some("http://127.0.0.1:1"), 1).await;
+        assert_eq!(report.get("sequencer").unwrap().status, CheckStatus::Warning);
+    }
+}
@@ -0,0 +1,146 @@
+//! Verification of the code semantics of delegated accounts.
+//!
+//! The code of an account delegated with [EIP-7702](https://eips.ethereum.org/EIPS/eip-7702) is
+//! read through two paths that have to agree: `eth_getCode`, which reads the code from the
+//! database, and the `EXTCODESIZE`, `EXTCODEHASH` and `EXTCODECOPY` instructions, which read it
+//! through the EVM. Both return the delegation designator, but a bug in either path, or a change
+//! of them in a reth upgrade, makes contracts see different code than wallets and explorers.
+//!
+//! `odyssey_verifyDelegationSemantics` executes a helper contract that reads the code of the
+//! account with the `EXTCODE*` instructions in an `eth_call`, and compares the result with
+//! `eth_getCode`. The helper is injected with a state override, so it does not have to be
+//! deployed.
+
+use alloy_primitives::{address, hex, keccak256, Address, Bytes, B256, U256};
+use alloy_rpc_types_eth::{
+    state::{AccountOverride, StateOverride},
+    TransactionRequest,
+};
+use odyssey_common::parse_delegation_designator;
+use serde::{Deserialize, Serialize};
+
+/// The address the helper contract is injected at.
+pub const EXTCODE_HELPER_ADDRESS: Address = address!("00000000000000000000000000000000000de1e9");
+
+/// The helper contract that reads the code of the address in its calldata.
+///
+/// It returns the result of `EXTCODESIZE` and `EXTCODEHASH` as 32 byte words, followed by the
+/// result of `EXTCODECOPY` of the whole code:
+///
+/// ```text
+/// PUSH1 0x00 CALLDATALOAD
+/// DUP1 EXTCODEHASH PUSH1 0x20 MSTORE
+/// DUP1 EXTCODESIZE DUP1 PUSH1 0x00 MSTORE
+/// DUP1 PUSH1 0x00 PUSH1 0x40 DUP5 EXTCODECOPY
+/// PUSH1 0x40 ADD PUSH1 0x00 RETURN
+/// ```
+pub const EXTCODE_HELPER: [u8; 27] = hex!("600035803f602052803b806000528060006040843c6040016000f3");
+
+/// Returns the call of the helper contract that reads the code of the account.
+pub fn extcode_request(account: Address) -> TransactionRequest {
+    TransactionRequest::default()
+        .to(EXTCODE_HELPER_ADDRESS)
+        .input(Bytes::from(account.into_word()).into())
+}
+
+/// Returns the state override that injects the helper contract.
+pub fn extcode_state_override() -> StateOverride {
+    let mut state_override = StateOverride::default();
+    state_override.insert(
+        EXTCODE_HELPER_ADDRESS,
+        AccountOverride { code: Some(EXTCODE_HELPER.into()), ..Default::default() },
+    );
+    state_override
+}
+
+/// The code of an account as read by `eth_getCode` and by the `EXTCODE*` instructions, as
+/// returned by `odyssey_verifyDelegationSemantics`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DelegationSemantics {
+    /// The address the account delegates to, according to `eth_getCode`.
+    pub designator: Option<Address>,
+    /// The code returned by `eth_getCode`.
+    pub code: Bytes,
+    /// The size of the code returned by `EXTCODESIZE`.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub extcodesize: u64,
+    /// The hash of the code returned by `EXTCODEHASH`.
+    pub extcodehash: B256,
+    /// The code copied by `EXTCODECOPY`.
+    pub extcodecopy: Bytes,
+    /// Whether both paths read the same code.
+    pub consistent: bool,
+}
+
+impl DelegationSemantics {
+    /// Compares the code returned by `eth_getCode` with the output of the helper contract.
+    ///
+    /// Returns `None` if the output is malformed.
+    pub fn new(code: Bytes, output: &[u8]) -> Option<Self> {
+        let extcodesize = B256::try_from(output.get(..32)?).ok()?;
+        let extcodesize = u64::try_from(U256::from_be_bytes(extcodesize.0)).ok()?;
+        let extcodehash = B256::try_from(output.get(32..64)?).ok()?;
+        let extcodecopy = Bytes::copy_from_slice(output.get(64..)?);
+        if extcodecopy.len() as u64 != extcodesize {
+            return None;
+        }
+
+        // `EXTCODEHASH` is zero for accounts that do not exist
+        let hash_matches =
+            extcodehash == keccak256(&code) || (extcodehash.is_zero() && code.is_empty());
+        let consistent = extcodecopy == code && hash_matches;
+        Some(Self {
+            designator: parse_delegation_designator(&code),
+            code,
+            extcodesize,
+            extcodehash,
+            extcodecopy,
+            consistent,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odyssey_common::delegation_designator;
+
+    fn output(size: usize, hash: B256, code: &[u8]) -> Vec<u8> {
+        [B256::from(U256::from(size)).as_slice(), hash.as_slice(), code].concat()
+    }
+
+    #[test]
+    fn compares_code() {
+        let designator = Address::with_last_byte(1);
+        let code = delegation_designator(designator);
+
+        let semantics =
+            DelegationSemantics::new(code.clone(), &output(23, keccak256(&code), &code)).unwrap();
+        assert!(semantics.consistent);
+        assert_eq!(semantics.designator, Some(designator));
+
+        // the instructions follow the delegation
+        let implementation = Bytes::from_static(&[0x60, 0x00]);
+        let semantics = DelegationSemantics::new(
+            code.clone(),
+            &output(2, keccak256(&implementation), &implementation),
+        )
+        .unwrap();
+        assert!(!semantics.consistent);
+        let json = serde_json::to_value(&semantics).unwrap();
+        assert_eq!(json["extcodesize"], "0x2");
+
+        // accounts that do not exist
+        let semantics =
+            DelegationSemantics::new(Bytes::new(), &output(0, B256::ZERO, &[])).unwrap();
+        assert!(semantics.consistent);
+        assert_eq!(semantics.designator, None);
+    }
+
+    #[test]
+    fn rejects_malformed_output() {
+        assert_eq!(DelegationSemantics::new(Bytes::new(), &[0; 32]), None);
+        assert_eq!(DelegationSemantics::new(Bytes::new(), &output(1, B256::ZERO, &[])), None);
+    }
+}
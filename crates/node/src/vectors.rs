@@ -0,0 +1,397 @@
+//! Test vectors of [EIP-7702](https://eips.ethereum.org/EIPS/eip-7702) edge cases.
+//!
+//! The [`VectorGenerator`] signs a sequence of transactions that exercise the rules of
+//! authorizations which are easy to get wrong: wildcard chain ids, chains of delegations, accounts
+//! that delegate in their own transaction, cleared delegations and long authorization lists. Every
+//! vector carries the outcome it is expected to have, assuming the vectors are included in order,
+//! so e2e tests and `odyssey gen-7702-vectors` can submit them to a devnet and assert the result
+//! without maintaining the transactions by hand.
+
+use alloy_consensus::{SignableTransaction, TxEip7702, TxEnvelope};
+use alloy_eips::{
+    eip2718::Encodable2718,
+    eip7702::{Authorization, SignedAuthorization},
+};
+use alloy_primitives::{Address, Bytes, TxHash, U256};
+use alloy_signer::SignerSync;
+use alloy_signer_local::PrivateKeySigner;
+use odyssey_common::parse_delegation_designator;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// The default number of authorizations in the vector with the longest authorization list.
+pub const DEFAULT_MAX_AUTHORIZATIONS: usize = 256;
+
+/// The gas of a vector on top of the intrinsic gas of its authorizations.
+const BASE_GAS_LIMIT: u64 = 100_000;
+
+/// The intrinsic gas of an authorization.
+const AUTHORIZATION_GAS: u64 = 25_000;
+
+/// The outcome a test vector is expected to have.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpectedOutcome {
+    /// Whether the transaction succeeds.
+    pub success: bool,
+    /// The address every authority of the transaction delegates to after it is included, or
+    /// `None` if its delegation is cleared.
+    pub delegations: BTreeMap<Address, Option<Address>>,
+}
+
+impl ExpectedOutcome {
+    fn delegated(
+        success: bool,
+        delegations: impl IntoIterator<Item = (Address, Option<Address>)>,
+    ) -> Self {
+        Self { success, delegations: delegations.into_iter().collect() }
+    }
+
+    /// Compares the outcome with the status of the receipt and the code of the authorities after
+    /// the transaction was included, and describes every mismatch.
+    pub fn mismatches(&self, success: bool, codes: &HashMap<Address, Bytes>) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if success != self.success {
+            mismatches.push(format!("expected success {}, got {success}", self.success));
+        }
+        for (authority, expected) in &self.delegations {
+            let code = codes.get(authority).cloned().unwrap_or_default();
+            let delegation = parse_delegation_designator(&code);
+            if delegation != *expected || (expected.is_none() && !code.is_empty()) {
+                mismatches.push(format!(
+                    "expected {authority} to delegate to {expected:?}, got code {code}"
+                ));
+            }
+        }
+        mismatches
+    }
+}
+
+/// A signed transaction exercising an edge case of EIP-7702, and its expected outcome.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestVector {
+    /// The name of the edge case.
+    pub name: String,
+    /// The sender of the transaction.
+    pub from: Address,
+    /// The hash of the transaction.
+    pub transaction_hash: TxHash,
+    /// The EIP-2718 encoded transaction.
+    pub raw: Bytes,
+    /// The outcome the transaction is expected to have.
+    pub expected: ExpectedOutcome,
+}
+
+/// The nonces of the accounts of a [`VectorGenerator`] before the first vector.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VectorNonces {
+    /// The nonce of the sender.
+    pub sender: u64,
+    /// The nonce of the authority.
+    pub authority: u64,
+    /// The nonce of the chained authority.
+    pub chained: u64,
+}
+
+/// Signs the test vectors of EIP-7702 edge cases.
+///
+/// The sender pays for all vectors except the ones the authority sends to delegate itself, so both
+/// need ether. The chained authority only signs authorizations.
+#[derive(Debug, Clone)]
+pub struct VectorGenerator {
+    chain_id: u64,
+    delegation: Address,
+    sender: PrivateKeySigner,
+    authority: PrivateKeySigner,
+    chained: PrivateKeySigner,
+    max_fee_per_gas: u128,
+    max_priority_fee_per_gas: u128,
+    max_authorizations: usize,
+}
+
+impl VectorGenerator {
+    /// Creates a new generator of vectors that delegate to the given contract.
+    pub fn new(
+        chain_id: u64,
+        delegation: Address,
+        sender: PrivateKeySigner,
+        authority: PrivateKeySigner,
+        chained: PrivateKeySigner,
+    ) -> Self {
+        Self {
+            chain_id,
+            delegation,
+            sender,
+            authority,
+            chained,
+            max_fee_per_gas: 1_000_000_000,
+            max_priority_fee_per_gas: 1_000_000,
+            max_authorizations: DEFAULT_MAX_AUTHORIZATIONS,
+        }
+    }
+
+    /// Sets the fees of the vectors.
+    pub const fn with_fees(
+        mut self,
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+    ) -> Self {
+        self.max_fee_per_gas = max_fee_per_gas;
+        self.max_priority_fee_per_gas = max_priority_fee_per_gas;
+        self
+    }
+
+    /// Sets the number of authorizations in the vector with the longest authorization list.
+    pub const fn with_max_authorizations(mut self, max_authorizations: usize) -> Self {
+        self.max_authorizations = max_authorizations;
+        self
+    }
+
+    /// Signs the vectors, starting at the given nonces.
+    ///
+    /// Vectors call the zero address, so their success does not depend on the delegated contract,
+    /// except for the vector whose call is the edge case.
+    ///
+    /// The expected outcomes assume that the vectors are included in order, and that no other
+    /// transactions of the accounts are included in between.
+    pub fn generate(&self, nonces: VectorNonces) -> alloy_signer::Result<Vec<TestVector>> {
+        let VectorNonces { sender: mut sender_nonce, authority: mut nonce, chained } = nonces;
+        let authority = self.authority.address();
+        let chained_authority = self.chained.address();
+        let mut vectors = Vec::new();
+
+        // a plain delegation
+        let authorizations =
+            vec![self.authorize(&self.authority, self.chain_id, self.delegation, nonce)?];
+        nonce += 1;
+        vectors.push(self.vector(
+            "delegation",
+            &self.sender,
+            &mut sender_nonce,
+            Address::ZERO,
+            authorizations,
+            ExpectedOutcome::delegated(true, [(authority, Some(self.delegation))]),
+        )?);
+
+        // delegating to the zero address clears the code of the account
+        let authorizations =
+            vec![self.authorize(&self.authority, self.chain_id, Address::ZERO, nonce)?];
+        nonce += 1;
+        vectors.push(self.vector(
+            "cleared-delegation",
+            &self.sender,
+            &mut sender_nonce,
+            Address::ZERO,
+            authorizations,
+            ExpectedOutcome::delegated(true, [(authority, None)]),
+        )?);
+
+        // an authorization with chain id zero is valid on every chain
+        let authorizations = vec![self.authorize(&self.authority, 0, self.delegation, nonce)?];
+        nonce += 1;
+        vectors.push(self.vector(
+            "wildcard-chain-id",
+            &self.sender,
+            &mut sender_nonce,
+            Address::ZERO,
+            authorizations,
+            ExpectedOutcome::delegated(true, [(authority, Some(self.delegation))]),
+        )?);
+
+        // designators are not followed, so calling an account that delegates to a delegated
+        // account executes the designator, which starts with an invalid opcode
+        let authorizations = vec![
+            self.authorize(&self.chained, self.chain_id, self.delegation, chained)?,
+            self.authorize(&self.authority, self.chain_id, chained_authority, nonce)?,
+        ];
+        nonce += 1;
+        vectors.push(self.vector(
+            "delegation-chain",
+            &self.sender,
+            &mut sender_nonce,
+            authority,
+            authorizations,
+            ExpectedOutcome::delegated(
+                false,
+                [(authority, Some(chained_authority)), (chained_authority, Some(self.delegation))],
+            ),
+        )?);
+
+        // the nonce of the sender is incremented before the authorizations are processed, so an
+        // authority sending its own authorization signs the nonce after the transaction
+        let authorizations =
+            vec![self.authorize(&self.authority, self.chain_id, self.delegation, nonce + 1)?];
+        vectors.push(self.vector(
+            "self-delegation",
+            &self.authority,
+            &mut nonce,
+            Address::ZERO,
+            authorizations,
+            ExpectedOutcome::delegated(true, [(authority, Some(self.delegation))]),
+        )?);
+        nonce += 1;
+
+        // an authorization with the nonce of the transaction is skipped, the delegation is
+        // unchanged
+        let authorizations =
+            vec![self.authorize(&self.authority, self.chain_id, Address::ZERO, nonce)?];
+        vectors.push(self.vector(
+            "self-delegation-stale-nonce",
+            &self.authority,
+            &mut nonce,
+            Address::ZERO,
+            authorizations,
+            ExpectedOutcome::delegated(true, [(authority, Some(self.delegation))]),
+        )?);
+
+        // every authorization of a list is applied in order, the last one wins
+        let authorizations = (0..self.max_authorizations as u64)
+            .map(|i| {
+                let address = if i + 1 == self.max_authorizations as u64 {
+                    self.delegation
+                } else {
+                    chained_authority
+                };
+                self.authorize(&self.authority, self.chain_id, address, nonce + i)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        vectors.push(self.vector(
+            "max-authorization-list",
+            &self.sender,
+            &mut sender_nonce,
+            Address::ZERO,
+            authorizations,
+            ExpectedOutcome::delegated(true, [(authority, Some(self.delegation))]),
+        )?);
+
+        Ok(vectors)
+    }
+
+    /// Signs an authorization of the signer to delegate to the address.
+    fn authorize(
+        &self,
+        signer: &PrivateKeySigner,
+        chain_id: u64,
+        address: Address,
+        nonce: u64,
+    ) -> alloy_signer::Result<SignedAuthorization> {
+        let authorization = Authorization { chain_id, address, nonce };
+        let signature = signer.sign_hash_sync(&authorization.signature_hash())?;
+        Ok(authorization.into_signed(signature))
+    }
+
+    /// Signs a transaction of the signer calling `to` with the authorizations, and increments the
+    /// nonce of the signer.
+    fn vector(
+        &self,
+        name: &str,
+        signer: &PrivateKeySigner,
+        nonce: &mut u64,
+        to: Address,
+        authorization_list: Vec<SignedAuthorization>,
+        expected: ExpectedOutcome,
+    ) -> alloy_signer::Result<TestVector> {
+        let tx = TxEip7702 {
+            chain_id: self.chain_id,
+            nonce: *nonce,
+            gas_limit: BASE_GAS_LIMIT + AUTHORIZATION_GAS * authorization_list.len() as u64,
+            max_fee_per_gas: self.max_fee_per_gas,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas,
+            to,
+            value: U256::ZERO,
+            access_list: Default::default(),
+            authorization_list,
+            input: Bytes::new(),
+        };
+        *nonce += 1;
+        let signature = signer.sign_hash_sync(&tx.signature_hash())?;
+        let envelope: TxEnvelope = tx.into_signed(signature).into();
+        Ok(TestVector {
+            name: name.to_string(),
+            from: signer.address(),
+            transaction_hash: *envelope.tx_hash(),
+            raw: envelope.encoded_2718().into(),
+            expected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::Transaction;
+    use alloy_eips::eip2718::Decodable2718;
+    use odyssey_common::delegation_designator;
+
+    fn generator() -> VectorGenerator {
+        VectorGenerator::new(
+            1337,
+            Address::with_last_byte(1),
+            PrivateKeySigner::random(),
+            PrivateKeySigner::random(),
+            PrivateKeySigner::random(),
+        )
+        .with_max_authorizations(4)
+    }
+
+    #[test]
+    fn generates_vectors() {
+        let generator = generator();
+        let nonces = VectorNonces { sender: 5, authority: 2, chained: 0 };
+        let vectors = generator.generate(nonces).unwrap();
+        let names: Vec<_> = vectors.iter().map(|vector| vector.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "delegation",
+                "cleared-delegation",
+                "wildcard-chain-id",
+                "delegation-chain",
+                "self-delegation",
+                "self-delegation-stale-nonce",
+                "max-authorization-list"
+            ]
+        );
+
+        let authority = generator.authority.address();
+        let mut authority_nonces = Vec::new();
+        for vector in &vectors {
+            let envelope = TxEnvelope::decode_2718(&mut vector.raw.as_ref()).unwrap();
+            assert_eq!(*envelope.tx_hash(), vector.transaction_hash);
+            assert_eq!(envelope.recover_signer().unwrap(), vector.from);
+            if vector.from == authority {
+                authority_nonces.push(envelope.nonce());
+            }
+            for authorization in envelope.authorization_list().unwrap() {
+                let signer = authorization.recover_authority().unwrap();
+                if signer == authority {
+                    authority_nonces.push(authorization.nonce);
+                }
+            }
+        }
+        // the stale authorization reuses the nonce of its transaction
+        assert_eq!(authority_nonces, [2, 3, 4, 5, 6, 7, 8, 8, 9, 10, 11, 12]);
+
+        let max = vectors.last().unwrap();
+        assert_eq!(max.expected.delegations[&authority], Some(Address::with_last_byte(1)));
+    }
+
+    #[test]
+    fn compares_outcomes() {
+        let authority = Address::with_last_byte(2);
+        let expected =
+            ExpectedOutcome::delegated(true, [(authority, Some(Address::with_last_byte(1)))]);
+        let mut codes =
+            HashMap::from([(authority, delegation_designator(Address::with_last_byte(1)))]);
+        assert!(expected.mismatches(true, &codes).is_empty());
+        assert_eq!(expected.mismatches(false, &codes).len(), 1);
+
+        codes.clear();
+        assert_eq!(expected.mismatches(true, &codes).len(), 1);
+        let cleared = ExpectedOutcome::delegated(true, [(authority, None)]);
+        assert!(cleared.mismatches(true, &codes).is_empty());
+        codes.insert(authority, Bytes::from_static(&[0x00]));
+        assert_eq!(cleared.mismatches(true, &codes).len(), 1);
+    }
+}
@@ -0,0 +1,92 @@
+//! Version and experiment information of the node.
+//!
+//! `odyssey_getVersionInfo` returns the version and git commit of the binary, the Odyssey
+//! experiments scheduled in its chain spec, whether the wallet service is enabled, and the
+//! hardforks that are active at the current head, so bug reports can be matched to the exact
+//! build and experiment set an endpoint is running.
+
+use crate::forks::OdysseyHardfork;
+use reth_chainspec::{EthChainSpec, ForkCondition, Hardfork, Hardforks, Head};
+use reth_optimism_chainspec::OpChainSpec;
+use serde::{Deserialize, Serialize};
+
+/// The git commit the node was built from, if known.
+pub const GIT_SHA: Option<&str> = option_env!("ODYSSEY_GIT_SHA");
+
+/// The version and experiments of the node, as returned by `odyssey_getVersionInfo`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionInfo {
+    /// The version of Odyssey.
+    pub version: String,
+    /// The git commit the node was built from, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// The chain of the chain spec.
+    pub chain: String,
+    /// The Odyssey experiments scheduled in the chain spec.
+    pub experiments: Vec<String>,
+    /// Whether the `wallet_` namespace is served.
+    pub wallet: bool,
+    /// The number of the current head.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub head: u64,
+    /// The hardforks that are active at the current head, including the Odyssey experiments.
+    pub active_hardforks: Vec<String>,
+}
+
+impl VersionInfo {
+    /// Returns the version information of a node with the given chain spec, at the given head.
+    pub fn new(chain_spec: &OpChainSpec, head: &Head, wallet: bool) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit: GIT_SHA.map(str::to_string),
+            chain: chain_spec.chain().to_string(),
+            experiments: OdysseyHardfork::ALL
+                .into_iter()
+                .filter(|fork| chain_spec.fork(*fork) != ForkCondition::Never)
+                .map(|fork| fork.name().to_string())
+                .collect(),
+            wallet,
+            head: head.number,
+            active_hardforks: chain_spec
+                .forks_iter()
+                .filter(|(_, condition)| condition.active_at_head(head))
+                .map(|(fork, _)| fork.name().to_string())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chainspec::ODYSSEY_DEV, forks::ForkOverride};
+
+    #[test]
+    fn reports_active_hardforks() {
+        let mut chain_spec = (**ODYSSEY_DEV).clone();
+        ForkOverride { fork: OdysseyHardfork::Exp1, timestamp: 100 }.apply(&mut chain_spec);
+
+        let before = VersionInfo::new(
+            &chain_spec,
+            &Head { number: 1, timestamp: 99, ..Default::default() },
+            true,
+        );
+        assert_eq!(before.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(before.experiments, vec!["Exp1", "Exp2"]);
+        assert!(before.active_hardforks.iter().any(|fork| fork == "Cancun"));
+        assert!(!before.active_hardforks.iter().any(|fork| fork == "Exp1"));
+
+        let after = VersionInfo::new(
+            &chain_spec,
+            &Head { number: 2, timestamp: 100, ..Default::default() },
+            false,
+        );
+        assert!(after.active_hardforks.iter().any(|fork| fork == "Exp1"));
+
+        let json = serde_json::to_value(&after).unwrap();
+        assert_eq!(json["head"], "0x2");
+        assert_eq!(json["activeHardforks"], serde_json::to_value(&after.active_hardforks).unwrap());
+    }
+}
@@ -0,0 +1,130 @@
+//! Initiation of withdrawals to L1.
+//!
+//! Withdrawals are initiated by calling `initiateWithdrawal` on the
+//! [L2ToL1MessagePasser](https://specs.optimism.io/protocol/withdrawals.html#the-l2tol1messagepasser-contract).
+//! `odyssey_buildWithdrawalTx` builds that call, and returns it along with the withdrawal it
+//! initiates and the hash the withdrawal is proven and finalized by on L1.
+
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use alloy_rpc_types_eth::TransactionRequest;
+use alloy_sol_types::{sol, SolCall, SolValue};
+use serde::{Deserialize, Serialize};
+
+/// The default minimum gas limit of the execution of a withdrawal on L1.
+///
+/// This is the gas limit the message passer uses for withdrawals initiated by sending ether to it.
+pub const DEFAULT_WITHDRAWAL_GAS_LIMIT: u64 = 100_000;
+
+sol! {
+    /// The OP Stack L2 to L1 message passer predeploy.
+    interface L2ToL1MessagePasser {
+        /// Initiates a withdrawal of the sent value, calling the target with the data on L1.
+        function initiateWithdrawal(address _target, uint256 _gasLimit, bytes memory _data) external payable;
+
+        /// Returns the nonce of the next withdrawal, with the message version in its upper two
+        /// bytes.
+        function messageNonce() external view returns (uint256);
+    }
+}
+
+/// A withdrawal initiated through the message passer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Withdrawal {
+    /// The versioned nonce of the withdrawal.
+    pub nonce: U256,
+    /// The account that initiates the withdrawal.
+    pub sender: Address,
+    /// The account that is called on L1.
+    pub target: Address,
+    /// The value that is withdrawn.
+    pub value: U256,
+    /// The minimum gas limit of the call on L1.
+    pub gas_limit: U256,
+    /// The data of the call on L1.
+    pub data: Bytes,
+}
+
+impl Withdrawal {
+    /// Returns the hash of the withdrawal, as stored in the `sentMessages` of the message passer.
+    pub fn hash(&self) -> B256 {
+        keccak256(
+            (self.nonce, self.sender, self.target, self.value, self.gas_limit, self.data.clone())
+                .abi_encode_params(),
+        )
+    }
+
+    /// Returns the request that initiates the withdrawal through the given message passer.
+    pub fn initiate_request(&self, message_passer: Address) -> TransactionRequest {
+        let call = L2ToL1MessagePasser::initiateWithdrawalCall {
+            _target: self.target,
+            _gasLimit: self.gas_limit,
+            _data: self.data.clone(),
+        };
+        TransactionRequest::default()
+            .from(self.sender)
+            .to(message_passer)
+            .value(self.value)
+            .input(Bytes::from(call.abi_encode()).into())
+    }
+}
+
+/// An unsigned transaction that initiates a withdrawal, as returned by
+/// `odyssey_buildWithdrawalTx`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawalTx {
+    /// The transaction to sign and send, with its estimated gas limit.
+    ///
+    /// The nonce and fees of the transaction are not set.
+    pub request: TransactionRequest,
+    /// The withdrawal the transaction initiates.
+    pub withdrawal: Withdrawal,
+    /// The expected hash of the withdrawal.
+    ///
+    /// This only matches the hash of the initiated withdrawal if no other withdrawal is included
+    /// before the transaction, as the nonce of the withdrawal is assigned on inclusion.
+    pub withdrawal_hash: B256,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+    use odyssey_common::WITHDRAWAL_CONTRACT;
+
+    #[test]
+    fn hashes_withdrawal() {
+        let withdrawal = Withdrawal {
+            nonce: U256::from(1) << 240,
+            sender: address!("0000000000000000000000000000000000000001"),
+            target: address!("0000000000000000000000000000000000000002"),
+            value: U256::from(3),
+            gas_limit: U256::from(DEFAULT_WITHDRAWAL_GAS_LIMIT),
+            data: Bytes::from_static(&[0xab]),
+        };
+
+        // the fields are encoded as separate parameters, not as a tuple
+        let mut encoded = Vec::new();
+        encoded.extend(withdrawal.nonce.to_be_bytes::<32>());
+        encoded.extend(withdrawal.sender.into_word());
+        encoded.extend(withdrawal.target.into_word());
+        encoded.extend(withdrawal.value.to_be_bytes::<32>());
+        encoded.extend(withdrawal.gas_limit.to_be_bytes::<32>());
+        encoded.extend(U256::from(6 * 32).to_be_bytes::<32>());
+        encoded.extend(U256::from(1).to_be_bytes::<32>());
+        let mut data = [0; 32];
+        data[0] = 0xab;
+        encoded.extend(data);
+        assert_eq!(withdrawal.hash(), keccak256(&encoded));
+
+        let request = withdrawal.initiate_request(WITHDRAWAL_CONTRACT);
+        assert_eq!(request.from, Some(withdrawal.sender));
+        assert_eq!(request.value, Some(withdrawal.value));
+        let input = request.input.input().unwrap();
+        let call = L2ToL1MessagePasser::initiateWithdrawalCall::abi_decode(input, true).unwrap();
+        assert_eq!(call._target, withdrawal.target);
+        assert_eq!(call._gasLimit, withdrawal.gas_limit);
+        assert_eq!(call._data, withdrawal.data);
+    }
+}
@@ -0,0 +1,657 @@
+//! Allow and deny lists of the wallet, persisted to disk.
+//!
+//! Operators respond to abuse without redeploying by listing the authorities, destinations,
+//! implementations and function selectors that are sponsored or not. Every kind of entry has an
+//! allowlist and a denylist: requests with a denied entry are rejected, and if the allowlist of a
+//! kind is not empty, only requests with an allowed entry of that kind are sponsored.
+//!
+//! The lists are stored as JSON in a file, which is reloaded when it is modified, and are managed
+//! through the `admin_` namespace, which writes every change back to the file.
+//!
+//! Every change made through the `admin_` namespace bumps the version of the lists and is appended
+//! to their history, along with when and by whom it was made. The implementations that are
+//! sponsored are exposed through `wallet_getCapabilities` together with their history, so audits
+//! can reconstruct which implementations were sponsored at any point in time.
+
+use crate::{selectors::parse_selector, DelegationCapability, OdysseyWalletError};
+use alloy_primitives::{Address, Selector};
+use odyssey_common::unix_epoch_ms;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tracing::{debug, info, warn};
+
+/// The default interval at which the file of the access lists is checked for changes.
+pub const DEFAULT_ACCESS_LISTS_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The kind of entries of an access list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccessListKind {
+    /// Accounts that requests are sponsored for, and the authorities of their authorizations.
+    Authority,
+    /// The destinations of requests.
+    Destination,
+    /// The implementations accounts delegate to.
+    Implementation,
+    /// The selectors of the functions that are called.
+    Selector,
+}
+
+impl fmt::Display for AccessListKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Authority => "authority",
+            Self::Destination => "destination",
+            Self::Implementation => "implementation",
+            Self::Selector => "selector",
+        })
+    }
+}
+
+/// Whether the entries of an access list are allowed or denied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccessMode {
+    /// Only requests with allowed entries are sponsored.
+    Allow,
+    /// Requests with denied entries are rejected.
+    Deny,
+}
+
+/// The allowed and denied entries of one kind.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "T: Ord + Serialize + serde::de::DeserializeOwned")]
+pub struct AccessList<T> {
+    /// The allowed entries. Every entry is allowed if this is empty.
+    #[serde(default)]
+    pub allow: BTreeSet<T>,
+    /// The denied entries.
+    #[serde(default)]
+    pub deny: BTreeSet<T>,
+}
+
+impl<T> Default for AccessList<T> {
+    fn default() -> Self {
+        Self { allow: BTreeSet::new(), deny: BTreeSet::new() }
+    }
+}
+
+impl<T: Ord> AccessList<T> {
+    /// Returns `true` if the entry is not denied, and allowed if there is an allowlist.
+    pub fn permits(&self, entry: &T) -> bool {
+        !self.deny.contains(entry) && (self.allow.is_empty() || self.allow.contains(entry))
+    }
+
+    /// Returns `true` if no entries are allowed or denied.
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    fn entries_mut(&mut self, mode: AccessMode) -> &mut BTreeSet<T> {
+        match mode {
+            AccessMode::Allow => &mut self.allow,
+            AccessMode::Deny => &mut self.deny,
+        }
+    }
+}
+
+impl<T: Ord + Copy + fmt::Display> AccessList<T> {
+    /// Adds or removes the entries, returning the entries that changed.
+    fn update(&mut self, mode: AccessMode, entries: Vec<T>, add: bool) -> Vec<String> {
+        let list = self.entries_mut(mode);
+        entries
+            .into_iter()
+            .filter(|entry| if add { list.insert(*entry) } else { list.remove(entry) })
+            .map(|entry| entry.to_string())
+            .collect()
+    }
+
+    /// Checks that the entry is permitted.
+    fn check(&self, kind: AccessListKind, entry: T) -> Result<(), OdysseyWalletError> {
+        if self.permits(&entry) {
+            return Ok(());
+        }
+        Err(OdysseyWalletError::AccessDenied { list: kind, entry: entry.to_string() })
+    }
+}
+
+/// The access lists of the wallet, as stored on disk.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AccessLists {
+    /// The accounts requests are sponsored for, and the authorities of their authorizations.
+    pub authorities: AccessList<Address>,
+    /// The destinations of requests.
+    pub destinations: AccessList<Address>,
+    /// The implementations accounts delegate to.
+    pub implementations: AccessList<Address>,
+    /// The selectors of the called functions.
+    pub selectors: AccessList<Selector>,
+    /// The number of changes made to the lists.
+    pub version: u64,
+    /// The unix timestamp in seconds of the latest change, if any.
+    pub updated_at: Option<u64>,
+    /// Who made the latest change, if known.
+    pub updated_by: Option<String>,
+    /// The changes made to the lists, oldest first.
+    pub history: Vec<AccessListChange>,
+}
+
+/// A change of an access list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessListChange {
+    /// The version of the lists after the change.
+    pub version: u64,
+    /// The unix timestamp in seconds of the change.
+    pub updated_at: u64,
+    /// Who made the change, if known.
+    pub updated_by: Option<String>,
+    /// The kind of the changed list.
+    pub list: AccessListKind,
+    /// Whether the allowlist or the denylist changed.
+    pub mode: AccessMode,
+    /// Whether the entries were added or removed.
+    pub added: bool,
+    /// The entries that were added or removed.
+    pub entries: Vec<String>,
+}
+
+/// The capabilities of the wallet service, along with their history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletCapabilities {
+    /// The implementations accounts can delegate to with sponsorship.
+    pub delegation: DelegationCapability,
+    /// The version of the access lists the capabilities are derived from.
+    pub version: u64,
+    /// The unix timestamp in seconds of the latest change of the access lists, if any.
+    pub updated_at: Option<u64>,
+    /// Who made the latest change of the access lists, if known.
+    pub updated_by: Option<String>,
+    /// The changes made to the implementation lists, oldest first.
+    pub history: Vec<AccessListChange>,
+}
+
+/// The entries of a request that are checked against the [`AccessLists`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct AccessRequest {
+    /// The account the request is sponsored for, and the authorities of its authorizations.
+    pub(crate) authorities: Vec<Address>,
+    /// The destination of the request.
+    pub(crate) destination: Option<Address>,
+    /// The implementation the destination delegates to, if known.
+    pub(crate) implementation: Option<Address>,
+    /// The implementations the authorizations of the request delegate to, except for the zero
+    /// address, which clears the delegation.
+    pub(crate) authorized_implementations: Vec<Address>,
+    /// The selector of the called function, if the input has one.
+    pub(crate) selector: Option<Selector>,
+}
+
+impl AccessLists {
+    /// Checks the entries of a request.
+    ///
+    /// Requests without an entry of a kind with an allowlist are rejected, except for
+    /// implementations, which are only checked if they are known.
+    pub(crate) fn check(&self, request: &AccessRequest) -> Result<(), OdysseyWalletError> {
+        for authority in &request.authorities {
+            self.authorities.check(AccessListKind::Authority, *authority)?;
+        }
+        if let Some(destination) = request.destination {
+            self.destinations.check(AccessListKind::Destination, destination)?;
+        } else if !self.destinations.allow.is_empty() {
+            return Err(OdysseyWalletError::IllegalDestination);
+        }
+        for implementation in
+            request.implementation.iter().chain(&request.authorized_implementations)
+        {
+            self.implementations.check(AccessListKind::Implementation, *implementation)?;
+        }
+        match request.selector {
+            Some(selector) => self.selectors.check(AccessListKind::Selector, selector)?,
+            None if !self.selectors.allow.is_empty() => {
+                return Err(OdysseyWalletError::AccessDenied {
+                    list: AccessListKind::Selector,
+                    entry: "(none)".to_string(),
+                })
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Adds or removes entries of the given kind, returning the number of entries that changed.
+    ///
+    /// Entries are addresses, or selectors given as hex or as a function signature. If any entry
+    /// changed, the version is bumped and the change is recorded in the history.
+    fn update(
+        &mut self,
+        kind: AccessListKind,
+        mode: AccessMode,
+        entries: &[String],
+        add: bool,
+        updated_by: Option<String>,
+    ) -> Result<usize, String> {
+        let addresses = || {
+            entries
+                .iter()
+                .map(|entry| {
+                    Address::from_str(entry).map_err(|err| format!("invalid {kind} {entry}: {err}"))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let changed = match kind {
+            AccessListKind::Authority => self.authorities.update(mode, addresses()?, add),
+            AccessListKind::Destination => self.destinations.update(mode, addresses()?, add),
+            AccessListKind::Implementation => self.implementations.update(mode, addresses()?, add),
+            AccessListKind::Selector => {
+                let selectors = entries
+                    .iter()
+                    .map(|entry| parse_selector(entry))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.selectors.update(mode, selectors, add)
+            }
+        };
+        if changed.is_empty() {
+            return Ok(0);
+        }
+
+        let updated_at = unix_epoch_ms() / 1000;
+        self.version += 1;
+        self.updated_at = Some(updated_at);
+        self.updated_by.clone_from(&updated_by);
+        let count = changed.len();
+        self.history.push(AccessListChange {
+            version: self.version,
+            updated_at,
+            updated_by,
+            list: kind,
+            mode,
+            added: add,
+            entries: changed,
+        });
+        Ok(count)
+    }
+
+    /// Returns the capabilities that are derived from the lists.
+    pub fn capabilities(&self) -> WalletCapabilities {
+        WalletCapabilities {
+            delegation: DelegationCapability {
+                addresses: self.implementations.allow.iter().copied().collect(),
+                denied: self.implementations.deny.iter().copied().collect(),
+            },
+            version: self.version,
+            updated_at: self.updated_at,
+            updated_by: self.updated_by.clone(),
+            history: self
+                .history
+                .iter()
+                .filter(|change| change.list == AccessListKind::Implementation)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Returns `true` if implementations are allowed or denied.
+    fn checks_implementations(&self) -> bool {
+        !self.implementations.is_empty()
+    }
+}
+
+/// The access lists of the wallet, optionally backed by a file.
+#[derive(Debug, Clone, Default)]
+pub struct AccessControl {
+    inner: Arc<AccessControlInner>,
+}
+
+#[derive(Debug, Default)]
+struct AccessControlInner {
+    lists: RwLock<AccessLists>,
+    /// The file the lists are stored in, if any.
+    path: Option<PathBuf>,
+    /// The modification time of the file when it was last loaded or written.
+    modified: RwLock<Option<SystemTime>>,
+    /// Serializes writes to the file.
+    write: tokio::sync::Mutex<()>,
+}
+
+impl AccessControl {
+    /// Creates access control with the given lists, which are not persisted.
+    pub fn new(lists: AccessLists) -> Self {
+        Self {
+            inner: Arc::new(AccessControlInner { lists: RwLock::new(lists), ..Default::default() }),
+        }
+    }
+
+    /// Loads the access lists from the given file.
+    ///
+    /// The lists are empty if the file does not exist, and the file is created on the first
+    /// change.
+    pub fn load(path: impl Into<PathBuf>) -> eyre::Result<Self> {
+        let path = path.into();
+        let (lists, modified) = match read_lists(&path) {
+            Ok(loaded) => loaded,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(err) => {
+                return Err(eyre::eyre!(
+                    "failed to load access lists from {}: {err}",
+                    path.display()
+                ))
+            }
+        };
+        Ok(Self {
+            inner: Arc::new(AccessControlInner {
+                lists: RwLock::new(lists),
+                path: Some(path),
+                modified: RwLock::new(modified),
+                write: Default::default(),
+            }),
+        })
+    }
+
+    /// Returns the current access lists.
+    pub fn lists(&self) -> AccessLists {
+        self.inner.lists.read().clone()
+    }
+
+    /// Returns the capabilities that are derived from the current access lists.
+    pub fn capabilities(&self) -> WalletCapabilities {
+        self.inner.lists.read().capabilities()
+    }
+
+    /// Checks the entries of a request against the access lists.
+    pub(crate) fn check(&self, request: &AccessRequest) -> Result<(), OdysseyWalletError> {
+        self.inner.lists.read().check(request)
+    }
+
+    /// Returns `true` if the implementations of requests need to be checked.
+    pub(crate) fn checks_implementations(&self) -> bool {
+        self.inner.lists.read().checks_implementations()
+    }
+
+    /// Adds entries to an access list on behalf of `updated_by`, and writes the lists to the file,
+    /// if any.
+    ///
+    /// Returns the number of entries that were added.
+    pub async fn add(
+        &self,
+        kind: AccessListKind,
+        mode: AccessMode,
+        entries: &[String],
+        updated_by: Option<String>,
+    ) -> Result<usize, OdysseyWalletError> {
+        self.update(kind, mode, entries, true, updated_by).await
+    }
+
+    /// Removes entries from an access list on behalf of `updated_by`, and writes the lists to the
+    /// file, if any.
+    ///
+    /// Returns the number of entries that were removed.
+    pub async fn remove(
+        &self,
+        kind: AccessListKind,
+        mode: AccessMode,
+        entries: &[String],
+        updated_by: Option<String>,
+    ) -> Result<usize, OdysseyWalletError> {
+        self.update(kind, mode, entries, false, updated_by).await
+    }
+
+    async fn update(
+        &self,
+        kind: AccessListKind,
+        mode: AccessMode,
+        entries: &[String],
+        add: bool,
+        updated_by: Option<String>,
+    ) -> Result<usize, OdysseyWalletError> {
+        let _write = self.inner.write.lock().await;
+        // the change is applied to a copy, so the lists in use never differ from the file
+        let mut lists = self.lists();
+        let changed = lists
+            .update(kind, mode, entries, add, updated_by)
+            .map_err(|reason| OdysseyWalletError::InvalidAccessListEntry { list: kind, reason })?;
+        if changed == 0 {
+            return Ok(0);
+        }
+
+        if let Some(path) = &self.inner.path {
+            let modified = write_lists(path, &lists).await.map_err(|err| {
+                OdysseyWalletError::InternalError(eyre::eyre!(
+                    "failed to write access lists: {err}"
+                ))
+            })?;
+            *self.inner.modified.write() = modified;
+        }
+        *self.inner.lists.write() = lists;
+        info!(target: "rpc::wallet", %kind, ?mode, add, changed, "Updated access list");
+        Ok(changed)
+    }
+
+    /// Reloads the lists if the file was modified since it was last loaded or written.
+    ///
+    /// Returns `true` if the lists were reloaded.
+    pub async fn reload(&self) -> eyre::Result<bool> {
+        let Some(path) = &self.inner.path else { return Ok(false) };
+        let _write = self.inner.write.lock().await;
+        let modified = tokio::fs::metadata(path).await.and_then(|meta| meta.modified()).ok();
+        if modified.is_none() || modified == *self.inner.modified.read() {
+            return Ok(false);
+        }
+
+        let path = path.clone();
+        let (lists, modified) = tokio::task::spawn_blocking(move || read_lists(&path)).await??;
+        *self.inner.lists.write() = lists;
+        *self.inner.modified.write() = modified;
+        Ok(true)
+    }
+
+    /// Spawns a task that reloads the lists when the file is modified, checking the file at the
+    /// given interval.
+    ///
+    /// The task stops once the access control is dropped.
+    pub fn spawn_reload(&self, period: Duration) {
+        if self.inner.path.is_none() {
+            return;
+        }
+        let inner = Arc::downgrade(&self.inner);
+        tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let Some(inner) = inner.upgrade() else { break };
+                match (Self { inner }).reload().await {
+                    Ok(true) => info!(target: "rpc::wallet", "Reloaded access lists"),
+                    Ok(false) => {}
+                    // the current lists are kept until the file is fixed
+                    Err(err) => warn!(target: "rpc::wallet", %err, "Invalid access lists"),
+                }
+            }
+        });
+    }
+}
+
+/// Reads the access lists and the modification time of the file.
+fn read_lists(path: &Path) -> std::io::Result<(AccessLists, Option<SystemTime>)> {
+    let data = std::fs::read(path)?;
+    let lists = serde_json::from_slice(&data)?;
+    let modified = std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    debug!(target: "rpc::wallet", ?path, "Loaded access lists");
+    Ok((lists, modified))
+}
+
+/// Writes the access lists to the file, returning its new modification time.
+async fn write_lists(path: &Path, lists: &AccessLists) -> std::io::Result<Option<SystemTime>> {
+    // write to a temporary file first, so the lists are never partially written
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec_pretty(lists)?).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(tokio::fs::metadata(path).await.and_then(|meta| meta.modified()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::hex;
+
+    const ACCOUNT: Address = Address::with_last_byte(1);
+    const IMPLEMENTATION: Address = Address::with_last_byte(2);
+
+    fn request() -> AccessRequest {
+        AccessRequest {
+            authorities: vec![ACCOUNT],
+            destination: Some(ACCOUNT),
+            implementation: Some(IMPLEMENTATION),
+            authorized_implementations: vec![IMPLEMENTATION],
+            selector: Some(Selector::from(hex!("a9059cbb"))),
+        }
+    }
+
+    #[test]
+    fn checks_requests() {
+        let mut lists = AccessLists::default();
+        assert!(lists.check(&request()).is_ok());
+
+        lists.authorities.deny.insert(ACCOUNT);
+        assert!(matches!(
+            lists.check(&request()),
+            Err(OdysseyWalletError::AccessDenied { list: AccessListKind::Authority, .. })
+        ));
+
+        let mut lists = AccessLists::default();
+        lists.implementations.allow.insert(Address::with_last_byte(3));
+        assert!(matches!(
+            lists.check(&request()),
+            Err(OdysseyWalletError::AccessDenied { list: AccessListKind::Implementation, .. })
+        ));
+        lists.implementations.allow.insert(IMPLEMENTATION);
+        assert!(lists.check(&request()).is_ok());
+
+        // every authorization is checked
+        let batch = AccessRequest {
+            authorized_implementations: vec![IMPLEMENTATION, Address::with_last_byte(4)],
+            ..request()
+        };
+        assert!(matches!(
+            lists.check(&batch),
+            Err(OdysseyWalletError::AccessDenied { list: AccessListKind::Implementation, .. })
+        ));
+
+        // calls without a selector are rejected if only some selectors are allowed
+        lists
+            .update(
+                AccessListKind::Selector,
+                AccessMode::Allow,
+                &["transfer(address,uint256)".to_string()],
+                true,
+                None,
+            )
+            .unwrap();
+        assert!(lists.check(&request()).is_ok());
+        assert!(lists.check(&AccessRequest { selector: None, ..request() }).is_err());
+    }
+
+    #[tokio::test]
+    async fn persists_and_reloads_lists() {
+        let path = std::env::temp_dir().join(format!("odyssey-access-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let access = AccessControl::load(&path).unwrap();
+        assert_eq!(access.lists(), AccessLists::default());
+        let entries = vec![ACCOUNT.to_string()];
+        assert_eq!(
+            access.add(AccessListKind::Authority, AccessMode::Deny, &entries, None).await.unwrap(),
+            1
+        );
+        assert_eq!(
+            access.add(AccessListKind::Authority, AccessMode::Deny, &entries, None).await.unwrap(),
+            0
+        );
+        assert!(access
+            .add(AccessListKind::Destination, AccessMode::Deny, &["0x01".to_string()], None)
+            .await
+            .is_err());
+        assert!(!access.reload().await.unwrap());
+
+        // the change was written to the file
+        let loaded = AccessControl::load(&path).unwrap();
+        assert!(loaded.lists().authorities.deny.contains(&ACCOUNT));
+        assert_eq!(loaded.lists().version, 1);
+
+        // changes to the file are picked up by reloading
+        let mut lists = AccessLists::default();
+        lists.destinations.deny.insert(IMPLEMENTATION);
+        std::fs::write(&path, serde_json::to_vec(&lists).unwrap()).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(1);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert!(access.reload().await.unwrap());
+        assert_eq!(access.lists(), lists);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_lists_if_write_fails() {
+        let path = std::env::temp_dir()
+            .join(format!("odyssey-access-missing-{}", std::process::id()))
+            .join("access.json");
+        let access = AccessControl::load(&path).unwrap();
+
+        let entries = vec![ACCOUNT.to_string()];
+        assert!(access
+            .add(AccessListKind::Authority, AccessMode::Deny, &entries, None)
+            .await
+            .is_err());
+        assert_eq!(access.lists(), AccessLists::default());
+    }
+
+    #[tokio::test]
+    async fn versions_capabilities() {
+        let access = AccessControl::default();
+        let implementations = vec![IMPLEMENTATION.to_string(), ACCOUNT.to_string()];
+        let operator = Some("operator".to_string());
+        access
+            .add(AccessListKind::Implementation, AccessMode::Allow, &implementations, None)
+            .await
+            .unwrap();
+        access
+            .add(AccessListKind::Selector, AccessMode::Deny, &["0xa9059cbb".to_string()], None)
+            .await
+            .unwrap();
+        access
+            .remove(
+                AccessListKind::Implementation,
+                AccessMode::Allow,
+                &[ACCOUNT.to_string()],
+                operator.clone(),
+            )
+            .await
+            .unwrap();
+
+        let capabilities = access.capabilities();
+        assert_eq!(capabilities.delegation.addresses, vec![IMPLEMENTATION]);
+        assert_eq!(capabilities.version, 3);
+        assert!(capabilities.updated_at.is_some());
+        assert_eq!(capabilities.updated_by, operator);
+
+        // only changes of the implementations are part of the capabilities
+        let history: Vec<_> = capabilities
+            .history
+            .iter()
+            .map(|change| (change.version, change.added, change.entries.len()))
+            .collect();
+        assert_eq!(history, vec![(1, true, 2), (3, false, 1)]);
+        assert_eq!(capabilities.history[1].updated_by, operator);
+        assert_eq!(access.lists().history.len(), 3);
+    }
+}
@@ -0,0 +1,198 @@
+//! Pausing of sponsorship while the upstream is unavailable.
+//!
+//! While the upstream restarts, every call to it fails, so sponsoring would fail every queued
+//! request, and burn the nonce slots of the sponsor in the process. [`UpstreamAvailability`]
+//! counts consecutive calls that fail because the upstream could not be reached. Once
+//! [`AvailabilityConfig::failure_threshold`] calls failed in a row, the upstream is considered
+//! unavailable:
+//!
+//! - new requests are rejected with [`OdysseyWalletError::ServiceUnavailable`], which tells the
+//!   client when to retry,
+//! - requests that are already queued wait for the upstream to recover before they are sent,
+//!   instead of failing, and
+//! - the upstream is probed at [`AvailabilityConfig::probe_interval`] until it responds, which
+//!   resumes sponsorship.
+
+use crate::{estimation::is_transient, OdysseyWalletError};
+use metrics::{Counter, Gauge};
+use metrics_derive::Metrics;
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use tokio::sync::watch;
+
+/// The default number of consecutive failed calls after which the upstream is considered
+/// unavailable.
+pub const DEFAULT_UNAVAILABLE_THRESHOLD: usize = 5;
+
+/// The default interval at which an unavailable upstream is probed.
+pub const DEFAULT_AVAILABILITY_PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The error code of requests rejected while the upstream is unavailable.
+pub const SERVICE_UNAVAILABLE_CODE: i32 = -32021;
+
+/// Configuration of the detection of an unavailable upstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AvailabilityConfig {
+    /// The number of consecutive calls that have to fail because the upstream could not be
+    /// reached, before the upstream is considered unavailable.
+    pub failure_threshold: usize,
+    /// The interval at which the upstream is probed while it is unavailable.
+    pub probe_interval: Duration,
+}
+
+impl Default for AvailabilityConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_UNAVAILABLE_THRESHOLD,
+            probe_interval: DEFAULT_AVAILABILITY_PROBE_INTERVAL,
+        }
+    }
+}
+
+/// Tracks whether the upstream is available.
+#[derive(Debug)]
+pub(crate) struct UpstreamAvailability {
+    config: AvailabilityConfig,
+    /// The number of consecutive calls that failed because the upstream could not be reached.
+    failures: AtomicUsize,
+    /// Whether the upstream is available.
+    available: watch::Sender<bool>,
+    metrics: AvailabilityMetrics,
+}
+
+impl UpstreamAvailability {
+    /// Creates a new tracker of an upstream that is available.
+    pub(crate) fn new(config: AvailabilityConfig) -> Self {
+        let metrics = AvailabilityMetrics::default();
+        metrics.upstream_available.set(1.0);
+        Self { config, failures: AtomicUsize::new(0), available: watch::Sender::new(true), metrics }
+    }
+
+    /// Returns the interval at which the upstream is probed while it is unavailable.
+    pub(crate) const fn probe_interval(&self) -> Duration {
+        self.config.probe_interval
+    }
+
+    /// Returns an error telling the client when to retry if the upstream is unavailable.
+    pub(crate) fn check(&self) -> Result<(), OdysseyWalletError> {
+        if *self.available.borrow() {
+            return Ok(());
+        }
+        self.metrics.unavailable_rejections.increment(1);
+        Err(OdysseyWalletError::ServiceUnavailable {
+            retry_after: self.config.probe_interval.as_secs_f64().ceil().max(1.0) as u64,
+        })
+    }
+
+    /// Waits until the upstream is available.
+    pub(crate) async fn wait(&self) {
+        let mut available = self.available.subscribe();
+        // the sender is owned by the tracker, so the channel can not be closed
+        let _ = available.wait_for(|available| *available).await;
+    }
+
+    /// Records the outcome of a call to the upstream.
+    ///
+    /// Returns `true` if the call made the upstream unavailable, in which case the caller has to
+    /// probe the upstream until it recovers.
+    pub(crate) fn observe<R>(&self, result: &Result<R, OdysseyWalletError>) -> bool {
+        match result {
+            Err(err) if is_transient(err) => {
+                let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+                if failures < self.config.failure_threshold {
+                    return false;
+                }
+                let paused = self
+                    .available
+                    .send_if_modified(|available| std::mem::replace(available, false));
+                if paused {
+                    self.metrics.upstream_available.set(0.0);
+                }
+                paused
+            }
+            Err(_) => false,
+            Ok(_) => {
+                self.failures.store(0, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// Marks the upstream as available again, resuming the queued requests.
+    pub(crate) fn recovered(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+        self.available.send_replace(true);
+        self.metrics.upstream_available.set(1.0);
+    }
+}
+
+/// Metrics for the availability of the upstream.
+#[derive(Metrics)]
+#[metrics(scope = "wallet")]
+struct AvailabilityMetrics {
+    /// Whether the upstream is available
+    upstream_available: Gauge,
+    /// Number of requests rejected because the upstream was unavailable
+    unavailable_rejections: Counter,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_transport::TransportErrorKind;
+    use std::sync::Arc;
+
+    fn unreachable() -> Result<(), OdysseyWalletError> {
+        Err(OdysseyWalletError::InternalError(TransportErrorKind::backend_gone().into()))
+    }
+
+    fn availability() -> UpstreamAvailability {
+        UpstreamAvailability::new(AvailabilityConfig {
+            failure_threshold: 2,
+            probe_interval: Duration::from_millis(1_500),
+        })
+    }
+
+    #[test]
+    fn pauses_after_consecutive_failures() {
+        let availability = availability();
+        assert!(!availability.observe(&unreachable()));
+        // successful calls reset the failures, errors caused by the request are not counted
+        assert!(!availability.observe(&Ok(())));
+        assert!(!availability.observe(&unreachable()));
+        assert!(!availability.observe::<()>(&Err(OdysseyWalletError::IllegalDestination)));
+        assert!(availability.check().is_ok());
+
+        // only the call that makes the upstream unavailable starts probing
+        assert!(availability.observe(&unreachable()));
+        assert!(!availability.observe(&unreachable()));
+        assert!(matches!(
+            availability.check(),
+            Err(OdysseyWalletError::ServiceUnavailable { retry_after: 2 })
+        ));
+
+        availability.recovered();
+        assert!(availability.check().is_ok());
+        assert!(!availability.observe(&unreachable()));
+    }
+
+    #[tokio::test]
+    async fn resumes_waiting_requests() {
+        let availability = Arc::new(availability());
+        for _ in 0..2 {
+            availability.observe(&unreachable());
+        }
+
+        let waiting = tokio::spawn({
+            let availability = availability.clone();
+            async move { availability.wait().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        availability.recovered();
+        waiting.await.unwrap();
+    }
+}
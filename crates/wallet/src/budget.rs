@@ -0,0 +1,202 @@
+//! Per-block sponsorship budget.
+//!
+//! Sponsored transactions compete with organic transactions for block space. The
+//! [`SponsorBudget`] limits the total gas of sponsored transactions that are waiting for inclusion
+//! to the budget of a single block, so sponsorship can not crowd out organic transactions or burn
+//! through the sponsor's funds during spam storms. New sponsorships wait for blocks to include
+//! pending sponsored transactions, and are rejected if the budget does not free up in time.
+
+use crate::OdysseyWalletError;
+use alloy_primitives::TxHash;
+use futures::{Stream, StreamExt};
+use parking_lot::Mutex;
+use reth_chain_state::CanonStateNotification;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::Notify, time::Instant};
+
+/// The default maximum gas of sponsored transactions per block.
+pub const DEFAULT_MAX_SPONSORED_GAS_PER_BLOCK: u64 = 2_000_000;
+
+/// The default time a sponsorship waits for the budget to free up.
+pub const DEFAULT_BUDGET_WAIT: Duration = Duration::from_secs(4);
+
+/// Configuration of the [`SponsorBudget`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetConfig {
+    /// The maximum gas of sponsored transactions per block.
+    pub max_gas_per_block: u64,
+    /// The time a sponsorship waits for the budget to free up before it is rejected.
+    pub max_wait: Duration,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            max_gas_per_block: DEFAULT_MAX_SPONSORED_GAS_PER_BLOCK,
+            max_wait: DEFAULT_BUDGET_WAIT,
+        }
+    }
+}
+
+/// Tracks the gas of sponsored transactions that are waiting for inclusion.
+#[derive(Debug, Clone)]
+pub struct SponsorBudget {
+    inner: Arc<SponsorBudgetInner>,
+}
+
+#[derive(Debug)]
+struct SponsorBudgetInner {
+    config: BudgetConfig,
+    state: Mutex<BudgetState>,
+    /// Notifies waiting sponsorships when gas is released.
+    released: Notify,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    /// The gas of reserved and pending sponsored transactions.
+    pending_gas: u64,
+    /// The gas of sent sponsored transactions that are not included yet.
+    pending: HashMap<TxHash, u64>,
+}
+
+impl SponsorBudget {
+    /// Creates a new budget with the given configuration.
+    pub fn new(config: BudgetConfig) -> Self {
+        Self {
+            inner: Arc::new(SponsorBudgetInner {
+                config,
+                state: Default::default(),
+                released: Notify::new(),
+            }),
+        }
+    }
+
+    /// Returns the gas of sponsored transactions that are waiting for inclusion.
+    pub fn pending_gas(&self) -> u64 {
+        self.inner.state.lock().pending_gas
+    }
+
+    /// Reserves budget for a sponsored transaction with the given gas limit, waiting for pending
+    /// sponsored transactions to be included if the budget is exhausted.
+    pub(crate) async fn reserve(&self, gas: u64) -> Result<BudgetReservation, OdysseyWalletError> {
+        let max_gas = self.inner.config.max_gas_per_block;
+        if gas > max_gas {
+            return Err(OdysseyWalletError::BudgetExhausted);
+        }
+
+        let deadline = Instant::now() + self.inner.config.max_wait;
+        loop {
+            // register for notifications before checking, so no release is missed
+            let released = self.inner.released.notified();
+            {
+                let mut state = self.inner.state.lock();
+                if state.pending_gas + gas <= max_gas {
+                    state.pending_gas += gas;
+                    return Ok(BudgetReservation { budget: self.clone(), gas: Some(gas) });
+                }
+            }
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                return Err(OdysseyWalletError::BudgetExhausted);
+            }
+        }
+    }
+
+    /// Releases the budget of a sponsored transaction that was included or dropped.
+    pub fn release(&self, tx_hash: &TxHash) {
+        let mut state = self.inner.state.lock();
+        if let Some(gas) = state.pending.remove(tx_hash) {
+            state.pending_gas -= gas;
+            drop(state);
+            self.inner.released.notify_waiters();
+        }
+    }
+
+    /// Spawns a task that releases the budget of sponsored transactions as they are included in
+    /// canonical blocks.
+    pub fn spawn<St>(self, mut st: St)
+    where
+        St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
+    {
+        tokio::task::spawn(async move {
+            while let Some(notification) = st.next().await {
+                for block in notification.committed().blocks_iter() {
+                    for tx in &block.body.transactions {
+                        self.release(&tx.hash());
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Budget reserved for a sponsored transaction.
+///
+/// The budget is released when the reservation is dropped, unless the transaction was sent.
+#[derive(Debug)]
+pub(crate) struct BudgetReservation {
+    budget: SponsorBudget,
+    gas: Option<u64>,
+}
+
+impl BudgetReservation {
+    /// Keeps the budget reserved until the sent transaction is released.
+    pub(crate) fn sent(mut self, tx_hash: TxHash) {
+        if let Some(gas) = self.gas.take() {
+            self.budget.inner.state.lock().pending.insert(tx_hash, gas);
+        }
+    }
+}
+
+impl Drop for BudgetReservation {
+    fn drop(&mut self) {
+        if let Some(gas) = self.gas.take() {
+            self.budget.inner.state.lock().pending_gas -= gas;
+            self.budget.inner.released.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget() -> SponsorBudget {
+        SponsorBudget::new(BudgetConfig {
+            max_gas_per_block: 100_000,
+            max_wait: Duration::from_millis(50),
+        })
+    }
+
+    #[tokio::test]
+    async fn rejects_when_exhausted() {
+        let budget = budget();
+        budget.reserve(60_000).await.unwrap().sent(TxHash::with_last_byte(1));
+        assert_eq!(budget.pending_gas(), 60_000);
+
+        assert!(matches!(budget.reserve(50_000).await, Err(OdysseyWalletError::BudgetExhausted)));
+        assert!(matches!(budget.reserve(200_000).await, Err(OdysseyWalletError::BudgetExhausted)));
+
+        // unsent reservations are released
+        drop(budget.reserve(40_000).await.unwrap());
+        assert_eq!(budget.pending_gas(), 60_000);
+    }
+
+    #[tokio::test]
+    async fn waits_for_inclusion() {
+        let budget = budget();
+        let tx_hash = TxHash::with_last_byte(1);
+        budget.reserve(60_000).await.unwrap().sent(tx_hash);
+
+        let waiting = tokio::spawn({
+            let budget = budget.clone();
+            async move {
+                budget.reserve(50_000).await.map(|r| r.sent(TxHash::with_last_byte(2))).is_ok()
+            }
+        });
+        tokio::task::yield_now().await;
+        budget.release(&tx_hash);
+        assert!(waiting.await.unwrap());
+        assert_eq!(budget.pending_gas(), 50_000);
+    }
+}
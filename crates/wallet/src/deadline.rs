@@ -0,0 +1,93 @@
+//! Deadlines of sponsorship requests.
+//!
+//! Clients can pass a `validUntil` unix timestamp in seconds to `odyssey_sendTransaction`. A
+//! request whose deadline passes while it waits in the
+//! [`TransactionQueue`](crate::TransactionQueue), for budget or for its nonce slot is dropped
+//! before it is signed, so that clients can retry without the stale request being sponsored as
+//! well.
+//!
+//! Transactions that were sent before their deadline are rebroadcast until they are included or
+//! dropped from the pool, since later sponsored transactions depend on their nonce.
+
+use crate::OdysseyWalletError;
+use odyssey_common::Clock;
+use std::{future::Future, time::Duration};
+use tokio::time::Instant;
+
+/// The instant until which a request may be sent, if any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Deadline {
+    /// The unix timestamp in seconds of the deadline, and the instant it passes.
+    at: Option<(u64, Instant)>,
+}
+
+impl Deadline {
+    /// Creates the deadline of a request that is valid until the given unix timestamp in seconds.
+    ///
+    /// Fails if the deadline already passed according to the clock.
+    pub(crate) fn new(
+        clock: &dyn Clock,
+        valid_until: Option<u64>,
+    ) -> Result<Self, OdysseyWalletError> {
+        let Some(valid_until) = valid_until else { return Ok(Self::default()) };
+        let remaining = valid_until.saturating_mul(1000).saturating_sub(clock.unix_epoch_ms());
+        if remaining == 0 {
+            return Err(OdysseyWalletError::RequestExpired(valid_until));
+        }
+        Ok(Self { at: Some((valid_until, Instant::now() + Duration::from_millis(remaining))) })
+    }
+
+    /// Fails if the deadline passed.
+    pub(crate) fn check(&self) -> Result<(), OdysseyWalletError> {
+        match self.at {
+            Some((valid_until, at)) if at <= Instant::now() => {
+                Err(OdysseyWalletError::RequestExpired(valid_until))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Waits for the future, failing if the deadline passes first.
+    ///
+    /// The future is dropped if the deadline passes, which releases the queue position or nonce
+    /// slot it waits for.
+    pub(crate) async fn run<F: Future>(&self, fut: F) -> Result<F::Output, OdysseyWalletError> {
+        match self.at {
+            Some((valid_until, at)) => tokio::time::timeout_at(at, fut)
+                .await
+                .map_err(|_| OdysseyWalletError::RequestExpired(valid_until)),
+            None => Ok(fut.await),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odyssey_common::MockClock;
+
+    #[tokio::test]
+    async fn expires_requests() {
+        let clock = MockClock::new(999_950);
+
+        // requests without a deadline never expire
+        let deadline = Deadline::new(&clock, None).unwrap();
+        assert_eq!(deadline.run(std::future::ready(1)).await.unwrap(), 1);
+
+        // the deadline passes 50ms from now
+        let deadline = Deadline::new(&clock, Some(1_000)).unwrap();
+        assert!(deadline.check().is_ok());
+        assert!(matches!(
+            deadline.run(std::future::pending::<()>()).await,
+            Err(OdysseyWalletError::RequestExpired(1_000))
+        ));
+        assert!(deadline.check().is_err());
+
+        // requests that arrive after their deadline are rejected
+        clock.advance(Duration::from_millis(50));
+        assert!(matches!(
+            Deadline::new(&clock, Some(1_000)),
+            Err(OdysseyWalletError::RequestExpired(1_000))
+        ));
+    }
+}
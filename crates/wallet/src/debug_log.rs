@@ -0,0 +1,281 @@
+//! Logging of raw RPC requests and responses for debugging.
+//!
+//! Some failures, such as transaction requests with both `yParity` and `v` in an authorization,
+//! depend on the exact encoding of the request, which is lost once it is deserialized. The
+//! [`RpcDebugLogLayer`] logs the parameters and the response of calls to the enabled namespaces
+//! byte for byte, so these failures can be diagnosed from the logs of the server.
+//!
+//! The values of sensitive fields, such as signatures, are replaced with `"[redacted]"`, leaving
+//! the rest of the payload untouched. Only one of every [`RpcDebugLogConfig::sample_every`] calls
+//! is logged, to keep the volume of the logs manageable on busy servers.
+
+use futures::future::{BoxFuture, FutureExt};
+use jsonrpsee::{
+    server::{middleware::rpc::RpcServiceT, MethodResponse},
+    types::Request,
+};
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tower::Layer;
+use tracing::info;
+
+/// The fields whose values are redacted by default.
+pub const DEFAULT_REDACTED_FIELDS: &[&str] =
+    &["signature", "privateKey", "secretKey", "secret", "password", "apiKey"];
+
+/// The value redacted fields are replaced with.
+const REDACTED: &str = "\"[redacted]\"";
+
+/// Configuration of the logging of raw RPC requests and responses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcDebugLogConfig {
+    /// The namespaces whose calls are logged, e.g. `wallet`. Nothing is logged if this is empty.
+    pub namespaces: Vec<String>,
+    /// The fields whose values are redacted, compared case-insensitively.
+    pub redacted_fields: Vec<String>,
+    /// Only one of every `sample_every` calls to the enabled namespaces is logged.
+    pub sample_every: u64,
+}
+
+impl RpcDebugLogConfig {
+    /// Returns whether calls to the method are logged.
+    fn logs(&self, method: &str) -> bool {
+        self.namespaces.iter().any(|namespace| {
+            method.strip_prefix(namespace.as_str()).is_some_and(|rest| rest.starts_with('_'))
+        })
+    }
+}
+
+impl Default for RpcDebugLogConfig {
+    fn default() -> Self {
+        Self {
+            namespaces: Vec::new(),
+            redacted_fields: DEFAULT_REDACTED_FIELDS
+                .iter()
+                .map(|field| field.to_string())
+                .collect(),
+            sample_every: 1,
+        }
+    }
+}
+
+/// RPC middleware that logs the raw parameters and responses of calls to the enabled namespaces.
+#[derive(Debug, Clone)]
+pub struct RpcDebugLogLayer {
+    config: Arc<RpcDebugLogConfig>,
+    calls: Arc<AtomicU64>,
+}
+
+impl RpcDebugLogLayer {
+    /// Creates a new middleware with the given configuration.
+    pub fn new(config: RpcDebugLogConfig) -> Self {
+        Self { config: Arc::new(config), calls: Default::default() }
+    }
+}
+
+impl<S> Layer<S> for RpcDebugLogLayer {
+    type Service = RpcDebugLog<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        RpcDebugLog { service, config: self.config.clone(), calls: self.calls.clone() }
+    }
+}
+
+/// The service created by [`RpcDebugLogLayer`].
+#[derive(Debug, Clone)]
+pub struct RpcDebugLog<S> {
+    service: S,
+    config: Arc<RpcDebugLogConfig>,
+    calls: Arc<AtomicU64>,
+}
+
+impl<'a, S> RpcServiceT<'a> for RpcDebugLog<S>
+where
+    S: RpcServiceT<'a> + Send + Sync + 'a,
+    S::Future: Send,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        if !self.config.logs(request.method_name())
+            || self.calls.fetch_add(1, Ordering::Relaxed) % self.config.sample_every.max(1) != 0
+        {
+            return self.service.call(request).boxed();
+        }
+
+        let method = request.method_name().to_string();
+        let id = request.id.to_string();
+        let params = request
+            .params()
+            .as_str()
+            .map(|params| redact(params, &self.config.redacted_fields).into_owned())
+            .unwrap_or_default();
+        let config = self.config.clone();
+        let started_at = Instant::now();
+        let response = self.service.call(request);
+        async move {
+            let response = response.await;
+            info!(
+                target: "rpc::debug",
+                %method,
+                %id,
+                %params,
+                response = %redact(response.as_result(), &config.redacted_fields),
+                elapsed = ?started_at.elapsed(),
+                "RPC call"
+            );
+            response
+        }
+        .boxed()
+    }
+}
+
+/// Replaces the values of the given fields in the JSON with `"[redacted]"`, leaving the rest of
+/// it byte for byte intact, including duplicate fields and whitespace.
+///
+/// Malformed JSON is redacted as far as it can be scanned.
+pub fn redact<'a>(json: &'a str, fields: &[String]) -> Cow<'a, str> {
+    let bytes = json.as_bytes();
+    let mut redacted = String::new();
+    // the end of the part of the JSON that was copied to `redacted`
+    let mut copied = 0;
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if bytes[pos] != b'"' {
+            pos += 1;
+            continue;
+        }
+        let end = string_end(bytes, pos);
+        let name = json.get(pos + 1..end - 1).unwrap_or_default();
+        let colon = skip_whitespace(bytes, end);
+        pos = end;
+        if bytes.get(colon) != Some(&b':')
+            || !fields.iter().any(|field| field.eq_ignore_ascii_case(name))
+        {
+            continue;
+        }
+        let value = skip_whitespace(bytes, colon + 1);
+        let value_end = value_end(bytes, value);
+        redacted.push_str(&json[copied..value]);
+        redacted.push_str(REDACTED);
+        copied = value_end;
+        pos = value_end;
+    }
+    if copied == 0 {
+        return Cow::Borrowed(json);
+    }
+    redacted.push_str(&json[copied..]);
+    Cow::Owned(redacted)
+}
+
+/// Returns the position after the string starting at `start`, or the end of the JSON if the
+/// string is not terminated.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut pos = start + 1;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'"' => return pos + 1,
+            _ => pos += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Returns the position of the first byte at or after `pos` that is not whitespace.
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Returns the position after the value starting at `start`.
+fn value_end(bytes: &[u8], start: usize) -> usize {
+    match bytes.get(start) {
+        Some(b'"') => string_end(bytes, start),
+        Some(b'{' | b'[') => {
+            let mut depth = 0usize;
+            let mut pos = start;
+            while pos < bytes.len() {
+                match bytes[pos] {
+                    b'"' => {
+                        pos = string_end(bytes, pos);
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return pos + 1;
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+            bytes.len()
+        }
+        _ => {
+            let mut pos = start;
+            while pos < bytes.len()
+                && !matches!(bytes[pos], b',' | b'}' | b']')
+                && !bytes[pos].is_ascii_whitespace()
+            {
+                pos += 1;
+            }
+            pos
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redact_default(json: &str) -> Cow<'_, str> {
+        redact(json, &RpcDebugLogConfig::default().redacted_fields)
+    }
+
+    #[test]
+    fn redacts_sensitive_fields() {
+        // duplicate fields and whitespace are kept
+        let json = r#"[{"yParity":"0x1", "yParity" : "0x0","v":"0x1b"}]"#;
+        assert!(matches!(redact_default(json), Cow::Borrowed(_)));
+        assert_eq!(redact_default(json), json);
+
+        assert_eq!(
+            redact_default(r#"["0x01", "0xabcd"]"#),
+            r#"["0x01", "0xabcd"]"#,
+            "values are not field names"
+        );
+        assert_eq!(
+            redact_default(r#"{"digest":"0x01","Signature" : "0x\"ab","nonce":1}"#),
+            r#"{"digest":"0x01","Signature" : "[redacted]","nonce":1}"#
+        );
+        assert_eq!(
+            redact_default(r#"{"apiKey":{"a":[1,"}"]},"password":12,"secret":null}"#),
+            r#"{"apiKey":"[redacted]","password":"[redacted]","secret":"[redacted]"}"#
+        );
+
+        // malformed JSON
+        assert_eq!(redact_default(r#"{"signature":"0x"#), r#"{"signature":"[redacted]"#);
+        assert_eq!(redact_default(r#"{"signature""#), r#"{"signature""#);
+    }
+
+    #[test]
+    fn logs_enabled_namespaces() {
+        let config =
+            RpcDebugLogConfig { namespaces: vec!["wallet".to_string()], ..Default::default() };
+        assert!(config.logs("wallet_sendTransaction"));
+        assert!(!config.logs("walletx_sendTransaction"));
+        assert!(!config.logs("odyssey_sendTransaction"));
+        assert!(!RpcDebugLogConfig::default().logs("wallet_sendTransaction"));
+    }
+}
@@ -0,0 +1,267 @@
+//! Cache of the delegations of sponsored destinations.
+//!
+//! Every sponsored request reads the code of its destination to check that it delegates to an
+//! implementation, and reads it again to look up the implementation its usage is recorded for.
+//! The [`DelegationCache`] keeps the implementation every destination delegates to, along with the
+//! hash of its code, so the code is read once for as long as the delegation does not change.
+//!
+//! An entry is invalidated
+//!
+//! - once a canonical block changes the code of the account, or a reorg reverts a block that
+//!   touched the account, see [`DelegationCache::spawn`],
+//! - once a sponsored transaction that was sponsored for the account is included, and
+//! - once it is older than [`DelegationCacheConfig::max_age`].
+//!
+//! A cached delegation is only as fresh as the canonical state notifications it follows, so the
+//! cache is only used by the node. The relay has no notifications, and a destination that
+//! redelegated elsewhere would be sponsored against its stale delegation, so it reads the code of
+//! the destination for every request.
+//!
+//! Once the cache is full, expired entries and the oldest entries are evicted in a batch, so the
+//! cost of an eviction is spread across the insertions that fill the freed space.
+
+use alloy_primitives::{keccak256, Address, B256};
+use futures::{Stream, StreamExt};
+use metrics::Counter;
+use metrics_derive::Metrics;
+use odyssey_common::{parse_delegation_designator, Clock};
+use parking_lot::RwLock;
+use reth_chain_state::CanonStateNotification;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The default maximum number of cached delegations.
+pub const DEFAULT_DELEGATION_CACHE_SIZE: usize = 65_536;
+
+/// The fraction of the maximum number of entries that is freed when the cache is full.
+const EVICTION_BATCH_DIVISOR: usize = 8;
+
+/// The default time a delegation is cached for.
+///
+/// Delegations are invalidated as soon as their code changes if the cache follows canonical state
+/// notifications, so the age mainly bounds the time unused entries take up space.
+pub const DEFAULT_DELEGATION_CACHE_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// Configuration of the [`DelegationCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DelegationCacheConfig {
+    /// The maximum number of cached delegations. The oldest delegations are evicted once the cache
+    /// is full.
+    pub max_entries: usize,
+    /// The time a delegation is cached for.
+    pub max_age: Duration,
+}
+
+impl Default for DelegationCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: DEFAULT_DELEGATION_CACHE_SIZE,
+            max_age: DEFAULT_DELEGATION_CACHE_MAX_AGE,
+        }
+    }
+}
+
+/// The delegation of an account, as read from its code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedDelegation {
+    /// The address the account delegates to, if its code is a delegation designator.
+    ///
+    /// The zero address is a cleared delegation.
+    pub implementation: Option<Address>,
+    /// The hash of the code of the account.
+    pub code_hash: B256,
+}
+
+impl CachedDelegation {
+    /// Returns the delegation of an account with the given code.
+    pub fn new(code: &[u8]) -> Self {
+        Self { implementation: parse_delegation_designator(code), code_hash: keccak256(code) }
+    }
+}
+
+/// Caches the delegations of accounts until their code changes.
+#[derive(Debug, Clone)]
+pub struct DelegationCache {
+    inner: Arc<DelegationCacheInner>,
+}
+
+#[derive(Debug)]
+struct DelegationCacheInner {
+    config: DelegationCacheConfig,
+    clock: Arc<dyn Clock>,
+    /// The cached delegations, and the time they were read.
+    entries: RwLock<HashMap<Address, (CachedDelegation, Instant)>>,
+    metrics: DelegationCacheMetrics,
+}
+
+impl DelegationCache {
+    /// Creates a new cache that reads the time from the given [`Clock`].
+    pub fn new(config: DelegationCacheConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            inner: Arc::new(DelegationCacheInner {
+                config,
+                clock,
+                entries: Default::default(),
+                metrics: Default::default(),
+            }),
+        }
+    }
+
+    /// Returns the cached delegation of the account, if it is cached and not expired.
+    pub fn get(&self, account: &Address) -> Option<CachedDelegation> {
+        let now = self.inner.clock.now();
+        let cached = self.inner.entries.read().get(account).copied().and_then(|(cached, at)| {
+            (now.saturating_duration_since(at) <= self.inner.config.max_age).then_some(cached)
+        });
+        match cached {
+            Some(_) => self.inner.metrics.delegation_cache_hits.increment(1),
+            None => self.inner.metrics.delegation_cache_misses.increment(1),
+        }
+        cached
+    }
+
+    /// Caches the delegation of the account.
+    pub fn insert(&self, account: Address, delegation: CachedDelegation) {
+        let now = self.inner.clock.now();
+        let max_entries = self.inner.config.max_entries;
+        if max_entries == 0 {
+            return;
+        }
+        let mut entries = self.inner.entries.write();
+        if entries.len() >= max_entries && !entries.contains_key(&account) {
+            self.evict(&mut entries, now);
+        }
+        entries.insert(account, (delegation, now));
+    }
+
+    /// Evicts the expired entries, and the oldest entries until a batch of entries is free.
+    fn evict(&self, entries: &mut HashMap<Address, (CachedDelegation, Instant)>, now: Instant) {
+        let max_age = self.inner.config.max_age;
+        entries.retain(|_, (_, at)| now.saturating_duration_since(*at) <= max_age);
+
+        let max_entries = self.inner.config.max_entries;
+        let keep = max_entries - max_entries.div_ceil(EVICTION_BATCH_DIVISOR);
+        if entries.len() > keep {
+            let mut times: Vec<_> = entries.values().map(|(_, at)| *at).collect();
+            let (_, &mut cutoff, _) = times.select_nth_unstable(entries.len() - keep - 1);
+            entries.retain(|_, (_, at)| *at > cutoff);
+        }
+    }
+
+    /// Removes the cached delegation of the account.
+    pub fn invalidate(&self, account: &Address) {
+        self.inner.entries.write().remove(account);
+    }
+
+    /// Removes the cached delegation of the account if its code changed.
+    fn invalidate_changed(&self, account: &Address, code_hash: Option<B256>) {
+        let mut entries = self.inner.entries.write();
+        if entries.get(account).is_some_and(|(cached, _)| Some(cached.code_hash) != code_hash) {
+            entries.remove(account);
+        }
+    }
+
+    /// Spawns a task that invalidates the cached delegations of accounts whose code is changed by
+    /// canonical blocks, and of accounts touched by reverted blocks.
+    pub fn spawn<St>(self, mut st: St)
+    where
+        St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
+    {
+        tokio::task::spawn(async move {
+            while let Some(notification) = st.next().await {
+                if let Some(reverted) = notification.reverted() {
+                    for account in reverted.execution_outcome().bundle.state.keys() {
+                        self.invalidate(account);
+                    }
+                }
+                for (account, state) in &notification.committed().execution_outcome().bundle.state {
+                    self.invalidate_changed(
+                        account,
+                        state.info.as_ref().map(|info| info.code_hash),
+                    );
+                }
+            }
+        });
+    }
+}
+
+/// Metrics for the delegation cache.
+#[derive(Metrics)]
+#[metrics(scope = "wallet")]
+struct DelegationCacheMetrics {
+    /// Number of delegations read from the cache
+    delegation_cache_hits: Counter,
+    /// Number of delegations read from the upstream because they were not cached
+    delegation_cache_misses: Counter,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odyssey_common::{delegation_designator, MockClock};
+
+    fn cache(clock: Arc<MockClock>, max_entries: usize) -> DelegationCache {
+        DelegationCache::new(
+            DelegationCacheConfig { max_entries, max_age: Duration::from_secs(2) },
+            clock,
+        )
+    }
+
+    #[test]
+    fn caches_delegations() {
+        let clock = Arc::new(MockClock::default());
+        let cache = cache(clock.clone(), 1);
+        let (account, implementation) = (Address::with_last_byte(1), Address::with_last_byte(0xff));
+        let delegation = CachedDelegation::new(&delegation_designator(implementation));
+        assert_eq!(delegation.implementation, Some(implementation));
+        assert_eq!(CachedDelegation::new(&[]).implementation, None);
+
+        cache.insert(account, delegation);
+        assert_eq!(cache.get(&account), Some(delegation));
+
+        // the entry expires
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(cache.get(&account), None);
+    }
+
+    #[test]
+    fn evicts_oldest_entries() {
+        let clock = Arc::new(MockClock::default());
+        let cache = cache(clock.clone(), 16);
+        let delegation =
+            CachedDelegation::new(&delegation_designator(Address::with_last_byte(0xff)));
+        for i in 0..16 {
+            cache.insert(Address::with_last_byte(i), delegation);
+            clock.advance(Duration::from_millis(10));
+        }
+
+        // the two oldest entries are evicted to make room for the next entries
+        cache.insert(Address::with_last_byte(16), delegation);
+        assert_eq!(cache.inner.entries.read().len(), 15);
+        assert_eq!(cache.get(&Address::with_last_byte(0)), None);
+        assert_eq!(cache.get(&Address::with_last_byte(1)), None);
+        assert_eq!(cache.get(&Address::with_last_byte(2)), Some(delegation));
+        assert_eq!(cache.get(&Address::with_last_byte(16)), Some(delegation));
+
+        cache.insert(Address::with_last_byte(17), delegation);
+        assert_eq!(cache.inner.entries.read().len(), 16);
+    }
+
+    #[test]
+    fn invalidates_changed_code() {
+        let cache = cache(Arc::new(MockClock::default()), 10);
+        let account = Address::with_last_byte(1);
+        let delegation = CachedDelegation::new(&delegation_designator(Address::with_last_byte(2)));
+
+        cache.insert(account, delegation);
+        cache.invalidate_changed(&account, Some(delegation.code_hash));
+        assert_eq!(cache.get(&account), Some(delegation));
+
+        // redelegated
+        cache.invalidate_changed(&account, Some(B256::with_last_byte(1)));
+        assert_eq!(cache.get(&account), None);
+    }
+}
@@ -0,0 +1,135 @@
+//! Reverse index of the delegations of sponsored accounts.
+//!
+//! Once a sponsored transaction is included, the current delegation of every authority it was
+//! sponsored for is read from the chain and recorded in the [`DelegationIndex`]. This answers
+//! which accounts sponsored by this service delegate to an implementation, without scanning the
+//! chain. Since accounts can redelegate without the service, `wallet_getDelegators` confirms every
+//! indexed delegation against the chain before returning it.
+
+use alloy_primitives::Address;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+/// The default number of delegators returned per page.
+pub const DEFAULT_DELEGATORS_PAGE_SIZE: usize = 100;
+
+/// The maximum number of delegators returned per page.
+pub const MAX_DELEGATORS_PAGE_SIZE: usize = 1_000;
+
+/// A page of accounts delegating to an implementation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delegators {
+    /// The delegating accounts, in ascending order.
+    pub delegators: Vec<Address>,
+    /// The cursor of the next page, if there are more delegators.
+    pub next_cursor: Option<Address>,
+}
+
+/// Indexes the delegations of sponsored accounts by implementation.
+#[derive(Debug, Clone, Default)]
+pub struct DelegationIndex {
+    inner: Arc<RwLock<DelegationIndexInner>>,
+}
+
+#[derive(Debug, Default)]
+struct DelegationIndexInner {
+    /// The implementation every indexed account delegates to.
+    implementations: HashMap<Address, Address>,
+    /// The accounts delegating to an implementation, ordered for pagination.
+    delegators: HashMap<Address, BTreeSet<Address>>,
+}
+
+impl DelegationIndex {
+    /// Records the current delegation of an account, or removes the account if it does not
+    /// delegate.
+    pub fn update(&self, authority: Address, implementation: Option<Address>) {
+        let implementation = implementation.filter(|implementation| !implementation.is_zero());
+        let mut inner = self.inner.write();
+
+        let previous = match implementation {
+            Some(implementation) => inner.implementations.insert(authority, implementation),
+            None => inner.implementations.remove(&authority),
+        };
+        if previous == implementation {
+            return;
+        }
+        if let Some(previous) = previous {
+            if let Some(delegators) = inner.delegators.get_mut(&previous) {
+                delegators.remove(&authority);
+                if delegators.is_empty() {
+                    inner.delegators.remove(&previous);
+                }
+            }
+        }
+        if let Some(implementation) = implementation {
+            inner.delegators.entry(implementation).or_default().insert(authority);
+        }
+    }
+
+    /// Returns the implementation the account delegates to, if it is indexed.
+    pub fn implementation(&self, authority: &Address) -> Option<Address> {
+        self.inner.read().implementations.get(authority).copied()
+    }
+
+    /// Returns up to `limit` indexed accounts delegating to the implementation, in ascending
+    /// order, starting after the cursor.
+    pub fn page(
+        &self,
+        implementation: &Address,
+        cursor: Option<Address>,
+        limit: usize,
+    ) -> Vec<Address> {
+        let inner = self.inner.read();
+        let Some(delegators) = inner.delegators.get(implementation) else { return Vec::new() };
+        match cursor {
+            Some(cursor) => delegators
+                .range((std::ops::Bound::Excluded(cursor), std::ops::Bound::Unbounded))
+                .take(limit)
+                .copied()
+                .collect(),
+            None => delegators.iter().take(limit).copied().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_delegators() {
+        let index = DelegationIndex::default();
+        let implementation = Address::with_last_byte(0xff);
+        for byte in 1..=5 {
+            index.update(Address::with_last_byte(byte), Some(implementation));
+        }
+
+        let first = index.page(&implementation, None, 2);
+        assert_eq!(first, vec![Address::with_last_byte(1), Address::with_last_byte(2)]);
+        let rest = index.page(&implementation, first.last().copied(), 10);
+        assert_eq!(rest.len(), 3);
+        assert_eq!(rest[0], Address::with_last_byte(3));
+    }
+
+    #[test]
+    fn moves_redelegated_accounts() {
+        let index = DelegationIndex::default();
+        let (a, b) = (Address::with_last_byte(0xa), Address::with_last_byte(0xb));
+        let authority = Address::with_last_byte(1);
+
+        index.update(authority, Some(a));
+        index.update(authority, Some(b));
+        assert!(index.page(&a, None, 10).is_empty());
+        assert_eq!(index.page(&b, None, 10), vec![authority]);
+
+        // cleared delegations are removed
+        index.update(authority, Some(Address::ZERO));
+        assert!(index.page(&b, None, 10).is_empty());
+        assert_eq!(index.implementation(&authority), None);
+    }
+}
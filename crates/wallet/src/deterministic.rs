@@ -0,0 +1,129 @@
+//! A deterministic sponsor for tests.
+//!
+//! Fee estimates and the nonce of the sponsor usually depend on the state of the chain and the
+//! mempool, so the exact bytes of sponsored transactions can not be asserted on. In deterministic
+//! mode, the wallet sponsors transactions with fixed fees, assigns nonces from a local counter,
+//! and reads the time for quote and prepared call expiries from a frozen clock. Since signatures
+//! are deterministic ([RFC 6979]), sponsoring the same requests in the same order produces the
+//! same transactions and hashes.
+//!
+//! This is only intended for tests and devnets.
+//!
+//! [RFC 6979]: https://datatracker.ietf.org/doc/html/rfc6979
+
+use crate::{OdysseyWalletError, Upstream};
+use alloy_primitives::TxHash;
+use alloy_provider::utils::Eip1559Estimation;
+use alloy_rpc_types::TransactionRequest;
+use odyssey_common::{Clock, MockClock};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// The default max fee per gas of the deterministic sponsor, 2 gwei.
+pub const DEFAULT_DETERMINISTIC_MAX_FEE_PER_GAS: u128 = 2_000_000_000;
+
+/// The default max priority fee per gas of the deterministic sponsor, 1 gwei.
+pub const DEFAULT_DETERMINISTIC_MAX_PRIORITY_FEE_PER_GAS: u128 = 1_000_000_000;
+
+/// Configuration of the deterministic mode of the wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeterministicConfig {
+    /// The max fee per gas of every sponsored transaction.
+    pub max_fee_per_gas: u128,
+    /// The max priority fee per gas of every sponsored transaction.
+    pub max_priority_fee_per_gas: u128,
+    /// The nonce of the first sponsored transaction.
+    pub nonce: u64,
+    /// The unix timestamp in milliseconds the clock of the wallet is frozen at.
+    pub timestamp_ms: u64,
+}
+
+impl Default for DeterministicConfig {
+    fn default() -> Self {
+        Self {
+            max_fee_per_gas: DEFAULT_DETERMINISTIC_MAX_FEE_PER_GAS,
+            max_priority_fee_per_gas: DEFAULT_DETERMINISTIC_MAX_PRIORITY_FEE_PER_GAS,
+            nonce: 0,
+            timestamp_ms: 0,
+        }
+    }
+}
+
+impl DeterministicConfig {
+    /// Returns a clock frozen at the configured timestamp.
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        Arc::new(MockClock::new(self.timestamp_ms))
+    }
+}
+
+/// Sponsors transactions with fixed fees and nonces from a local counter.
+#[derive(Debug)]
+pub(crate) struct DeterministicSponsor {
+    fees: Eip1559Estimation,
+    /// The nonce of the next sponsored transaction.
+    nonce: Mutex<u64>,
+}
+
+impl DeterministicSponsor {
+    /// Creates a new sponsor with the given configuration.
+    pub(crate) fn new(config: DeterministicConfig) -> Self {
+        Self {
+            fees: Eip1559Estimation {
+                max_fee_per_gas: config.max_fee_per_gas,
+                max_priority_fee_per_gas: config.max_priority_fee_per_gas,
+            },
+            nonce: Mutex::new(config.nonce),
+        }
+    }
+
+    /// Returns the fixed fees of sponsored transactions.
+    pub(crate) const fn fees(&self) -> Eip1559Estimation {
+        self.fees
+    }
+
+    /// Signs the transaction with the next nonce and sends it to the upstream.
+    ///
+    /// The nonce is only used up if the transaction was sent.
+    pub(crate) async fn sign_and_send<T: Upstream + Sync>(
+        &self,
+        upstream: &T,
+        mut tx: TransactionRequest,
+    ) -> Result<TxHash, OdysseyWalletError> {
+        let mut nonce = self.nonce.lock().await;
+        tx.nonce = Some(*nonce);
+        let tx_hash = upstream.sign_and_send(tx).await?;
+        *nonce += 1;
+        Ok(tx_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockUpstream;
+    use alloy_primitives::Address;
+
+    #[tokio::test]
+    async fn assigns_fixed_fees_and_nonces() {
+        let sponsor =
+            DeterministicSponsor::new(DeterministicConfig { nonce: 5, ..Default::default() });
+        assert_eq!(sponsor.fees().max_fee_per_gas, DEFAULT_DETERMINISTIC_MAX_FEE_PER_GAS);
+
+        let upstream = MockUpstream::new();
+        let tx = TransactionRequest::default().to(Address::ZERO);
+        sponsor.sign_and_send(&upstream, tx.clone()).await.unwrap();
+
+        // failed transactions do not use up the nonce
+        upstream.fail_next_send(OdysseyWalletError::IllegalDestination);
+        assert!(sponsor.sign_and_send(&upstream, tx.clone()).await.is_err());
+        sponsor.sign_and_send(&upstream, tx).await.unwrap();
+        let nonces: Vec<_> = upstream.sent().iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, [Some(5), Some(6)]);
+    }
+
+    #[test]
+    fn frozen_clock() {
+        let clock = DeterministicConfig { timestamp_ms: 1_000, ..Default::default() }.clock();
+        assert_eq!(clock.unix_epoch_ms(), 1_000);
+    }
+}
@@ -0,0 +1,123 @@
+//! Dry-run mode for staging environments.
+//!
+//! In dry-run mode, requests are validated, estimated and queued as usual, but sponsored
+//! transactions are signed and discarded instead of sent. The hash of the signed transaction is
+//! returned to the caller, and the transaction is recorded in a [`DryRunLog`], so integrations
+//! can be tested without a funded sponsor.
+
+use crate::{OdysseyWalletError, Upstream};
+use alloy_consensus::{SignableTransaction, TxEnvelope, TypedTransaction};
+use alloy_network::eip2718::Encodable2718;
+use alloy_primitives::{Address, Bytes, TxHash};
+use alloy_rpc_types::TransactionRequest;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, sync::Arc};
+
+/// The default number of dry-run transactions that are recorded.
+pub const DEFAULT_DRY_RUN_HISTORY: usize = 1_000;
+
+/// A sponsored transaction that was signed but not sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunTransaction {
+    /// The hash of the transaction.
+    pub transaction_hash: TxHash,
+    /// The account the transaction was sponsored for, if any.
+    pub account: Option<Address>,
+    /// The signed transaction, encoded as it would have been sent.
+    pub raw: Bytes,
+}
+
+/// Records the most recent dry-run transactions.
+#[derive(Debug, Clone)]
+pub struct DryRunLog {
+    inner: Arc<Mutex<VecDeque<DryRunTransaction>>>,
+    capacity: usize,
+}
+
+impl Default for DryRunLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_DRY_RUN_HISTORY)
+    }
+}
+
+impl DryRunLog {
+    /// Creates a new log that keeps the given number of most recent transactions.
+    pub fn new(capacity: usize) -> Self {
+        Self { inner: Default::default(), capacity }
+    }
+
+    /// Records a transaction, evicting the oldest transaction if the log is full.
+    pub fn record(&self, tx: DryRunTransaction) {
+        let mut inner = self.inner.lock();
+        if inner.len() >= self.capacity {
+            inner.pop_front();
+        }
+        inner.push_back(tx);
+    }
+
+    /// Returns the recorded transactions, oldest first.
+    pub fn transactions(&self) -> Vec<DryRunTransaction> {
+        self.inner.lock().iter().cloned().collect()
+    }
+}
+
+/// Signs the transaction request with the sponsor's key without sending it.
+///
+/// The nonce is zero unless it is set on the request.
+pub(crate) async fn sign_and_discard<T: Upstream + Sync>(
+    upstream: &T,
+    mut tx: TransactionRequest,
+    account: Option<Address>,
+) -> Result<DryRunTransaction, OdysseyWalletError> {
+    tx.nonce.get_or_insert(0);
+    let tx = tx.build_typed_tx().map_err(|_| OdysseyWalletError::InvalidTransactionRequest)?;
+    let envelope: TxEnvelope = match tx {
+        TypedTransaction::Legacy(tx) => {
+            let signature = upstream.sign_hash(&tx.signature_hash()).await?;
+            tx.into_signed(signature).into()
+        }
+        TypedTransaction::Eip2930(tx) => {
+            let signature = upstream.sign_hash(&tx.signature_hash()).await?;
+            tx.into_signed(signature).into()
+        }
+        TypedTransaction::Eip1559(tx) => {
+            let signature = upstream.sign_hash(&tx.signature_hash()).await?;
+            tx.into_signed(signature).into()
+        }
+        TypedTransaction::Eip7702(tx) => {
+            let signature = upstream.sign_hash(&tx.signature_hash()).await?;
+            tx.into_signed(signature).into()
+        }
+        _ => return Err(OdysseyWalletError::InvalidTransactionRequest),
+    };
+
+    Ok(DryRunTransaction {
+        transaction_hash: *envelope.tx_hash(),
+        account,
+        raw: envelope.encoded_2718().into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(byte: u8) -> DryRunTransaction {
+        DryRunTransaction {
+            transaction_hash: TxHash::with_last_byte(byte),
+            account: None,
+            raw: Bytes::new(),
+        }
+    }
+
+    #[test]
+    fn keeps_most_recent_transactions() {
+        let log = DryRunLog::new(2);
+        log.record(tx(1));
+        log.record(tx(2));
+        log.record(tx(3));
+        assert_eq!(log.transactions(), vec![tx(2), tx(3)]);
+    }
+}
@@ -0,0 +1,272 @@
+//! Fallbacks for the estimation of sponsored transactions while the upstream is unreachable.
+//!
+//! Without a fallback, a request is rejected as soon as the gas or fee estimation fails. With
+//! [`EstimationFallbackConfig`], estimations that fail because the upstream could not be reached
+//! are retried with an exponential backoff. If the upstream is still unreachable after the retries,
+//! and cached estimates are enabled, the request is estimated from the most recent fee estimate
+//! and a conservative gas limit: the highest gas estimate recently seen for the same destination
+//! and selector, plus a margin.
+//!
+//! Only requests whose destination and selector were estimated before, and that do not carry
+//! authorizations, can use cached estimates. Estimations that fail because the request reverts
+//! are never retried.
+
+use crate::OdysseyWalletError;
+use alloy_primitives::{Address, Selector};
+use alloy_provider::utils::Eip1559Estimation;
+use alloy_rpc_types::TransactionRequest;
+use alloy_transport::{RpcError, TransportError};
+use metrics::Counter;
+use metrics_derive::Metrics;
+use odyssey_common::Clock;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// The default number of times an estimation is retried if the upstream is unreachable.
+pub const DEFAULT_ESTIMATION_RETRIES: usize = 2;
+
+/// The default delay before the first retry of an estimation, which is doubled on every retry.
+pub const DEFAULT_ESTIMATION_BACKOFF: Duration = Duration::from_millis(200);
+
+/// The maximum number of destination and selector pairs whose gas estimates are cached.
+const MAX_CACHED_GAS_ESTIMATES: usize = 4096;
+
+/// Configuration of the fallbacks of estimations that fail because the upstream is unreachable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EstimationFallbackConfig {
+    /// The number of times an estimation is retried.
+    pub retries: usize,
+    /// The delay before the first retry, which is doubled on every retry.
+    pub backoff: Duration,
+    /// The maximum age of the fee estimate that requests are estimated from once the retries are
+    /// exhausted. Cached estimates are not used if this is not set.
+    pub max_cache_age: Option<Duration>,
+}
+
+impl Default for EstimationFallbackConfig {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_ESTIMATION_RETRIES,
+            backoff: DEFAULT_ESTIMATION_BACKOFF,
+            max_cache_age: None,
+        }
+    }
+}
+
+/// Returns `true` if the error is caused by an unreachable upstream, rather than by the request.
+pub(crate) fn is_transient(err: &OdysseyWalletError) -> bool {
+    match err {
+        OdysseyWalletError::InternalError(err) => {
+            matches!(err.downcast_ref::<TransportError>(), Some(RpcError::Transport(_)))
+        }
+        _ => false,
+    }
+}
+
+/// The destination and selector of a request, which the gas estimates are cached by.
+type GasKey = (Address, Option<Selector>);
+
+/// Retries estimations, and estimates requests from cached estimates, if the upstream is
+/// unreachable.
+#[derive(Debug)]
+pub(crate) struct EstimationFallback {
+    config: EstimationFallbackConfig,
+    clock: Arc<dyn Clock>,
+    /// The most recent fee estimate, and the time it was made.
+    fees: Mutex<Option<(Eip1559Estimation, Instant)>>,
+    /// The highest gas estimate per destination and selector.
+    gas: Mutex<HashMap<GasKey, u64>>,
+    metrics: EstimationFallbackMetrics,
+}
+
+impl EstimationFallback {
+    /// Creates a new fallback that reads the time from the given [`Clock`].
+    pub(crate) fn new(config: EstimationFallbackConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            config,
+            clock,
+            fees: Default::default(),
+            gas: Default::default(),
+            metrics: Default::default(),
+        }
+    }
+
+    /// Estimates the request with the given estimation, retrying it and falling back to cached
+    /// estimates if the upstream is unreachable.
+    pub(crate) async fn estimate<F, Fut>(
+        &self,
+        request: &TransactionRequest,
+        mut estimate: F,
+    ) -> Result<(u64, Eip1559Estimation), OdysseyWalletError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(u64, Eip1559Estimation), OdysseyWalletError>>,
+    {
+        let mut backoff = self.config.backoff;
+        let mut retries = self.config.retries;
+        loop {
+            let err = match estimate().await {
+                Ok((gas, fees)) => {
+                    self.record(request, gas, fees);
+                    return Ok((gas, fees));
+                }
+                Err(err) if is_transient(&err) => err,
+                Err(err) => return Err(err),
+            };
+
+            if retries > 0 {
+                retries -= 1;
+                self.metrics.estimation_retries.increment(1);
+                tokio::time::sleep(backoff).await;
+                backoff = backoff.saturating_mul(2);
+                continue;
+            }
+
+            return match self.cached(request) {
+                Some(cached) => {
+                    warn!(target: "rpc::wallet", %err, "Upstream unreachable, using cached estimate");
+                    self.metrics.cached_estimates.increment(1);
+                    Ok(cached)
+                }
+                None => Err(err),
+            };
+        }
+    }
+
+    /// Records a successful estimation of the request.
+    fn record(&self, request: &TransactionRequest, gas: u64, fees: Eip1559Estimation) {
+        *self.fees.lock() = Some((fees, self.clock.now()));
+        let Some(key) = gas_key(request) else { return };
+        let mut estimates = self.gas.lock();
+        if estimates.len() < MAX_CACHED_GAS_ESTIMATES || estimates.contains_key(&key) {
+            let estimate = estimates.entry(key).or_default();
+            *estimate = (*estimate).max(gas);
+        }
+    }
+
+    /// Returns a conservative estimate of the request from the cached estimates, if the request
+    /// was estimated before and the fee estimate is recent enough.
+    fn cached(&self, request: &TransactionRequest) -> Option<(u64, Eip1559Estimation)> {
+        let max_age = self.config.max_cache_age?;
+        let (fees, estimated_at) = (*self.fees.lock())?;
+        if self.clock.now().saturating_duration_since(estimated_at) > max_age {
+            return None;
+        }
+        let gas = *self.gas.lock().get(&gas_key(request)?)?;
+        // leave a margin of 20% for state changes since the estimate
+        Some((gas.saturating_add(gas / 5), fees))
+    }
+}
+
+/// Returns the key of the gas estimates of the request, if its gas estimate can be cached.
+fn gas_key(request: &TransactionRequest) -> Option<GasKey> {
+    if request.authorization_list.is_some() {
+        return None;
+    }
+    let to = *request.to?.to()?;
+    let input = request.input.input().map(|input| input.as_ref()).unwrap_or_default();
+    Some((to, input.get(..4).map(Selector::from_slice)))
+}
+
+/// Metrics for the fallbacks of estimations.
+#[derive(Metrics)]
+#[metrics(scope = "wallet")]
+struct EstimationFallbackMetrics {
+    /// Number of estimations retried because the upstream was unreachable
+    estimation_retries: Counter,
+    /// Number of requests estimated from cached estimates because the upstream was unreachable
+    cached_estimates: Counter,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_transport::TransportErrorKind;
+    use odyssey_common::MockClock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const FEES: Eip1559Estimation =
+        Eip1559Estimation { max_fee_per_gas: 100, max_priority_fee_per_gas: 1 };
+
+    fn unreachable() -> OdysseyWalletError {
+        OdysseyWalletError::InternalError(TransportErrorKind::backend_gone().into())
+    }
+
+    fn request(to: Address) -> TransactionRequest {
+        TransactionRequest::default().to(to).input(vec![1, 2, 3, 4, 5].into())
+    }
+
+    fn fallback(clock: Arc<MockClock>, max_cache_age: Option<Duration>) -> EstimationFallback {
+        EstimationFallback::new(
+            EstimationFallbackConfig { retries: 2, backoff: Duration::ZERO, max_cache_age },
+            clock,
+        )
+    }
+
+    #[test]
+    fn detects_transient_errors() {
+        assert!(is_transient(&unreachable()));
+        let reverted = TransportError::ErrorResp(
+            serde_json::from_str(r#"{"code":3,"message":"execution reverted"}"#).unwrap(),
+        );
+        assert!(!is_transient(&OdysseyWalletError::InternalError(reverted.into())));
+        assert!(!is_transient(&OdysseyWalletError::IllegalDestination));
+    }
+
+    #[tokio::test]
+    async fn retries_unreachable_upstream() {
+        let fallback = fallback(Arc::new(MockClock::default()), None);
+        let attempts = AtomicUsize::new(0);
+        let estimate = fallback
+            .estimate(&request(Address::ZERO), || async {
+                match attempts.fetch_add(1, Ordering::Relaxed) {
+                    0 | 1 => Err(unreachable()),
+                    _ => Ok((21_000, FEES)),
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(estimate, (21_000, FEES));
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+
+        // other errors are not retried
+        attempts.store(0, Ordering::Relaxed);
+        let result = fallback
+            .estimate(&request(Address::ZERO), || async {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                Err(OdysseyWalletError::IllegalDestination)
+            })
+            .await;
+        assert!(matches!(result, Err(OdysseyWalletError::IllegalDestination)));
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_cached_estimates() {
+        let clock = Arc::new(MockClock::default());
+        let fallback = fallback(clock.clone(), Some(Duration::from_secs(60)));
+        let (known, unknown) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        for gas in [50_000, 40_000] {
+            fallback.estimate(&request(known), || async { Ok((gas, FEES)) }).await.unwrap();
+        }
+
+        // the highest estimate of the destination and selector, with a margin
+        let estimate =
+            fallback.estimate(&request(known), || async { Err(unreachable()) }).await.unwrap();
+        assert_eq!(estimate, (60_000, FEES));
+        assert!(fallback
+            .estimate(&request(unknown), || async { Err(unreachable()) })
+            .await
+            .is_err());
+
+        // the fee estimate is too old
+        clock.advance(Duration::from_secs(61));
+        assert!(fallback.estimate(&request(known), || async { Err(unreachable()) }).await.is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Expiry of server-side state of the wallet service.
+//!
+//! Prepared transactions, quotes, the submission times of sponsored transactions and idempotency
+//! keys are kept in memory until they are used. Every store has its own time-to-live, and entries
+//! are removed once it elapses. Lookups ignore expired entries, but entries that are never looked
+//! up again are only removed by a background task that runs at a fixed interval, so a long-running
+//! service does not accumulate them without bound.
+
+use crate::{
+    idempotency::DEFAULT_IDEMPOTENCY_KEY_TTL, prepared::DEFAULT_PREPARED_CALLS_TTL,
+    quote::DEFAULT_QUOTE_TTL,
+};
+use metrics::Counter;
+use metrics_derive::Metrics;
+use std::time::Duration;
+
+/// The default interval at which expired entries are removed.
+pub const DEFAULT_EXPIRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The default time the submission of a sponsored transaction is tracked for, if its inclusion is
+/// not observed.
+pub const DEFAULT_PENDING_TRANSACTION_TTL: Duration = Duration::from_secs(600);
+
+/// Configuration of the expiry of server-side state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpiryConfig {
+    /// The interval at which expired entries are removed.
+    pub interval: Duration,
+    /// The time a prepared transaction can be sent for.
+    pub prepared_calls_ttl: Duration,
+    /// The time a quote is valid for.
+    pub quote_ttl: Duration,
+    /// The time the submission of a sponsored transaction is tracked for, if its inclusion is not
+    /// observed.
+    pub pending_transaction_ttl: Duration,
+    /// The time the transaction sponsored for an idempotency key is returned for repeated calls.
+    pub idempotency_key_ttl: Duration,
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_EXPIRY_INTERVAL,
+            prepared_calls_ttl: DEFAULT_PREPARED_CALLS_TTL,
+            quote_ttl: DEFAULT_QUOTE_TTL,
+            pending_transaction_ttl: DEFAULT_PENDING_TRANSACTION_TTL,
+            idempotency_key_ttl: DEFAULT_IDEMPOTENCY_KEY_TTL,
+        }
+    }
+}
+
+/// The number of entries removed from each store in one expiry run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ExpiredEntries {
+    /// The number of expired prepared transactions.
+    pub(crate) prepared_calls: usize,
+    /// The number of expired quotes.
+    pub(crate) quotes: usize,
+    /// The number of sponsored transactions whose submission is no longer tracked.
+    pub(crate) pending_transactions: usize,
+    /// The number of expired idempotency keys.
+    pub(crate) idempotency_keys: usize,
+}
+
+impl ExpiredEntries {
+    /// Returns the total number of removed entries.
+    pub(crate) const fn total(&self) -> usize {
+        self.prepared_calls + self.quotes + self.pending_transactions + self.idempotency_keys
+    }
+}
+
+/// Metrics for the expiry of server-side state.
+#[derive(Metrics)]
+#[metrics(scope = "wallet")]
+pub(crate) struct ExpiryMetrics {
+    /// Number of prepared transactions that expired before they were sent
+    expired_prepared_calls: Counter,
+    /// Number of quotes that expired before they were used
+    expired_quotes: Counter,
+    /// Number of sponsored transactions whose inclusion was not observed before they expired
+    expired_pending_transactions: Counter,
+    /// Number of idempotency keys that expired
+    expired_idempotency_keys: Counter,
+}
+
+impl ExpiryMetrics {
+    /// Records the entries removed in one expiry run.
+    pub(crate) fn record(&self, expired: ExpiredEntries) {
+        self.expired_prepared_calls.increment(expired.prepared_calls as u64);
+        self.expired_quotes.increment(expired.quotes as u64);
+        self.expired_pending_transactions.increment(expired.pending_transactions as u64);
+        self.expired_idempotency_keys.increment(expired.idempotency_keys as u64);
+    }
+}
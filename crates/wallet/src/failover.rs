@@ -0,0 +1,481 @@
+//! Failover between multiple upstream RPC endpoints.
+//!
+//! [`FailoverTransport`] is an Alloy transport that sends every request to the first healthy
+//! endpoint, in the order they were configured. If an endpoint is unreachable, it is marked as
+//! unhealthy and the request is retried on the next endpoint after a jittered backoff. Health
+//! checks run in the background, and only mark an endpoint as healthy if it serves the expected
+//! chain, so the relay never signs or sends transactions against a node of another chain.
+//!
+//! Retrying a request that sends a signed transaction is safe, since the transaction hash does not
+//! change between attempts. If a failed attempt reached the pool of an endpoint before the
+//! connection broke, a retry is rejected because the transaction is already known or its nonce is
+//! used. The retry then succeeds with the hash of the transaction if the endpoint knows it.
+
+use alloy_json_rpc::{
+    Id, Request, RequestPacket, Response, ResponsePacket, ResponsePayload, RpcParam, RpcReturn,
+};
+use alloy_primitives::{keccak256, Bytes, TxHash, U64};
+use alloy_transport::{BoxTransport, Transport, TransportError, TransportErrorKind, TransportFut};
+use alloy_transport_http::Http;
+use serde_json::value::RawValue;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tower::Service;
+use tracing::{debug, warn};
+use url::Url;
+
+/// The default interval between health checks of the endpoints.
+pub const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The default number of times a request is retried on another endpoint.
+pub const DEFAULT_UPSTREAM_RETRIES: usize = 3;
+
+/// The base delay between retries, which is doubled on every retry.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// Configuration of a [`FailoverTransport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailoverConfig {
+    /// The number of times a request is retried on another endpoint if an endpoint is
+    /// unreachable.
+    pub retries: usize,
+    /// The interval between health checks of the endpoints.
+    pub health_check_interval: Duration,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_UPSTREAM_RETRIES,
+            health_check_interval: DEFAULT_HEALTH_CHECK_INTERVAL,
+        }
+    }
+}
+
+/// An Alloy transport that fails over between multiple endpoints.
+#[derive(Debug, Clone)]
+pub struct FailoverTransport {
+    inner: Arc<FailoverTransportInner>,
+}
+
+#[derive(Debug)]
+struct FailoverTransportInner {
+    endpoints: Vec<Endpoint>,
+    config: FailoverConfig,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    /// The name of the endpoint in logs.
+    name: String,
+    transport: BoxTransport,
+    healthy: AtomicBool,
+}
+
+impl FailoverTransport {
+    /// Creates a new transport that fails over between the given endpoints, in order of
+    /// preference.
+    ///
+    /// All endpoints are considered healthy until they are checked. Fails if there are no
+    /// endpoints, or if the health check interval is zero.
+    pub fn new(
+        endpoints: Vec<(String, BoxTransport)>,
+        config: FailoverConfig,
+    ) -> eyre::Result<Self> {
+        if endpoints.is_empty() {
+            eyre::bail!("at least one upstream endpoint is required");
+        }
+        if config.health_check_interval.is_zero() {
+            eyre::bail!("the health check interval must be positive");
+        }
+        let endpoints = endpoints
+            .into_iter()
+            .map(|(name, transport)| Endpoint { name, transport, healthy: AtomicBool::new(true) })
+            .collect();
+        Ok(Self { inner: Arc::new(FailoverTransportInner { endpoints, config }) })
+    }
+
+    /// Creates a new transport that fails over between the given HTTP endpoints, in order of
+    /// preference.
+    pub fn http(urls: &[Url], config: FailoverConfig) -> eyre::Result<Self> {
+        Self::new(
+            urls.iter()
+                .map(|url| (url.to_string(), Http::<reqwest::Client>::new(url.clone()).boxed()))
+                .collect(),
+            config,
+        )
+    }
+
+    /// Checks that all reachable endpoints serve the same chain, and returns its chain id.
+    ///
+    /// Unreachable endpoints are marked as unhealthy. Fails if no endpoint is reachable, or if
+    /// the endpoints serve different chains.
+    pub async fn check_chain_id(&self) -> eyre::Result<u64> {
+        let mut chain_id = None;
+        for endpoint in &self.inner.endpoints {
+            match endpoint.chain_id().await {
+                Ok(id) => match chain_id {
+                    Some(expected) if expected != id => eyre::bail!(
+                        "upstream {} serves chain {id}, expected chain {expected}",
+                        endpoint.name
+                    ),
+                    _ => chain_id = Some(id),
+                },
+                Err(err) => {
+                    warn!(target: "rpc::wallet", %err, upstream = %endpoint.name, "Upstream is unreachable");
+                    endpoint.healthy.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+        chain_id.ok_or_else(|| eyre::eyre!("no upstream is reachable"))
+    }
+
+    /// Spawns a task that periodically checks that the endpoints are reachable and serve the
+    /// chain with the given id.
+    ///
+    /// The task stops once the transport is dropped.
+    pub fn spawn_health_checks(&self, chain_id: u64) {
+        let inner = Arc::downgrade(&self.inner);
+        let mut interval = tokio::time::interval(self.inner.config.health_check_interval);
+        tokio::spawn(async move {
+            loop {
+                interval.tick().await;
+                let Some(inner) = Weak::upgrade(&inner) else { break };
+                for endpoint in &inner.endpoints {
+                    endpoint.check_health(chain_id).await;
+                }
+            }
+        });
+    }
+
+    /// Returns whether the endpoint at the given index is healthy.
+    pub fn is_healthy(&self, index: usize) -> bool {
+        self.inner.endpoints.get(index).is_some_and(|e| e.healthy.load(Ordering::Relaxed))
+    }
+
+    /// Returns the endpoints to try a request on, in order.
+    ///
+    /// If no endpoint is healthy, all endpoints are tried, since the health checks may lag
+    /// behind.
+    fn candidates(&self) -> Vec<&Endpoint> {
+        let healthy: Vec<_> =
+            self.inner.endpoints.iter().filter(|e| e.healthy.load(Ordering::Relaxed)).collect();
+        if healthy.is_empty() {
+            self.inner.endpoints.iter().collect()
+        } else {
+            healthy
+        }
+    }
+
+    /// Sends the request to the first healthy endpoint, failing over to the next endpoint if it
+    /// is unreachable.
+    async fn request(self, request: RequestPacket) -> Result<ResponsePacket, TransportError> {
+        let candidates = self.candidates();
+        let tx_hash = sent_transaction_hash(&request);
+        let mut attempt = 0;
+        loop {
+            let endpoint = candidates[attempt % candidates.len()];
+            match endpoint.transport.clone().call(request.clone()).await {
+                Err(TransportError::Transport(err)) if attempt < self.inner.config.retries => {
+                    debug!(
+                        target: "rpc::wallet",
+                        %err,
+                        upstream = %endpoint.name,
+                        attempt,
+                        "Failing over to another upstream"
+                    );
+                    endpoint.healthy.store(false, Ordering::Relaxed);
+                    tokio::time::sleep(backoff(attempt)).await;
+                    attempt += 1;
+                }
+                // an earlier attempt may have sent the transaction
+                result if attempt > 0 => {
+                    return match tx_hash {
+                        Some(tx_hash) => endpoint.resolve_resent(tx_hash, result).await,
+                        None => result,
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Endpoint {
+    /// Calls the method on the endpoint.
+    async fn call<P: RpcParam, R: RpcReturn>(
+        &self,
+        method: &'static str,
+        params: P,
+    ) -> Result<R, TransportError> {
+        let request = Request::new(method, Id::Number(0), params)
+            .serialize()
+            .map_err(TransportError::ser_err)?;
+        match self.transport.clone().call(request.into()).await? {
+            ResponsePacket::Single(response) => match response.payload {
+                ResponsePayload::Success(result) => serde_json::from_str(result.get())
+                    .map_err(|err| TransportError::deser_err(err, result.get())),
+                ResponsePayload::Failure(err) => Err(TransportError::ErrorResp(err)),
+            },
+            ResponsePacket::Batch(_) => {
+                Err(TransportErrorKind::custom_str("unexpected batch response"))
+            }
+        }
+    }
+
+    /// Queries the chain id of the endpoint.
+    async fn chain_id(&self) -> Result<u64, TransportError> {
+        self.call::<_, U64>("eth_chainId", ()).await.map(|id| id.to::<u64>())
+    }
+
+    /// Resolves the response to a transaction that was sent again after an earlier attempt
+    /// failed.
+    ///
+    /// If the earlier attempt reached the pool, the endpoint rejects the transaction as already
+    /// known, or its nonce as too low once it is included. The transaction was sent if the
+    /// endpoint knows its hash, so the response is replaced with the hash.
+    async fn resolve_resent(
+        &self,
+        tx_hash: TxHash,
+        result: Result<ResponsePacket, TransportError>,
+    ) -> Result<ResponsePacket, TransportError> {
+        let Ok(ResponsePacket::Single(response)) = &result else { return result };
+        let ResponsePayload::Failure(err) = &response.payload else { return result };
+        let message = err.message.to_lowercase();
+        let sent = if message.contains("already known") || message.contains("known transaction") {
+            true
+        } else if message.contains("nonce too low") {
+            // the nonce may have been used by another transaction
+            self.call::<_, Option<serde_json::Value>>("eth_getTransactionByHash", (tx_hash,))
+                .await
+                .is_ok_and(|tx| tx.is_some())
+        } else {
+            false
+        };
+        if !sent {
+            return result;
+        }
+
+        debug!(
+            target: "rpc::wallet",
+            upstream = %self.name,
+            %tx_hash,
+            "Resent transaction was already sent"
+        );
+        let tx_hash = RawValue::from_string(format!("\"{tx_hash}\""))
+            .map_err(|err| TransportError::deser_err(err, ""))?;
+        Ok(ResponsePacket::Single(Response {
+            id: response.id.clone(),
+            payload: ResponsePayload::Success(tx_hash),
+        }))
+    }
+
+    /// Marks the endpoint as healthy if it is reachable and serves the chain with the given id.
+    async fn check_health(&self, chain_id: u64) {
+        let healthy = match self.chain_id().await {
+            Ok(id) if id == chain_id => true,
+            Ok(id) => {
+                warn!(target: "rpc::wallet", upstream = %self.name, id, chain_id, "Upstream serves another chain");
+                false
+            }
+            Err(err) => {
+                debug!(target: "rpc::wallet", %err, upstream = %self.name, "Upstream is unhealthy");
+                false
+            }
+        };
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            debug!(target: "rpc::wallet", upstream = %self.name, healthy, "Upstream health changed");
+        }
+    }
+}
+
+impl Service<RequestPacket> for FailoverTransport {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        Box::pin(self.clone().request(request))
+    }
+}
+
+/// Returns the hash of the transaction sent by the request, if it is an `eth_sendRawTransaction`
+/// call.
+fn sent_transaction_hash(request: &RequestPacket) -> Option<TxHash> {
+    let RequestPacket::Single(request) = request else { return None };
+    if request.method() != "eth_sendRawTransaction" {
+        return None;
+    }
+    let (raw,) = serde_json::from_str::<(Bytes,)>(request.params()?.get()).ok()?;
+    Some(keccak256(raw))
+}
+
+/// Returns the delay before the given retry, with up to 50% random jitter, so that retries of
+/// concurrent requests are spread out.
+fn backoff(attempt: usize) -> Duration {
+    let delay = RETRY_BASE_DELAY * 2u32.saturating_pow(attempt as u32);
+    let random = RandomState::new().build_hasher().finish();
+    delay + delay.mul_f64((random % 1000) as f64 / 2000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_json_rpc::ErrorPayload;
+    use std::sync::atomic::AtomicUsize;
+
+    /// A transport that answers `eth_chainId`, or fails if it is down.
+    #[derive(Debug, Clone)]
+    struct MockTransport {
+        chain_id: u64,
+        down: Arc<AtomicBool>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl MockTransport {
+        fn new(chain_id: u64) -> Self {
+            Self { chain_id, down: Default::default(), calls: Default::default() }
+        }
+    }
+
+    impl Service<RequestPacket> for MockTransport {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: RequestPacket) -> Self::Future {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            let down = self.down.load(Ordering::Relaxed);
+            let chain_id = self.chain_id;
+            Box::pin(async move {
+                if down {
+                    return Err(TransportErrorKind::backend_gone());
+                }
+                let RequestPacket::Single(request) = request else { unreachable!() };
+                let result = RawValue::from_string(format!("\"{:#x}\"", chain_id)).unwrap();
+                Ok(ResponsePacket::Single(Response {
+                    id: request.id().clone(),
+                    payload: ResponsePayload::Success(result),
+                }))
+            })
+        }
+    }
+
+    fn transport(mocks: &[&MockTransport]) -> FailoverTransport {
+        FailoverTransport::new(
+            mocks
+                .iter()
+                .enumerate()
+                .map(|(i, mock)| (i.to_string(), BoxTransport::new((*mock).clone())))
+                .collect(),
+            FailoverConfig::default(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_healthy_endpoint() {
+        let (a, b) = (MockTransport::new(1), MockTransport::new(1));
+        let transport = transport(&[&a, &b]);
+        assert_eq!(transport.check_chain_id().await.unwrap(), 1);
+
+        a.down.store(true, Ordering::Relaxed);
+        let endpoint = &transport.inner.endpoints[0];
+        let request = Request::new("eth_chainId", Id::Number(0), ()).serialize().unwrap();
+        assert!(transport.clone().call(request.clone().into()).await.is_ok());
+        assert!(!transport.is_healthy(0));
+        assert_eq!(b.calls.load(Ordering::Relaxed), 2);
+
+        // the endpoint recovers
+        a.down.store(false, Ordering::Relaxed);
+        endpoint.check_health(1).await;
+        assert!(transport.is_healthy(0));
+        transport.clone().call(request.into()).await.unwrap();
+        assert_eq!(b.calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn rejects_inconsistent_chain_ids() {
+        let (a, b) = (MockTransport::new(1), MockTransport::new(2));
+        assert!(transport(&[&a, &b]).check_chain_id().await.is_err());
+
+        // endpoints of another chain are unhealthy
+        let transport = transport(&[&a]);
+        transport.inner.endpoints[0].check_health(2).await;
+        assert!(!transport.is_healthy(0));
+    }
+
+    #[tokio::test]
+    async fn resolves_resent_transactions() {
+        let raw = Bytes::from_static(&[0x02, 0xc0]);
+        let request = Request::new("eth_sendRawTransaction", Id::Number(1), (raw.clone(),))
+            .serialize()
+            .unwrap()
+            .into();
+        let tx_hash = sent_transaction_hash(&request).unwrap();
+        assert_eq!(tx_hash, keccak256(&raw));
+
+        let transport = transport(&[&MockTransport::new(1)]);
+        let endpoint = &transport.inner.endpoints[0];
+        let rejected = |message: &'static str| {
+            Ok(ResponsePacket::Single(Response {
+                id: Id::Number(1),
+                payload: ResponsePayload::Failure(ErrorPayload {
+                    code: -32000,
+                    message: message.into(),
+                    data: None,
+                }),
+            }))
+        };
+        for message in ["already known", "nonce too low"] {
+            let ResponsePacket::Single(response) =
+                endpoint.resolve_resent(tx_hash, rejected(message)).await.unwrap()
+            else {
+                unreachable!()
+            };
+            let ResponsePayload::Success(result) = response.payload else {
+                panic!("{message} is resolved")
+            };
+            assert_eq!(result.get(), format!("\"{tx_hash}\""));
+        }
+
+        // other errors are returned as is
+        let ResponsePacket::Single(response) =
+            endpoint.resolve_resent(tx_hash, rejected("insufficient funds")).await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert!(matches!(response.payload, ResponsePayload::Failure(_)));
+    }
+
+    #[tokio::test]
+    async fn requires_reachable_endpoint() {
+        let a = MockTransport::new(1);
+        a.down.store(true, Ordering::Relaxed);
+        assert!(transport(&[&a]).check_chain_id().await.is_err());
+    }
+
+    #[test]
+    fn requires_endpoints() {
+        assert!(FailoverTransport::new(Vec::new(), FailoverConfig::default()).is_err());
+        let config = FailoverConfig { health_check_interval: Duration::ZERO, ..Default::default() };
+        let endpoint = BoxTransport::new(MockTransport::new(1));
+        assert!(FailoverTransport::new(vec![("a".to_string(), endpoint)], config).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! Fee telemetry of sponsored transactions.
+//!
+//! The fees actually paid by included sponsored transactions are recorded, split into the base
+//! fee, the priority fee and the L1 data fee, so operators can set sane fee ceilings for the wallet
+//! and notice when the fee market is manipulated to drain the sponsor. Statistics over the recent
+//! transactions are exported as metrics and through `wallet_getSponsorFeeStats`.
+
+use alloy_primitives::{U128, U256};
+use metrics::Histogram;
+use metrics_derive::Metrics;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, sync::Arc};
+
+/// The default number of recent sponsored transactions the fee statistics are computed over.
+pub const DEFAULT_FEE_SAMPLES: usize = 1024;
+
+/// The fees paid by an included sponsored transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SponsorFee {
+    /// The base fee per gas.
+    pub base_fee_per_gas: u128,
+    /// The priority fee per gas.
+    pub priority_fee_per_gas: u128,
+    /// The L1 data fee in wei, if the chain charges one and the upstream reports it.
+    pub l1_fee: Option<u128>,
+}
+
+impl SponsorFee {
+    /// Splits the effective gas price of a transaction into its base and priority fee, given the
+    /// maximum priority fee per gas of the transaction.
+    ///
+    /// If the base fee rose so far that the maximum fee capped the effective gas price, the
+    /// priority fee can be lower than the maximum, which is not visible from the effective gas
+    /// price alone. The priority fee is overstated in that case.
+    pub fn new(
+        effective_gas_price: u128,
+        max_priority_fee_per_gas: u128,
+        l1_fee: Option<u128>,
+    ) -> Self {
+        let priority_fee_per_gas = max_priority_fee_per_gas.min(effective_gas_price);
+        Self {
+            base_fee_per_gas: effective_gas_price - priority_fee_per_gas,
+            priority_fee_per_gas,
+            l1_fee,
+        }
+    }
+}
+
+/// Reads the L1 data fee from a JSON receipt, which OP Stack chains add to Ethereum receipts.
+pub(crate) fn receipt_l1_fee(receipt: &serde_json::Value) -> Option<u128> {
+    let fee = receipt.get("l1Fee")?;
+    serde_json::from_value::<U128>(fee.clone()).ok().map(|fee| fee.to())
+}
+
+/// Percentiles of a fee over recent sponsored transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeePercentiles {
+    /// The median fee.
+    pub p50: U256,
+    /// The 90th percentile of the fees.
+    pub p90: U256,
+    /// The 99th percentile of the fees.
+    pub p99: U256,
+    /// The maximum fee.
+    pub max: U256,
+}
+
+impl FeePercentiles {
+    fn new(mut samples: Vec<u128>) -> Self {
+        samples.sort_unstable();
+        let percentile = |p: usize| {
+            // nearest-rank method
+            let rank = (samples.len() * p).div_ceil(100);
+            U256::from(samples.get(rank.saturating_sub(1)).copied().unwrap_or_default())
+        };
+        Self {
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: U256::from(samples.last().copied().unwrap_or_default()),
+        }
+    }
+}
+
+/// Statistics about the fees paid by recent sponsored transactions.
+///
+/// Base and priority fees are per gas, L1 data fees are per transaction. All fees are in wei.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SponsorFeeStats {
+    /// The number of transactions the statistics are computed over.
+    pub samples: u64,
+    /// The base fees per gas.
+    pub base_fee_per_gas: FeePercentiles,
+    /// The priority fees per gas.
+    pub priority_fee_per_gas: FeePercentiles,
+    /// The L1 data fees, over the transactions whose L1 data fee is known.
+    pub l1_fee: FeePercentiles,
+}
+
+/// Records the fees paid by sponsored transactions.
+#[derive(Debug, Clone)]
+pub struct SponsorFeeTracker {
+    inner: Arc<SponsorFeeTrackerInner>,
+}
+
+#[derive(Debug)]
+struct SponsorFeeTrackerInner {
+    max_samples: usize,
+    /// The fees of the most recent transactions, oldest first.
+    samples: Mutex<VecDeque<SponsorFee>>,
+    metrics: SponsorFeeMetrics,
+}
+
+impl SponsorFeeTracker {
+    /// Creates a new tracker that computes its statistics over the given number of recent
+    /// transactions.
+    pub fn new(max_samples: usize) -> Self {
+        Self {
+            inner: Arc::new(SponsorFeeTrackerInner {
+                max_samples,
+                samples: Default::default(),
+                metrics: Default::default(),
+            }),
+        }
+    }
+
+    /// Records the fees paid by an included sponsored transaction.
+    pub fn record(&self, fee: SponsorFee) {
+        let mut samples = self.inner.samples.lock();
+        if samples.len() >= self.inner.max_samples {
+            samples.pop_front();
+        }
+        samples.push_back(fee);
+
+        let metrics = &self.inner.metrics;
+        metrics.base_fee_per_gas.record(fee.base_fee_per_gas as f64);
+        metrics.priority_fee_per_gas.record(fee.priority_fee_per_gas as f64);
+        if let Some(l1_fee) = fee.l1_fee {
+            metrics.l1_fee.record(l1_fee as f64);
+        }
+    }
+
+    /// Returns the statistics about the fees paid by recent sponsored transactions.
+    pub fn stats(&self) -> SponsorFeeStats {
+        let samples = self.inner.samples.lock();
+        SponsorFeeStats {
+            samples: samples.len() as u64,
+            base_fee_per_gas: FeePercentiles::new(
+                samples.iter().map(|fee| fee.base_fee_per_gas).collect(),
+            ),
+            priority_fee_per_gas: FeePercentiles::new(
+                samples.iter().map(|fee| fee.priority_fee_per_gas).collect(),
+            ),
+            l1_fee: FeePercentiles::new(samples.iter().filter_map(|fee| fee.l1_fee).collect()),
+        }
+    }
+}
+
+impl Default for SponsorFeeTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FEE_SAMPLES)
+    }
+}
+
+/// Metrics for the fees paid by sponsored transactions.
+#[derive(Metrics)]
+#[metrics(scope = "wallet")]
+struct SponsorFeeMetrics {
+    /// Base fee per gas paid by included sponsored transactions, in wei
+    base_fee_per_gas: Histogram,
+    /// Priority fee per gas paid by included sponsored transactions, in wei
+    priority_fee_per_gas: Histogram,
+    /// L1 data fee paid by included sponsored transactions, in wei
+    l1_fee: Histogram,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_effective_gas_price() {
+        assert_eq!(
+            SponsorFee::new(12, 2, Some(100)),
+            SponsorFee { base_fee_per_gas: 10, priority_fee_per_gas: 2, l1_fee: Some(100) }
+        );
+        // the priority fee can not exceed the effective gas price
+        assert_eq!(
+            SponsorFee::new(1, 2, None),
+            SponsorFee { base_fee_per_gas: 0, priority_fee_per_gas: 1, l1_fee: None }
+        );
+    }
+
+    #[test]
+    fn reads_l1_fee_from_receipts() {
+        let receipt = serde_json::json!({ "gasUsed": "0x5208", "l1Fee": "0x2a" });
+        assert_eq!(receipt_l1_fee(&receipt), Some(42));
+        assert_eq!(receipt_l1_fee(&serde_json::json!({ "gasUsed": "0x5208" })), None);
+    }
+
+    #[test]
+    fn computes_fee_stats() {
+        let tracker = SponsorFeeTracker::new(3);
+        assert_eq!(tracker.stats(), SponsorFeeStats::default());
+
+        for base_fee in 1..=4 {
+            tracker.record(SponsorFee::new(base_fee + 1, 1, (base_fee > 2).then_some(base_fee)));
+        }
+
+        let stats = tracker.stats();
+        assert_eq!(stats.samples, 3);
+        assert_eq!(
+            stats.base_fee_per_gas,
+            FeePercentiles {
+                p50: U256::from(3),
+                p90: U256::from(4),
+                p99: U256::from(4),
+                max: U256::from(4)
+            }
+        );
+        assert_eq!(stats.priority_fee_per_gas.max, U256::from(1));
+        // only the transactions whose L1 data fee is known are considered
+        assert_eq!(stats.l1_fee.p50, U256::from(3));
+        assert_eq!(stats.l1_fee.max, U256::from(4));
+    }
+}
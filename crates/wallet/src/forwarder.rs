@@ -0,0 +1,295 @@
+//! Forwarding of `wallet_` requests to another wallet service.
+//!
+//! Replicas forward wallet requests to the sequencer or a relay, so that there is only a single
+//! signer managing the sponsor's nonce, while every node can expose the namespace.
+//!
+//! Subscriptions are not forwarded, clients have to subscribe to the wallet service directly.
+
+use crate::{
+    Delegators, InclusionStats, OdysseyWalletApiServer, PendingSponsoredTransaction, PreparedCalls,
+    SignedQuote, SponsorFeeStats, SponsoredFeeHistory, SponsoredSimulation,
+    SponsoredTransactionStatus, TokenFeeStats, WalletCapabilities,
+};
+use alloy_primitives::{Address, Bytes, TxHash, B256, U64};
+use alloy_rpc_types::{state::StateOverride, BlockNumberOrTag, TransactionRequest};
+use jsonrpsee::{
+    core::{
+        async_trait, client::ClientT, params::ArrayParams, ClientError, RpcResult,
+        SubscriptionResult,
+    },
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+    types::{
+        error::{INTERNAL_ERROR_CODE, METHOD_NOT_FOUND_CODE},
+        ErrorObject,
+    },
+    PendingSubscriptionSink,
+};
+use serde::de::DeserializeOwned;
+use std::{sync::Arc, time::Duration};
+use tracing::{debug, instrument, trace};
+
+/// The default timeout of a forwarded request.
+pub const DEFAULT_FORWARD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The default number of times a forwarded request is retried if the service is unreachable.
+pub const DEFAULT_FORWARD_RETRIES: usize = 3;
+
+/// The delay between retries of a forwarded request.
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Implementation of the Odyssey `wallet_` namespace that forwards all requests to another
+/// wallet service.
+#[derive(Debug, Clone)]
+pub struct OdysseyWalletForwarder {
+    inner: Arc<OdysseyWalletForwarderInner>,
+}
+
+#[derive(Debug)]
+struct OdysseyWalletForwarderInner {
+    client: HttpClient,
+    retries: usize,
+}
+
+impl OdysseyWalletForwarder {
+    /// Creates a new forwarder to the wallet service at the given URL.
+    ///
+    /// Requests time out after the given duration, and are retried the given number of times if
+    /// the service is unreachable.
+    pub fn new(url: &str, timeout: Duration, retries: usize) -> eyre::Result<Self> {
+        let client = HttpClientBuilder::default().request_timeout(timeout).build(url)?;
+        Ok(Self { inner: Arc::new(OdysseyWalletForwarderInner { client, retries }) })
+    }
+
+    /// Forwards the request to the wallet service.
+    ///
+    /// Requests are only retried on transport errors. Timed out requests are not retried, since
+    /// the service may have processed them already.
+    #[instrument(target = "rpc::wallet", skip_all, fields(%method))]
+    async fn forward<R: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        params: ArrayParams,
+    ) -> RpcResult<R> {
+        let mut attempt = 0;
+        loop {
+            match self.inner.client.request(method, params.clone()).await {
+                Ok(response) => return Ok(response),
+                Err(ClientError::Call(err)) => return Err(err),
+                Err(ClientError::Transport(err)) if attempt < self.inner.retries => {
+                    attempt += 1;
+                    debug!(
+                        target: "rpc::wallet",
+                        %err,
+                        method,
+                        attempt,
+                        "Retrying forwarded request"
+                    );
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+                Err(err) => {
+                    return Err(ErrorObject::owned(
+                        INTERNAL_ERROR_CODE,
+                        format!("failed to forward request: {err}"),
+                        None::<()>,
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl OdysseyWalletApiServer for OdysseyWalletForwarder {
+    async fn send_transaction(
+        &self,
+        request: TransactionRequest,
+        valid_until: Option<U64>,
+        idempotency_key: Option<String>,
+    ) -> RpcResult<TxHash> {
+        trace!(
+            target: "rpc::wallet",
+            ?request,
+            ?valid_until,
+            ?idempotency_key,
+            "Forwarding odyssey_sendTransaction"
+        );
+        self.forward("wallet_sendTransaction", rpc_params![request, valid_until, idempotency_key])
+            .await
+    }
+
+    async fn fee_history(
+        &self,
+        block_count: U64,
+        newest_block: BlockNumberOrTag,
+        reward_percentiles: Option<Vec<f64>>,
+    ) -> RpcResult<SponsoredFeeHistory> {
+        trace!(
+            target: "rpc::wallet",
+            ?block_count,
+            ?newest_block,
+            "Forwarding odyssey_feeHistory"
+        );
+        self.forward(
+            "wallet_feeHistory",
+            rpc_params![block_count, newest_block, reward_percentiles],
+        )
+        .await
+    }
+
+    async fn get_inclusion_stats(&self) -> RpcResult<InclusionStats> {
+        trace!(target: "rpc::wallet", "Forwarding wallet_getInclusionStats");
+        self.forward("wallet_getInclusionStats", rpc_params![]).await
+    }
+
+    async fn get_sponsor_fee_stats(&self) -> RpcResult<SponsorFeeStats> {
+        trace!(target: "rpc::wallet", "Forwarding wallet_getSponsorFeeStats");
+        self.forward("wallet_getSponsorFeeStats", rpc_params![]).await
+    }
+
+    async fn get_token_fee_stats(&self) -> RpcResult<Option<TokenFeeStats>> {
+        trace!(target: "rpc::wallet", "Forwarding wallet_getTokenFeeStats");
+        self.forward("wallet_getTokenFeeStats", rpc_params![]).await
+    }
+
+    async fn prepare_calls(&self, request: TransactionRequest) -> RpcResult<PreparedCalls> {
+        trace!(target: "rpc::wallet", ?request, "Forwarding wallet_prepareCalls");
+        self.forward("wallet_prepareCalls", rpc_params![request]).await
+    }
+
+    async fn send_prepared_calls(&self, digest: B256, signature: Bytes) -> RpcResult<TxHash> {
+        trace!(target: "rpc::wallet", %digest, "Forwarding wallet_sendPreparedCalls");
+        self.forward("wallet_sendPreparedCalls", rpc_params![digest, signature]).await
+    }
+
+    async fn get_quote(&self, request: TransactionRequest) -> RpcResult<SignedQuote> {
+        trace!(target: "rpc::wallet", ?request, "Forwarding wallet_getQuote");
+        self.forward("wallet_getQuote", rpc_params![request]).await
+    }
+
+    async fn send_quoted_transaction(
+        &self,
+        request: TransactionRequest,
+        quote: SignedQuote,
+    ) -> RpcResult<TxHash> {
+        trace!(target: "rpc::wallet", ?request, "Forwarding wallet_sendQuotedTransaction");
+        self.forward("wallet_sendQuotedTransaction", rpc_params![request, quote]).await
+    }
+
+    async fn get_capabilities(&self) -> RpcResult<WalletCapabilities> {
+        trace!(target: "rpc::wallet", "Forwarding wallet_getCapabilities");
+        self.forward("wallet_getCapabilities", rpc_params![]).await
+    }
+
+    async fn get_delegators(
+        &self,
+        implementation: Address,
+        cursor: Option<Address>,
+        limit: Option<U64>,
+    ) -> RpcResult<Delegators> {
+        trace!(target: "rpc::wallet", %implementation, ?cursor, "Forwarding wallet_getDelegators");
+        self.forward("wallet_getDelegators", rpc_params![implementation, cursor, limit]).await
+    }
+
+    async fn pending_sponsored_transactions(&self) -> RpcResult<Vec<PendingSponsoredTransaction>> {
+        trace!(target: "rpc::wallet", "Forwarding wallet_pendingSponsoredTransactions");
+        self.forward("wallet_pendingSponsoredTransactions", rpc_params![]).await
+    }
+
+    async fn get_transaction_status(
+        &self,
+        tx_hash: TxHash,
+    ) -> RpcResult<Option<SponsoredTransactionStatus>> {
+        trace!(target: "rpc::wallet", %tx_hash, "Forwarding wallet_getTransactionStatus");
+        self.forward("wallet_getTransactionStatus", rpc_params![tx_hash]).await
+    }
+
+    async fn simulate_sponsored_transaction(
+        &self,
+        request: TransactionRequest,
+        state_overrides: Option<StateOverride>,
+    ) -> RpcResult<SponsoredSimulation> {
+        trace!(target: "rpc::wallet", ?request, "Forwarding wallet_simulateSponsoredTransaction");
+        self.forward("wallet_simulateSponsoredTransaction", rpc_params![request, state_overrides])
+            .await
+    }
+
+    async fn subscribe_my_transactions(
+        &self,
+        pending: PendingSubscriptionSink,
+        authority: Address,
+    ) -> SubscriptionResult {
+        trace!(target: "rpc::wallet", %authority, "Rejecting wallet_subscribeMyTransactions");
+        pending
+            .reject(ErrorObject::owned(
+                METHOD_NOT_FOUND_CODE,
+                "subscriptions are not forwarded, subscribe to the wallet service directly",
+                None::<()>,
+            ))
+            .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OdysseyWalletApiClient;
+    use jsonrpsee::{
+        server::{Server, ServerHandle},
+        types::error::INVALID_PARAMS_CODE,
+        RpcModule,
+    };
+    use std::net::SocketAddr;
+
+    async fn upstream() -> (SocketAddr, ServerHandle) {
+        let mut module = RpcModule::new(());
+        module
+            .register_method::<RpcResult<_>, _>("wallet_getInclusionStats", |_, _, _| {
+                Ok(InclusionStats { samples: 1, ..Default::default() })
+            })
+            .unwrap();
+        module
+            .register_method::<RpcResult<TxHash>, _>("wallet_sendTransaction", |_, _, _| {
+                Err(ErrorObject::owned(INVALID_PARAMS_CODE, "tx value not zero", None::<()>))
+            })
+            .unwrap();
+
+        let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        (addr, server.start(module))
+    }
+
+    #[tokio::test]
+    async fn forwards_requests() {
+        let (addr, _handle) = upstream().await;
+        let forwarder =
+            OdysseyWalletForwarder::new(&format!("http://{addr}"), DEFAULT_FORWARD_TIMEOUT, 0)
+                .unwrap();
+
+        let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+        let forwarder_addr = server.local_addr().unwrap();
+        let _handle = server.start(forwarder.into_rpc());
+        let client =
+            HttpClientBuilder::default().build(format!("http://{forwarder_addr}")).unwrap();
+
+        let stats = client.get_inclusion_stats().await.unwrap();
+        assert_eq!(stats.samples, 1);
+
+        // errors of the upstream are returned as is
+        let err =
+            client.send_transaction(TransactionRequest::default(), None, None).await.unwrap_err();
+        let ClientError::Call(err) = err else { panic!("unexpected error: {err:?}") };
+        assert_eq!(err.code(), INVALID_PARAMS_CODE);
+        assert_eq!(err.message(), "tx value not zero");
+    }
+
+    #[tokio::test]
+    async fn fails_if_unreachable() {
+        // nothing is listening on this port
+        let forwarder =
+            OdysseyWalletForwarder::new("http://127.0.0.1:1", DEFAULT_FORWARD_TIMEOUT, 1).unwrap();
+        let err = forwarder.get_inclusion_stats().await.unwrap_err();
+        assert_eq!(err.code(), INTERNAL_ERROR_CODE);
+    }
+}
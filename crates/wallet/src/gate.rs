@@ -0,0 +1,319 @@
+//! Experiment gating for RPC methods.
+//!
+//! Odyssey adds and removes experiments frequently. The methods of an experiment are gated behind
+//! its flag with the [`ExperimentGateLayer`]: while the experiment is not active, calls fail with
+//! a structured "experiment not enabled" error that names the flag and its activation status,
+//! instead of a "method not found" error.
+//!
+//! Gates are configured as `<method>=<experiment>:<activation>`, see [`MethodGate`].
+
+use futures::future::{BoxFuture, FutureExt};
+use jsonrpsee::{
+    server::{middleware::rpc::RpcServiceT, MethodResponse},
+    types::{ErrorObject, Request},
+};
+use odyssey_common::{Clock, SystemClock};
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, sync::Arc};
+use tower::Layer;
+use tracing::debug;
+
+/// The error code of calls to methods of experiments that are not active.
+pub const EXPERIMENT_NOT_ENABLED_CODE: i32 = -32020;
+
+/// The error message of calls to methods of experiments that are not active.
+pub const EXPERIMENT_NOT_ENABLED_MSG: &str = "experiment not enabled";
+
+/// When an experiment is active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExperimentActivation {
+    /// The experiment is always active.
+    Enabled,
+    /// The experiment is never active.
+    Disabled,
+    /// The experiment is active from the given unix timestamp in seconds.
+    Timestamp(u64),
+}
+
+impl ExperimentActivation {
+    /// Returns whether the experiment is active at the given unix timestamp in seconds.
+    pub const fn is_active_at(&self, timestamp: u64) -> bool {
+        match self {
+            Self::Enabled => true,
+            Self::Disabled => false,
+            Self::Timestamp(activation) => timestamp >= *activation,
+        }
+    }
+}
+
+/// The activation status of an experiment that is not active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExperimentStatus {
+    /// The experiment is disabled.
+    Disabled,
+    /// The experiment is scheduled to activate later.
+    Scheduled,
+}
+
+/// The data of the error of calls to methods of experiments that are not active.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExperimentNotEnabled {
+    /// The flag of the experiment.
+    pub experiment: String,
+    /// The activation status of the experiment.
+    pub status: ExperimentStatus,
+    /// The unix timestamp the experiment activates at, if it is scheduled.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "alloy_rpc_types::serde_helpers::quantity::opt"
+    )]
+    pub activation_timestamp: Option<u64>,
+}
+
+impl FromStr for ExperimentActivation {
+    type Err = String;
+
+    /// Parses `enabled`, `disabled` or a unix timestamp in seconds.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "enabled" => Ok(Self::Enabled),
+            "disabled" => Ok(Self::Disabled),
+            timestamp => timestamp.parse().map(Self::Timestamp).map_err(|_| {
+                format!("invalid activation {s}, expected enabled, disabled or a timestamp")
+            }),
+        }
+    }
+}
+
+/// A gate of the methods of an experiment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodGate {
+    /// The gated method, or the gated namespace if it ends with `_`.
+    pub method: String,
+    /// The flag of the experiment.
+    pub experiment: String,
+    /// When the experiment is active.
+    pub activation: ExperimentActivation,
+}
+
+impl FromStr for MethodGate {
+    type Err = String;
+
+    /// Parses a gate in the form `<method>=<experiment>:<activation>`, e.g.
+    /// `wallet_=exp2:1700000000`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (method, experiment) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <method>=<experiment>:<activation>, got {s}"))?;
+        let (experiment, activation) = experiment
+            .split_once(':')
+            .ok_or_else(|| format!("expected <method>=<experiment>:<activation>, got {s}"))?;
+        if method.is_empty() || experiment.is_empty() {
+            return Err(format!("expected <method>=<experiment>:<activation>, got {s}"));
+        }
+        Ok(Self {
+            method: method.to_string(),
+            experiment: experiment.to_string(),
+            activation: activation.parse()?,
+        })
+    }
+}
+
+impl MethodGate {
+    /// Returns whether the gate applies to the method with the given name.
+    fn applies_to(&self, method: &str) -> bool {
+        if self.method.ends_with('_') {
+            method.starts_with(&self.method)
+        } else {
+            method == self.method
+        }
+    }
+}
+
+/// The methods that are gated behind experiment flags.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExperimentGates {
+    gates: Vec<MethodGate>,
+}
+
+impl FromIterator<MethodGate> for ExperimentGates {
+    fn from_iter<I: IntoIterator<Item = MethodGate>>(iter: I) -> Self {
+        Self { gates: iter.into_iter().collect() }
+    }
+}
+
+impl ExperimentGates {
+    /// Gates a method behind the flag of an experiment.
+    ///
+    /// A whole namespace is gated by passing its prefix, e.g. `wallet_`.
+    pub fn gate(
+        mut self,
+        method: impl Into<String>,
+        experiment: impl Into<String>,
+        activation: ExperimentActivation,
+    ) -> Self {
+        self.gates.push(MethodGate {
+            method: method.into(),
+            experiment: experiment.into(),
+            activation,
+        });
+        self
+    }
+
+    /// Returns whether no methods are gated.
+    pub const fn is_empty(&self) -> bool {
+        self.gates.is_empty()
+    }
+
+    /// Returns the reason the method can not be called at the given unix timestamp in seconds,
+    /// or `None` if all experiments gating the method are active.
+    pub fn check(&self, method: &str, timestamp: u64) -> Option<ExperimentNotEnabled> {
+        self.gates
+            .iter()
+            .find(|gate| gate.applies_to(method) && !gate.activation.is_active_at(timestamp))
+            .map(|gate| {
+                let (status, activation_timestamp) = match gate.activation {
+                    ExperimentActivation::Timestamp(timestamp) => {
+                        (ExperimentStatus::Scheduled, Some(timestamp))
+                    }
+                    _ => (ExperimentStatus::Disabled, None),
+                };
+                ExperimentNotEnabled {
+                    experiment: gate.experiment.clone(),
+                    status,
+                    activation_timestamp,
+                }
+            })
+    }
+}
+
+/// RPC middleware that rejects calls to methods of experiments that are not active.
+#[derive(Debug, Clone)]
+pub struct ExperimentGateLayer {
+    gates: Arc<ExperimentGates>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ExperimentGateLayer {
+    /// Creates a new middleware with the given gates.
+    pub fn new(gates: ExperimentGates) -> Self {
+        Self::with_clock(gates, Arc::new(SystemClock))
+    }
+
+    /// Creates a new middleware with the given gates, that reads the time from the given
+    /// [`Clock`].
+    pub fn with_clock(gates: ExperimentGates, clock: Arc<dyn Clock>) -> Self {
+        Self { gates: Arc::new(gates), clock }
+    }
+}
+
+impl<S> Layer<S> for ExperimentGateLayer {
+    type Service = ExperimentGate<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        ExperimentGate { service, gates: self.gates.clone(), clock: self.clock.clone() }
+    }
+}
+
+/// The service created by [`ExperimentGateLayer`].
+#[derive(Debug, Clone)]
+pub struct ExperimentGate<S> {
+    service: S,
+    gates: Arc<ExperimentGates>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<'a, S> RpcServiceT<'a> for ExperimentGate<S>
+where
+    S: RpcServiceT<'a> + Send + Sync + 'a,
+    S::Future: Send,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let timestamp = self.clock.unix_epoch_ms() / 1000;
+        let Some(reason) = self.gates.check(request.method_name(), timestamp) else {
+            return self.service.call(request).boxed();
+        };
+
+        debug!(
+            target: "rpc::wallet",
+            method = request.method_name(),
+            experiment = %reason.experiment,
+            "Rejecting call to gated method"
+        );
+        let response = MethodResponse::error(
+            request.id,
+            ErrorObject::owned(
+                EXPERIMENT_NOT_ENABLED_CODE,
+                EXPERIMENT_NOT_ENABLED_MSG,
+                Some(reason),
+            ),
+        );
+        futures::future::ready(response).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_gates() {
+        let gates = ExperimentGates::default()
+            .gate("wallet_", "exp2", ExperimentActivation::Timestamp(100))
+            .gate("odyssey_sendTransaction", "exp1", ExperimentActivation::Disabled)
+            .gate("odyssey_feeHistory", "exp1", ExperimentActivation::Enabled);
+
+        let reason = gates.check("wallet_getDelegators", 99).unwrap();
+        assert_eq!(reason.experiment, "exp2");
+        assert_eq!(reason.status, ExperimentStatus::Scheduled);
+        assert_eq!(reason.activation_timestamp, Some(100));
+        assert!(gates.check("wallet_getDelegators", 100).is_none());
+
+        let reason = gates.check("odyssey_sendTransaction", 100).unwrap();
+        assert_eq!(reason.status, ExperimentStatus::Disabled);
+        assert!(gates.check("odyssey_feeHistory", 0).is_none());
+        assert!(gates.check("odyssey_sendTransactionSync", 0).is_none());
+    }
+
+    #[test]
+    fn parses_gates() {
+        let gate: MethodGate = "wallet_=exp2:100".parse().unwrap();
+        assert_eq!(
+            gate,
+            MethodGate {
+                method: "wallet_".to_string(),
+                experiment: "exp2".to_string(),
+                activation: ExperimentActivation::Timestamp(100),
+            }
+        );
+        let gate: MethodGate = "odyssey_sendTransaction=exp1:disabled".parse().unwrap();
+        assert_eq!(gate.activation, ExperimentActivation::Disabled);
+
+        assert!("wallet_".parse::<MethodGate>().is_err());
+        assert!("wallet_=exp2".parse::<MethodGate>().is_err());
+        assert!("wallet_=exp2:soon".parse::<MethodGate>().is_err());
+        assert!("=exp2:enabled".parse::<MethodGate>().is_err());
+    }
+
+    #[test]
+    fn serializes_reason() {
+        let reason = ExperimentNotEnabled {
+            experiment: "exp2".to_string(),
+            status: ExperimentStatus::Scheduled,
+            activation_timestamp: Some(100),
+        };
+        assert_eq!(
+            serde_json::to_value(&reason).unwrap(),
+            serde_json::json!({
+                "experiment": "exp2",
+                "status": "scheduled",
+                "activationTimestamp": "0x64"
+            })
+        );
+    }
+}
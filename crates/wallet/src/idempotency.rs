@@ -0,0 +1,261 @@
+//! Idempotency keys of sponsorship requests.
+//!
+//! Clients can pass an idempotency key to `odyssey_sendTransaction`. The hash of the sponsored
+//! transaction is stored under the key, and calls with a key that was already used return that
+//! hash instead of sponsoring another transaction, so clients can safely retry requests whose
+//! response was lost. Unlike deduplicating by content, identical requests with different keys, or
+//! without a key, are sponsored every time.
+//!
+//! Keys are scoped to the account the request is sponsored for, so accounts can not look up or
+//! block the keys of other accounts, and are bound to the request they were first used for: a call
+//! that reuses a key for a different request is rejected. Keys are at most
+//! [`MAX_IDEMPOTENCY_KEY_LENGTH`] bytes long, and at most [`MAX_IDEMPOTENCY_KEYS`] keys are kept.
+//!
+//! Concurrent calls with the same key wait for the first call, until their deadline passes or for
+//! at most [`MAX_IDEMPOTENCY_WAIT`]. If the first call fails, the key is not stored and the next
+//! call is sponsored as usual. Keys are kept until their time-to-live elapses.
+
+use crate::{deadline::Deadline, OdysseyWalletError};
+use alloy_primitives::{Address, TxHash, B256};
+use odyssey_common::Clock;
+use parking_lot::Mutex;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::OwnedMutexGuard;
+
+/// The default time the result of a request with an idempotency key is kept for.
+pub const DEFAULT_IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(3600);
+
+/// The maximum length of an idempotency key in bytes.
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 128;
+
+/// The maximum number of idempotency keys that are kept at once.
+pub const MAX_IDEMPOTENCY_KEYS: usize = 65_536;
+
+/// The maximum time a call waits for a concurrent call with the same idempotency key.
+const MAX_IDEMPOTENCY_WAIT: Duration = Duration::from_secs(30);
+
+/// The hash of the transaction sponsored for a key, and the time after which the key expires.
+type IdempotencyState = Arc<tokio::sync::Mutex<Option<(TxHash, Instant)>>>;
+
+/// A key, and the hash of the request it was used for.
+#[derive(Debug)]
+struct IdempotencyEntry {
+    request_hash: B256,
+    state: IdempotencyState,
+}
+
+impl IdempotencyEntry {
+    /// Returns whether no call holds the key and no transaction is stored for it at the given
+    /// time, i.e. the key can be used for another request.
+    fn is_unused(&self, now: Instant) -> bool {
+        self.state.try_lock().is_ok_and(|state| state.map_or(true, |(_, expires)| expires <= now))
+    }
+}
+
+/// The result of claiming an idempotency key.
+#[derive(Debug)]
+pub(crate) enum IdempotencyClaim {
+    /// A transaction was already sponsored for the key.
+    Sent(TxHash),
+    /// The key is not used yet. Concurrent calls with the key wait until the claim is dropped.
+    New(IdempotencyGuard),
+}
+
+/// An idempotency key that was claimed by a call.
+#[derive(Debug)]
+pub(crate) struct IdempotencyGuard {
+    entry: OwnedMutexGuard<Option<(TxHash, Instant)>>,
+    expires_at: Instant,
+}
+
+impl IdempotencyGuard {
+    /// Stores the hash of the transaction sponsored for the key.
+    pub(crate) fn sent(mut self, tx_hash: TxHash) {
+        *self.entry = Some((tx_hash, self.expires_at));
+    }
+}
+
+/// Stores the transactions sponsored for idempotency keys until the keys expire.
+#[derive(Debug)]
+pub(crate) struct IdempotencyStore {
+    clock: Arc<dyn Clock>,
+    ttl: Duration,
+    entries: Mutex<HashMap<(Address, String), IdempotencyEntry>>,
+}
+
+impl IdempotencyStore {
+    /// Creates a new store that reads the time from the given [`Clock`], and keeps keys for the
+    /// given duration.
+    pub(crate) fn new(clock: Arc<dyn Clock>, ttl: Duration) -> Self {
+        Self { clock, ttl, entries: Default::default() }
+    }
+
+    /// Claims the key of the account for the request with the given hash, waiting for concurrent
+    /// calls with the same key until the deadline passes.
+    pub(crate) async fn claim(
+        &self,
+        account: Address,
+        key: String,
+        request_hash: B256,
+        deadline: &Deadline,
+    ) -> Result<IdempotencyClaim, OdysseyWalletError> {
+        if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+            return Err(OdysseyWalletError::InvalidIdempotencyKey);
+        }
+
+        let state = {
+            let mut entries = self.entries.lock();
+            let len = entries.len();
+            match entries.entry((account, key)) {
+                Entry::Occupied(mut entry) => {
+                    if entry.get().request_hash != request_hash {
+                        if !entry.get().is_unused(self.clock.now()) {
+                            return Err(OdysseyWalletError::IdempotencyKeyReused);
+                        }
+                        entry.insert(IdempotencyEntry { request_hash, state: Default::default() });
+                    }
+                    entry.get().state.clone()
+                }
+                Entry::Vacant(entry) => {
+                    if len >= MAX_IDEMPOTENCY_KEYS {
+                        return Err(OdysseyWalletError::TooManyIdempotencyKeys);
+                    }
+                    entry
+                        .insert(IdempotencyEntry { request_hash, state: Default::default() })
+                        .state
+                        .clone()
+                }
+            }
+        };
+
+        let state = deadline
+            .run(tokio::time::timeout(MAX_IDEMPOTENCY_WAIT, state.lock_owned()))
+            .await?
+            .map_err(|_| OdysseyWalletError::IdempotencyKeyInUse)?;
+        let now = self.clock.now();
+        if let Some((tx_hash, expires_at)) = *state {
+            if expires_at > now {
+                return Ok(IdempotencyClaim::Sent(tx_hash));
+            }
+        }
+        Ok(IdempotencyClaim::New(IdempotencyGuard { entry: state, expires_at: now + self.ttl }))
+    }
+
+    /// Removes expired keys, and keys whose call failed, returning the number of removed keys.
+    ///
+    /// Keys that are claimed by a call in progress are kept.
+    pub(crate) fn remove_expired(&self) -> usize {
+        let now = self.clock.now();
+        let mut entries = self.entries.lock();
+        let len = entries.len();
+        entries.retain(|_, entry| !entry.is_unused(now));
+        len - entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odyssey_common::MockClock;
+
+    const ACCOUNT: Address = Address::with_last_byte(1);
+    const REQUEST: B256 = B256::with_last_byte(1);
+
+    async fn claim(store: &IdempotencyStore, key: &str) -> IdempotencyClaim {
+        store.claim(ACCOUNT, key.to_string(), REQUEST, &Deadline::default()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn returns_sponsored_transactions() {
+        let clock = Arc::new(MockClock::default());
+        let store = IdempotencyStore::new(clock.clone(), Duration::from_secs(60));
+        let tx_hash = TxHash::with_last_byte(1);
+
+        let IdempotencyClaim::New(guard) = claim(&store, "a").await else {
+            panic!("key is not used yet")
+        };
+        guard.sent(tx_hash);
+        assert!(matches!(
+            claim(&store, "a").await,
+            IdempotencyClaim::Sent(sent) if sent == tx_hash
+        ));
+
+        // a failed call does not use the key
+        drop(claim(&store, "b").await);
+        assert!(matches!(claim(&store, "b").await, IdempotencyClaim::New(_)));
+
+        // keys expire
+        clock.advance(Duration::from_secs(60));
+        assert!(matches!(claim(&store, "a").await, IdempotencyClaim::New(_)));
+        assert_eq!(store.remove_expired(), 2);
+    }
+
+    #[tokio::test]
+    async fn scopes_keys_to_accounts_and_requests() {
+        let store = IdempotencyStore::new(Arc::new(MockClock::default()), Duration::from_secs(60));
+        let IdempotencyClaim::New(guard) = claim(&store, "a").await else {
+            panic!("key is not used yet")
+        };
+        guard.sent(TxHash::with_last_byte(1));
+
+        // the key of another account is independent
+        let other = store
+            .claim(Address::with_last_byte(2), "a".to_string(), REQUEST, &Deadline::default())
+            .await
+            .unwrap();
+        assert!(matches!(other, IdempotencyClaim::New(_)));
+        drop(other);
+
+        // the key can not be reused for another request
+        let reused = store
+            .claim(ACCOUNT, "a".to_string(), B256::with_last_byte(2), &Deadline::default())
+            .await;
+        assert!(matches!(reused, Err(OdysseyWalletError::IdempotencyKeyReused)));
+
+        // unless the call it was used for failed
+        drop(claim(&store, "b").await);
+        let other_request = store
+            .claim(ACCOUNT, "b".to_string(), B256::with_last_byte(2), &Deadline::default())
+            .await;
+        assert!(matches!(other_request, Ok(IdempotencyClaim::New(_))));
+
+        let long_key = "a".repeat(MAX_IDEMPOTENCY_KEY_LENGTH + 1);
+        let rejected = store.claim(ACCOUNT, long_key, REQUEST, &Deadline::default()).await;
+        assert!(matches!(rejected, Err(OdysseyWalletError::InvalidIdempotencyKey)));
+    }
+
+    #[tokio::test]
+    async fn waits_for_concurrent_calls() {
+        let store = Arc::new(IdempotencyStore::new(
+            Arc::new(MockClock::default()),
+            DEFAULT_IDEMPOTENCY_KEY_TTL,
+        ));
+        let IdempotencyClaim::New(guard) = claim(&store, "a").await else {
+            panic!("key is not used yet")
+        };
+
+        let concurrent = tokio::spawn({
+            let store = store.clone();
+            async move { claim(&store, "a").await }
+        });
+        // keys claimed by a call in progress are kept
+        assert_eq!(store.remove_expired(), 0);
+        guard.sent(TxHash::with_last_byte(1));
+        assert!(matches!(concurrent.await.unwrap(), IdempotencyClaim::Sent(_)));
+    }
+
+    #[tokio::test]
+    async fn stops_waiting_at_the_deadline() {
+        let store = IdempotencyStore::new(Arc::new(MockClock::default()), Duration::from_secs(60));
+        let _guard = claim(&store, "a").await;
+
+        // the deadline passes 50ms from now
+        let deadline = Deadline::new(&MockClock::new(999_950), Some(1_000)).unwrap();
+        let waiting = store.claim(ACCOUNT, "a".to_string(), REQUEST, &deadline).await;
+        assert!(matches!(waiting, Err(OdysseyWalletError::RequestExpired(_))));
+    }
+}
@@ -0,0 +1,263 @@
+//! Time-to-inclusion tracking of sponsored transactions.
+//!
+//! The time from accepting a sponsored transaction until it is included in a block is recorded per
+//! transaction, so operators can verify that sponsorship meets a latency SLA. Inclusion is either
+//! observed from canonical state notifications, or from the receipt once it becomes available.
+
+use alloy_primitives::TxHash;
+use futures::{Stream, StreamExt};
+use metrics::{Counter, Histogram};
+use metrics_derive::Metrics;
+use odyssey_common::{Clock, SystemClock};
+use parking_lot::Mutex;
+use reth_chain_state::CanonStateNotification;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The default number of recent inclusion times the statistics are computed over.
+pub const DEFAULT_INCLUSION_SAMPLES: usize = 1024;
+
+/// Statistics about the inclusion times of recent sponsored transactions.
+///
+/// All durations are in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InclusionStats {
+    /// The number of sponsored transactions that are not included yet.
+    pub pending: u64,
+    /// The number of inclusion times the statistics are computed over.
+    pub samples: u64,
+    /// The mean inclusion time.
+    pub mean_ms: u64,
+    /// The median inclusion time.
+    pub p50_ms: u64,
+    /// The 90th percentile of the inclusion times.
+    pub p90_ms: u64,
+    /// The 99th percentile of the inclusion times.
+    pub p99_ms: u64,
+    /// The maximum inclusion time.
+    pub max_ms: u64,
+}
+
+/// Tracks the time from submission to inclusion of sponsored transactions.
+#[derive(Debug, Clone)]
+pub struct InclusionTracker {
+    inner: Arc<InclusionTrackerInner>,
+}
+
+#[derive(Debug)]
+struct InclusionTrackerInner {
+    clock: Arc<dyn Clock>,
+    max_samples: usize,
+    state: Mutex<InclusionState>,
+    metrics: InclusionMetrics,
+}
+
+#[derive(Debug, Default)]
+struct InclusionState {
+    /// The submission time of transactions that are not included yet.
+    submitted: HashMap<TxHash, Instant>,
+    /// The most recent inclusion times, oldest first.
+    samples: VecDeque<Duration>,
+}
+
+impl InclusionTracker {
+    /// Creates a new tracker that reads the time from the given [`Clock`] and computes its
+    /// statistics over the given number of recent inclusion times.
+    pub fn new(clock: Arc<dyn Clock>, max_samples: usize) -> Self {
+        Self {
+            inner: Arc::new(InclusionTrackerInner {
+                clock,
+                max_samples,
+                state: Default::default(),
+                metrics: Default::default(),
+            }),
+        }
+    }
+
+    /// Records that the transaction was accepted.
+    pub fn submitted(&self, tx_hash: TxHash) {
+        let now = self.inner.clock.now();
+        self.inner.state.lock().submitted.insert(tx_hash, now);
+    }
+
+    /// Records that the transaction was included in a block.
+    ///
+    /// Returns the time it took to include the transaction, or `None` if the transaction is not
+    /// tracked, or its inclusion was already recorded.
+    pub fn included(&self, tx_hash: &TxHash) -> Option<Duration> {
+        let mut state = self.inner.state.lock();
+        let submitted = state.submitted.remove(tx_hash)?;
+        let elapsed = self.inner.clock.now().saturating_duration_since(submitted);
+
+        if state.samples.len() >= self.inner.max_samples {
+            state.samples.pop_front();
+        }
+        state.samples.push_back(elapsed);
+        self.inner.metrics.inclusion_time.record(elapsed.as_secs_f64());
+
+        Some(elapsed)
+    }
+
+    /// Returns the time since the transaction was accepted, or `None` if the transaction is not
+    /// tracked, or was already included.
+    pub fn age(&self, tx_hash: &TxHash) -> Option<Duration> {
+        let submitted = *self.inner.state.lock().submitted.get(tx_hash)?;
+        Some(self.inner.clock.now().saturating_duration_since(submitted))
+    }
+
+    /// Stops tracking a transaction that was not included in time.
+    pub fn dropped(&self, tx_hash: &TxHash) {
+        if self.inner.state.lock().submitted.remove(tx_hash).is_some() {
+            self.inner.metrics.dropped_transactions.increment(1);
+        }
+    }
+
+    /// Stops tracking transactions that were accepted longer than the given duration ago, returning
+    /// the number of transactions that are no longer tracked.
+    pub fn remove_expired(&self, ttl: Duration) -> usize {
+        let now = self.inner.clock.now();
+        let mut state = self.inner.state.lock();
+        let len = state.submitted.len();
+        state.submitted.retain(|_, submitted| now.saturating_duration_since(*submitted) < ttl);
+        let expired = len - state.submitted.len();
+        self.inner.metrics.dropped_transactions.increment(expired as u64);
+        expired
+    }
+
+    /// Returns the statistics about the inclusion times of recent sponsored transactions.
+    pub fn stats(&self) -> InclusionStats {
+        let state = self.inner.state.lock();
+        let mut samples: Vec<_> = state.samples.iter().map(|d| d.as_millis() as u64).collect();
+        samples.sort_unstable();
+
+        let percentile = |p: usize| {
+            // nearest-rank method
+            let rank = (samples.len() * p).div_ceil(100);
+            samples.get(rank.saturating_sub(1)).copied().unwrap_or_default()
+        };
+
+        InclusionStats {
+            pending: state.submitted.len() as u64,
+            samples: samples.len() as u64,
+            mean_ms: samples
+                .iter()
+                .sum::<u64>()
+                .checked_div(samples.len() as u64)
+                .unwrap_or_default(),
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+            p99_ms: percentile(99),
+            max_ms: samples.last().copied().unwrap_or_default(),
+        }
+    }
+
+    /// Spawns a task that records the inclusion of tracked transactions from canonical state
+    /// notifications.
+    pub fn spawn<St>(self, mut st: St)
+    where
+        St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
+    {
+        tokio::task::spawn(async move {
+            while let Some(notification) = st.next().await {
+                for block in notification.committed().blocks_iter() {
+                    for tx in &block.body.transactions {
+                        self.included(&tx.hash());
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl Default for InclusionTracker {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemClock), DEFAULT_INCLUSION_SAMPLES)
+    }
+}
+
+/// Metrics for the inclusion of sponsored transactions.
+#[derive(Metrics)]
+#[metrics(scope = "wallet")]
+struct InclusionMetrics {
+    /// Time from accepting a sponsored transaction until it is included in a block, in seconds
+    inclusion_time: Histogram,
+    /// Number of sponsored transactions that were not included in time
+    dropped_transactions: Counter,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odyssey_common::MockClock;
+
+    #[test]
+    fn records_inclusion_time() {
+        let clock = Arc::new(MockClock::default());
+        let tracker = InclusionTracker::new(clock.clone(), 8);
+        let (a, b) = (TxHash::with_last_byte(1), TxHash::with_last_byte(2));
+
+        tracker.submitted(a);
+        tracker.submitted(b);
+        assert_eq!(tracker.stats().pending, 2);
+
+        clock.advance(Duration::from_millis(1_000));
+        assert_eq!(tracker.age(&a), Some(Duration::from_millis(1_000)));
+        assert_eq!(tracker.included(&a), Some(Duration::from_millis(1_000)));
+        // inclusion is only recorded once
+        assert_eq!(tracker.included(&a), None);
+        assert_eq!(tracker.age(&a), None);
+
+        clock.advance(Duration::from_millis(2_000));
+        assert_eq!(tracker.included(&b), Some(Duration::from_millis(3_000)));
+
+        assert_eq!(
+            tracker.stats(),
+            InclusionStats {
+                pending: 0,
+                samples: 2,
+                mean_ms: 2_000,
+                p50_ms: 1_000,
+                p90_ms: 3_000,
+                p99_ms: 3_000,
+                max_ms: 3_000,
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_recent_samples() {
+        let clock = Arc::new(MockClock::default());
+        let tracker = InclusionTracker::new(clock.clone(), 2);
+
+        for i in 1..=3 {
+            let tx_hash = TxHash::with_last_byte(i);
+            tracker.submitted(tx_hash);
+            clock.advance(Duration::from_millis(i as u64 * 100));
+            tracker.included(&tx_hash);
+        }
+
+        let stats = tracker.stats();
+        assert_eq!(stats.samples, 2);
+        assert_eq!(stats.p50_ms, 200);
+        assert_eq!(stats.max_ms, 300);
+
+        let dropped = TxHash::with_last_byte(4);
+        tracker.submitted(dropped);
+        tracker.dropped(&dropped);
+        assert_eq!(tracker.stats().pending, 0);
+        assert_eq!(tracker.included(&dropped), None);
+
+        let expired = TxHash::with_last_byte(5);
+        tracker.submitted(expired);
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(tracker.remove_expired(Duration::from_millis(1_000)), 0);
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(tracker.remove_expired(Duration::from_millis(1_000)), 1);
+        assert_eq!(tracker.included(&expired), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! RPC middleware for methods that are served by another server.
+//!
+//! The middleware of the standalone wallet server is configured on the server, but the RPC server
+//! of the node does not accept custom middleware. [`layer_methods`] applies the middleware to the
+//! methods themselves instead, so the same layers run for the methods that are merged into the
+//! RPC server of the node.
+
+use futures::future::{BoxFuture, FutureExt};
+use jsonrpsee::{
+    core::server::{AsyncMethod, MethodCallback, SyncMethod},
+    server::{middleware::rpc::RpcServiceT, MethodResponse},
+    types::{Params, Request, TwoPointZero},
+    ConnectionId, Methods,
+};
+use serde_json::value::RawValue;
+use std::{borrow::Cow, sync::Arc};
+use tower::Layer;
+
+/// Returns the methods with the middleware applied to every call.
+///
+/// Subscriptions are left untouched.
+pub fn layer_methods<L>(methods: impl Into<Methods>, layer: L) -> Methods
+where
+    L: Layer<LayeredMethod> + Send + Sync + 'static,
+    L::Service: RpcServiceT<'static, Future = BoxFuture<'static, MethodResponse>>,
+{
+    let methods = methods.into();
+    let layer = Arc::new(layer);
+    let mut layered = Methods::new();
+    for name in methods.method_names() {
+        let handler = match methods.method(name) {
+            Some(MethodCallback::Sync(callback)) => Handler::Sync(callback.clone()),
+            Some(MethodCallback::Async(callback)) => Handler::Async(callback.clone()),
+            Some(callback) => {
+                layered.verify_and_insert(name, callback.clone()).expect("method names are unique");
+                continue;
+            }
+            None => continue,
+        };
+        let layer = layer.clone();
+        let callback = MethodCallback::Async(Arc::new(
+            move |id, params, conn_id, max_response_size, extensions| {
+                let service = layer.layer(LayeredMethod {
+                    handler: handler.clone(),
+                    conn_id,
+                    max_response_size,
+                });
+                service.call(Request {
+                    jsonrpc: TwoPointZero,
+                    id,
+                    method: Cow::Borrowed(name),
+                    // the parameters were parsed by the server, so they are valid JSON
+                    params: params
+                        .as_str()
+                        .and_then(|params| RawValue::from_string(params.to_string()).ok())
+                        .map(Cow::Owned),
+                    extensions,
+                })
+            },
+        ));
+        layered.verify_and_insert(name, callback).expect("method names are unique");
+    }
+    layered
+}
+
+/// The handler of a method call.
+#[derive(Clone)]
+enum Handler {
+    Sync(SyncMethod),
+    Async(AsyncMethod<'static>),
+}
+
+/// The innermost service of the middleware applied by [`layer_methods`], which calls the method.
+#[derive(Clone)]
+pub struct LayeredMethod {
+    handler: Handler,
+    conn_id: ConnectionId,
+    max_response_size: usize,
+}
+
+impl std::fmt::Debug for LayeredMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LayeredMethod")
+            .field("conn_id", &self.conn_id)
+            .field("max_response_size", &self.max_response_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> RpcServiceT<'a> for LayeredMethod {
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let Request { id, params, extensions, .. } = request;
+        let params = Params::new(params.as_ref().map(|params| params.get()));
+        match &self.handler {
+            Handler::Sync(callback) => {
+                futures::future::ready(callback(id, params, self.max_response_size, extensions))
+                    .boxed()
+            }
+            Handler::Async(callback) => callback(
+                id.into_owned(),
+                params.into_owned(),
+                self.conn_id,
+                self.max_response_size,
+                extensions,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LoadShedConfig, LoadShedLayer};
+    use jsonrpsee::{
+        core::params::ArrayParams, types::error::SERVER_IS_BUSY_CODE, MethodsError, RpcModule,
+    };
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn applies_middleware() {
+        let mut module = RpcModule::new(());
+        module.register_method("odyssey_sync", |_, _, _| "sync").unwrap();
+        module.register_async_method("odyssey_async", |_, _, _| async { "async" }).unwrap();
+        let layer = |max_in_flight| {
+            LoadShedLayer::new(LoadShedConfig { max_in_flight, ..Default::default() })
+        };
+
+        let methods = layer_methods(module.clone(), layer(1));
+        let shed = layer_methods(module, layer(0));
+        for (method, result) in [("odyssey_sync", "sync"), ("odyssey_async", "async")] {
+            assert_eq!(
+                methods.call::<_, String>(method, ArrayParams::new()).await.unwrap(),
+                result
+            );
+
+            let err = shed.call::<_, Value>(method, ArrayParams::new()).await.unwrap_err();
+            assert!(matches!(err, MethodsError::JsonRpc(err) if err.code() == SERVER_IS_BUSY_CODE));
+        }
+    }
+}
@@ -4,6 +4,20 @@
 //!
 //! - `odyssey_sendTransaction` that can perform service-sponsored [EIP-7702][eip-7702] delegations
 //!   and send other service-sponsored transactions on behalf of EOAs with delegated code.
+//! - `odyssey_feeHistory` that returns the fee history of the chain along with the fees the service
+//!   would currently pay to sponsor a transaction.
+//! - `wallet_prepareCalls` and `wallet_sendPreparedCalls` that split `odyssey_sendTransaction` in
+//!   two steps: the service returns the transaction it would sign, and only sends it once the
+//!   account approves it with a signature.
+//! - `wallet_getQuote` and `wallet_sendQuotedTransaction` that commit the sponsor to the gas limit
+//!   and fees of a transaction for a limited time with a signed quote.
+//! - `wallet_getDelegators` that returns the sponsored accounts delegating to an implementation.
+//! - `wallet_pendingSponsoredTransactions` that lists the transactions of the sponsor that are
+//!   waiting in the transaction pool.
+//! - `wallet_simulateSponsoredTransaction` that validates and executes a sponsored transaction with
+//!   optional state overrides, without signing or sending it.
+//! - `wallet_subscribeMyTransactions` that streams status updates of the sponsored transactions of
+//!   an authority over WebSocket.
 //!
 //! # Restrictions
 //!
@@ -11,33 +25,303 @@
 //! rudimentary abuse of the service's funds. For example, transactions cannot contain any
 //! `value`.
 //!
+//! The outcome of every sponsored transaction is tracked per account, and accounts whose sponsored
+//! transactions revert too often are banned from sponsorship. Banned accounts can be inspected and
+//! unbanned using the `admin_` namespace in [`OdysseyWalletAdminApiServer`].
+//!
+//! Authorities, destinations, implementations and function selectors can be allowed or denied
+//! with persistent access lists that are managed through the `admin_` namespace, see
+//! [`AccessControl`].
+//!
+//! Authorizations with a chain ID of 0 are valid on every chain, and can be rejected or restricted
+//! to trusted implementations, see [`WildcardAuthorizationPolicy`].
+//!
+//! The number of outstanding requests per account is limited. Admitted requests are estimated
+//! concurrently, and once they are ready to be sent they are queued in a FIFO lane per account.
+//! Lanes are served in round-robin order, so that a single account can not monopolize the budget
+//! and nonces of the sponsor. Only waiting for budget, signing and sending is serialized.
+//!
+//! The gas of sponsored transactions waiting for inclusion can be limited to a per-block budget,
+//! see [`SponsorBudget`].
+//!
+//! The time from accepting a sponsored transaction until it is included in a block is tracked, and
+//! exported as metrics and through `wallet_getInclusionStats`.
+//! The fees paid by sponsored transactions are exported as metrics and through
+//! `wallet_getSponsorFeeStats` as well, see [`SponsorFeeTracker`]. The sponsored transactions and
+//! their gas can be exported per delegation implementation as well, see
+//! [`OdysseyWalletConfig::tracked_implementations`].
+//!
+//! The delegations of sponsored destinations can be cached until their code changes, saving the
+//! reads of their code on the hot path, see [`DelegationCache`].
+//!
+//! The wallet server, and the RPC server of the node through [`layer_methods`], shed calls by
+//! priority under overload, see [`LoadShedLayer`].
+//! Methods of experiments can be gated behind experiment flags, see [`ExperimentGateLayer`].
+//! Transaction requests are validated before they are deserialized, so common mistakes in the
+//! encoding of authorizations are reported with the offending field, see
+//! [`RequestValidationLayer`]. The raw requests and responses of the wallet server can be logged
+//! for debugging, see [`RpcDebugLogLayer`]. The same middleware can be applied to methods that are
+//! served by the RPC server of the node, see [`layer_methods`].
+//!
+//! The relay can send transactions through multiple upstream nodes, failing over between them with
+//! [`FailoverTransport`]. While the upstream is unavailable, new requests are rejected and queued
+//! requests wait for it to recover, see [`AvailabilityConfig`].
+//!
+//! Nodes that do not sponsor transactions themselves can forward the namespace to another wallet
+//! service using [`OdysseyWalletForwarder`].
+//!
 //! [eip-5792]: https://eips.ethereum.org/EIPS/eip-5792
 //! [eip-7702]: https://eips.ethereum.org/EIPS/eip-7702
 
 #![cfg_attr(not(test), warn(unused_crate_dependencies))]
 
+use alloy_consensus::{SignableTransaction, Transaction as _, TypedTransaction};
 use alloy_network::{
-    eip2718::Encodable2718, Ethereum, EthereumWallet, NetworkWallet, TransactionBuilder,
+    eip2718::Encodable2718, Ethereum, EthereumWallet, NetworkWallet, ReceiptResponse,
+    TransactionBuilder,
+};
+use alloy_primitives::{
+    address, eip191_hash_message, keccak256, Address, Bytes, ChainId, PrimitiveSignature, Selector,
+    TxHash, TxKind, B256, U256, U64,
+};
+use alloy_provider::{ext::TxPoolApi, utils::Eip1559Estimation, Provider, WalletProvider};
+use alloy_rpc_types::{
+    simulate::{SimBlock, SimCallResult, SimulatePayload, SimulatedBlock},
+    state::{AccountOverride, EvmOverrides, StateOverride},
+    BlockId, BlockNumberOrTag, FeeHistory, SignedAuthorization, TransactionReceipt,
+    TransactionRequest,
 };
-use alloy_primitives::{Address, Bytes, ChainId, TxHash, TxKind, U256};
-use alloy_provider::{utils::Eip1559Estimation, Provider, WalletProvider};
-use alloy_rpc_types::{BlockId, TransactionRequest};
+use alloy_signer::SignerSync;
+use alloy_signer_local::PrivateKeySigner;
+use alloy_sol_types::{sol, SolCall};
 use alloy_transport::Transport;
 use jsonrpsee::{
-    core::{async_trait, RpcResult},
+    core::{async_trait, RpcResult, SubscriptionResult},
     proc_macros::rpc,
+    PendingSubscriptionSink,
 };
 use metrics::Counter;
 use metrics_derive::Metrics;
+use odyssey_common::{delegation_designator, parse_delegation_designator, Clock, SystemClock};
 
-use reth_rpc_eth_api::helpers::{EthCall, EthTransactions, FullEthApi, LoadFee, LoadState};
-use reth_storage_api::StateProviderFactory;
+use reth_rpc_eth_api::{
+    helpers::{EthCall, EthFees, EthTransactions, FullEthApi, LoadFee, LoadState},
+    RpcNodeCore,
+};
+use reth_storage_api::{errors::provider::ProviderError, StateProviderFactory};
+use reth_transaction_pool::{PoolTransaction, TransactionPool};
 use serde::{Deserialize, Serialize};
-use std::{marker::PhantomData, sync::Arc};
-use tracing::{trace, warn};
+use std::{
+    collections::HashSet,
+    marker::PhantomData,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+use tracing::{debug, info, instrument, trace, warn};
 
 use reth_optimism_rpc as _;
-use tokio::sync::Mutex;
+
+mod access;
+use access::AccessRequest;
+pub use access::{
+    AccessControl, AccessList, AccessListChange, AccessListKind, AccessLists, AccessMode,
+    WalletCapabilities, DEFAULT_ACCESS_LISTS_RELOAD_INTERVAL,
+};
+
+mod availability;
+use availability::UpstreamAvailability;
+pub use availability::{
+    AvailabilityConfig, DEFAULT_AVAILABILITY_PROBE_INTERVAL, DEFAULT_UNAVAILABLE_THRESHOLD,
+    SERVICE_UNAVAILABLE_CODE,
+};
+
+mod budget;
+pub use budget::{
+    BudgetConfig, SponsorBudget, DEFAULT_BUDGET_WAIT, DEFAULT_MAX_SPONSORED_GAS_PER_BLOCK,
+};
+
+mod deadline;
+use deadline::Deadline;
+
+mod debug_log;
+pub use debug_log::{
+    redact, RpcDebugLog, RpcDebugLogConfig, RpcDebugLogLayer, DEFAULT_REDACTED_FIELDS,
+};
+
+mod delegation_cache;
+pub use delegation_cache::{
+    CachedDelegation, DelegationCache, DelegationCacheConfig, DEFAULT_DELEGATION_CACHE_MAX_AGE,
+    DEFAULT_DELEGATION_CACHE_SIZE,
+};
+
+mod delegators;
+pub use delegators::{
+    DelegationIndex, Delegators, DEFAULT_DELEGATORS_PAGE_SIZE, MAX_DELEGATORS_PAGE_SIZE,
+};
+
+mod deterministic;
+use deterministic::DeterministicSponsor;
+pub use deterministic::{
+    DeterministicConfig, DEFAULT_DETERMINISTIC_MAX_FEE_PER_GAS,
+    DEFAULT_DETERMINISTIC_MAX_PRIORITY_FEE_PER_GAS,
+};
+
+mod dry_run;
+pub use dry_run::{DryRunLog, DryRunTransaction, DEFAULT_DRY_RUN_HISTORY};
+
+mod estimation;
+use estimation::EstimationFallback;
+pub use estimation::{
+    EstimationFallbackConfig, DEFAULT_ESTIMATION_BACKOFF, DEFAULT_ESTIMATION_RETRIES,
+};
+
+mod expiry;
+use expiry::{ExpiredEntries, ExpiryMetrics};
+pub use expiry::{ExpiryConfig, DEFAULT_EXPIRY_INTERVAL, DEFAULT_PENDING_TRANSACTION_TTL};
+
+mod failover;
+pub use failover::{
+    FailoverConfig, FailoverTransport, DEFAULT_HEALTH_CHECK_INTERVAL, DEFAULT_UPSTREAM_RETRIES,
+};
+
+mod fees;
+use fees::receipt_l1_fee;
+pub use fees::{
+    FeePercentiles, SponsorFee, SponsorFeeStats, SponsorFeeTracker, DEFAULT_FEE_SAMPLES,
+};
+
+mod forwarder;
+pub use forwarder::{OdysseyWalletForwarder, DEFAULT_FORWARD_RETRIES, DEFAULT_FORWARD_TIMEOUT};
+
+mod gate;
+pub use gate::{
+    ExperimentActivation, ExperimentGate, ExperimentGateLayer, ExperimentGates,
+    ExperimentNotEnabled, ExperimentStatus, MethodGate, EXPERIMENT_NOT_ENABLED_CODE,
+    EXPERIMENT_NOT_ENABLED_MSG,
+};
+
+mod idempotency;
+use idempotency::{IdempotencyClaim, IdempotencyStore};
+pub use idempotency::{
+    DEFAULT_IDEMPOTENCY_KEY_TTL, MAX_IDEMPOTENCY_KEYS, MAX_IDEMPOTENCY_KEY_LENGTH,
+};
+
+mod inclusion;
+pub use inclusion::{InclusionStats, InclusionTracker, DEFAULT_INCLUSION_SAMPLES};
+
+mod layered;
+pub use layered::{layer_methods, LayeredMethod};
+
+mod normalize;
+pub use normalize::LegacyCompatibility;
+use normalize::{normalize_tx_request, set_fees};
+
+mod pipeline;
+use pipeline::NoncePipeline;
+
+mod prepared;
+use prepared::PreparedCallsStore;
+pub use prepared::{PreparedCalls, DEFAULT_PREPARED_CALLS_TTL, MAX_PREPARED_CALLS_PER_ACCOUNT};
+
+mod queue;
+pub use queue::{
+    PendingRequest, QueueError, QueueLanes, QueuePermit, TransactionQueue,
+    DEFAULT_MAX_PENDING_PER_ACCOUNT,
+};
+
+mod quote;
+use quote::QuoteBook;
+pub use quote::{Quote, SignedQuote, DEFAULT_QUOTE_TTL, MAX_QUOTES_PER_ACCOUNT};
+
+mod refund;
+pub use refund::OVERESTIMATE_RATIO;
+use refund::{GasRefund, GasRefundMetrics};
+
+mod reputation;
+pub use reputation::{
+    AccountReputation, Reputation, ReputationConfig, TransactionOutcome, DEFAULT_MAX_REVERT_RATE,
+    DEFAULT_MIN_SAMPLES, MAX_TRACKED_ACCOUNTS,
+};
+
+mod selectors;
+pub use selectors::{SelectorAllowlist, SelectorRule};
+
+mod server;
+pub use server::{
+    WalletServerConfig, DEFAULT_MAX_CONNECTIONS, DEFAULT_MAX_REQUEST_SIZE_MB,
+    DEFAULT_MAX_RESPONSE_SIZE_MB, DEFAULT_WALLET_HTTP_PORT,
+};
+
+mod shed;
+pub use shed::{
+    LoadShed, LoadShedConfig, LoadShedLayer, MethodClass, DEFAULT_MAX_IN_FLIGHT,
+    DEFAULT_MAX_LATENCY, DEFAULT_SEND_DEADLINE,
+};
+
+mod simulate;
+pub use simulate::{SimulatedCall, SponsoredSimulation};
+
+mod status;
+pub use status::{
+    ChainHeads, SponsoredTransactionStatus, TransactionStatus, TransactionStatusFeed,
+    TransactionStatusUpdate, DEFAULT_STATUS_CHANNEL_CAPACITY,
+};
+
+mod telemetry;
+pub use telemetry::{otlp_layer, TraceContext, TraceContextLayer};
+
+#[cfg(test)]
+mod test_utils;
+
+mod token_fee;
+pub use token_fee::{TokenFeeConfig, TokenFeeLedger, TokenFeeStats};
+
+mod usage;
+use usage::ImplementationUsage;
+
+mod validation;
+pub use validation::{
+    validate_request_params, validated_methods, InvalidField, RequestValidation,
+    RequestValidationLayer,
+};
+
+mod wildcard;
+use wildcard::AuthorizationMetrics;
+pub use wildcard::{WildcardAuthorizationMode, WildcardAuthorizationPolicy};
+
+/// The interval at which the outcome of a sponsored transaction is polled.
+const OUTCOME_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The number of times the outcome of a sponsored transaction is polled before it is checked
+/// whether the transaction is still waiting in the transaction pool.
+const OUTCOME_POLL_ATTEMPTS: usize = 150;
+
+/// The default maximum cost of a sponsored transaction in wei, including the L1 data fee.
+///
+/// This is 0.01 ether.
+pub const DEFAULT_MAX_SPONSORED_COST: U256 = U256::from_limbs([10_000_000_000_000_000, 0, 0, 0]);
+
+/// The default maximum number of authorizations of a sponsored transaction.
+pub const DEFAULT_MAX_AUTHORIZATIONS: usize = 16;
+
+/// The maximum gas of a sponsored transaction, excluding its authorizations.
+const MAX_SPONSORED_GAS: u64 = 350_000;
+
+/// The gas a sponsored transaction may use per authorization, on top of [`MAX_SPONSORED_GAS`].
+///
+/// This is the intrinsic gas of an authorization for an account that does not exist yet.
+const AUTHORIZATION_GAS_ALLOWANCE: u64 = 25_000;
+
+/// The address of the OP Stack gas price oracle predeploy.
+const GAS_PRICE_ORACLE: Address = address!("420000000000000000000000000000000000000F");
+
+sol! {
+    /// The OP Stack gas price oracle predeploy.
+    interface GasPriceOracle {
+        /// Returns the L1 data fee of an unsigned, RLP encoded transaction.
+        function getL1Fee(bytes memory data) external view returns (uint256);
+    }
+}
 
 /// An upstream is capable of estimating, signing, and propagating signed transactions for a
 /// specific chain.
@@ -49,27 +333,139 @@ pub trait Upstream {
     /// Get the code at a specific address.
     async fn get_code(&self, address: Address) -> Result<Bytes, OdysseyWalletError>;
 
-    /// Estimate the transaction request's gas usage and fees.
+    /// Get the nonce of the next transaction of the sponsor, counting the transactions that are
+    /// waiting in the transaction pool.
+    async fn next_nonce(&self) -> Result<u64, OdysseyWalletError>;
+
+    /// Estimate the transaction request's gas usage against the state at the given block with the
+    /// given state overrides.
+    async fn estimate_gas(
+        &self,
+        tx: &TransactionRequest,
+        overrides: &StateOverride,
+        block: BlockId,
+    ) -> Result<u64, OdysseyWalletError>;
+
+    /// Estimate the fees per gas the sponsor would currently pay for a transaction.
+    async fn estimate_fees(&self) -> Result<Eip1559Estimation, OdysseyWalletError>;
+
+    /// Estimate the transaction request's gas usage against the state at the given block with the
+    /// given state overrides, and the current fees.
     async fn estimate(
         &self,
         tx: &TransactionRequest,
-    ) -> Result<(u64, Eip1559Estimation), OdysseyWalletError>;
+        overrides: &StateOverride,
+        block: BlockId,
+    ) -> Result<(u64, Eip1559Estimation), OdysseyWalletError>
+    where
+        Self: Sync,
+    {
+        let (estimate, fee_estimate) =
+            tokio::join!(self.estimate_gas(tx, overrides, block), self.estimate_fees());
+        Ok((estimate?, fee_estimate?))
+    }
+
+    /// Execute the transaction request as a call against the state at the given block.
+    async fn call(
+        &self,
+        tx: &TransactionRequest,
+        block: BlockId,
+    ) -> Result<Bytes, OdysseyWalletError>;
+
+    /// Execute the transaction request as a call against the state at the given block with the
+    /// given state overrides, and estimate its gas usage if it succeeds.
+    ///
+    /// Failed executions are returned as a [`SimulatedCall`] that did not succeed.
+    async fn simulate(
+        &self,
+        tx: &TransactionRequest,
+        overrides: &StateOverride,
+        block: BlockId,
+    ) -> Result<SimulatedCall, OdysseyWalletError>;
+
+    /// Execute the transaction request against the state at the given block with
+    /// `eth_simulateV1`, returning its result along with the logs it emitted.
+    async fn simulate_logs(
+        &self,
+        tx: &TransactionRequest,
+        block: BlockId,
+    ) -> Result<SimCallResult, OdysseyWalletError>;
+
+    /// Estimate the L1 data fee of the transaction request, using the gas price oracle at the given
+    /// block.
+    ///
+    /// The request must be complete except for the nonce.
+    async fn estimate_l1_fee(
+        &self,
+        tx: &TransactionRequest,
+        block: BlockId,
+    ) -> Result<U256, OdysseyWalletError>
+    where
+        Self: Sync,
+    {
+        let data = encode_unsigned(tx.clone())?;
+        let call = TransactionRequest::default()
+            .to(GAS_PRICE_ORACLE)
+            .input(Bytes::from(GasPriceOracle::getL1FeeCall { data }.abi_encode()).into());
+        let output = self.call(&call, block).await?;
+
+        // chains that are not OP Stack chains do not have a gas price oracle
+        if output.is_empty() {
+            return Ok(U256::ZERO);
+        }
+
+        GasPriceOracle::getL1FeeCall::abi_decode_returns(&output, true)
+            .map(|fee| fee._0)
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))
+    }
+
+    /// Get the fee history for the requested range of blocks.
+    async fn fee_history(
+        &self,
+        block_count: u64,
+        newest_block: BlockNumberOrTag,
+        reward_percentiles: Option<Vec<f64>>,
+    ) -> Result<FeeHistory, OdysseyWalletError>;
+
+    /// Sign a hash with the key of the account that sponsors transactions.
+    async fn sign_hash(&self, hash: &B256) -> Result<PrimitiveSignature, OdysseyWalletError>;
 
     /// Sign the transaction request and send it to the upstream.
     async fn sign_and_send(&self, tx: TransactionRequest) -> Result<TxHash, OdysseyWalletError>;
+
+    /// Get the outcome of a transaction, if it was included in a block.
+    async fn get_transaction_outcome(
+        &self,
+        tx_hash: TxHash,
+    ) -> Result<Option<TransactionOutcome>, OdysseyWalletError>;
+
+    /// Get the transactions of the sponsor that are waiting in the transaction pool.
+    ///
+    /// The age of the returned transactions is not set.
+    async fn pending_transactions(
+        &self,
+    ) -> Result<Vec<PendingSponsoredTransaction>, OdysseyWalletError>;
+
+    /// Get the latest block number, and the safe and finalized block numbers of the fork choice.
+    async fn chain_heads(&self) -> Result<ChainHeads, OdysseyWalletError>;
 }
 
 /// A wrapper around an Alloy provider for signing and sending sponsored transactions.
+///
+/// Use a provider with a [`FailoverTransport`] to fail over between multiple nodes.
 #[derive(Debug)]
 pub struct AlloyUpstream<P, T> {
     provider: P,
+    signer: PrivateKeySigner,
     _transport: PhantomData<T>,
 }
 
 impl<P, T> AlloyUpstream<P, T> {
-    /// Create a new [`AlloyUpstream`]
-    pub const fn new(provider: P) -> Self {
-        Self { provider, _transport: PhantomData }
+    /// Create a new [`AlloyUpstream`].
+    ///
+    /// The signer must be the default signer of the provider.
+    pub const fn new(provider: P, signer: PrivateKeySigner) -> Self {
+        Self { provider, signer, _transport: PhantomData }
     }
 }
 
@@ -90,17 +486,108 @@ where
             .map_err(|err| OdysseyWalletError::InternalError(err.into()))
     }
 
-    async fn estimate(
+    async fn next_nonce(&self) -> Result<u64, OdysseyWalletError> {
+        self.provider
+            .get_transaction_count(self.provider.default_signer_address())
+            .pending()
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))
+    }
+
+    async fn estimate_gas(
         &self,
         tx: &TransactionRequest,
-    ) -> Result<(u64, Eip1559Estimation), OdysseyWalletError> {
-        let (estimate, fee_estimate) =
-            tokio::join!(self.provider.estimate_gas(tx), self.provider.estimate_eip1559_fees(None));
+        overrides: &StateOverride,
+        block: BlockId,
+    ) -> Result<u64, OdysseyWalletError> {
+        let mut estimate = self.provider.estimate_gas(tx).block(block);
+        // not every upstream accepts state overrides
+        if !overrides.is_empty() {
+            estimate = estimate.overrides(overrides);
+        }
+        estimate.await.map_err(|err| OdysseyWalletError::InternalError(err.into()))
+    }
+
+    async fn estimate_fees(&self) -> Result<Eip1559Estimation, OdysseyWalletError> {
+        self.provider
+            .estimate_eip1559_fees(None)
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))
+    }
+
+    async fn call(
+        &self,
+        tx: &TransactionRequest,
+        block: BlockId,
+    ) -> Result<Bytes, OdysseyWalletError> {
+        self.provider
+            .call(tx)
+            .block(block)
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))
+    }
+
+    async fn simulate(
+        &self,
+        tx: &TransactionRequest,
+        overrides: &StateOverride,
+        block: BlockId,
+    ) -> Result<SimulatedCall, OdysseyWalletError> {
+        let output = match self.provider.call(tx).block(block).overrides(overrides).await {
+            Ok(output) => output,
+            Err(err) => match err.as_error_resp() {
+                // the upstream executed the call, and it failed
+                Some(payload) => {
+                    return Ok(SimulatedCall::failed(
+                        payload.message.to_string(),
+                        payload.as_revert_data().unwrap_or_default(),
+                    ))
+                }
+                None => return Err(OdysseyWalletError::InternalError(err.into())),
+            },
+        };
+        let gas = self
+            .provider
+            .estimate_gas(tx)
+            .block(block)
+            .overrides(overrides)
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))?;
+        Ok(SimulatedCall::succeeded(output, gas))
+    }
+
+    async fn simulate_logs(
+        &self,
+        tx: &TransactionRequest,
+        block: BlockId,
+    ) -> Result<SimCallResult, OdysseyWalletError> {
+        let payload = SimulatePayload::default().extend(SimBlock::default().call(tx.clone()));
+        self.provider
+            .raw_request::<_, Vec<SimulatedBlock>>("eth_simulateV1".into(), (payload, block))
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))?
+            .into_iter()
+            .flat_map(|block| block.calls)
+            .next()
+            .ok_or_else(|| OdysseyWalletError::InternalError(eyre::eyre!("empty simulation")))
+    }
+
+    async fn fee_history(
+        &self,
+        block_count: u64,
+        newest_block: BlockNumberOrTag,
+        reward_percentiles: Option<Vec<f64>>,
+    ) -> Result<FeeHistory, OdysseyWalletError> {
+        self.provider
+            .get_fee_history(block_count, newest_block, &reward_percentiles.unwrap_or_default())
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))
+    }
 
-        Ok((
-            estimate.map_err(|err| OdysseyWalletError::InternalError(err.into()))?,
-            fee_estimate.map_err(|err| OdysseyWalletError::InternalError(err.into()))?,
-        ))
+    async fn sign_hash(&self, hash: &B256) -> Result<PrimitiveSignature, OdysseyWalletError> {
+        self.signer
+            .sign_hash_sync(hash)
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))
     }
 
     async fn sign_and_send(&self, tx: TransactionRequest) -> Result<TxHash, OdysseyWalletError> {
@@ -110,6 +597,74 @@ where
             .map_err(|err| OdysseyWalletError::InternalError(err.into()))
             .map(|pending| *pending.tx_hash())
     }
+
+    async fn get_transaction_outcome(
+        &self,
+        tx_hash: TxHash,
+    ) -> Result<Option<TransactionOutcome>, OdysseyWalletError> {
+        // the receipt is requested as JSON, since the Ethereum receipt does not have the L1 data
+        // fee of OP Stack receipts
+        let Some(receipt) = self
+            .provider
+            .raw_request::<_, Option<serde_json::Value>>(
+                "eth_getTransactionReceipt".into(),
+                (tx_hash,),
+            )
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))?
+        else {
+            return Ok(None);
+        };
+        let l1_fee = receipt_l1_fee(&receipt);
+        let receipt: TransactionReceipt = serde_json::from_value(receipt)
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))?;
+        Ok(Some(TransactionOutcome {
+            success: receipt.status(),
+            gas_used: receipt.gas_used() as u64,
+            effective_gas_price: receipt.effective_gas_price(),
+            l1_fee,
+            block_number: receipt.block_number().unwrap_or_default(),
+        }))
+    }
+
+    async fn pending_transactions(
+        &self,
+    ) -> Result<Vec<PendingSponsoredTransaction>, OdysseyWalletError> {
+        let content = self
+            .provider
+            .txpool_content_from(self.provider.default_signer_address())
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))?;
+        Ok(content
+            .pending
+            .values()
+            .chain(content.queued.values())
+            .map(|tx| PendingSponsoredTransaction {
+                hash: *tx.inner.tx_hash(),
+                nonce: tx.nonce(),
+                to: tx.to(),
+                max_fee_per_gas: tx.max_fee_per_gas(),
+                max_priority_fee_per_gas: tx.max_priority_fee_per_gas(),
+                age_ms: None,
+            })
+            .collect())
+    }
+
+    async fn chain_heads(&self) -> Result<ChainHeads, OdysseyWalletError> {
+        let number = |tag| async move {
+            self.provider
+                .get_block_by_number(tag, false.into())
+                .await
+                .map(|block| block.map(|block| block.header.number))
+        };
+        let (latest, safe, finalized) = tokio::try_join!(
+            self.provider.get_block_number(),
+            number(BlockNumberOrTag::Safe),
+            number(BlockNumberOrTag::Finalized)
+        )
+        .map_err(|err| OdysseyWalletError::InternalError(err.into()))?;
+        Ok(ChainHeads { latest, safe, finalized })
+    }
 }
 
 /// A handle to a Reth upstream that signs transactions and injects them directly into the
@@ -118,13 +673,15 @@ where
 pub struct RethUpstream<Provider, Eth> {
     provider: Provider,
     eth_api: Eth,
+    signer: PrivateKeySigner,
     wallet: EthereumWallet,
 }
 
 impl<Provider, Eth> RethUpstream<Provider, Eth> {
-    /// Create a new [`RethUpstream`].
-    pub const fn new(provider: Provider, eth_api: Eth, wallet: EthereumWallet) -> Self {
-        Self { provider, eth_api, wallet }
+    /// Create a new [`RethUpstream`] that sponsors transactions with the given signer.
+    pub fn new(provider: Provider, eth_api: Eth, signer: PrivateKeySigner) -> Self {
+        let wallet = EthereumWallet::from(signer.clone());
+        Self { provider, eth_api, signer, wallet }
     }
 }
 
@@ -150,26 +707,109 @@ where
             .unwrap_or_default())
     }
 
-    async fn estimate(
+    async fn next_nonce(&self) -> Result<u64, OdysseyWalletError> {
+        LoadState::next_available_nonce(
+            &self.eth_api,
+            NetworkWallet::<Ethereum>::default_signer_address(&self.wallet),
+        )
+        .await
+        .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))
+    }
+
+    async fn estimate_gas(
         &self,
         tx: &TransactionRequest,
-    ) -> Result<(u64, Eip1559Estimation), OdysseyWalletError> {
-        let (estimate, fee_estimate) = tokio::join!(
-            EthCall::estimate_gas_at(&self.eth_api, tx.clone(), BlockId::latest(), None),
-            LoadFee::eip1559_fees(&self.eth_api, None, None)
-        );
+        overrides: &StateOverride,
+        block: BlockId,
+    ) -> Result<u64, OdysseyWalletError> {
+        let overrides = Some(overrides.clone()).filter(|overrides| !overrides.is_empty());
+        EthCall::estimate_gas_at(&self.eth_api, tx.clone(), block, overrides)
+            .await
+            .map(|estimate| estimate.to())
+            .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))
+    }
 
-        Ok((
-            estimate
-                .map(|estimate| estimate.to())
-                .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))?,
-            fee_estimate
-                .map(|(base, prio)| Eip1559Estimation {
-                    max_fee_per_gas: (base + prio).to(),
-                    max_priority_fee_per_gas: prio.to(),
-                })
-                .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))?,
-        ))
+    async fn estimate_fees(&self) -> Result<Eip1559Estimation, OdysseyWalletError> {
+        LoadFee::eip1559_fees(&self.eth_api, None, None)
+            .await
+            .map(|(base, prio)| Eip1559Estimation {
+                max_fee_per_gas: (base + prio).to(),
+                max_priority_fee_per_gas: prio.to(),
+            })
+            .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))
+    }
+
+    async fn call(
+        &self,
+        tx: &TransactionRequest,
+        block: BlockId,
+    ) -> Result<Bytes, OdysseyWalletError> {
+        EthCall::call(&self.eth_api, tx.clone(), Some(block), EvmOverrides::default())
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))
+    }
+
+    async fn simulate(
+        &self,
+        tx: &TransactionRequest,
+        overrides: &StateOverride,
+        block: BlockId,
+    ) -> Result<SimulatedCall, OdysseyWalletError> {
+        let output = match EthCall::call(
+            &self.eth_api,
+            tx.clone(),
+            Some(block),
+            EvmOverrides::state(Some(overrides.clone())),
+        )
+        .await
+        {
+            Ok(output) => output,
+            Err(err) => {
+                let err: jsonrpsee::types::ErrorObject<'static> = err.into();
+                let output = err
+                    .data()
+                    .and_then(|data| serde_json::from_str(data.get()).ok())
+                    .unwrap_or_default();
+                return Ok(SimulatedCall::failed(err.message(), output));
+            }
+        };
+        let gas =
+            EthCall::estimate_gas_at(&self.eth_api, tx.clone(), block, Some(overrides.clone()))
+                .await
+                .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))?;
+        Ok(SimulatedCall::succeeded(output, gas.to()))
+    }
+
+    async fn simulate_logs(
+        &self,
+        tx: &TransactionRequest,
+        block: BlockId,
+    ) -> Result<SimCallResult, OdysseyWalletError> {
+        let payload = SimulatePayload::default().extend(SimBlock::default().call(tx.clone()));
+        EthCall::simulate_v1(&self.eth_api, payload, Some(block))
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))?
+            .into_iter()
+            .flat_map(|block| block.calls)
+            .next()
+            .ok_or_else(|| OdysseyWalletError::InternalError(eyre::eyre!("empty simulation")))
+    }
+
+    async fn fee_history(
+        &self,
+        block_count: u64,
+        newest_block: BlockNumberOrTag,
+        reward_percentiles: Option<Vec<f64>>,
+    ) -> Result<FeeHistory, OdysseyWalletError> {
+        EthFees::fee_history(&self.eth_api, block_count, newest_block, reward_percentiles)
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))
+    }
+
+    async fn sign_hash(&self, hash: &B256) -> Result<PrimitiveSignature, OdysseyWalletError> {
+        self.signer
+            .sign_hash_sync(hash)
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))
     }
 
     async fn sign_and_send(
@@ -182,7 +822,8 @@ where
         )
         .await
         .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))?;
-        tx.nonce = Some(next_nonce);
+        // the nonce is usually assigned by the caller
+        tx.nonce.get_or_insert(next_nonce);
 
         // build and sign
         let envelope =
@@ -201,6 +842,55 @@ where
             .await
             .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))
     }
+
+    async fn get_transaction_outcome(
+        &self,
+        tx_hash: TxHash,
+    ) -> Result<Option<TransactionOutcome>, OdysseyWalletError> {
+        Ok(EthTransactions::transaction_receipt(&self.eth_api, tx_hash)
+            .await
+            .map_err(|err| OdysseyWalletError::InternalError(eyre::Report::new(err)))?
+            .map(|receipt| TransactionOutcome {
+                success: receipt.status(),
+                gas_used: receipt.gas_used() as u64,
+                effective_gas_price: receipt.effective_gas_price(),
+                l1_fee: serde_json::to_value(&receipt).ok().as_ref().and_then(receipt_l1_fee),
+                block_number: receipt.block_number().unwrap_or_default(),
+            }))
+    }
+
+    async fn pending_transactions(
+        &self,
+    ) -> Result<Vec<PendingSponsoredTransaction>, OdysseyWalletError> {
+        Ok(self
+            .eth_api
+            .pool()
+            .get_transactions_by_sender(NetworkWallet::<Ethereum>::default_signer_address(
+                &self.wallet,
+            ))
+            .into_iter()
+            .map(|tx| PendingSponsoredTransaction {
+                hash: *tx.transaction.hash(),
+                nonce: tx.transaction.nonce(),
+                to: tx.transaction.to(),
+                max_fee_per_gas: tx.transaction.max_fee_per_gas(),
+                max_priority_fee_per_gas: tx.transaction.max_priority_fee_per_gas(),
+                age_ms: None,
+            })
+            .collect())
+    }
+
+    async fn chain_heads(&self) -> Result<ChainHeads, OdysseyWalletError> {
+        // the safe and finalized blocks are tracked from the fork choice updates of the node
+        let heads = || {
+            Ok::<_, ProviderError>(ChainHeads {
+                latest: self.provider.best_block_number()?,
+                safe: self.provider.safe_block_number()?,
+                finalized: self.provider.finalized_block_number()?,
+            })
+        };
+        heads().map_err(|err| OdysseyWalletError::InternalError(err.into()))
+    }
 }
 
 /// The capability to perform [EIP-7702][eip-7702] delegations, sponsored by the service.
@@ -211,13 +901,78 @@ where
 /// [eip-7702]: https://eips.ethereum.org/EIPS/eip-7702
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct DelegationCapability {
-    /// A list of valid delegation contracts.
+    /// A list of valid delegation contracts. Every contract that is not denied is valid if this is
+    /// empty.
     pub addresses: Vec<Address>,
+    /// A list of delegation contracts that are never sponsored.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied: Vec<Address>,
+}
+
+/// The fees per gas the service would currently pay to sponsor a transaction.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SponsorFees {
+    /// The max fee per gas the sponsor would pay.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub max_fee_per_gas: u128,
+    /// The max priority fee per gas the sponsor would pay.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl From<Eip1559Estimation> for SponsorFees {
+    fn from(estimate: Eip1559Estimation) -> Self {
+        Self {
+            max_fee_per_gas: estimate.max_fee_per_gas,
+            max_priority_fee_per_gas: estimate.max_priority_fee_per_gas,
+        }
+    }
+}
+
+/// The fee history of the chain, extended with the fees the service would currently pay to
+/// sponsor a transaction.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SponsoredFeeHistory {
+    /// The fee history as returned by `eth_feeHistory`.
+    #[serde(flatten)]
+    pub history: FeeHistory,
+    /// The fees the service would use if a transaction was sponsored right now.
+    pub sponsor_fees: SponsorFees,
+}
+
+/// A transaction of the sponsor that is waiting in the transaction pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingSponsoredTransaction {
+    /// The hash of the transaction.
+    pub hash: TxHash,
+    /// The nonce of the transaction.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub nonce: u64,
+    /// The destination of the transaction.
+    pub to: Option<Address>,
+    /// The max fee per gas of the transaction, or its gas price.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub max_fee_per_gas: u128,
+    /// The max priority fee per gas of the transaction, if it is not a legacy transaction.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "alloy_rpc_types::serde_helpers::quantity::opt"
+    )]
+    pub max_priority_fee_per_gas: Option<u128>,
+    /// The time in milliseconds since the service sent the transaction.
+    ///
+    /// This is not set for transactions the service did not send since it started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_ms: Option<u64>,
 }
 
 /// Odyssey `wallet_` RPC namespace.
-#[cfg_attr(not(test), rpc(server, namespace = "wallet"))]
-#[cfg_attr(test, rpc(server, client, namespace = "wallet"))]
+#[cfg_attr(not(any(test, feature = "client")), rpc(server, namespace = "wallet"))]
+#[cfg_attr(any(test, feature = "client"), rpc(server, client, namespace = "wallet"))]
 pub trait OdysseyWalletApi {
     /// Send a sponsored transaction.
     ///
@@ -231,21 +986,159 @@ pub trait OdysseyWalletApi {
     /// The service will sign the transaction and inject it into the transaction pool, provided it
     /// is valid. The nonce is managed by the service.
     ///
+    /// If `validUntil` is set, the request is dropped if the unix timestamp in seconds passes
+    /// before the transaction is sent, so clients can retry without the stale request being
+    /// sponsored as well.
+    ///
+    /// If `idempotencyKey` is set, the hash of the sponsored transaction is stored under the key,
+    /// and later calls with the same key and request return that hash instead of sponsoring
+    /// another transaction. Keys are scoped to the destination of the request, and calls that
+    /// reuse a key for a different request are rejected. Calls that fail do not use the key.
+    ///
     /// [eip-7702]: https://eips.ethereum.org/EIPS/eip-7702
     /// [eip-1559]: https://eips.ethereum.org/EIPS/eip-1559
     #[method(name = "sendTransaction", aliases = ["odyssey_sendTransaction"])]
-    async fn send_transaction(&self, request: TransactionRequest) -> RpcResult<TxHash>;
-}
+    async fn send_transaction(
+        &self,
+        request: TransactionRequest,
+        #[argument(rename = "validUntil")] valid_until: Option<U64>,
+        #[argument(rename = "idempotencyKey")] idempotency_key: Option<String>,
+    ) -> RpcResult<TxHash>;
 
-/// Errors returned by the wallet API.
-#[derive(Debug, thiserror::Error)]
-pub enum OdysseyWalletError {
-    /// The transaction value is not 0.
+    /// Returns the fee history of the chain along with the fees the service would currently pay
+    /// to sponsor a transaction.
     ///
-    /// The value should be 0 to prevent draining the service.
-    #[error("tx value not zero")]
-    ValueNotZero,
-    /// The from field is set on the transaction.
+    /// Clients can compare the sponsor fees to the fee history to decide whether to pay for a
+    /// transaction themselves or to have it sponsored.
+    #[method(name = "feeHistory", aliases = ["odyssey_feeHistory"])]
+    async fn fee_history(
+        &self,
+        block_count: U64,
+        newest_block: BlockNumberOrTag,
+        reward_percentiles: Option<Vec<f64>>,
+    ) -> RpcResult<SponsoredFeeHistory>;
+
+    /// Returns statistics about the time it took to include recent sponsored transactions.
+    #[method(name = "getInclusionStats")]
+    async fn get_inclusion_stats(&self) -> RpcResult<InclusionStats>;
+
+    /// Returns statistics about the fees paid by recent sponsored transactions, split into base
+    /// fee, priority fee and L1 data fee.
+    #[method(name = "getSponsorFeeStats", aliases = ["odyssey_getSponsorFeeStats"])]
+    async fn get_sponsor_fee_stats(&self) -> RpcResult<SponsorFeeStats>;
+
+    /// Returns the token and price sponsored transactions have to pay the sponsor with, along with
+    /// statistics about the collected payments, or `null` if sponsored transactions are free.
+    ///
+    /// See [`TokenFeeConfig`].
+    #[method(name = "getTokenFeeStats")]
+    async fn get_token_fee_stats(&self) -> RpcResult<Option<TokenFeeStats>>;
+
+    /// Prepares a sponsored transaction without sending it.
+    ///
+    /// The request is validated like in `wallet_sendTransaction`, and the transaction the service
+    /// would sign is returned along with a digest. The transaction is sent once the account signs
+    /// the digest and passes the signature to `wallet_sendPreparedCalls` before it expires.
+    #[method(name = "prepareCalls")]
+    async fn prepare_calls(&self, request: TransactionRequest) -> RpcResult<PreparedCalls>;
+
+    /// Sends a sponsored transaction prepared by `wallet_prepareCalls`.
+    ///
+    /// The signature must be an [EIP-191][eip-191] signature of the digest by the account the
+    /// transaction is sponsored for.
+    ///
+    /// [eip-191]: https://eips.ethereum.org/EIPS/eip-191
+    #[method(name = "sendPreparedCalls")]
+    async fn send_prepared_calls(&self, digest: B256, signature: Bytes) -> RpcResult<TxHash>;
+
+    /// Returns a quote for sponsoring a transaction, signed by the sponsor.
+    ///
+    /// The request is validated like in `wallet_sendTransaction`, and the sponsor commits to the
+    /// gas limit and fees of the transaction until the quote expires.
+    #[method(name = "getQuote")]
+    async fn get_quote(&self, request: TransactionRequest) -> RpcResult<SignedQuote>;
+
+    /// Send a sponsored transaction with the gas limit and fees of a quote from `wallet_getQuote`.
+    ///
+    /// The request must be the same as the quoted request, and the quote must not be expired or
+    /// used already.
+    #[method(name = "sendQuotedTransaction")]
+    async fn send_quoted_transaction(
+        &self,
+        request: TransactionRequest,
+        quote: SignedQuote,
+    ) -> RpcResult<TxHash>;
+
+    /// Returns the accounts sponsored by this service that currently delegate to the given
+    /// implementation, in ascending order.
+    ///
+    /// Up to `limit` accounts are returned per page. Pass the `nextCursor` of the response as the
+    /// cursor to get the next page.
+    #[method(name = "getDelegators")]
+    async fn get_delegators(
+        &self,
+        implementation: Address,
+        cursor: Option<Address>,
+        limit: Option<U64>,
+    ) -> RpcResult<Delegators>;
+
+    /// Returns the implementations accounts can delegate to with sponsorship, along with the
+    /// version of the access lists they are derived from and the history of their changes.
+    #[method(name = "getCapabilities")]
+    async fn get_capabilities(&self) -> RpcResult<WalletCapabilities>;
+
+    /// Returns the transactions of the sponsor that are waiting in the transaction pool, ordered by
+    /// nonce.
+    ///
+    /// A gap in the nonces means the following transactions are stuck until the missing nonce is
+    /// sent.
+    #[method(name = "pendingSponsoredTransactions")]
+    async fn pending_sponsored_transactions(&self) -> RpcResult<Vec<PendingSponsoredTransaction>>;
+
+    /// Returns the status of a transaction, along with its confirmation depth and whether its
+    /// block is safe or finalized, or `null` if the transaction is neither included nor waiting for
+    /// inclusion.
+    ///
+    /// Transactions whose block is not finalized can still be reorged out.
+    #[method(name = "getTransactionStatus")]
+    async fn get_transaction_status(
+        &self,
+        tx_hash: TxHash,
+    ) -> RpcResult<Option<SponsoredTransactionStatus>>;
+
+    /// Simulates the sponsorship of a transaction without signing or sending it.
+    ///
+    /// The request is validated like in `wallet_sendTransaction`, and executed against the latest
+    /// state with the given state overrides, e.g. to simulate a delegation to a contract that is
+    /// not deployed yet.
+    #[method(name = "simulateSponsoredTransaction")]
+    async fn simulate_sponsored_transaction(
+        &self,
+        request: TransactionRequest,
+        state_overrides: Option<StateOverride>,
+    ) -> RpcResult<SponsoredSimulation>;
+
+    /// Subscribes to status updates of the transactions sponsored for the given authority.
+    ///
+    /// An update is sent when a transaction is sent, and when it is included in a block or
+    /// dropped. This is only available over WebSocket.
+    #[subscription(
+        name = "subscribeMyTransactions",
+        unsubscribe = "unsubscribeMyTransactions",
+        item = TransactionStatusUpdate
+    )]
+    async fn subscribe_my_transactions(&self, authority: Address) -> SubscriptionResult;
+}
+
+/// Errors returned by the wallet API.
+#[derive(Debug, thiserror::Error)]
+pub enum OdysseyWalletError {
+    /// The transaction value is not 0.
+    ///
+    /// The value should be 0 to prevent draining the service.
+    #[error("tx value not zero")]
+    ValueNotZero,
+    /// The from field is set on the transaction.
     ///
     /// Requests with the from field are rejected, since it is implied that it will always be the
     /// service.
@@ -264,19 +1157,151 @@ pub enum OdysseyWalletError {
     /// - The bytecode is not an EIP-7702 delegation designator
     #[error("the destination of the transaction is not a delegated account")]
     IllegalDestination,
+    /// A field of the transaction request is not supported, or conflicts with another field.
+    #[error("invalid field `{field}`: {reason}")]
+    InvalidField {
+        /// The name of the offending field.
+        field: &'static str,
+        /// Why the field is invalid.
+        reason: &'static str,
+    },
+    /// The destination delegates to an implementation that is not on the allowlist.
+    #[error("implementation {0} is not sponsored")]
+    ImplementationNotAllowed(Address),
+    /// The function called on the destination is not allowed for its implementation.
+    ///
+    /// Calls without a selector are only allowed if the implementation allows any call.
+    #[error(
+        "selector {} is not sponsored for implementation {implementation}",
+        selector.map_or_else(|| "(none)".to_string(), |selector| selector.to_string())
+    )]
+    SelectorNotAllowed {
+        /// The implementation the destination delegates to.
+        implementation: Address,
+        /// The selector of the call, if the input has one.
+        selector: Option<Selector>,
+    },
+    /// An authorization with a chain ID of 0 delegates to an implementation that is not sponsored
+    /// for wildcard authorizations.
+    #[error("authorization for any chain (chain ID 0) delegating to {0} is not sponsored")]
+    WildcardAuthorizationNotAllowed(Address),
+    /// The account is banned from sponsorship.
+    ///
+    /// Accounts are banned if too many of their sponsored transactions revert.
+    #[error("account {0} is banned from sponsorship")]
+    AccountBanned(Address),
+    /// An entry of the request is denied, or not allowed, by the access lists.
+    #[error("{list} {entry} is not sponsored")]
+    AccessDenied {
+        /// The kind of the entry.
+        list: AccessListKind,
+        /// The entry that is not sponsored.
+        entry: String,
+    },
+    /// An entry to add to or remove from an access list is invalid.
+    #[error("invalid {list} entry: {reason}")]
+    InvalidAccessListEntry {
+        /// The kind of the access list.
+        list: AccessListKind,
+        /// Why the entry is invalid.
+        reason: String,
+    },
+    /// The request could not be queued.
+    #[error(transparent)]
+    Queue(#[from] QueueError),
     /// The transaction request was invalid.
     ///
     /// This is likely an internal error, as most of the request is built by the service.
     #[error("invalid tx request")]
     InvalidTransactionRequest,
+    /// The prepared transaction does not exist, was already sent, or expired.
+    #[error("prepared calls not found or expired")]
+    PreparedCallsNotFound,
+    /// The signature of the prepared transaction is not from the account it is sponsored for.
+    #[error("invalid signature for prepared calls")]
+    InvalidPreparedCallsSignature,
+    /// Too many prepared transactions are waiting to be sent.
+    #[error("too many prepared calls")]
+    TooManyPreparedCalls,
+    /// The quote was not signed by the sponsor, or does not match the request.
+    #[error("invalid quote")]
+    InvalidQuote,
+    /// The quote expired, or was already used.
+    #[error("quote expired or already used")]
+    QuoteExpired,
+    /// Too many quotes are waiting to be used.
+    #[error("too many outstanding quotes")]
+    TooManyQuotes,
+    /// The deadline of the request passed before the transaction was sent.
+    #[error("request expired at {0} before it was sent")]
+    RequestExpired(u64),
+    /// The idempotency key is empty or too long.
+    #[error("idempotency key must be between 1 and {} bytes long", MAX_IDEMPOTENCY_KEY_LENGTH)]
+    InvalidIdempotencyKey,
+    /// The idempotency key was already used for a different request of the account.
+    #[error("idempotency key was used for a different request")]
+    IdempotencyKeyReused,
+    /// Too many idempotency keys are stored.
+    #[error("too many idempotency keys")]
+    TooManyIdempotencyKeys,
+    /// A concurrent call with the same idempotency key did not finish in time.
+    #[error("idempotency key is in use by another call")]
+    IdempotencyKeyInUse,
+    /// The sponsored transactions waiting for inclusion use up the per-block gas budget.
+    #[error("sponsorship budget exhausted, try again later")]
+    BudgetExhausted,
+    /// The request has more authorizations than are sponsored in one transaction.
+    #[error("request has {count} authorizations, the maximum is {max}")]
+    TooManyAuthorizations {
+        /// The number of authorizations of the request.
+        count: usize,
+        /// The maximum number of authorizations.
+        max: usize,
+    },
     /// The request was estimated to consume too much gas.
     ///
-    /// The gas usage by each request is limited to counteract draining the services funds.
+    /// The gas usage by each request is limited to counteract draining the services funds. The
+    /// limit grows with the number of authorizations of the request.
     #[error("request would use too much gas: estimated {estimate}")]
     GasEstimateTooHigh {
         /// The amount of gas the request was estimated to consume.
         estimate: u64,
     },
+    /// The request was estimated to cost too much.
+    ///
+    /// The cost of each request, including the L1 data fee, is limited to counteract draining the
+    /// services funds.
+    #[error(
+        "request would cost too much: estimated {execution_cost} wei for execution and {l1_fee} \
+         wei for L1 data, the maximum is {max_cost} wei"
+    )]
+    CostTooHigh {
+        /// The cost of executing the request on L2 in wei.
+        execution_cost: U256,
+        /// The L1 data fee of the request in wei.
+        l1_fee: U256,
+        /// The maximum cost of a request in wei.
+        max_cost: U256,
+    },
+    /// The request does not pay the sponsor enough of the token sponsored transactions are paid
+    /// in, see [`TokenFeeConfig`].
+    #[error(
+        "request must transfer {required} of token {token} to the sponsor, but transfers {paid}"
+    )]
+    TokenFeeNotPaid {
+        /// The token sponsored transactions are paid in.
+        token: Address,
+        /// The amount of the token the request has to transfer to the sponsor.
+        required: U256,
+        /// The amount of the token the request transfers to the sponsor in simulation.
+        paid: U256,
+    },
+    /// The upstream is unavailable, e.g. because it is restarting.
+    #[error("service unavailable, retry after {retry_after} seconds")]
+    ServiceUnavailable {
+        /// The number of seconds after which the request can be retried.
+        retry_after: u64,
+    },
     /// An internal error occurred.
     #[error(transparent)]
     InternalError(#[from] eyre::Error),
@@ -284,6 +1309,13 @@ pub enum OdysseyWalletError {
 
 impl From<OdysseyWalletError> for jsonrpsee::types::error::ErrorObject<'static> {
     fn from(error: OdysseyWalletError) -> Self {
+        if let OdysseyWalletError::ServiceUnavailable { retry_after } = error {
+            return jsonrpsee::types::error::ErrorObject::owned(
+                SERVICE_UNAVAILABLE_CODE,
+                error.to_string(),
+                Some(serde_json::json!({ "retryAfter": retry_after })),
+            );
+        }
         jsonrpsee::types::error::ErrorObject::owned::<()>(
             jsonrpsee::types::error::INVALID_PARAMS_CODE,
             error.to_string(),
@@ -292,6 +1324,106 @@ impl From<OdysseyWalletError> for jsonrpsee::types::error::ErrorObject<'static>
     }
 }
 
+/// Configuration for the [`OdysseyWallet`].
+#[derive(Debug, Clone)]
+pub struct OdysseyWalletConfig {
+    /// The configuration of the account reputation tracker.
+    pub reputation: ReputationConfig,
+    /// The maximum number of outstanding requests per account.
+    pub max_pending_per_account: usize,
+    /// How requests that are ready to be sent are queued, see [`QueueLanes`].
+    pub queue_lanes: QueueLanes,
+    /// The maximum cost of a sponsored transaction in wei, including the L1 data fee.
+    pub max_sponsored_cost: U256,
+    /// The maximum number of authorizations of a sponsored transaction.
+    ///
+    /// The gas limit of a sponsored transaction grows with its authorizations, so this bounds its
+    /// aggregate gas.
+    pub max_authorizations: usize,
+    /// The expiry of prepared transactions, quotes, pending transactions and idempotency keys.
+    pub expiry: ExpiryConfig,
+    /// Enables the deterministic mode for tests, see [`DeterministicConfig`].
+    pub deterministic: Option<DeterministicConfig>,
+    /// Whether to sign and discard sponsored transactions instead of sending them, see
+    /// [`DryRunLog`].
+    pub dry_run: bool,
+    /// The per-block gas budget of sponsored transactions, if any.
+    pub budget: Option<BudgetConfig>,
+    /// The token sponsored transactions have to pay the sponsor with, if any, see
+    /// [`TokenFeeConfig`].
+    pub token_fee: Option<TokenFeeConfig>,
+    /// How estimations are retried and estimated from cached estimates if the upstream is
+    /// unreachable, see [`EstimationFallbackConfig`]. Requests are rejected if this is not set.
+    pub estimation_fallback: Option<EstimationFallbackConfig>,
+    /// How requests for legacy and EIP-2930 transactions are sponsored.
+    pub legacy_compatibility: LegacyCompatibility,
+    /// The functions that are sponsored per implementation, see [`SelectorAllowlist`].
+    pub selectors: SelectorAllowlist,
+    /// The allow and deny lists of the wallet, see [`AccessControl`].
+    pub access: AccessControl,
+    /// The block whose state sponsored transactions are estimated and simulated against.
+    ///
+    /// Estimating against the pending block accounts for transactions that are waiting in the
+    /// transaction pool, such as earlier transactions of the same account.
+    pub estimate_block: BlockId,
+    /// How authorizations with a chain ID of 0 are sponsored, see
+    /// [`WildcardAuthorizationPolicy`].
+    pub wildcard_authorizations: WildcardAuthorizationPolicy,
+    /// How an unavailable upstream is detected, see [`AvailabilityConfig`].
+    pub availability: AvailabilityConfig,
+    /// The implementations whose sponsored transactions and gas are exported as metrics, labeled
+    /// by the implementation.
+    ///
+    /// Only these implementations are labeled, so the number of exported series stays bounded.
+    pub tracked_implementations: HashSet<Address>,
+    /// How the delegations of sponsored destinations are cached, see [`DelegationCache`]. The code
+    /// of the destination is read for every request if this is not set.
+    ///
+    /// The cache has to follow canonical state notifications with [`DelegationCache::spawn`], so
+    /// it should only be enabled where they are available.
+    pub delegation_cache: Option<DelegationCacheConfig>,
+}
+
+impl Default for OdysseyWalletConfig {
+    fn default() -> Self {
+        Self {
+            reputation: ReputationConfig::default(),
+            max_pending_per_account: DEFAULT_MAX_PENDING_PER_ACCOUNT,
+            queue_lanes: QueueLanes::default(),
+            max_sponsored_cost: DEFAULT_MAX_SPONSORED_COST,
+            max_authorizations: DEFAULT_MAX_AUTHORIZATIONS,
+            expiry: ExpiryConfig::default(),
+            deterministic: None,
+            dry_run: false,
+            budget: None,
+            token_fee: None,
+            estimation_fallback: None,
+            legacy_compatibility: LegacyCompatibility::Disabled,
+            selectors: SelectorAllowlist::default(),
+            access: AccessControl::default(),
+            estimate_block: BlockId::latest(),
+            wildcard_authorizations: WildcardAuthorizationPolicy::default(),
+            availability: AvailabilityConfig::default(),
+            tracked_implementations: HashSet::default(),
+            delegation_cache: None,
+        }
+    }
+}
+
+impl OdysseyWalletConfig {
+    /// Sets the maximum number of outstanding requests per account.
+    pub const fn with_max_pending_per_account(mut self, max_pending_per_account: usize) -> Self {
+        self.max_pending_per_account = max_pending_per_account;
+        self
+    }
+
+    /// Sets how requests that are ready to be sent are queued.
+    pub const fn with_queue_lanes(mut self, queue_lanes: QueueLanes) -> Self {
+        self.queue_lanes = queue_lanes;
+        self
+    }
+}
+
 /// Implementation of the Odyssey `wallet_` namespace.
 #[derive(Debug)]
 pub struct OdysseyWallet<T> {
@@ -301,100 +1433,896 @@ pub struct OdysseyWallet<T> {
 impl<T> OdysseyWallet<T> {
     /// Create a new Odyssey wallet module.
     pub fn new(upstream: T, chain_id: ChainId) -> Self {
+        Self::with_config(upstream, chain_id, OdysseyWalletConfig::default())
+    }
+
+    /// Create a new Odyssey wallet module with the given [`OdysseyWalletConfig`].
+    pub fn with_config(upstream: T, chain_id: ChainId, config: OdysseyWalletConfig) -> Self {
+        let clock: Arc<dyn Clock> =
+            config.deterministic.map_or_else(|| Arc::new(SystemClock) as _, |d| d.clock());
         let inner = OdysseyWalletInner {
             upstream,
             chain_id,
-            permit: Default::default(),
+            clock: clock.clone(),
+            reputation: Reputation::new(config.reputation),
+            queue: TransactionQueue::with_lanes(config.max_pending_per_account, config.queue_lanes),
+            nonces: NoncePipeline::default(),
+            inclusion: InclusionTracker::default(),
+            fees: SponsorFeeTracker::default(),
+            prepared: PreparedCallsStore::new(clock.clone(), config.expiry.prepared_calls_ttl),
+            quotes: QuoteBook::new(clock.clone(), config.expiry.quote_ttl),
+            idempotency: IdempotencyStore::new(clock.clone(), config.expiry.idempotency_key_ttl),
+            expiry: config.expiry,
+            expiry_metrics: ExpiryMetrics::default(),
+            max_sponsored_cost: config.max_sponsored_cost,
+            max_authorizations: config.max_authorizations,
             metrics: WalletMetrics::default(),
+            refund_metrics: GasRefundMetrics::default(),
+            deterministic: config.deterministic.map(DeterministicSponsor::new),
+            dry_run: config.dry_run.then(DryRunLog::default),
+            budget: config.budget.map(SponsorBudget::new),
+            token_fees: config.token_fee.map(TokenFeeLedger::new),
+            estimation_fallback: config
+                .estimation_fallback
+                .map(|fallback| EstimationFallback::new(fallback, clock.clone())),
+            legacy_compatibility: config.legacy_compatibility,
+            selectors: config.selectors,
+            access: config.access,
+            estimate_block: config.estimate_block,
+            wildcard_authorizations: config.wildcard_authorizations,
+            authorization_metrics: AuthorizationMetrics::default(),
+            availability: UpstreamAvailability::new(config.availability),
+            implementation_usage: ImplementationUsage::new(config.tracked_implementations),
+            delegation_cache: config
+                .delegation_cache
+                .map(|cache| DelegationCache::new(cache, clock.clone())),
+            status: TransactionStatusFeed::default(),
+            delegations: DelegationIndex::default(),
         };
         Self { inner: Arc::new(inner) }
     }
 
+    /// Returns the [`Reputation`] tracker of this wallet.
+    pub fn reputation(&self) -> &Reputation {
+        &self.inner.reputation
+    }
+
+    /// Returns the [`DryRunLog`] of this wallet, if dry-run mode is enabled.
+    pub fn dry_run_log(&self) -> Option<&DryRunLog> {
+        self.inner.dry_run.as_ref()
+    }
+
+    /// Returns the [`SponsorBudget`] of this wallet, if the budget is enabled.
+    pub fn budget(&self) -> Option<&SponsorBudget> {
+        self.inner.budget.as_ref()
+    }
+
+    /// Returns the [`DelegationCache`] of this wallet, if delegations are cached.
+    pub fn delegation_cache(&self) -> Option<&DelegationCache> {
+        self.inner.delegation_cache.as_ref()
+    }
+
+    /// Returns the [`TokenFeeLedger`] of this wallet, if sponsored transactions are paid in a
+    /// token.
+    pub fn token_fee_ledger(&self) -> Option<&TokenFeeLedger> {
+        self.inner.token_fees.as_ref()
+    }
+
+    /// Returns the [`TransactionStatusFeed`] of this wallet.
+    pub fn status_feed(&self) -> &TransactionStatusFeed {
+        &self.inner.status
+    }
+
+    /// Returns the [`DelegationIndex`] of this wallet.
+    pub fn delegation_index(&self) -> &DelegationIndex {
+        &self.inner.delegations
+    }
+
+    /// Returns the [`AccessControl`] of this wallet.
+    pub fn access_control(&self) -> &AccessControl {
+        &self.inner.access
+    }
+
+    /// Returns the [`InclusionTracker`] of this wallet.
+    pub fn inclusion_tracker(&self) -> &InclusionTracker {
+        &self.inner.inclusion
+    }
+
+    /// Returns the [`SponsorFeeTracker`] of this wallet.
+    pub fn fee_tracker(&self) -> &SponsorFeeTracker {
+        &self.inner.fees
+    }
+
     #[allow(clippy::missing_const_for_fn)]
     fn chain_id(&self) -> ChainId {
         self.inner.chain_id
     }
 }
 
-#[async_trait]
-impl<T> OdysseyWalletApiServer for OdysseyWallet<T>
+impl<T> OdysseyWallet<T>
+where
+    T: Sync + Send + 'static,
+{
+    /// Spawns a task that removes expired prepared transactions, quotes, pending transactions and
+    /// idempotency keys at the configured interval.
+    ///
+    /// The task stops once the wallet is dropped.
+    pub fn spawn_expiry(&self) {
+        let inner = Arc::downgrade(&self.inner);
+        let period = self.inner.expiry.interval;
+        tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let Some(inner) = inner.upgrade() else { break };
+                let expired = inner.remove_expired();
+                if expired.total() > 0 {
+                    debug!(target: "rpc::wallet", ?expired, "Removed expired entries");
+                }
+            }
+        });
+    }
+}
+
+impl<T> OdysseyWallet<T>
 where
     T: Upstream + Sync + Send + 'static,
 {
-    async fn send_transaction(&self, mut request: TransactionRequest) -> RpcResult<TxHash> {
-        trace!(target: "rpc::wallet", ?request, "Serving odyssey_sendTransaction");
+    /// Validates and normalizes a sponsored transaction request.
+    ///
+    /// Returns the normalized request along with the account it is sponsored for, if any.
+    async fn validate_request(
+        &self,
+        request: TransactionRequest,
+    ) -> Result<(TransactionRequest, Option<Address>), OdysseyWalletError> {
+        self.validate_request_with_overrides(request, &StateOverride::default()).await
+    }
+
+    /// Validates and normalizes a sponsored transaction request against the latest state with the
+    /// given state overrides.
+    ///
+    /// Returns the normalized request along with the account it is sponsored for, if any.
+    #[instrument(target = "rpc::wallet", skip_all)]
+    async fn validate_request_with_overrides(
+        &self,
+        request: TransactionRequest,
+        overrides: &StateOverride,
+    ) -> Result<(TransactionRequest, Option<Address>), OdysseyWalletError> {
+        // reject new requests while the upstream is unavailable
+        self.inner.availability.check()?;
 
-        // validate fields common to eip-7702 and eip-1559
-        if let Err(err) = validate_tx_request(&request) {
-            self.inner.metrics.invalid_send_transaction_calls.increment(1);
-            return Err(err.into());
+        // validate fields common to eip-7702 and eip-1559, and normalize the remaining fields
+        let request = validate_tx_request(&request).and_then(|()| {
+            normalize_tx_request(request, self.chain_id(), self.inner.legacy_compatibility)
+        })?;
+
+        // ensure authorizations that are valid on every chain are sponsored
+        let authorizations = request.authorization_list.as_deref().unwrap_or_default();
+        let wildcard = self.inner.wildcard_authorizations.check(authorizations);
+        self.inner.authorization_metrics.record(authorizations, wildcard.is_err());
+        wildcard?;
+
+        // the request is sponsored for the authority of every authorization
+        self.validate_authorizations(authorizations)?;
+
+        // reject accounts that are banned from sponsorship
+        let account = request.to.and_then(|to| to.to().copied());
+        if let Some(account) = account.filter(|account| self.inner.reputation.is_banned(account)) {
+            return Err(OdysseyWalletError::AccountBanned(account));
         }
 
         // validate destination
-        match (request.authorization_list.is_some(), request.to) {
+        let implementation = match (request.authorization_list.is_some(), request.to) {
             // if this is an eip-1559 tx, ensure that it is an account that delegates to a
             // whitelisted address
             (false, Some(TxKind::Call(addr))) => {
-                let code = self.inner.upstream.get_code(addr).await?;
-                match code.as_ref() {
+                match self.current_delegation(addr, overrides).await? {
                     // A valid EIP-7702 delegation
-                    [0xef, 0x01, 0x00, address @ ..] => {
-                        let addr = Address::from_slice(address);
+                    Some(addr) => {
                         // the delegation was cleared
                         if addr.is_zero() {
-                            self.inner.metrics.invalid_send_transaction_calls.increment(1);
-                            return Err(OdysseyWalletError::IllegalDestination.into());
+                            return Err(OdysseyWalletError::IllegalDestination);
                         }
+                        Some(addr)
                     }
                     // Not an EIP-7702 delegation, or an empty (cleared) delegation
-                    _ => {
-                        self.inner.metrics.invalid_send_transaction_calls.increment(1);
-                        return Err(OdysseyWalletError::IllegalDestination.into());
-                    }
+                    None => return Err(OdysseyWalletError::IllegalDestination),
                 }
             }
             // if it's an eip-7702 tx, let it through
-            (true, _) => (),
+            (true, _) => None,
             // create tx's disallowed
-            _ => {
-                self.inner.metrics.invalid_send_transaction_calls.increment(1);
-                return Err(OdysseyWalletError::IllegalDestination.into());
+            _ => return Err(OdysseyWalletError::IllegalDestination),
+        };
+
+        // ensure the called function is sponsored for the implementation
+        let input = request.input.input().map(|input| input.as_ref()).unwrap_or_default();
+        let implementation =
+            if !self.inner.selectors.is_empty() || self.inner.access.checks_implementations() {
+                let implementation = match (implementation, account) {
+                    (Some(implementation), _) => implementation,
+                    (None, Some(account)) => {
+                        self.delegated_implementation(&request, account, overrides).await?
+                    }
+                    (None, None) => return Err(OdysseyWalletError::IllegalDestination),
+                };
+                self.inner.selectors.check(implementation, input)?;
+                Some(implementation)
+            } else {
+                implementation
+            };
+
+        // ensure no entry of the request is denied by the access lists
+        self.inner.access.check(&AccessRequest {
+            authorities: sponsored_authorities(&request, account),
+            destination: account,
+            implementation,
+            authorized_implementations: authorizations
+                .iter()
+                .map(|authorization| authorization.address)
+                .filter(|implementation| !implementation.is_zero())
+                .collect(),
+            selector: input.get(..4).map(Selector::from_slice),
+        })?;
+
+        Ok((request, account))
+    }
+
+    /// Validates every authorization of a request.
+    ///
+    /// The number of authorizations is limited, the authority of every authorization has to be
+    /// recoverable and must not be banned, and the implementation it delegates to must be
+    /// sponsored. Authorizations that delegate to the zero address clear the delegation, and are
+    /// sponsored for any implementation.
+    fn validate_authorizations(
+        &self,
+        authorizations: &[SignedAuthorization],
+    ) -> Result<(), OdysseyWalletError> {
+        if authorizations.len() > self.inner.max_authorizations {
+            return Err(OdysseyWalletError::TooManyAuthorizations {
+                count: authorizations.len(),
+                max: self.inner.max_authorizations,
+            });
+        }
+        for authorization in authorizations {
+            let authority = authorization
+                .signature()
+                .and_then(|signature| {
+                    signature.recover_address_from_prehash(&authorization.signature_hash())
+                })
+                .map_err(|_| OdysseyWalletError::InvalidField {
+                    field: "authorizationList",
+                    reason: "the authority of an authorization can not be recovered",
+                })?;
+            if self.inner.reputation.is_banned(&authority) {
+                return Err(OdysseyWalletError::AccountBanned(authority));
+            }
+            if !authorization.address.is_zero() {
+                self.inner.selectors.check_implementation(authorization.address)?;
             }
         }
+        Ok(())
+    }
 
-        // we acquire the permit here so that all following operations are performed exclusively
-        let _permit = self.inner.permit.lock().await;
+    /// Returns the implementation the account delegates to once the request is included: the
+    /// address of the last authorization of the account in the request, or its current delegation.
+    async fn delegated_implementation(
+        &self,
+        request: &TransactionRequest,
+        account: Address,
+        overrides: &StateOverride,
+    ) -> Result<Address, OdysseyWalletError> {
+        let authorized = request.authorization_list.iter().flatten().rev().find(|authorization| {
+            authorization
+                .signature()
+                .and_then(|signature| {
+                    signature.recover_address_from_prehash(&authorization.signature_hash())
+                })
+                .is_ok_and(|authority| authority == account)
+        });
+        let implementation = match authorized {
+            Some(authorization) => authorization.address,
+            None => self.current_delegation(account, overrides).await?.unwrap_or_default(),
+        };
+        if implementation.is_zero() {
+            return Err(OdysseyWalletError::IllegalDestination);
+        }
+        Ok(implementation)
+    }
 
-        // set chain id
-        request.chain_id = Some(self.chain_id());
+    /// Returns the address the account currently delegates to, if its code is a delegation
+    /// designator.
+    ///
+    /// The code is read from the state overrides, if overridden, and otherwise from the
+    /// [`DelegationCache`], if enabled, before it is read from the upstream.
+    async fn current_delegation(
+        &self,
+        account: Address,
+        overrides: &StateOverride,
+    ) -> Result<Option<Address>, OdysseyWalletError> {
+        if let Some(code) = overrides.get(&account).and_then(|account| account.code.as_ref()) {
+            return Ok(parse_delegation_designator(code));
+        }
+        let Some(cache) = &self.inner.delegation_cache else {
+            return Ok(parse_delegation_designator(&self.inner.upstream.get_code(account).await?));
+        };
+        if let Some(cached) = cache.get(&account) {
+            return Ok(cached.implementation);
+        }
+        let delegation = CachedDelegation::new(&self.inner.upstream.get_code(account).await?);
+        cache.insert(account, delegation);
+        Ok(delegation.implementation)
+    }
 
-        // set gas limit
+    /// Fills in the chain ID, gas limit and fees of a validated request, and ensures that its
+    /// total cost is within limits.
+    #[instrument(target = "rpc::wallet", skip_all)]
+    async fn build_request(
+        &self,
+        request: TransactionRequest,
+    ) -> Result<TransactionRequest, OdysseyWalletError> {
         // note: we also set the `from` field here to correctly estimate for contracts that use e.g.
         // `tx.origin`
+        let request = self.sponsored_by_signer(request);
+        let overrides = self.cached_delegation_overrides(&request);
+        let estimate =
+            || self.inner.upstream.estimate(&request, &overrides, self.inner.estimate_block);
+        let (estimate, fee_estimate) =
+            self.observe_upstream(match &self.inner.estimation_fallback {
+                Some(fallback) => fallback.estimate(&request, estimate).await,
+                None => estimate().await,
+            })?;
+        let (request, _) = self.apply_estimate(request, estimate, fee_estimate).await?;
+        Ok(request)
+    }
+
+    /// Returns state overrides that set the code of the destination to its cached delegation, so
+    /// the estimation does not read the code of the destination again.
+    ///
+    /// Requests with authorizations change the delegations of their authorities, so they are
+    /// estimated against the state.
+    fn cached_delegation_overrides(&self, request: &TransactionRequest) -> StateOverride {
+        let mut overrides = StateOverride::default();
+        let (Some(cache), Some(TxKind::Call(destination))) =
+            (&self.inner.delegation_cache, request.to)
+        else {
+            return overrides;
+        };
+        if request.authorization_list.as_ref().is_some_and(|list| !list.is_empty()) {
+            return overrides;
+        }
+        if let Some(implementation) =
+            cache.get(&destination).and_then(|cached| cached.implementation)
+        {
+            overrides.insert(
+                destination,
+                AccountOverride {
+                    code: Some(delegation_designator(implementation)),
+                    ..Default::default()
+                },
+            );
+        }
+        overrides
+    }
+
+    /// Sets the chain ID of a validated request, and the sponsor as its sender.
+    fn sponsored_by_signer(&self, mut request: TransactionRequest) -> TransactionRequest {
+        request.chain_id = Some(self.chain_id());
         request.from = Some(self.inner.upstream.default_signer_address());
-        let (estimate, fee_estimate) = self
-            .inner
-            .upstream
-            .estimate(&request)
-            .await
-            .inspect_err(|_| self.inner.metrics.invalid_send_transaction_calls.increment(1))?;
-        if estimate >= 350_000 {
-            self.inner.metrics.invalid_send_transaction_calls.increment(1);
-            return Err(OdysseyWalletError::GasEstimateTooHigh { estimate }.into());
+        request
+    }
+
+    /// Sets the gas limit and fees of a request, and ensures that its total cost is within limits.
+    ///
+    /// Returns the request along with its L1 data fee.
+    async fn apply_estimate(
+        &self,
+        mut request: TransactionRequest,
+        estimate: u64,
+        fee_estimate: Eip1559Estimation,
+    ) -> Result<(TransactionRequest, U256), OdysseyWalletError> {
+        // set gas limit
+        let fee_estimate =
+            self.inner.deterministic.as_ref().map_or(fee_estimate, DeterministicSponsor::fees);
+        let authorizations = request.authorization_list.as_ref().map_or(0, Vec::len) as u64;
+        if estimate >= MAX_SPONSORED_GAS + AUTHORIZATION_GAS_ALLOWANCE * authorizations {
+            return Err(OdysseyWalletError::GasEstimateTooHigh { estimate });
         }
         request.gas = Some(estimate);
 
         // set gas price
-        request.max_fee_per_gas = Some(fee_estimate.max_fee_per_gas);
-        request.max_priority_fee_per_gas = Some(fee_estimate.max_priority_fee_per_gas);
-        request.gas_price = None;
+        set_fees(&mut request, fee_estimate.max_fee_per_gas, fee_estimate.max_priority_fee_per_gas);
+
+        // ensure the total cost, including the L1 data fee, is within limits
+        let l1_fee =
+            self.inner.upstream.estimate_l1_fee(&request, self.inner.estimate_block).await?;
+        let execution_cost = U256::from(estimate) * U256::from(fee_estimate.max_fee_per_gas);
+        if execution_cost.saturating_add(l1_fee) > self.inner.max_sponsored_cost {
+            return Err(OdysseyWalletError::CostTooHigh {
+                execution_cost,
+                l1_fee,
+                max_cost: self.inner.max_sponsored_cost,
+            });
+        }
+
+        Ok((request, l1_fee))
+    }
+
+    /// Simulates a built request, and ensures that it pays the sponsor for its maximum cost in the
+    /// token sponsored transactions are paid in, if enabled.
+    ///
+    /// Returns the amount of the token the request pays.
+    async fn verify_token_fee(
+        &self,
+        request: &TransactionRequest,
+    ) -> Result<Option<U256>, OdysseyWalletError> {
+        let Some(ledger) = &self.inner.token_fees else { return Ok(None) };
+
+        let l1_fee =
+            self.inner.upstream.estimate_l1_fee(request, self.inner.estimate_block).await?;
+        let cost = U256::from(request.gas.unwrap_or_default())
+            * U256::from(request.max_fee_per_gas.or(request.gas_price).unwrap_or_default());
+        let call = self.inner.upstream.simulate_logs(request, self.inner.estimate_block).await?;
+        // the logs of a failed call are discarded, so it pays nothing
+        let logs = if call.status { call.logs.as_slice() } else { &[] };
+        ledger.verify(logs, self.inner.upstream.default_signer_address(), cost + l1_fee).map(Some)
+    }
+
+    /// Signs and sends a built request with the next nonce of the sponsor once it is the turn of
+    /// the request in the queue, and tracks its inclusion and outcome.
+    #[instrument(target = "rpc::wallet", skip_all, fields(?account))]
+    async fn submit(
+        &self,
+        request: TransactionRequest,
+        account: Option<Address>,
+        pending: PendingRequest,
+        deadline: Deadline,
+    ) -> Result<TxHash, OdysseyWalletError> {
+        deadline.check()?;
+        let token_fee = self.verify_token_fee(&request).await?;
+        if let Some(log) = &self.inner.dry_run {
+            let tx = dry_run::sign_and_discard(&self.inner.upstream, request, account).await?;
+            info!(
+                target: "rpc::wallet",
+                tx_hash = %tx.transaction_hash,
+                ?account,
+                raw = %tx.raw,
+                "Discarding dry-run transaction"
+            );
+            let tx_hash = tx.transaction_hash;
+            log.record(tx);
+            return Ok(tx_hash);
+        }
+
+        // the delegations of these accounts are indexed once the transaction is included
+        let authorities = sponsored_authorities(&request, account);
+        let gas_limit = request.gas.unwrap_or_default();
+        let max_priority_fee_per_gas = request.max_priority_fee_per_gas.unwrap_or_default();
+        let implementation = self.tracked_implementation(&request, account).await;
+
+        // the permit is held until the nonce slot is reserved, so budget and nonces are handed to
+        // accounts in round-robin order
+        let permit = deadline.run(pending.acquire()).await??;
+
+        // queued requests wait for an unavailable upstream to recover, instead of failing
+        deadline.run(self.inner.availability.wait()).await?;
+
+        // wait for budget in the upcoming blocks, which is released once the transaction is
+        // included or dropped
+        let reservation = match &self.inner.budget {
+            Some(budget) => Some(deadline.run(budget.reserve(gas_limit)).await??),
+            None => None,
+        };
+
+        // the slot is only reserved once the request is ready to be sent, so the turn covers
+        // nothing but assigning the nonce and sending, and waiting requests do not hold up others.
+        // The next request waits for budget while this one is sent, and stays outstanding until
+        // it is sent.
+        let slot = self.inner.nonces.reserve();
+        let pending = permit.release();
+        let mut turn = deadline.run(slot.turn()).await?;
+
+        let tx_hash = self
+            .observe_upstream(match &self.inner.deterministic {
+                Some(sponsor) => sponsor.sign_and_send(&self.inner.upstream, request).await,
+                None => match turn.nonce(&self.inner.upstream).await {
+                    Ok(nonce) => self.inner.upstream.sign_and_send(request.nonce(nonce)).await,
+                    Err(err) => Err(err),
+                },
+            })
+            .inspect_err(
+                |err| warn!(target: "rpc::wallet", ?err, "Error adding sponsored tx to pool"),
+            )?;
+        turn.sent();
+        if let Some(reservation) = reservation {
+            reservation.sent(tx_hash);
+        }
+        drop(pending);
+        if let (Some(ledger), Some(amount)) = (&self.inner.token_fees, token_fee) {
+            ledger.submitted(tx_hash, amount);
+        }
+        self.inner.inclusion.submitted(tx_hash);
+        if let Some(implementation) = &implementation {
+            self.inner.implementation_usage.sponsored(implementation);
+        }
+        if let Some(authority) = account {
+            self.inner.status.publish(TransactionStatusUpdate {
+                transaction_hash: tx_hash,
+                authority,
+                status: TransactionStatus::Pending,
+                gas_used: None,
+            });
+        }
+
+        // track the outcome of the transaction for the reputation of the account
+        tokio::spawn(track_outcome(
+            self.inner.clone(),
+            account,
+            authorities,
+            tx_hash,
+            gas_limit,
+            max_priority_fee_per_gas,
+            implementation,
+        ));
+
+        Ok(tx_hash)
+    }
+
+    /// Records the outcome of a call to the upstream, and probes the upstream until it recovers if
+    /// the call made it unavailable.
+    fn observe_upstream<R>(
+        &self,
+        result: Result<R, OdysseyWalletError>,
+    ) -> Result<R, OdysseyWalletError> {
+        if self.inner.availability.observe(&result) {
+            warn!(target: "rpc::wallet", "Upstream unavailable, pausing sponsorship");
+            tokio::spawn(probe_upstream(
+                Arc::downgrade(&self.inner),
+                self.inner.availability.probe_interval(),
+            ));
+        }
+        result
+    }
+
+    /// Returns the implementation the account delegates to once the request is included, if its
+    /// usage is tracked.
+    async fn tracked_implementation(
+        &self,
+        request: &TransactionRequest,
+        account: Option<Address>,
+    ) -> Option<Address> {
+        if self.inner.implementation_usage.is_empty() {
+            return None;
+        }
+        let implementation = self
+            .delegated_implementation(request, account?, &StateOverride::default())
+            .await
+            .ok()?;
+        self.inner.implementation_usage.tracks(&implementation).then_some(implementation)
+    }
+}
+
+#[async_trait]
+impl<T> OdysseyWalletApiServer for OdysseyWallet<T>
+where
+    T: Upstream + Sync + Send + 'static,
+{
+    async fn send_transaction(
+        &self,
+        request: TransactionRequest,
+        valid_until: Option<U64>,
+        idempotency_key: Option<String>,
+    ) -> RpcResult<TxHash> {
+        trace!(
+            target: "rpc::wallet",
+            ?request,
+            ?valid_until,
+            ?idempotency_key,
+            "Serving odyssey_sendTransaction"
+        );
+
+        let deadline = Deadline::new(&*self.inner.clock, valid_until.map(|ts| ts.to()))
+            .inspect_err(|_| self.inner.metrics.expired_send_transaction_calls.increment(1))?;
+        // the key is bound to the request as sent by the client
+        let request_hash =
+            idempotency_key.as_ref().map(|_| serde_json::to_vec(&request).map(keccak256));
+        let (request, account) = self
+            .validate_request(request)
+            .await
+            .inspect_err(|_| self.inner.metrics.invalid_send_transaction_calls.increment(1))?;
+
+        // the key is held until the transaction is sent, so repeated calls wait for this one
+        let idempotency = match idempotency_key.zip(request_hash) {
+            Some((key, request_hash)) => {
+                let request_hash = request_hash.map_err(eyre::Error::from)?;
+                match self
+                    .inner
+                    .idempotency
+                    .claim(account.unwrap_or_default(), key, request_hash, &deadline)
+                    .await?
+                {
+                    IdempotencyClaim::Sent(tx_hash) => return Ok(tx_hash),
+                    IdempotencyClaim::New(guard) => Some(guard),
+                }
+            }
+            None => None,
+        };
+
+        // the request is estimated concurrently with other requests, and only waits for the permit
+        // of the queue once it is ready to be sent
+        let pending = self
+            .inner
+            .queue
+            .admit(account.unwrap_or_default())
+            .map_err(OdysseyWalletError::from)
+            .inspect_err(|_| self.inner.metrics.invalid_send_transaction_calls.increment(1))?;
+
+        let request = self
+            .build_request(request)
+            .await
+            .inspect_err(|_| self.inner.metrics.invalid_send_transaction_calls.increment(1))?;
 
         // all checks passed, increment the valid calls counter
         self.inner.metrics.valid_send_transaction_calls.increment(1);
 
-        Ok(self.inner.upstream.sign_and_send(request).await.inspect_err(
-            |err| warn!(target: "rpc::wallet", ?err, "Error adding sponsored tx to pool"),
-        )?)
+        let tx_hash =
+            self.submit(request, account, pending, deadline).await.inspect_err(|err| {
+                if matches!(err, OdysseyWalletError::RequestExpired(_)) {
+                    self.inner.metrics.expired_send_transaction_calls.increment(1);
+                }
+            })?;
+        if let Some(guard) = idempotency {
+            guard.sent(tx_hash);
+        }
+        Ok(tx_hash)
+    }
+
+    async fn prepare_calls(&self, request: TransactionRequest) -> RpcResult<PreparedCalls> {
+        trace!(target: "rpc::wallet", ?request, "Serving wallet_prepareCalls");
+
+        let (request, account) = self.validate_request(request).await?;
+        // the account has to approve the transaction, so it must be known
+        let account = account.ok_or(OdysseyWalletError::IllegalDestination)?;
+        let request = self.build_request(request).await?;
+
+        let digest = keccak256(encode_unsigned(request.clone())?);
+        let expiry = self.inner.prepared.insert(digest, request.clone(), account)?;
+
+        Ok(PreparedCalls { request, digest, expiry })
+    }
+
+    async fn send_prepared_calls(&self, digest: B256, signature: Bytes) -> RpcResult<TxHash> {
+        trace!(target: "rpc::wallet", %digest, "Serving wallet_sendPreparedCalls");
+        self.inner.availability.check()?;
+
+        let prepared =
+            self.inner.prepared.get(&digest).ok_or(OdysseyWalletError::PreparedCallsNotFound)?;
+
+        // only the account the transaction is sponsored for can approve it
+        let signer = PrimitiveSignature::try_from(signature.as_ref())
+            .and_then(|signature| signature.recover_address_from_msg(digest))
+            .map_err(|_| OdysseyWalletError::InvalidPreparedCallsSignature)?;
+        if signer != prepared.account {
+            return Err(OdysseyWalletError::InvalidPreparedCallsSignature.into());
+        }
+
+        // the account may have been banned since the transaction was prepared
+        if self.inner.reputation.is_banned(&prepared.account) {
+            return Err(OdysseyWalletError::AccountBanned(prepared.account).into());
+        }
+
+        let pending = self.inner.queue.admit(prepared.account).map_err(OdysseyWalletError::from)?;
+
+        // the transaction may have been sent or expired in the meantime
+        let prepared =
+            self.inner.prepared.remove(&digest).ok_or(OdysseyWalletError::PreparedCallsNotFound)?;
+
+        Ok(self
+            .submit(prepared.request, Some(prepared.account), pending, Deadline::default())
+            .await?)
+    }
+
+    async fn get_quote(&self, request: TransactionRequest) -> RpcResult<SignedQuote> {
+        trace!(target: "rpc::wallet", ?request, "Serving wallet_getQuote");
+
+        let (request, account) = self.validate_request(request).await?;
+        let request = self.build_request(request).await?;
+
+        let quote = Quote {
+            gas: request.gas.unwrap_or_default(),
+            max_fee_per_gas: request.max_fee_per_gas.or(request.gas_price).unwrap_or_default(),
+            max_priority_fee_per_gas: request.max_priority_fee_per_gas.unwrap_or_default(),
+            digest: keccak256(encode_unsigned(request)?),
+            expiry: self.inner.quotes.expiry(),
+        };
+        let signature = self.inner.upstream.sign_hash(&eip191_hash_message(quote.hash())).await?;
+        self.inner.quotes.insert(&quote, account.unwrap_or_default())?;
+
+        Ok(SignedQuote { quote, signature })
+    }
+
+    async fn send_quoted_transaction(
+        &self,
+        request: TransactionRequest,
+        quote: SignedQuote,
+    ) -> RpcResult<TxHash> {
+        trace!(target: "rpc::wallet", ?request, ?quote, "Serving wallet_sendQuotedTransaction");
+
+        // only quotes signed by the sponsor are accepted
+        let SignedQuote { quote, signature } = quote;
+        let sponsor = self.inner.upstream.default_signer_address();
+        if signature.recover_address_from_msg(quote.hash()).ok() != Some(sponsor) {
+            return Err(OdysseyWalletError::InvalidQuote.into());
+        }
+
+        // the request is sponsored with exactly the quoted gas limit and fees
+        let (mut request, account) = self.validate_request(request).await?;
+        request.chain_id = Some(self.chain_id());
+        request.from = Some(sponsor);
+        request.gas = Some(quote.gas);
+        set_fees(&mut request, quote.max_fee_per_gas, quote.max_priority_fee_per_gas);
+        if keccak256(encode_unsigned(request.clone())?) != quote.digest {
+            return Err(OdysseyWalletError::InvalidQuote.into());
+        }
+
+        let pending = self
+            .inner
+            .queue
+            .admit(account.unwrap_or_default())
+            .map_err(OdysseyWalletError::from)?;
+
+        // quotes can only be used once, and are returned if the transaction is not sent
+        let quote = self.inner.quotes.take(&quote).ok_or(OdysseyWalletError::QuoteExpired)?;
+        let tx_hash = self.submit(request, account, pending, Deadline::default()).await?;
+        quote.used();
+        Ok(tx_hash)
+    }
+
+    async fn fee_history(
+        &self,
+        block_count: U64,
+        newest_block: BlockNumberOrTag,
+        reward_percentiles: Option<Vec<f64>>,
+    ) -> RpcResult<SponsoredFeeHistory> {
+        trace!(target: "rpc::wallet", ?block_count, ?newest_block, "Serving odyssey_feeHistory");
+
+        let (history, sponsor_fees) = tokio::join!(
+            self.inner.upstream.fee_history(block_count.to(), newest_block, reward_percentiles),
+            self.inner.upstream.estimate_fees()
+        );
+
+        let sponsor_fees = match &self.inner.deterministic {
+            Some(sponsor) => sponsor.fees(),
+            None => sponsor_fees?,
+        };
+
+        Ok(SponsoredFeeHistory { history: history?, sponsor_fees: sponsor_fees.into() })
+    }
+
+    async fn get_inclusion_stats(&self) -> RpcResult<InclusionStats> {
+        trace!(target: "rpc::wallet", "Serving wallet_getInclusionStats");
+        Ok(self.inner.inclusion.stats())
+    }
+
+    async fn get_sponsor_fee_stats(&self) -> RpcResult<SponsorFeeStats> {
+        trace!(target: "rpc::wallet", "Serving wallet_getSponsorFeeStats");
+        Ok(self.inner.fees.stats())
+    }
+
+    async fn get_token_fee_stats(&self) -> RpcResult<Option<TokenFeeStats>> {
+        trace!(target: "rpc::wallet", "Serving wallet_getTokenFeeStats");
+        Ok(self.inner.token_fees.as_ref().map(TokenFeeLedger::stats))
+    }
+
+    async fn get_capabilities(&self) -> RpcResult<WalletCapabilities> {
+        trace!(target: "rpc::wallet", "Serving wallet_getCapabilities");
+        Ok(self.inner.access.capabilities())
+    }
+
+    async fn get_delegators(
+        &self,
+        implementation: Address,
+        cursor: Option<Address>,
+        limit: Option<U64>,
+    ) -> RpcResult<Delegators> {
+        trace!(target: "rpc::wallet", %implementation, ?cursor, "Serving wallet_getDelegators");
+
+        let limit = limit
+            .map_or(DEFAULT_DELEGATORS_PAGE_SIZE, |limit| limit.saturating_to())
+            .min(MAX_DELEGATORS_PAGE_SIZE);
+        let mut delegators = Vec::with_capacity(limit);
+        let mut cursor = cursor;
+        while delegators.len() < limit {
+            let page =
+                self.inner.delegations.page(&implementation, cursor, limit - delegators.len());
+            let Some(last) = page.last() else {
+                return Ok(Delegators { delegators, next_cursor: None });
+            };
+            cursor = Some(*last);
+
+            // accounts can redelegate without the service, so the indexed delegations are
+            // confirmed against the chain
+            let codes = futures::future::try_join_all(
+                page.iter().map(|authority| self.inner.upstream.get_code(*authority)),
+            )
+            .await?;
+            for (authority, code) in page.into_iter().zip(codes) {
+                let current = parse_delegation_designator(&code);
+                if current == Some(implementation) {
+                    delegators.push(authority);
+                } else {
+                    self.inner.delegations.update(authority, current);
+                }
+            }
+        }
+
+        Ok(Delegators { delegators, next_cursor: cursor })
+    }
+
+    async fn pending_sponsored_transactions(&self) -> RpcResult<Vec<PendingSponsoredTransaction>> {
+        trace!(target: "rpc::wallet", "Serving wallet_pendingSponsoredTransactions");
+
+        let mut transactions = self.inner.upstream.pending_transactions().await?;
+        transactions.sort_by_key(|tx| tx.nonce);
+        for tx in &mut transactions {
+            tx.age_ms = self.inner.inclusion.age(&tx.hash).map(|age| age.as_millis() as u64);
+        }
+        Ok(transactions)
+    }
+
+    async fn get_transaction_status(
+        &self,
+        tx_hash: TxHash,
+    ) -> RpcResult<Option<SponsoredTransactionStatus>> {
+        trace!(target: "rpc::wallet", %tx_hash, "Serving wallet_getTransactionStatus");
+
+        let Some(outcome) = self.inner.upstream.get_transaction_outcome(tx_hash).await? else {
+            // sent transactions are tracked until they are included or dropped
+            return Ok(self
+                .inner
+                .inclusion
+                .age(&tx_hash)
+                .map(|_| SponsoredTransactionStatus::pending(tx_hash)));
+        };
+        let heads = self.inner.upstream.chain_heads().await?;
+        Ok(Some(SponsoredTransactionStatus::included(
+            tx_hash,
+            outcome.success,
+            outcome.block_number,
+            heads,
+        )))
+    }
+
+    async fn simulate_sponsored_transaction(
+        &self,
+        request: TransactionRequest,
+        state_overrides: Option<StateOverride>,
+    ) -> RpcResult<SponsoredSimulation> {
+        trace!(target: "rpc::wallet", ?request, "Serving wallet_simulateSponsoredTransaction");
+
+        let overrides = state_overrides.unwrap_or_default();
+        let (request, account) = self.validate_request_with_overrides(request, &overrides).await?;
+        let request = self.sponsored_by_signer(request);
+
+        let call =
+            self.inner.upstream.simulate(&request, &overrides, self.inner.estimate_block).await?;
+        let Some(estimate) = call.gas.filter(|_| call.success) else {
+            return Ok(SponsoredSimulation { account, request, call, l1_fee: None });
+        };
+        let fee_estimate = self.inner.upstream.estimate_fees().await?;
+        let (request, l1_fee) = self.apply_estimate(request, estimate, fee_estimate).await?;
+
+        Ok(SponsoredSimulation { account, request, call, l1_fee: Some(l1_fee) })
+    }
+
+    async fn subscribe_my_transactions(
+        &self,
+        pending: PendingSubscriptionSink,
+        authority: Address,
+    ) -> SubscriptionResult {
+        trace!(target: "rpc::wallet", %authority, "Serving wallet_subscribeMyTransactions");
+        self.inner.status.pipe(pending, authority).await
     }
 }
 
@@ -403,10 +2331,409 @@ where
 struct OdysseyWalletInner<T> {
     upstream: T,
     chain_id: ChainId,
-    /// Used to guard tx signing
-    permit: Mutex<()>,
+    /// The clock that deadlines and expiries are measured against
+    clock: Arc<dyn Clock>,
+    /// Tracks the outcomes of sponsored transactions per account
+    reputation: Reputation,
+    /// Used to guard tx signing, serving accounts fairly
+    queue: TransactionQueue,
+    /// The nonce slots of the sponsor.
+    nonces: NoncePipeline,
+    /// Tracks the time to inclusion of sponsored transactions
+    inclusion: InclusionTracker,
+    /// Tracks the fees paid by sponsored transactions
+    fees: SponsorFeeTracker,
+    /// Transactions prepared by `wallet_prepareCalls` that were not sent yet
+    prepared: PreparedCallsStore,
+    /// Quotes issued by `wallet_getQuote` that were not used yet
+    quotes: QuoteBook,
+    /// The transactions sponsored for idempotency keys of `odyssey_sendTransaction`
+    idempotency: IdempotencyStore,
+    /// The expiry of prepared transactions, quotes, pending transactions and idempotency keys
+    expiry: ExpiryConfig,
+    /// Metrics for the expiry of server-side state
+    expiry_metrics: ExpiryMetrics,
+    /// The maximum cost of a sponsored transaction in wei
+    max_sponsored_cost: U256,
+    /// The maximum number of authorizations of a sponsored transaction
+    max_authorizations: usize,
     /// Metrics for the `wallet_` RPC namespace.
     metrics: WalletMetrics,
+    /// Metrics for the gas used by sponsored transactions compared to their estimates
+    refund_metrics: GasRefundMetrics,
+    /// Sponsors transactions with fixed fees and nonces, if deterministic mode is enabled
+    deterministic: Option<DeterministicSponsor>,
+    /// Records transactions instead of sending them, if dry-run mode is enabled
+    dry_run: Option<DryRunLog>,
+    /// Limits the gas of sponsored transactions per block, if enabled
+    budget: Option<SponsorBudget>,
+    /// Verifies and accounts for the payments of sponsored transactions in a token, if enabled
+    token_fees: Option<TokenFeeLedger>,
+    /// Retries estimations and estimates from cached estimates while the upstream is
+    /// unreachable, if enabled
+    estimation_fallback: Option<EstimationFallback>,
+    /// How requests for legacy and EIP-2930 transactions are sponsored
+    legacy_compatibility: LegacyCompatibility,
+    /// The functions that are sponsored per implementation
+    selectors: SelectorAllowlist,
+    /// The allow and deny lists of the wallet
+    access: AccessControl,
+    /// The block whose state sponsored transactions are estimated against
+    estimate_block: BlockId,
+    /// How authorizations with a chain ID of 0 are sponsored
+    wildcard_authorizations: WildcardAuthorizationPolicy,
+    /// Metrics for the authorizations of sponsorship requests
+    authorization_metrics: AuthorizationMetrics,
+    /// Tracks whether the upstream is available, pausing sponsorship while it is not
+    availability: UpstreamAvailability,
+    /// Metrics for the usage of the tracked delegation implementations
+    implementation_usage: ImplementationUsage,
+    /// Broadcasts status updates of sponsored transactions to subscribers
+    status: TransactionStatusFeed,
+    /// Indexes the delegations of sponsored accounts by implementation
+    delegations: DelegationIndex,
+    /// Caches the delegations of sponsored destinations, if enabled
+    delegation_cache: Option<DelegationCache>,
+}
+
+impl<T> OdysseyWalletInner<T> {
+    /// Removes the expired entries of all stores.
+    fn remove_expired(&self) -> ExpiredEntries {
+        let expired = ExpiredEntries {
+            prepared_calls: self.prepared.remove_expired(),
+            quotes: self.quotes.remove_expired(),
+            pending_transactions: self
+                .inclusion
+                .remove_expired(self.expiry.pending_transaction_ttl),
+            idempotency_keys: self.idempotency.remove_expired(),
+        };
+        self.expiry_metrics.record(expired);
+        expired
+    }
+}
+
+/// Returns the accounts a request is sponsored for: the destination account, and the authorities
+/// of its authorization list.
+fn sponsored_authorities(request: &TransactionRequest, account: Option<Address>) -> Vec<Address> {
+    let mut authorities: Vec<_> = request
+        .authorization_list
+        .iter()
+        .flatten()
+        .filter_map(|authorization| {
+            authorization
+                .signature()
+                .ok()?
+                .recover_address_from_prehash(&authorization.signature_hash())
+                .ok()
+        })
+        .chain(account)
+        .collect();
+    authorities.sort_unstable();
+    authorities.dedup();
+    authorities
+}
+
+/// Probes an unavailable upstream until it responds, and resumes sponsorship once it does.
+///
+/// The task stops once the wallet is dropped.
+async fn probe_upstream<T: Upstream>(inner: Weak<OdysseyWalletInner<T>>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let Some(inner) = inner.upgrade() else { return };
+        if inner.upstream.next_nonce().await.is_ok() {
+            info!(target: "rpc::wallet", "Upstream recovered, resuming sponsorship");
+            inner.availability.recovered();
+            return;
+        }
+    }
+}
+
+/// Polls the upstream for the outcome of a sponsored transaction and records it in the reputation
+/// of the accounts it was sponsored for.
+///
+/// The inclusion of the transaction is recorded as well, unless it was already observed. Once the
+/// transaction is included, its gas used, and the gas it did not use of its gas limit, are charged
+/// to the destination and split evenly across the other accounts it was sponsored for, the fees it
+/// paid are recorded, its payment in the token is collected, and the delegations of the accounts
+/// are indexed and cached. The gas it used is recorded for the implementation it was sponsored for,
+/// if its usage is tracked.
+///
+/// A transaction that is not included within [`OUTCOME_POLL_ATTEMPTS`] polls is only treated as
+/// dropped, releasing its budget and settling its payment as failed, once it left the transaction
+/// pool. Until then, it keeps being tracked.
+async fn track_outcome<T: Upstream>(
+    inner: Arc<OdysseyWalletInner<T>>,
+    account: Option<Address>,
+    authorities: Vec<Address>,
+    tx_hash: TxHash,
+    gas_limit: u64,
+    max_priority_fee_per_gas: u128,
+    implementation: Option<Address>,
+) {
+    let mut interval = tokio::time::interval(OUTCOME_POLL_INTERVAL);
+    for attempt in 1.. {
+        interval.tick().await;
+        // the pool is checked before the outcome, so a transaction that left the pool because it
+        // was included is not mistaken for a dropped one
+        let dropped =
+            attempt % OUTCOME_POLL_ATTEMPTS == 0 && !is_pending(&inner.upstream, tx_hash).await;
+        match inner.upstream.get_transaction_outcome(tx_hash).await {
+            Ok(Some(outcome)) => {
+                inner.inclusion.included(&tx_hash);
+                if let Some(budget) = &inner.budget {
+                    budget.release(&tx_hash);
+                }
+                if let Some(ledger) = &inner.token_fees {
+                    ledger.settle(&tx_hash, outcome.success);
+                }
+                inner.fees.record(SponsorFee::new(
+                    outcome.effective_gas_price,
+                    max_priority_fee_per_gas,
+                    outcome.l1_fee,
+                ));
+                let refund = GasRefund { gas_limit, gas_used: outcome.gas_used };
+                inner.refund_metrics.record(refund);
+                if let Some(implementation) = &implementation {
+                    inner.implementation_usage.included(implementation, outcome.gas_used);
+                }
+                if refund.is_overestimated() {
+                    debug!(
+                        target: "rpc::wallet",
+                        %tx_hash,
+                        gas_limit,
+                        gas_used = outcome.gas_used,
+                        "Sponsored tx used far less gas than estimated"
+                    );
+                }
+                // the cost is attributed to every account the transaction was sponsored for
+                inner.reputation.record_sponsored(account, &authorities, outcome, refund.unused());
+                if let Some(account) = account {
+                    inner.status.publish(TransactionStatusUpdate {
+                        transaction_hash: tx_hash,
+                        authority: account,
+                        status: if outcome.success {
+                            TransactionStatus::Succeeded
+                        } else {
+                            TransactionStatus::Reverted
+                        },
+                        gas_used: Some(outcome.gas_used),
+                    });
+                }
+                for authority in authorities {
+                    match inner.upstream.get_code(authority).await {
+                        Ok(code) => {
+                            let delegation = CachedDelegation::new(&code);
+                            inner.delegations.update(authority, delegation.implementation);
+                            if let Some(cache) = &inner.delegation_cache {
+                                cache.insert(authority, delegation);
+                            }
+                        }
+                        Err(err) => debug!(
+                            target: "rpc::wallet",
+                            ?err,
+                            %authority,
+                            "Failed to index delegation"
+                        ),
+                    }
+                }
+                return;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                debug!(target: "rpc::wallet", ?err, %tx_hash, "Failed to get sponsored tx outcome")
+            }
+        }
+        if dropped {
+            break;
+        }
+    }
+    debug!(target: "rpc::wallet", %tx_hash, "Sponsored tx was dropped without being included");
+    inner.inclusion.dropped(&tx_hash);
+    // the nonce of the transaction is free again
+    inner.nonces.reset();
+    if let Some(budget) = &inner.budget {
+        budget.release(&tx_hash);
+    }
+    if let Some(ledger) = &inner.token_fees {
+        ledger.settle(&tx_hash, false);
+    }
+    if let Some(account) = account {
+        inner.status.publish(TransactionStatusUpdate {
+            transaction_hash: tx_hash,
+            authority: account,
+            status: TransactionStatus::Dropped,
+            gas_used: None,
+        });
+    }
+}
+
+/// Returns `true` if the sponsored transaction is waiting in the transaction pool, or if the pool
+/// can not be read.
+async fn is_pending<T: Upstream>(upstream: &T, tx_hash: TxHash) -> bool {
+    match upstream.pending_transactions().await {
+        Ok(pending) => pending.iter().any(|tx| tx.hash == tx_hash),
+        Err(err) => {
+            debug!(target: "rpc::wallet", ?err, %tx_hash, "Failed to get pending sponsored txs");
+            true
+        }
+    }
+}
+
+/// Odyssey `admin_` RPC namespace for managing the wallet service.
+#[cfg_attr(not(any(test, feature = "client")), rpc(server, namespace = "admin"))]
+#[cfg_attr(any(test, feature = "client"), rpc(server, client, namespace = "admin"))]
+pub trait OdysseyWalletAdminApi {
+    /// Returns all accounts that are banned from sponsorship, along with their recorded outcomes.
+    #[method(name = "getBannedAccounts")]
+    async fn get_banned_accounts(&self) -> RpcResult<Vec<AccountReputation>>;
+
+    /// Lifts the sponsorship ban of an account and resets its reputation.
+    ///
+    /// Returns `true` if the account was banned.
+    #[method(name = "unbanAccount")]
+    async fn unban_account(&self, address: Address) -> RpcResult<bool>;
+
+    /// Returns the most recent transactions that were signed but not sent in dry-run mode.
+    ///
+    /// Returns an empty list if dry-run mode is disabled.
+    #[method(name = "getDryRunTransactions")]
+    async fn get_dry_run_transactions(&self) -> RpcResult<Vec<DryRunTransaction>>;
+
+    /// Returns the allow and deny lists of the wallet.
+    #[method(name = "getAccessLists")]
+    async fn get_access_lists(&self) -> RpcResult<AccessLists>;
+
+    /// Adds entries to the allowlist or denylist of the given kind, and persists the lists.
+    ///
+    /// Entries are addresses, or selectors given as hex or as a function signature. The change is
+    /// recorded in the history of the lists, along with who made it, if given. Returns the number
+    /// of entries that were added.
+    #[method(name = "addAccessListEntries")]
+    async fn add_access_list_entries(
+        &self,
+        list: AccessListKind,
+        mode: AccessMode,
+        entries: Vec<String>,
+        #[argument(rename = "updatedBy")] updated_by: Option<String>,
+    ) -> RpcResult<usize>;
+
+    /// Removes entries from the allowlist or denylist of the given kind, and persists the lists.
+    ///
+    /// The change is recorded in the history of the lists, along with who made it, if given.
+    /// Returns the number of entries that were removed.
+    #[method(name = "removeAccessListEntries")]
+    async fn remove_access_list_entries(
+        &self,
+        list: AccessListKind,
+        mode: AccessMode,
+        entries: Vec<String>,
+        #[argument(rename = "updatedBy")] updated_by: Option<String>,
+    ) -> RpcResult<usize>;
+}
+
+/// Implementation of the Odyssey `admin_` namespace.
+#[derive(Debug, Clone)]
+pub struct OdysseyWalletAdmin {
+    reputation: Reputation,
+    dry_run: Option<DryRunLog>,
+    access: AccessControl,
+}
+
+impl OdysseyWalletAdmin {
+    /// Create a new admin module for the given [`Reputation`] tracker.
+    pub fn new(reputation: Reputation) -> Self {
+        Self { reputation, dry_run: None, access: AccessControl::default() }
+    }
+
+    /// Manages the given [`AccessControl`].
+    pub fn with_access_control(mut self, access: AccessControl) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Serves the transactions recorded in the given [`DryRunLog`].
+    pub fn with_dry_run_log(mut self, log: DryRunLog) -> Self {
+        self.dry_run = Some(log);
+        self
+    }
+}
+
+#[async_trait]
+impl OdysseyWalletAdminApiServer for OdysseyWalletAdmin {
+    async fn get_banned_accounts(&self) -> RpcResult<Vec<AccountReputation>> {
+        trace!(target: "rpc::wallet", "Serving admin_getBannedAccounts");
+        Ok(self.reputation.banned())
+    }
+
+    async fn unban_account(&self, address: Address) -> RpcResult<bool> {
+        trace!(target: "rpc::wallet", %address, "Serving admin_unbanAccount");
+        Ok(self.reputation.unban(&address))
+    }
+
+    async fn get_dry_run_transactions(&self) -> RpcResult<Vec<DryRunTransaction>> {
+        trace!(target: "rpc::wallet", "Serving admin_getDryRunTransactions");
+        Ok(self.dry_run.as_ref().map(DryRunLog::transactions).unwrap_or_default())
+    }
+
+    async fn get_access_lists(&self) -> RpcResult<AccessLists> {
+        trace!(target: "rpc::wallet", "Serving admin_getAccessLists");
+        Ok(self.access.lists())
+    }
+
+    async fn add_access_list_entries(
+        &self,
+        list: AccessListKind,
+        mode: AccessMode,
+        entries: Vec<String>,
+        updated_by: Option<String>,
+    ) -> RpcResult<usize> {
+        trace!(
+            target: "rpc::wallet",
+            %list,
+            ?mode,
+            ?entries,
+            ?updated_by,
+            "Serving admin_addAccessListEntries"
+        );
+        Ok(self.access.add(list, mode, &entries, updated_by).await?)
+    }
+
+    async fn remove_access_list_entries(
+        &self,
+        list: AccessListKind,
+        mode: AccessMode,
+        entries: Vec<String>,
+        updated_by: Option<String>,
+    ) -> RpcResult<usize> {
+        trace!(
+            target: "rpc::wallet",
+            %list,
+            ?mode,
+            ?entries,
+            ?updated_by,
+            "Serving admin_removeAccessListEntries"
+        );
+        Ok(self.access.remove(list, mode, &entries, updated_by).await?)
+    }
+}
+
+/// Encodes the transaction request as an unsigned transaction.
+///
+/// The nonce is filled in when the transaction is signed, so a placeholder is used if it is not
+/// set.
+fn encode_unsigned(mut tx: TransactionRequest) -> Result<Bytes, OdysseyWalletError> {
+    tx.nonce.get_or_insert(0);
+    let tx = tx.build_typed_tx().map_err(|_| OdysseyWalletError::InvalidTransactionRequest)?;
+
+    let mut buf = Vec::new();
+    match tx {
+        TypedTransaction::Legacy(tx) => tx.encode_for_signing(&mut buf),
+        TypedTransaction::Eip2930(tx) => tx.encode_for_signing(&mut buf),
+        TypedTransaction::Eip1559(tx) => tx.encode_for_signing(&mut buf),
+        TypedTransaction::Eip4844(tx) => tx.encode_for_signing(&mut buf),
+        TypedTransaction::Eip7702(tx) => tx.encode_for_signing(&mut buf),
+    }
+    Ok(buf.into())
 }
 
 fn validate_tx_request(request: &TransactionRequest) -> Result<(), OdysseyWalletError> {
@@ -436,13 +2763,56 @@ struct WalletMetrics {
     invalid_send_transaction_calls: Counter,
     /// Number of valid calls to `odyssey_sendTransaction`
     valid_send_transaction_calls: Counter,
+    /// Number of calls to `odyssey_sendTransaction` whose deadline passed before they were sent
+    expired_send_transaction_calls: Counter,
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{validate_tx_request, OdysseyWalletError};
-    use alloy_primitives::{Address, U256};
-    use alloy_rpc_types::TransactionRequest;
+    use super::*;
+    use crate::test_utils::{MockUpstream, MOCK_GAS_ESTIMATE, MOCK_GAS_USED, MOCK_MAX_FEE_PER_GAS};
+    use std::fmt::Debug;
+
+    const CHAIN_ID: ChainId = 1;
+
+    /// The implementation sponsored accounts delegate to.
+    fn implementation() -> Address {
+        Address::with_last_byte(0xaa)
+    }
+
+    /// Returns the signer of a new account that delegates to the implementation.
+    fn delegated_signer(upstream: &MockUpstream) -> PrivateKeySigner {
+        let signer = PrivateKeySigner::random();
+        upstream.delegate(signer.address(), implementation());
+        signer
+    }
+
+    /// Returns a new account that delegates to the implementation.
+    fn delegated_account(upstream: &MockUpstream) -> Address {
+        delegated_signer(upstream).address()
+    }
+
+    /// Returns a request that calls the account.
+    fn request(account: Address) -> TransactionRequest {
+        TransactionRequest::default()
+            .to(account)
+            .input(Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]).into())
+    }
+
+    /// Asserts that the call was rejected with the error.
+    fn assert_rejected<R: Debug>(result: RpcResult<R>, error: OdysseyWalletError) {
+        assert_eq!(result.unwrap_err().message(), error.to_string());
+    }
+
+    /// Yields until the condition holds, failing after a second.
+    async fn until(mut condition: impl FnMut() -> bool) {
+        let wait = async {
+            while !condition() {
+                tokio::task::yield_now().await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(1), wait).await.expect("condition holds");
+    }
 
     #[test]
     fn no_value_allowed() {
@@ -476,4 +2846,307 @@ mod tests {
 
         assert!(matches!(validate_tx_request(&TransactionRequest::default()), Ok(())));
     }
+
+    #[test]
+    fn encode_unsigned_without_nonce() {
+        let mut request = TransactionRequest::default()
+            .to(Address::ZERO)
+            .gas_limit(21_000)
+            .max_fee_per_gas(2)
+            .max_priority_fee_per_gas(1);
+        request.chain_id = Some(1);
+
+        // eip-1559 transaction type
+        assert_eq!(encode_unsigned(request.clone()).unwrap()[0], 0x02);
+
+        request.gas = None;
+        assert!(matches!(
+            encode_unsigned(request),
+            Err(OdysseyWalletError::InvalidTransactionRequest)
+        ));
+    }
+
+    #[tokio::test]
+    async fn sends_sponsored_transactions() {
+        let wallet = OdysseyWallet::new(MockUpstream::new(), CHAIN_ID);
+        let account = delegated_account(&wallet.inner.upstream);
+
+        let tx_hash = wallet.send_transaction(request(account), None, None).await.unwrap();
+        let sent = wallet.inner.upstream.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].from, Some(wallet.inner.upstream.default_signer_address()));
+        assert_eq!(sent[0].nonce, Some(0));
+        assert_eq!(sent[0].gas, Some(MOCK_GAS_ESTIMATE));
+        assert_eq!(sent[0].max_fee_per_gas, Some(MOCK_MAX_FEE_PER_GAS));
+        assert_eq!(sent[0].chain_id, Some(CHAIN_ID));
+        assert_eq!(wallet.inner.upstream.pending_transactions().await.unwrap()[0].hash, tx_hash);
+
+        // the next transaction uses the next nonce
+        wallet.send_transaction(request(account), None, None).await.unwrap();
+        assert_eq!(wallet.inner.upstream.sent()[1].nonce, Some(1));
+    }
+
+    #[tokio::test]
+    async fn rejects_undelegated_destinations() {
+        let wallet = OdysseyWallet::new(MockUpstream::new(), CHAIN_ID);
+        let account = PrivateKeySigner::random().address();
+
+        assert_rejected(
+            wallet.send_transaction(request(account), None, None).await,
+            OdysseyWalletError::IllegalDestination,
+        );
+        assert_rejected(
+            wallet.prepare_calls(request(account)).await,
+            OdysseyWalletError::IllegalDestination,
+        );
+        assert!(wallet.inner.upstream.sent().is_empty());
+    }
+
+    #[tokio::test]
+    async fn sends_prepared_calls_approved_by_the_account() {
+        let wallet = OdysseyWallet::new(MockUpstream::new(), CHAIN_ID);
+        let signer = delegated_signer(&wallet.inner.upstream);
+
+        let prepared = wallet.prepare_calls(request(signer.address())).await.unwrap();
+        assert_eq!(prepared.request.gas, Some(MOCK_GAS_ESTIMATE));
+        assert_eq!(prepared.digest, keccak256(encode_unsigned(prepared.request.clone()).unwrap()));
+
+        // only the account can approve the transaction
+        let signature = |signer: &PrivateKeySigner| {
+            let signature = signer.sign_message_sync(prepared.digest.as_slice()).unwrap();
+            Bytes::from(signature.as_bytes().to_vec())
+        };
+        assert_rejected(
+            wallet
+                .send_prepared_calls(prepared.digest, signature(&PrivateKeySigner::random()))
+                .await,
+            OdysseyWalletError::InvalidPreparedCallsSignature,
+        );
+
+        wallet.send_prepared_calls(prepared.digest, signature(&signer)).await.unwrap();
+        let sent = wallet.inner.upstream.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].clone().nonce(0), prepared.request.clone().nonce(0));
+
+        // prepared calls are only sent once
+        assert_rejected(
+            wallet.send_prepared_calls(prepared.digest, signature(&signer)).await,
+            OdysseyWalletError::PreparedCallsNotFound,
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bans_accounts_whose_transactions_revert() {
+        let config = OdysseyWalletConfig {
+            reputation: ReputationConfig { min_samples: 1, max_revert_rate: 0.0 },
+            ..Default::default()
+        };
+        let wallet = OdysseyWallet::with_config(MockUpstream::new(), CHAIN_ID, config);
+        let account = delegated_account(&wallet.inner.upstream);
+
+        let tx_hash = wallet.send_transaction(request(account), None, None).await.unwrap();
+        wallet.inner.upstream.include(tx_hash, false);
+        tokio::time::sleep(OUTCOME_POLL_INTERVAL).await;
+
+        let reputation = wallet.reputation().get(&account).unwrap();
+        assert_eq!(reputation.gas_wasted, MOCK_GAS_USED);
+        assert!(reputation.banned);
+        assert_rejected(
+            wallet.send_transaction(request(account), None, None).await,
+            OdysseyWalletError::AccountBanned(account),
+        );
+    }
+
+    #[tokio::test]
+    async fn limits_outstanding_requests_per_account() {
+        let config = OdysseyWalletConfig { max_pending_per_account: 1, ..Default::default() };
+        let wallet =
+            Arc::new(OdysseyWallet::with_config(MockUpstream::holding_sends(), CHAIN_ID, config));
+        let (a, b) =
+            (delegated_account(&wallet.inner.upstream), delegated_account(&wallet.inner.upstream));
+        let send = |account| {
+            let wallet = wallet.clone();
+            tokio::spawn(async move { wallet.send_transaction(request(account), None, None).await })
+        };
+
+        // the request of a is outstanding until it is sent
+        let sent_a = send(a);
+        until(|| wallet.inner.queue.pending(&a) == 1).await;
+        assert_rejected(
+            wallet.send_transaction(request(a), None, None).await,
+            QueueError::TooManyPendingRequests(a).into(),
+        );
+
+        // other accounts are still served
+        let sent_b = send(b);
+        until(|| wallet.inner.queue.pending(&b) == 1).await;
+        wallet.inner.upstream.release_sends(3);
+        sent_a.await.unwrap().unwrap();
+        sent_b.await.unwrap().unwrap();
+        assert_eq!(wallet.inner.queue.pending(&a), 0);
+        wallet.send_transaction(request(a), None, None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn sends_quoted_transactions_once() {
+        let wallet = OdysseyWallet::new(MockUpstream::new(), CHAIN_ID);
+        let account = delegated_account(&wallet.inner.upstream);
+
+        let quote = wallet.get_quote(request(account)).await.unwrap();
+        assert_eq!(quote.quote.gas, MOCK_GAS_ESTIMATE);
+        assert_eq!(quote.quote.max_fee_per_gas, MOCK_MAX_FEE_PER_GAS);
+
+        // quotes are bound to the request and signed by the sponsor
+        let mut tampered = quote;
+        tampered.quote.gas -= 1;
+        assert_rejected(
+            wallet.send_quoted_transaction(request(account), tampered).await,
+            OdysseyWalletError::InvalidQuote,
+        );
+        assert_rejected(
+            wallet
+                .send_quoted_transaction(request(account).input(Bytes::new().into()), quote)
+                .await,
+            OdysseyWalletError::InvalidQuote,
+        );
+
+        wallet.send_quoted_transaction(request(account), quote).await.unwrap();
+        assert_eq!(wallet.inner.upstream.sent()[0].gas, Some(quote.quote.gas));
+
+        // quotes can only be used once
+        assert_rejected(
+            wallet.send_quoted_transaction(request(account), quote).await,
+            OdysseyWalletError::QuoteExpired,
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_budget_of_pending_transactions() {
+        let config =
+            OdysseyWalletConfig { budget: Some(BudgetConfig::default()), ..Default::default() };
+        let wallet = OdysseyWallet::with_config(MockUpstream::new(), CHAIN_ID, config);
+        let budget = wallet.budget().unwrap().clone();
+        let account = delegated_account(&wallet.inner.upstream);
+        let outcome_timeout = OUTCOME_POLL_INTERVAL * OUTCOME_POLL_ATTEMPTS as u32;
+
+        wallet.send_transaction(request(account), None, None).await.unwrap();
+        assert_eq!(budget.pending_gas(), MOCK_GAS_ESTIMATE);
+
+        // the transaction is still waiting in the pool, so it is not dropped
+        tokio::time::sleep(outcome_timeout).await;
+        assert_eq!(budget.pending_gas(), MOCK_GAS_ESTIMATE);
+
+        // the budget is released once the transaction left the pool
+        wallet.inner.upstream.drop_pending();
+        tokio::time::sleep(outcome_timeout).await;
+        assert_eq!(budget.pending_gas(), 0);
+    }
+
+    #[tokio::test]
+    async fn sends_concurrent_requests_in_nonce_order() {
+        let wallet = Arc::new(OdysseyWallet::new(MockUpstream::holding_sends(), CHAIN_ID));
+        let sends: Vec<_> = (0..3)
+            .map(|_| {
+                let wallet = wallet.clone();
+                let account = delegated_account(&wallet.inner.upstream);
+                tokio::spawn(
+                    async move { wallet.send_transaction(request(account), None, None).await },
+                )
+            })
+            .collect();
+
+        // the first request is sent while the others already reserved their nonce slot, instead of
+        // waiting for the queue permit
+        until(|| wallet.inner.nonces.reserved() == 3).await;
+        wallet.inner.upstream.release_sends(3);
+        for send in sends {
+            send.await.unwrap().unwrap();
+        }
+
+        let nonces: Vec<_> = wallet.inner.upstream.sent().iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, [Some(0), Some(1), Some(2)]);
+        // the nonce is only fetched for the first request
+        assert_eq!(wallet.inner.upstream.nonce_requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn fetches_nonce_again_after_failed_send() {
+        let wallet = OdysseyWallet::new(MockUpstream::new(), CHAIN_ID);
+        let account = delegated_account(&wallet.inner.upstream);
+
+        wallet.send_transaction(request(account), None, None).await.unwrap();
+        wallet
+            .inner
+            .upstream
+            .fail_next_send(OdysseyWalletError::InternalError(eyre::eyre!("nonce too low")));
+        assert!(wallet.send_transaction(request(account), None, None).await.is_err());
+        wallet.send_transaction(request(account), None, None).await.unwrap();
+
+        let nonces: Vec<_> = wallet.inner.upstream.sent().iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, [Some(0), Some(1)]);
+        assert_eq!(wallet.inner.upstream.nonce_requests(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fetches_nonce_again_after_drop() {
+        let wallet = OdysseyWallet::new(MockUpstream::new(), CHAIN_ID);
+        let account = delegated_account(&wallet.inner.upstream);
+
+        wallet.send_transaction(request(account), None, None).await.unwrap();
+        // the transaction is dropped, so its nonce is free again
+        wallet.inner.upstream.drop_pending();
+        tokio::time::sleep(OUTCOME_POLL_INTERVAL * OUTCOME_POLL_ATTEMPTS as u32).await;
+        wallet.send_transaction(request(account), None, None).await.unwrap();
+
+        let nonces: Vec<_> = wallet.inner.upstream.sent().iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, [Some(0), Some(0)]);
+        assert_eq!(wallet.inner.upstream.nonce_requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn sends_requests_with_the_same_idempotency_key_once() {
+        let wallet = OdysseyWallet::new(MockUpstream::new(), CHAIN_ID);
+        let (a, b) =
+            (delegated_account(&wallet.inner.upstream), delegated_account(&wallet.inner.upstream));
+        let key = || Some("key".to_string());
+
+        let tx_hash = wallet.send_transaction(request(a), None, key()).await.unwrap();
+        assert_eq!(wallet.send_transaction(request(a), None, key()).await.unwrap(), tx_hash);
+        assert_eq!(wallet.inner.upstream.sent().len(), 1);
+
+        // the key is bound to the request
+        let other = request(a).input(Bytes::from_static(&[0xca, 0xfe]).into());
+        assert_rejected(
+            wallet.send_transaction(other, None, key()).await,
+            OdysseyWalletError::IdempotencyKeyReused,
+        );
+
+        // keys are scoped to the destination
+        assert_ne!(wallet.send_transaction(request(b), None, key()).await.unwrap(), tx_hash);
+        assert_eq!(wallet.inner.upstream.sent().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn charges_every_authority_of_reverted_transactions() {
+        let wallet = OdysseyWallet::new(MockUpstream::new(), CHAIN_ID);
+        let destination = delegated_account(&wallet.inner.upstream);
+        let authority = PrivateKeySigner::random();
+        let authorization = alloy_rpc_types::Authorization {
+            chain_id: CHAIN_ID,
+            address: implementation(),
+            nonce: 0,
+        };
+        let signature = authority.sign_hash_sync(&authorization.signature_hash()).unwrap();
+        let mut request = request(destination);
+        request.authorization_list = Some(vec![authorization.into_signed(signature)]);
+
+        let tx_hash = wallet.send_transaction(request, None, None).await.unwrap();
+        wallet.inner.upstream.include(tx_hash, false);
+        tokio::time::sleep(OUTCOME_POLL_INTERVAL).await;
+
+        // the destination is charged for the whole transaction, and the authority for its share
+        let reputation = wallet.reputation();
+        assert_eq!(reputation.get(&destination).unwrap().gas_wasted, MOCK_GAS_USED);
+        assert_eq!(reputation.get(&authority.address()).unwrap().gas_wasted, MOCK_GAS_USED / 2);
+    }
 }
@@ -0,0 +1,321 @@
+//! Normalization of sponsored transaction requests.
+//!
+//! Requests are reduced to the fields the service acts on, so that equal requests are sponsored
+//! the same way. Fields that conflict with each other, or that describe a transaction the service
+//! does not sponsor, are rejected with an error naming the offending field, instead of failing
+//! later during estimation.
+//!
+//! Requests for legacy and EIP-2930 transactions are rejected, unless a [`LegacyCompatibility`]
+//! mode is configured for older tooling.
+
+use crate::OdysseyWalletError;
+use alloy_consensus::constants::{
+    EIP1559_TX_TYPE_ID, EIP2930_TX_TYPE_ID, EIP7702_TX_TYPE_ID, LEGACY_TX_TYPE_ID,
+};
+use alloy_primitives::ChainId;
+use alloy_rpc_types::{TransactionInput, TransactionRequest};
+use std::str::FromStr;
+
+/// How requests for legacy (type 0) and EIP-2930 (type 1) transactions are sponsored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LegacyCompatibility {
+    /// Requests for legacy and EIP-2930 transactions are rejected.
+    #[default]
+    Disabled,
+    /// Requests for legacy and EIP-2930 transactions are sponsored as EIP-1559 transactions with
+    /// the same access list.
+    Upgrade,
+    /// Requests for legacy and EIP-2930 transactions are sponsored as requested, with the fee
+    /// paid as the gas price.
+    ///
+    /// Requests with a `gasPrice` and no transaction type are sponsored as legacy transactions,
+    /// or as EIP-2930 transactions if they have an access list.
+    Preserve,
+}
+
+impl FromStr for LegacyCompatibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disabled" => Ok(Self::Disabled),
+            "upgrade" => Ok(Self::Upgrade),
+            "preserve" => Ok(Self::Preserve),
+            _ => Err(format!(
+                "unknown legacy compatibility mode {s}, expected disabled, upgrade or preserve"
+            )),
+        }
+    }
+}
+
+/// Normalizes a sponsored transaction request for the given chain.
+///
+/// The gas limit, fees, chain ID and transaction type are cleared, since they are filled in by
+/// the service, and `input` and `data` are merged into `input`. If legacy and EIP-2930
+/// transactions are sponsored as requested, the transaction type is kept.
+pub(crate) fn normalize_tx_request(
+    mut request: TransactionRequest,
+    chain_id: ChainId,
+    compatibility: LegacyCompatibility,
+) -> Result<TransactionRequest, OdysseyWalletError> {
+    // blob transactions are never sponsored
+    if request.blob_versioned_hashes.is_some() {
+        return Err(invalid_field("blobVersionedHashes", "blob transactions are not sponsored"));
+    }
+    if request.sidecar.is_some() {
+        return Err(invalid_field("sidecar", "blob transactions are not sponsored"));
+    }
+    if request.max_fee_per_blob_gas.is_some() {
+        return Err(invalid_field("maxFeePerBlobGas", "blob transactions are not sponsored"));
+    }
+
+    // legacy and eip-1559 fees are mutually exclusive
+    if request.gas_price.is_some()
+        && (request.max_fee_per_gas.is_some() || request.max_priority_fee_per_gas.is_some())
+    {
+        return Err(invalid_field(
+            "gasPrice",
+            "conflicts with `maxFeePerGas` and `maxPriorityFeePerGas`",
+        ));
+    }
+
+    let expected_type = match &request.authorization_list {
+        Some(authorization_list) => {
+            if authorization_list.is_empty() {
+                return Err(invalid_field("authorizationList", "must not be empty"));
+            }
+            if request.access_list.is_some() {
+                return Err(invalid_field("accessList", "not supported on EIP-7702 transactions"));
+            }
+            EIP7702_TX_TYPE_ID
+        }
+        None => EIP1559_TX_TYPE_ID,
+    };
+    let legacy_type = match request.transaction_type {
+        Some(ty @ (LEGACY_TX_TYPE_ID | EIP2930_TX_TYPE_ID))
+            if expected_type == EIP1559_TX_TYPE_ID
+                && compatibility != LegacyCompatibility::Disabled =>
+        {
+            Some(ty)
+        }
+        Some(ty) if ty != expected_type => {
+            return Err(invalid_field(
+                "type",
+                "only EIP-1559 and EIP-7702 transactions are sponsored",
+            ))
+        }
+        None if expected_type == EIP1559_TX_TYPE_ID
+            && compatibility == LegacyCompatibility::Preserve
+            && request.gas_price.is_some() =>
+        {
+            Some(if request.access_list.is_some() { EIP2930_TX_TYPE_ID } else { LEGACY_TX_TYPE_ID })
+        }
+        _ => None,
+    };
+    let preserved_type = legacy_type.filter(|_| compatibility == LegacyCompatibility::Preserve);
+    match preserved_type {
+        Some(LEGACY_TX_TYPE_ID) if request.access_list.is_some() => {
+            return Err(invalid_field("accessList", "not supported on legacy transactions"));
+        }
+        // eip-2930 transactions are built from requests with an access list
+        Some(EIP2930_TX_TYPE_ID) => {
+            request.access_list.get_or_insert_with(Default::default);
+        }
+        _ => {}
+    }
+
+    if request.chain_id.is_some_and(|id| id != chain_id) {
+        return Err(invalid_field("chainId", "does not match the chain of the service"));
+    }
+
+    let input = std::mem::take(&mut request.input)
+        .try_into_unique_input()
+        .map_err(|_| invalid_field("input", "conflicts with `data`"))?;
+    request.input = TransactionInput::maybe_input(input);
+
+    // filled in by the service
+    request.gas = None;
+    request.gas_price = None;
+    request.max_fee_per_gas = None;
+    request.max_priority_fee_per_gas = None;
+    request.chain_id = None;
+    request.transaction_type = preserved_type;
+    // the value is validated to be zero
+    request.value = None;
+
+    Ok(request)
+}
+
+/// Sets the fees of a request, as the gas price if it is a legacy or EIP-2930 transaction.
+pub(crate) fn set_fees(
+    request: &mut TransactionRequest,
+    max_fee_per_gas: u128,
+    max_priority_fee_per_gas: u128,
+) {
+    if request
+        .transaction_type
+        .is_some_and(|ty| ty == LEGACY_TX_TYPE_ID || ty == EIP2930_TX_TYPE_ID)
+    {
+        request.gas_price = Some(max_fee_per_gas);
+    } else {
+        request.max_fee_per_gas = Some(max_fee_per_gas);
+        request.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+    }
+}
+
+const fn invalid_field(field: &'static str, reason: &'static str) -> OdysseyWalletError {
+    OdysseyWalletError::InvalidField { field, reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{Address, Bytes, PrimitiveSignature, U256};
+    use alloy_rpc_types::{AccessList, Authorization};
+
+    /// Returns a request that delegates to the zero address.
+    fn delegation() -> TransactionRequest {
+        let authorization = Authorization { chain_id: 1, address: Address::ZERO, nonce: 0 }
+            .into_signed(PrimitiveSignature::new(U256::ZERO, U256::ZERO, false));
+        TransactionRequest { authorization_list: Some(vec![authorization]), ..Default::default() }
+    }
+
+    fn assert_invalid_field(request: TransactionRequest, expected: &str) {
+        match normalize_tx_request(request, 1, LegacyCompatibility::Disabled) {
+            Err(OdysseyWalletError::InvalidField { field, .. }) => assert_eq!(field, expected),
+            res => panic!("expected invalid field `{expected}`, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn clears_service_fields() {
+        let mut request = TransactionRequest::default()
+            .to(Address::ZERO)
+            .gas_limit(1_000_000)
+            .max_fee_per_gas(2)
+            .max_priority_fee_per_gas(1)
+            .value(U256::ZERO)
+            .transaction_type(EIP1559_TX_TYPE_ID)
+            .input(TransactionInput::both(Bytes::from_static(&[1])));
+        request.chain_id = Some(1);
+
+        assert_eq!(
+            normalize_tx_request(request, 1, LegacyCompatibility::Disabled).unwrap(),
+            TransactionRequest::default()
+                .to(Address::ZERO)
+                .input(TransactionInput::new(Bytes::from_static(&[1])))
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_fields() {
+        let mut request = TransactionRequest::default().max_fee_per_gas(2);
+        request.gas_price = Some(1);
+        assert_invalid_field(request, "gasPrice");
+
+        let request = TransactionRequest {
+            input: TransactionInput {
+                input: Some(Bytes::from_static(&[1])),
+                data: Some(Bytes::from_static(&[2])),
+            },
+            ..Default::default()
+        };
+        assert_invalid_field(request, "input");
+
+        let mut request = TransactionRequest::default();
+        request.chain_id = Some(2);
+        assert_invalid_field(request, "chainId");
+
+        let request = TransactionRequest::default().transaction_type(EIP7702_TX_TYPE_ID);
+        assert_invalid_field(request, "type");
+    }
+
+    #[test]
+    fn rejects_invalid_delegations() {
+        let request =
+            TransactionRequest { access_list: Some(AccessList::default()), ..delegation() };
+        assert_invalid_field(request, "accessList");
+
+        let request =
+            TransactionRequest { authorization_list: Some(Vec::new()), ..Default::default() };
+        assert_invalid_field(request, "authorizationList");
+
+        let request = delegation().transaction_type(EIP7702_TX_TYPE_ID);
+        assert!(normalize_tx_request(request, 1, LegacyCompatibility::Disabled).is_ok());
+    }
+
+    #[test]
+    fn upgrades_legacy_requests() {
+        let upgrade = |request| normalize_tx_request(request, 1, LegacyCompatibility::Upgrade);
+
+        let request = TransactionRequest::default().transaction_type(LEGACY_TX_TYPE_ID);
+        assert_eq!(upgrade(request).unwrap(), TransactionRequest::default());
+
+        let request = TransactionRequest {
+            access_list: Some(AccessList::default()),
+            ..TransactionRequest::default().transaction_type(EIP2930_TX_TYPE_ID)
+        };
+        let request = upgrade(request).unwrap();
+        assert_eq!(request.transaction_type, None);
+        assert_eq!(request.access_list, Some(AccessList::default()));
+
+        // delegations are always eip-7702 transactions
+        assert!(upgrade(delegation().transaction_type(LEGACY_TX_TYPE_ID)).is_err());
+    }
+
+    #[test]
+    fn preserves_legacy_requests() {
+        let preserve = |request| normalize_tx_request(request, 1, LegacyCompatibility::Preserve);
+
+        let request = TransactionRequest::default().transaction_type(LEGACY_TX_TYPE_ID);
+        assert_eq!(preserve(request).unwrap().transaction_type, Some(LEGACY_TX_TYPE_ID));
+
+        let request = preserve(TransactionRequest::default().gas_price(1)).unwrap();
+        assert_eq!(request.transaction_type, Some(LEGACY_TX_TYPE_ID));
+        assert_eq!(request.gas_price, None);
+
+        let request = TransactionRequest::default().transaction_type(EIP2930_TX_TYPE_ID);
+        let request = preserve(request).unwrap();
+        assert_eq!(request.transaction_type, Some(EIP2930_TX_TYPE_ID));
+        assert_eq!(request.access_list, Some(AccessList::default()));
+
+        let request = TransactionRequest {
+            access_list: Some(AccessList::default()),
+            ..TransactionRequest::default().gas_price(1)
+        };
+        assert_eq!(preserve(request).unwrap().transaction_type, Some(EIP2930_TX_TYPE_ID));
+
+        let request = TransactionRequest {
+            access_list: Some(AccessList::default()),
+            ..TransactionRequest::default().transaction_type(LEGACY_TX_TYPE_ID)
+        };
+        assert!(preserve(request).is_err());
+
+        // eip-1559 requests are unchanged
+        let request = preserve(TransactionRequest::default().max_fee_per_gas(2)).unwrap();
+        assert_eq!(request.transaction_type, None);
+    }
+
+    #[test]
+    fn sets_fees_by_type() {
+        let mut request = TransactionRequest::default().transaction_type(LEGACY_TX_TYPE_ID);
+        set_fees(&mut request, 2, 1);
+        assert_eq!(request.gas_price, Some(2));
+        assert_eq!(request.max_fee_per_gas, None);
+
+        let mut request = TransactionRequest::default();
+        set_fees(&mut request, 2, 1);
+        assert_eq!(request.gas_price, None);
+        assert_eq!((request.max_fee_per_gas, request.max_priority_fee_per_gas), (Some(2), Some(1)));
+    }
+
+    #[test]
+    fn rejects_blob_fields() {
+        let request =
+            TransactionRequest { blob_versioned_hashes: Some(Vec::new()), ..Default::default() };
+        assert_invalid_field(request, "blobVersionedHashes");
+
+        let request = TransactionRequest { max_fee_per_blob_gas: Some(1), ..Default::default() };
+        assert_invalid_field(request, "maxFeePerBlobGas");
+    }
+}
@@ -0,0 +1,290 @@
+//! Ordered submission of sponsored transactions.
+//!
+//! Requests are estimated concurrently, and reserve a slot in the nonce sequence of the sponsor
+//! once they are ready to be sent, after they waited for the upstream to be available and for
+//! budget. Only signing and sending is serialized: a slot waits for its turn until every earlier
+//! slot was sent or released, and is then assigned the next nonce of the sponsor.
+//!
+//! The nonce of a slot is only fixed once it is its turn, so requests that fail to send or whose
+//! deadline passes release their slot without leaving a gap in the nonces of the sponsor. The next
+//! nonce is fetched from the upstream again after a failed send, and after a sent transaction was
+//! dropped, see [`NoncePipeline::reset`].
+
+use crate::{OdysseyWalletError, Upstream};
+use parking_lot::Mutex;
+use std::{collections::BTreeSet, sync::Arc};
+use tokio::sync::watch;
+
+/// Hands out nonce slots of the sponsor, and serializes their submission in the order they were
+/// reserved.
+#[derive(Debug, Clone)]
+pub(crate) struct NoncePipeline {
+    inner: Arc<NoncePipelineInner>,
+}
+
+#[derive(Debug)]
+struct NoncePipelineInner {
+    state: Mutex<PipelineState>,
+    /// The slot whose turn it is.
+    turn: watch::Sender<u64>,
+}
+
+#[derive(Debug, Default)]
+struct PipelineState {
+    /// The next slot that is reserved.
+    next_slot: u64,
+    /// The slot whose turn it is.
+    turn: u64,
+    /// Slots after the current turn that were released without being sent.
+    released: BTreeSet<u64>,
+    /// The nonce of the next transaction of the sponsor, if known.
+    next_nonce: Option<u64>,
+    /// The number of times the next nonce was reset, so turns that took their nonce before a reset
+    /// do not set it again.
+    epoch: u64,
+}
+
+impl NoncePipeline {
+    /// Creates a new pipeline that starts at the given nonce, or at the next nonce of the sponsor
+    /// according to the upstream.
+    pub(crate) fn new(next_nonce: Option<u64>) -> Self {
+        Self {
+            inner: Arc::new(NoncePipelineInner {
+                state: Mutex::new(PipelineState { next_nonce, ..Default::default() }),
+                turn: watch::Sender::new(0),
+            }),
+        }
+    }
+
+    /// Reserves the next slot.
+    pub(crate) fn reserve(&self) -> NonceSlot {
+        let mut state = self.inner.state.lock();
+        let slot = state.next_slot;
+        state.next_slot += 1;
+        NonceSlot { pipeline: Some(self.clone()), slot }
+    }
+
+    /// Returns the number of reserved slots that were not sent or released yet.
+    pub(crate) fn reserved(&self) -> u64 {
+        let state = self.inner.state.lock();
+        state.next_slot - state.turn - state.released.len() as u64
+    }
+
+    /// Releases a slot that was not sent, passing the turn on if it is the turn of the slot.
+    fn release(&self, slot: u64) {
+        let mut state = self.inner.state.lock();
+        if slot == state.turn {
+            self.advance(&mut state);
+        } else {
+            state.released.insert(slot);
+        }
+    }
+
+    /// Forgets the next nonce of the sponsor, so the next turn fetches it from the upstream.
+    ///
+    /// This is used once a sent transaction was dropped, since the nonce of the transaction is free
+    /// again.
+    pub(crate) fn reset(&self) {
+        let mut state = self.inner.state.lock();
+        state.next_nonce = None;
+        state.epoch += 1;
+    }
+
+    /// Finishes the slot whose turn it is, and sets the next nonce of the sponsor unless it was
+    /// reset since the turn started.
+    fn finish(&self, epoch: u64, next_nonce: Option<u64>) {
+        let mut state = self.inner.state.lock();
+        state.next_nonce = next_nonce.filter(|_| epoch == state.epoch);
+        self.advance(&mut state);
+    }
+
+    /// Passes the turn to the next slot that was not released.
+    fn advance(&self, state: &mut PipelineState) {
+        state.turn += 1;
+        while state.released.remove(&state.turn) {
+            state.turn += 1;
+        }
+        self.inner.turn.send_replace(state.turn);
+    }
+}
+
+impl Default for NoncePipeline {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// A reserved slot in the nonce sequence of the sponsor.
+///
+/// The slot is released if it is dropped before its turn.
+#[derive(Debug)]
+pub(crate) struct NonceSlot {
+    pipeline: Option<NoncePipeline>,
+    slot: u64,
+}
+
+impl NonceSlot {
+    /// Waits until every earlier slot was sent or released.
+    pub(crate) async fn turn(mut self) -> NonceTurn {
+        let pipeline = self.pipeline.clone().expect("slot is reserved");
+        let mut turn = pipeline.inner.turn.subscribe();
+        // the sender is owned by the pipeline, so the channel can not be closed
+        let _ = turn.wait_for(|turn| *turn == self.slot).await;
+        self.pipeline = None;
+        let (nonce, epoch) = {
+            let state = pipeline.inner.state.lock();
+            (state.next_nonce, state.epoch)
+        };
+        NonceTurn { pipeline: Some(pipeline), nonce, epoch }
+    }
+}
+
+impl Drop for NonceSlot {
+    fn drop(&mut self) {
+        if let Some(pipeline) = self.pipeline.take() {
+            pipeline.release(self.slot);
+        }
+    }
+}
+
+/// Exclusive access to sign and send the transaction of a slot.
+///
+/// If this is dropped without the transaction being sent, e.g. because the upstream rejected its
+/// nonce, the next nonce of the sponsor is fetched from the upstream again, as the transaction may
+/// or may not have been sent.
+#[derive(Debug)]
+pub(crate) struct NonceTurn {
+    pipeline: Option<NoncePipeline>,
+    nonce: Option<u64>,
+    /// The epoch of the pipeline when the turn started.
+    epoch: u64,
+}
+
+impl NonceTurn {
+    /// Returns the nonce of the slot, fetching the next nonce of the sponsor from the upstream if
+    /// it is not known.
+    pub(crate) async fn nonce<T: Upstream + Sync>(
+        &mut self,
+        upstream: &T,
+    ) -> Result<u64, OdysseyWalletError> {
+        match self.nonce {
+            Some(nonce) => Ok(nonce),
+            None => {
+                let nonce = upstream.next_nonce().await?;
+                Ok(*self.nonce.insert(nonce))
+            }
+        }
+    }
+
+    /// Marks the transaction of the slot as sent, using up its nonce.
+    pub(crate) fn sent(mut self) {
+        if let Some(pipeline) = self.pipeline.take() {
+            pipeline.finish(self.epoch, self.nonce.map(|nonce| nonce + 1));
+        }
+    }
+}
+
+impl Drop for NonceTurn {
+    fn drop(&mut self) {
+        if let Some(pipeline) = self.pipeline.take() {
+            pipeline.finish(self.epoch, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockUpstream;
+    use futures::FutureExt;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn sends_slots_in_order() {
+        let pipeline = NoncePipeline::new(Some(7));
+        let (a, b, c) = (pipeline.reserve(), pipeline.reserve(), pipeline.reserve());
+        assert_eq!(pipeline.reserved(), 3);
+
+        // c finished estimating first, but waits for a
+        let c = tokio::spawn(c.turn());
+        tokio::task::yield_now().await;
+        assert!(!c.is_finished());
+
+        // b failed estimating, so its nonce is used by c
+        drop(b);
+        let a = a.turn().await;
+        assert_eq!(a.nonce, Some(7));
+        a.sent();
+
+        let c = c.await.unwrap();
+        assert_eq!(c.nonce, Some(8));
+        assert_eq!(pipeline.reserved(), 1);
+
+        // the nonce is fetched again if the slot was not sent
+        drop(c);
+        let d = pipeline.reserve().turn().now_or_never().unwrap();
+        assert_eq!(d.nonce, None);
+        assert_eq!(pipeline.reserved(), 1);
+    }
+
+    #[tokio::test]
+    async fn fetches_nonce_again_after_reset() {
+        let pipeline = NoncePipeline::new(Some(3));
+        let a = pipeline.reserve().turn().await;
+        assert_eq!(a.nonce, Some(3));
+        a.sent();
+        let b = pipeline.reserve().turn().await;
+        assert_eq!(b.nonce, Some(4));
+
+        // the transaction of a was dropped while b was sent, so the nonce of a is free again
+        pipeline.reset();
+        b.sent();
+        let mut c = pipeline.reserve().turn().now_or_never().unwrap();
+        assert_eq!(c.nonce, None);
+
+        // the nonce is fetched from the upstream, and used by the next turns again
+        let upstream = MockUpstream::new();
+        assert_eq!(c.nonce(&upstream).await.unwrap(), 0);
+        assert_eq!(upstream.nonce_requests(), 1);
+        c.sent();
+        let d = pipeline.reserve().turn().now_or_never().unwrap();
+        assert_eq!(d.nonce, Some(1));
+    }
+
+    #[tokio::test]
+    async fn estimates_slots_concurrently() {
+        let pipeline = NoncePipeline::new(Some(0));
+        let (sent_tx, mut sent_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // every request holds its slot while it is estimated, and the estimates finish in reverse
+        let mut estimates = Vec::new();
+        let mut tasks = Vec::new();
+        for request in 0..3 {
+            let (estimated_tx, estimated_rx) = oneshot::channel::<()>();
+            estimates.push(estimated_tx);
+            let (slot, sent_tx) = (pipeline.reserve(), sent_tx.clone());
+            tasks.push(tokio::spawn(async move {
+                estimated_rx.await.unwrap();
+                let turn = slot.turn().await;
+                sent_tx.send((request, turn.nonce.unwrap())).unwrap();
+                turn.sent();
+            }));
+        }
+        assert_eq!(pipeline.reserved(), 3);
+
+        for estimated in estimates.into_iter().rev() {
+            estimated.send(()).unwrap();
+            tokio::task::yield_now().await;
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let mut sent = Vec::new();
+        while let Ok(tx) = sent_rx.try_recv() {
+            sent.push(tx);
+        }
+        assert_eq!(sent, vec![(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(pipeline.reserved(), 0);
+    }
+}
@@ -0,0 +1,179 @@
+//! Storage of prepared sponsored transactions.
+//!
+//! `wallet_prepareCalls` builds a sponsored transaction without sending it, so the account can
+//! review exactly what the sponsor will sign. The prepared transaction is stored under its digest
+//! until the account approves it with `wallet_sendPreparedCalls`, or until it expires.
+//!
+//! At most [`MAX_PREPARED_CALLS_PER_ACCOUNT`] prepared transactions are stored per account, so a
+//! single account can not use up the storage of all others.
+
+use crate::OdysseyWalletError;
+use alloy_primitives::{Address, B256};
+use alloy_rpc_types::TransactionRequest;
+use odyssey_common::Clock;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The default time a prepared transaction can be sent for.
+pub const DEFAULT_PREPARED_CALLS_TTL: Duration = Duration::from_secs(60);
+
+/// The maximum number of prepared transactions that are stored at once.
+const MAX_PREPARED_CALLS: usize = 10_000;
+
+/// The maximum number of prepared transactions that are stored per account.
+pub const MAX_PREPARED_CALLS_PER_ACCOUNT: usize = 16;
+
+/// A sponsored transaction prepared by `wallet_prepareCalls`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreparedCalls {
+    /// The transaction the service will sign and send.
+    ///
+    /// The nonce is assigned by the service when the transaction is sent.
+    pub request: TransactionRequest,
+    /// The digest the account has to sign ([EIP-191]) to send the transaction.
+    ///
+    /// [EIP-191]: https://eips.ethereum.org/EIPS/eip-191
+    pub digest: B256,
+    /// The unix timestamp in seconds after which the transaction can no longer be sent.
+    pub expiry: u64,
+}
+
+/// A prepared transaction waiting to be sent.
+#[derive(Debug, Clone)]
+pub(crate) struct PreparedEntry {
+    /// The transaction the service will sign and send.
+    pub(crate) request: TransactionRequest,
+    /// The account the transaction is sponsored for.
+    pub(crate) account: Address,
+    /// The time after which the transaction can no longer be sent.
+    expires_at: Instant,
+}
+
+/// Stores prepared transactions by digest until they are sent or expire.
+#[derive(Debug)]
+pub(crate) struct PreparedCallsStore {
+    clock: Arc<dyn Clock>,
+    ttl: Duration,
+    entries: Mutex<HashMap<B256, PreparedEntry>>,
+}
+
+impl PreparedCallsStore {
+    /// Creates a new store that reads the time from the given [`Clock`], and keeps prepared
+    /// transactions for the given duration.
+    pub(crate) fn new(clock: Arc<dyn Clock>, ttl: Duration) -> Self {
+        Self { clock, ttl, entries: Default::default() }
+    }
+
+    /// Stores a prepared transaction, returning its expiry as a unix timestamp in seconds.
+    pub(crate) fn insert(
+        &self,
+        digest: B256,
+        request: TransactionRequest,
+        account: Address,
+    ) -> Result<u64, OdysseyWalletError> {
+        let now = self.clock.now();
+        let mut entries = self.entries.lock();
+        entries.retain(|_, entry| entry.expires_at > now);
+        if !entries.contains_key(&digest)
+            && (entries.len() >= MAX_PREPARED_CALLS
+                || entries.values().filter(|entry| entry.account == account).count()
+                    >= MAX_PREPARED_CALLS_PER_ACCOUNT)
+        {
+            return Err(OdysseyWalletError::TooManyPreparedCalls);
+        }
+        entries.insert(digest, PreparedEntry { request, account, expires_at: now + self.ttl });
+
+        Ok((self.clock.unix_epoch_ms() + self.ttl.as_millis() as u64) / 1000)
+    }
+
+    /// Returns the prepared transaction with the given digest, unless it expired.
+    pub(crate) fn get(&self, digest: &B256) -> Option<PreparedEntry> {
+        let now = self.clock.now();
+        self.entries.lock().get(digest).filter(|entry| entry.expires_at > now).cloned()
+    }
+
+    /// Removes the prepared transaction with the given digest, returning it unless it expired.
+    pub(crate) fn remove(&self, digest: &B256) -> Option<PreparedEntry> {
+        let now = self.clock.now();
+        self.entries.lock().remove(digest).filter(|entry| entry.expires_at > now)
+    }
+
+    /// Removes expired prepared transactions, returning the number of removed transactions.
+    pub(crate) fn remove_expired(&self) -> usize {
+        let now = self.clock.now();
+        let mut entries = self.entries.lock();
+        let len = entries.len();
+        entries.retain(|_, entry| entry.expires_at > now);
+        len - entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odyssey_common::MockClock;
+
+    #[test]
+    fn expires_prepared_calls() {
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let store = PreparedCallsStore::new(clock.clone(), Duration::from_secs(60));
+        let (a, b) = (B256::with_last_byte(1), B256::with_last_byte(2));
+
+        assert_eq!(store.insert(a, TransactionRequest::default(), Address::ZERO).unwrap(), 1_060);
+        assert_eq!(store.get(&a).unwrap().account, Address::ZERO);
+
+        clock.advance(Duration::from_secs(30));
+        store.insert(b, TransactionRequest::default(), Address::ZERO).unwrap();
+
+        // `a` expired, `b` can be sent once
+        clock.advance(Duration::from_secs(30));
+        assert!(store.get(&a).is_none());
+        assert!(store.remove(&a).is_none());
+        assert!(store.remove(&b).is_some());
+        assert!(store.remove(&b).is_none());
+    }
+
+    #[test]
+    fn limits_prepared_calls_per_account() {
+        let store =
+            PreparedCallsStore::new(Arc::new(MockClock::default()), Duration::from_secs(60));
+        let account = Address::with_last_byte(1);
+        for i in 0..MAX_PREPARED_CALLS_PER_ACCOUNT as u8 {
+            store.insert(B256::with_last_byte(i), TransactionRequest::default(), account).unwrap();
+        }
+        let digest = B256::with_last_byte(MAX_PREPARED_CALLS_PER_ACCOUNT as u8);
+        assert!(matches!(
+            store.insert(digest, TransactionRequest::default(), account),
+            Err(OdysseyWalletError::TooManyPreparedCalls)
+        ));
+
+        // other accounts are not affected
+        store.insert(digest, TransactionRequest::default(), Address::with_last_byte(2)).unwrap();
+    }
+
+    #[test]
+    fn removes_expired_prepared_calls() {
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let store = PreparedCallsStore::new(clock.clone(), Duration::from_secs(60));
+
+        store
+            .insert(B256::with_last_byte(1), TransactionRequest::default(), Address::ZERO)
+            .unwrap();
+        clock.advance(Duration::from_secs(30));
+        store
+            .insert(B256::with_last_byte(2), TransactionRequest::default(), Address::ZERO)
+            .unwrap();
+        assert_eq!(store.remove_expired(), 0);
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(store.remove_expired(), 1);
+        assert_eq!(store.remove_expired(), 0);
+        assert!(store.get(&B256::with_last_byte(2)).is_some());
+    }
+}
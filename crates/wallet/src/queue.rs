@@ -0,0 +1,379 @@
+//! Fair queueing of sponsored transactions.
+//!
+//! To prevent a single account from monopolizing the sponsor's throughput, every account gets its
+//! own FIFO lane, and lanes are served in round-robin order. Alternatively, all requests can share
+//! a single lane, see [`QueueLanes`].
+//!
+//! Requests are first admitted with [`TransactionQueue::admit`], which limits the number of
+//! outstanding requests per account until they are sent, and are then estimated concurrently. Once
+//! a request is ready to be sent, it waits for the [`QueuePermit`], which it holds while it waits
+//! for budget and reserves its slot in the nonce sequence of the sponsor. Budget and nonces of the
+//! sponsor are therefore handed to accounts in round-robin order, and an account with many
+//! requests can only send one of them before every other waiting account sent one as well. The
+//! permit is released with [`QueuePermit::release`] before the request is signed and sent, so the
+//! next request can wait for budget in the meantime.
+
+use alloy_primitives::Address;
+use parking_lot::Mutex;
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    sync::Arc,
+};
+use tokio::sync::oneshot;
+
+/// The default maximum number of outstanding requests per account.
+pub const DEFAULT_MAX_PENDING_PER_ACCOUNT: usize = 8;
+
+/// How the [`TransactionQueue`] orders requests that are ready to be sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueueLanes {
+    /// Every account gets its own lane, and lanes are served in round-robin order.
+    #[default]
+    PerAccount,
+    /// All requests share a single lane, and are served in the order they are ready.
+    Shared,
+}
+
+/// Errors returned by the [`TransactionQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum QueueError {
+    /// The account has too many outstanding requests.
+    #[error("too many pending requests for account {0}")]
+    TooManyPendingRequests(Address),
+    /// The queue was shut down.
+    #[error("transaction queue was shut down")]
+    Closed,
+}
+
+/// A queue that grants exclusive access to the sponsor, serving accounts in round-robin order.
+#[derive(Debug, Clone)]
+pub struct TransactionQueue {
+    inner: Arc<TransactionQueueInner>,
+}
+
+#[derive(Debug)]
+struct TransactionQueueInner {
+    max_pending_per_account: usize,
+    lanes: QueueLanes,
+    state: Mutex<QueueState>,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    /// Whether a permit is currently held.
+    busy: bool,
+    /// The waiting requests per lane, keyed by the account of the lane, or by the zero address if
+    /// the lane is shared.
+    lanes: HashMap<Address, VecDeque<oneshot::Sender<QueueTurn>>>,
+    /// The lanes with waiting requests, in the order they are served.
+    order: VecDeque<Address>,
+    /// The number of outstanding requests per account, including the one holding the permit.
+    pending: HashMap<Address, usize>,
+}
+
+impl QueueState {
+    fn decrement_pending(&mut self, account: &Address) {
+        if let Entry::Occupied(mut entry) = self.pending.entry(*account) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+}
+
+impl TransactionQueue {
+    /// Creates a new queue with the given maximum number of outstanding requests per account, that
+    /// serves every account in its own lane.
+    pub fn new(max_pending_per_account: usize) -> Self {
+        Self::with_lanes(max_pending_per_account, QueueLanes::default())
+    }
+
+    /// Creates a new queue with the given maximum number of outstanding requests per account and
+    /// [`QueueLanes`].
+    pub fn with_lanes(max_pending_per_account: usize, lanes: QueueLanes) -> Self {
+        Self {
+            inner: Arc::new(TransactionQueueInner {
+                max_pending_per_account,
+                lanes,
+                state: Default::default(),
+            }),
+        }
+    }
+
+    /// Returns the number of outstanding requests of the account.
+    pub fn pending(&self, account: &Address) -> usize {
+        self.inner.state.lock().pending.get(account).copied().unwrap_or_default()
+    }
+
+    /// Admits a request of the account, without waiting for its turn.
+    ///
+    /// Returns an error if the account already has too many outstanding requests.
+    pub fn admit(&self, account: Address) -> Result<PendingRequest, QueueError> {
+        let mut state = self.inner.state.lock();
+        let pending = state.pending.entry(account).or_default();
+        if *pending >= self.inner.max_pending_per_account {
+            return Err(QueueError::TooManyPendingRequests(account));
+        }
+        *pending += 1;
+        Ok(PendingRequest { queue: Some(self.clone()), account })
+    }
+
+    /// Admits a request of the account, and waits until it is the account's turn to use the
+    /// sponsor.
+    ///
+    /// Returns an error if the account already has too many outstanding requests.
+    pub async fn acquire(&self, account: Address) -> Result<QueuePermit, QueueError> {
+        self.admit(account)?.acquire().await
+    }
+
+    /// Hands the permit to the next waiting request, if any.
+    fn hand_off(&self, state: &mut QueueState) {
+        while let Some(next) = state.order.pop_front() {
+            let Some(lane) = state.lanes.get_mut(&next) else { continue };
+            let Some(tx) = lane.pop_front() else { continue };
+            if lane.is_empty() {
+                state.lanes.remove(&next);
+            } else {
+                // the lane goes to the back of the line
+                state.order.push_back(next);
+            }
+
+            match tx.send(QueueTurn { queue: Some(self.clone()) }) {
+                Ok(()) => return,
+                // the request was cancelled while waiting, so we skip it
+                Err(mut turn) => turn.queue = None,
+            }
+        }
+
+        state.busy = false;
+    }
+}
+
+impl Default for TransactionQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_PENDING_PER_ACCOUNT)
+    }
+}
+
+/// An outstanding request of an account, admitted by the [`TransactionQueue`].
+///
+/// The request counts as outstanding until this, or the [`QueuePermit`] it was turned into, is
+/// dropped.
+#[derive(Debug)]
+pub struct PendingRequest {
+    queue: Option<TransactionQueue>,
+    account: Address,
+}
+
+impl PendingRequest {
+    /// Returns the account the request was admitted for.
+    pub const fn account(&self) -> Address {
+        self.account
+    }
+
+    /// Waits until it is the account's turn to use the sponsor.
+    pub async fn acquire(self) -> Result<QueuePermit, QueueError> {
+        let queue = self.queue.clone().expect("request is pending");
+        let rx = {
+            let mut state = queue.inner.state.lock();
+            if !state.busy {
+                state.busy = true;
+                let turn = QueueTurn { queue: Some(queue.clone()) };
+                return Ok(QueuePermit { _turn: turn, pending: self });
+            }
+
+            let lane = match queue.inner.lanes {
+                QueueLanes::PerAccount => self.account,
+                QueueLanes::Shared => Address::ZERO,
+            };
+            let (tx, rx) = oneshot::channel();
+            match state.lanes.entry(lane) {
+                Entry::Occupied(mut entry) => entry.get_mut().push_back(tx),
+                Entry::Vacant(entry) => {
+                    entry.insert(VecDeque::from([tx]));
+                    state.order.push_back(lane);
+                }
+            }
+            rx
+        };
+
+        let turn = rx.await.map_err(|_| QueueError::Closed)?;
+        Ok(QueuePermit { _turn: turn, pending: self })
+    }
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            queue.inner.state.lock().decrement_pending(&self.account);
+        }
+    }
+}
+
+/// Exclusive access to the sponsor, granted by the [`TransactionQueue`].
+///
+/// The next request is served once this is dropped.
+#[derive(Debug)]
+pub struct QueuePermit {
+    _turn: QueueTurn,
+    pending: PendingRequest,
+}
+
+impl QueuePermit {
+    /// Returns the account this permit was granted to.
+    pub const fn account(&self) -> Address {
+        self.pending.account
+    }
+
+    /// Passes the turn on to the next waiting request, while the request stays outstanding until
+    /// the returned [`PendingRequest`] is dropped.
+    pub fn release(self) -> PendingRequest {
+        self.pending
+    }
+}
+
+/// The turn of a request, which is handed to the next waiting request once dropped.
+///
+/// This is sent to waiting requests, so the turn is passed on if the request was cancelled after
+/// it was sent the turn.
+#[derive(Debug)]
+struct QueueTurn {
+    queue: Option<TransactionQueue>,
+}
+
+impl Drop for QueueTurn {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            queue.hand_off(&mut queue.inner.state.lock());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[tokio::test]
+    async fn serves_accounts_round_robin() {
+        let queue = TransactionQueue::new(8);
+        let (a, b) = (Address::with_last_byte(1), Address::with_last_byte(2));
+
+        let first = queue.acquire(a).await.unwrap();
+        let a2 = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.acquire(a).await }
+        });
+        tokio::task::yield_now().await;
+        let a3 = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.acquire(a).await }
+        });
+        tokio::task::yield_now().await;
+        let b1 = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.acquire(b).await }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(queue.pending(&a), 3);
+
+        drop(first);
+        let permit = a2.await.unwrap().unwrap();
+        assert_eq!(permit.account(), a);
+
+        // b was waiting, so it is served before the next request of a
+        drop(permit);
+        let permit = b1.await.unwrap().unwrap();
+        assert_eq!(permit.account(), b);
+
+        drop(permit);
+        let permit = a3.await.unwrap().unwrap();
+        assert_eq!(permit.account(), a);
+
+        drop(permit);
+        assert_eq!(queue.pending(&a), 0);
+        assert_eq!(queue.pending(&b), 0);
+        assert!(queue.acquire(b).now_or_never().is_some());
+    }
+
+    #[tokio::test]
+    async fn serves_shared_lane_in_order() {
+        let queue = TransactionQueue::with_lanes(8, QueueLanes::Shared);
+        let (a, b) = (Address::with_last_byte(1), Address::with_last_byte(2));
+
+        let first = queue.acquire(a).await.unwrap();
+        let mut waiting = Vec::new();
+        for account in [a, a, b] {
+            let queue = queue.clone();
+            waiting.push(tokio::spawn(async move { queue.acquire(account).await }));
+            tokio::task::yield_now().await;
+        }
+
+        // requests are served in the order they are ready, regardless of their account
+        drop(first);
+        for account in [a, a, b] {
+            let permit = waiting.remove(0).await.unwrap().unwrap();
+            assert_eq!(permit.account(), account);
+        }
+    }
+
+    #[tokio::test]
+    async fn limits_pending_per_account() {
+        let queue = TransactionQueue::new(1);
+        let (a, b) = (Address::with_last_byte(1), Address::with_last_byte(2));
+
+        let _permit = queue.acquire(a).await.unwrap();
+        assert_eq!(queue.acquire(a).await.unwrap_err(), QueueError::TooManyPendingRequests(a));
+        assert!(queue.acquire(b).now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn counts_admitted_requests() {
+        let queue = TransactionQueue::new(1);
+        let (a, b) = (Address::with_last_byte(1), Address::with_last_byte(2));
+
+        let pending = queue.admit(a).unwrap();
+        // the request of a is outstanding, but does not hold the permit until it is ready
+        assert!(queue.acquire(b).now_or_never().is_some());
+        assert_eq!(queue.admit(a).unwrap_err(), QueueError::TooManyPendingRequests(a));
+
+        // the request is still outstanding while it holds the permit
+        let permit = pending.acquire().await.unwrap();
+        assert_eq!(queue.pending(&a), 1);
+        assert!(queue.acquire(b).now_or_never().is_none());
+
+        drop(permit);
+        assert_eq!(queue.pending(&a), 0);
+        assert!(queue.acquire(a).now_or_never().is_some());
+    }
+
+    #[tokio::test]
+    async fn released_requests_stay_outstanding() {
+        let queue = TransactionQueue::new(1);
+        let (a, b) = (Address::with_last_byte(1), Address::with_last_byte(2));
+
+        // the next request is served once the permit is released
+        let pending = queue.acquire(a).await.unwrap().release();
+        assert!(queue.acquire(b).now_or_never().is_some());
+        assert_eq!(queue.admit(a).unwrap_err(), QueueError::TooManyPendingRequests(a));
+
+        drop(pending);
+        assert_eq!(queue.pending(&a), 0);
+    }
+
+    #[tokio::test]
+    async fn skips_cancelled_requests() {
+        let queue = TransactionQueue::new(8);
+        let (a, b) = (Address::with_last_byte(1), Address::with_last_byte(2));
+
+        let permit = queue.acquire(a).await.unwrap();
+        // poll once to enqueue the request, then cancel it
+        assert!(queue.acquire(b).now_or_never().is_none());
+        assert_eq!(queue.pending(&b), 0);
+
+        drop(permit);
+        assert_eq!(queue.pending(&b), 0);
+        assert!(queue.acquire(b).now_or_never().is_some());
+    }
+}
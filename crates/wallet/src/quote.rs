@@ -0,0 +1,228 @@
+//! Signed quotes for sponsored transactions.
+//!
+//! `wallet_getQuote` commits the sponsor to the gas limit and fees of a request until the quote
+//! expires, by signing the digest of the built transaction along with the expiry. The client can
+//! verify the commitment against the sponsor's address, and `wallet_sendQuotedTransaction` only
+//! sponsors the request with exactly the quoted gas limit and fees, so the sponsor is not exposed
+//! to fee drift between estimation and submission. Every quote can be used once: it is taken while
+//! its transaction is sent, and returned if sending fails, so the client can retry.
+//!
+//! At most [`MAX_QUOTES_PER_ACCOUNT`] unused quotes are tracked per account, so a single account
+//! can not use up the quotes of all others.
+
+use crate::OdysseyWalletError;
+use alloy_primitives::{keccak256, Address, PrimitiveSignature, B256};
+use odyssey_common::Clock;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The default time a quote is valid for.
+pub const DEFAULT_QUOTE_TTL: Duration = Duration::from_secs(30);
+
+/// The maximum number of unused quotes that are tracked at once.
+const MAX_QUOTES: usize = 10_000;
+
+/// The maximum number of unused quotes that are tracked per account.
+pub const MAX_QUOTES_PER_ACCOUNT: usize = 16;
+
+/// The gas limit and fees the sponsor commits to for a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quote {
+    /// The gas limit of the transaction.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub gas: u64,
+    /// The max fee per gas of the transaction.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub max_fee_per_gas: u128,
+    /// The max priority fee per gas of the transaction.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub max_priority_fee_per_gas: u128,
+    /// The hash of the unsigned transaction the sponsor will sign, with a nonce of zero.
+    pub digest: B256,
+    /// The unix timestamp in seconds after which the quote can no longer be used.
+    pub expiry: u64,
+}
+
+impl Quote {
+    /// Returns the hash the sponsor signs ([EIP-191]) to commit to the quote.
+    ///
+    /// This is `keccak256(digest || expiry)`, with the expiry as a big endian `u64`.
+    ///
+    /// [EIP-191]: https://eips.ethereum.org/EIPS/eip-191
+    pub fn hash(&self) -> B256 {
+        let mut buf = [0; 40];
+        buf[..32].copy_from_slice(self.digest.as_slice());
+        buf[32..].copy_from_slice(&self.expiry.to_be_bytes());
+        keccak256(buf)
+    }
+}
+
+/// A [`Quote`] signed by the sponsor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedQuote {
+    /// The quote.
+    #[serde(flatten)]
+    pub quote: Quote,
+    /// The signature of the sponsor over the [hash](Quote::hash) of the quote.
+    pub signature: PrimitiveSignature,
+}
+
+/// Tracks issued quotes until they are used or expire.
+#[derive(Debug)]
+pub(crate) struct QuoteBook {
+    clock: Arc<dyn Clock>,
+    ttl: Duration,
+    /// The account and expiry of unused quotes by quote hash.
+    quotes: Mutex<HashMap<B256, (Address, Instant)>>,
+}
+
+impl QuoteBook {
+    /// Creates a new quote book that reads the time from the given [`Clock`], and issues quotes
+    /// that are valid for the given duration.
+    pub(crate) fn new(clock: Arc<dyn Clock>, ttl: Duration) -> Self {
+        Self { clock, ttl, quotes: Default::default() }
+    }
+
+    /// Returns the expiry of a quote issued now, as a unix timestamp in seconds.
+    pub(crate) fn expiry(&self) -> u64 {
+        (self.clock.unix_epoch_ms() + self.ttl.as_millis() as u64) / 1000
+    }
+
+    /// Tracks a quote issued for the account, removing expired quotes.
+    pub(crate) fn insert(&self, quote: &Quote, account: Address) -> Result<(), OdysseyWalletError> {
+        let now = self.clock.now();
+        let mut quotes = self.quotes.lock();
+        quotes.retain(|_, (_, expires_at)| *expires_at > now);
+        if quotes.len() >= MAX_QUOTES
+            || quotes.values().filter(|(quoted, _)| *quoted == account).count()
+                >= MAX_QUOTES_PER_ACCOUNT
+        {
+            return Err(OdysseyWalletError::TooManyQuotes);
+        }
+        quotes.insert(quote.hash(), (account, now + self.ttl));
+        Ok(())
+    }
+
+    /// Takes a quote for a call, until the call marks it as [used](TakenQuote::used).
+    ///
+    /// Returns `None` if the quote was not issued, was already taken, or expired.
+    pub(crate) fn take(&self, quote: &Quote) -> Option<TakenQuote<'_>> {
+        let now = self.clock.now();
+        let hash = quote.hash();
+        let entry = self.quotes.lock().remove(&hash).filter(|(_, expires_at)| *expires_at > now)?;
+        Some(TakenQuote { book: self, hash, entry: Some(entry) })
+    }
+
+    /// Removes expired quotes, returning the number of removed quotes.
+    pub(crate) fn remove_expired(&self) -> usize {
+        let now = self.clock.now();
+        let mut quotes = self.quotes.lock();
+        let len = quotes.len();
+        quotes.retain(|_, (_, expires_at)| *expires_at > now);
+        len - quotes.len()
+    }
+}
+
+/// A quote that was taken by a call, which is returned to the [`QuoteBook`] when dropped unless it
+/// was used.
+#[derive(Debug)]
+pub(crate) struct TakenQuote<'a> {
+    book: &'a QuoteBook,
+    hash: B256,
+    entry: Option<(Address, Instant)>,
+}
+
+impl TakenQuote<'_> {
+    /// Marks the quote as used, once its transaction was sent.
+    pub(crate) fn used(mut self) {
+        self.entry = None;
+    }
+}
+
+impl Drop for TakenQuote<'_> {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            self.book.quotes.lock().insert(self.hash, entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::U256;
+    use odyssey_common::MockClock;
+
+    fn quote(expiry: u64) -> Quote {
+        Quote {
+            gas: 21_000,
+            max_fee_per_gas: 2,
+            max_priority_fee_per_gas: 1,
+            digest: B256::with_last_byte(1),
+            expiry,
+        }
+    }
+
+    #[test]
+    fn quotes_are_used_once() {
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let book = QuoteBook::new(clock.clone(), Duration::from_secs(30));
+        assert_eq!(book.expiry(), 1_030);
+
+        let (a, b) = (quote(1), quote(2));
+        assert_ne!(a.hash(), b.hash());
+
+        book.insert(&a, Address::ZERO).unwrap();
+        book.insert(&b, Address::ZERO).unwrap();
+        let taken = book.take(&a).unwrap();
+        assert!(book.take(&a).is_none());
+        taken.used();
+        assert!(book.take(&a).is_none());
+
+        // `b` is returned if its call fails
+        drop(book.take(&b).unwrap());
+        assert!(book.take(&b).is_some());
+
+        // `b` expired
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(book.remove_expired(), 1);
+        assert!(book.take(&b).is_none());
+    }
+
+    #[test]
+    fn limits_quotes_per_account() {
+        let book = QuoteBook::new(Arc::new(MockClock::default()), Duration::from_secs(30));
+        let account = Address::with_last_byte(1);
+        for expiry in 0..MAX_QUOTES_PER_ACCOUNT as u64 {
+            book.insert(&quote(expiry), account).unwrap();
+        }
+        assert!(matches!(
+            book.insert(&quote(MAX_QUOTES_PER_ACCOUNT as u64), account),
+            Err(OdysseyWalletError::TooManyQuotes)
+        ));
+
+        // other accounts are not affected
+        book.insert(&quote(MAX_QUOTES_PER_ACCOUNT as u64), Address::with_last_byte(2)).unwrap();
+    }
+
+    #[test]
+    fn quote_format() {
+        let quote = quote(1_030);
+        let signed = SignedQuote {
+            quote,
+            signature: PrimitiveSignature::new(U256::from(1), U256::from(2), false),
+        };
+        let json = serde_json::to_value(signed).unwrap();
+        assert_eq!(json["gas"], "0x5208");
+        assert_eq!(json["maxFeePerGas"], "0x2");
+        assert_eq!(json["expiry"], 1_030);
+        assert_eq!(serde_json::from_value::<SignedQuote>(json).unwrap(), signed);
+    }
+}
@@ -0,0 +1,86 @@
+//! Reclaiming of sponsored gas that was estimated but not used.
+//!
+//! Sponsored transactions are sent with the estimated gas limit, and budget is reserved for the
+//! full limit while they are pending. Once the receipt of a transaction is observed, the gas it did
+//! not use is credited back to the account it was sponsored for, so accounts are only charged for
+//! the gas they actually used, and the over-estimation is exported as metrics to tune the
+//! estimator.
+
+use metrics::{Counter, Histogram};
+use metrics_derive::Metrics;
+
+/// The ratio of used gas to the gas limit below which a transaction counts as over-estimated.
+pub const OVERESTIMATE_RATIO: f64 = 0.5;
+
+/// The gas of an included sponsored transaction compared to its gas limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GasRefund {
+    /// The gas limit the transaction was sponsored with.
+    pub(crate) gas_limit: u64,
+    /// The gas used by the transaction.
+    pub(crate) gas_used: u64,
+}
+
+impl GasRefund {
+    /// Returns the gas that was reserved but not used.
+    pub(crate) const fn unused(&self) -> u64 {
+        self.gas_limit.saturating_sub(self.gas_used)
+    }
+
+    /// Returns the ratio of used gas to the gas limit.
+    pub(crate) fn used_ratio(&self) -> f64 {
+        if self.gas_limit == 0 {
+            return 1.0;
+        }
+        self.gas_used as f64 / self.gas_limit as f64
+    }
+
+    /// Returns `true` if the transaction used less than [`OVERESTIMATE_RATIO`] of its gas limit.
+    pub(crate) fn is_overestimated(&self) -> bool {
+        self.used_ratio() < OVERESTIMATE_RATIO
+    }
+}
+
+/// Metrics for the gas estimates of sponsored transactions.
+#[derive(Metrics)]
+#[metrics(scope = "wallet")]
+pub(crate) struct GasRefundMetrics {
+    /// Ratio of the gas used by sponsored transactions to their estimated gas limit
+    gas_used_ratio: Histogram,
+    /// Total gas that was estimated for sponsored transactions but not used
+    unused_gas: Counter,
+    /// Number of sponsored transactions that used less than half of their estimated gas limit
+    overestimated_transactions: Counter,
+}
+
+impl GasRefundMetrics {
+    /// Records the gas used by an included sponsored transaction.
+    pub(crate) fn record(&self, refund: GasRefund) {
+        self.gas_used_ratio.record(refund.used_ratio());
+        self.unused_gas.increment(refund.unused());
+        if refund.is_overestimated() {
+            self.overestimated_transactions.increment(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_unused_gas() {
+        let refund = GasRefund { gas_limit: 100_000, gas_used: 40_000 };
+        assert_eq!(refund.unused(), 60_000);
+        assert_eq!(refund.used_ratio(), 0.4);
+        assert!(refund.is_overestimated());
+
+        let refund = GasRefund { gas_limit: 100_000, gas_used: 90_000 };
+        assert!(!refund.is_overestimated());
+
+        // gas used above the limit is never refunded
+        let refund = GasRefund { gas_limit: 21_000, gas_used: 30_000 };
+        assert_eq!(refund.unused(), 0);
+        assert!(!GasRefund { gas_limit: 0, gas_used: 0 }.is_overestimated());
+    }
+}
@@ -0,0 +1,409 @@
+//! Reputation tracking for sponsored accounts.
+//!
+//! Reverted sponsored transactions are a pure loss for the sponsor, so the outcome of every
+//! sponsored transaction is recorded per account. Accounts whose sponsored transactions revert
+//! more often than the configured rate are banned from further sponsorship.
+//!
+//! At most [`MAX_TRACKED_ACCOUNTS`] accounts are tracked. Once the limit is reached, the accounts
+//! whose outcomes were recorded least recently are forgotten in a batch. Banned accounts are only
+//! forgotten once there are not enough other accounts to forget, oldest ban first, so a ban can not
+//! be lifted by sponsoring transactions for many other accounts.
+
+use alloy_primitives::Address;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tracing::warn;
+
+/// The default minimum number of sponsored transactions before an account can be banned.
+pub const DEFAULT_MIN_SAMPLES: u64 = 5;
+
+/// The default maximum ratio of reverted sponsored transactions.
+pub const DEFAULT_MAX_REVERT_RATE: f64 = 0.5;
+
+/// The maximum number of accounts whose outcomes are tracked, including banned accounts.
+pub const MAX_TRACKED_ACCOUNTS: usize = 65_536;
+
+/// The fraction of the maximum number of tracked accounts that is freed when the limit is reached.
+const EVICTION_BATCH_DIVISOR: usize = 8;
+
+/// The outcome of a sponsored transaction that was included in a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionOutcome {
+    /// Whether the transaction succeeded.
+    pub success: bool,
+    /// The gas used by the transaction.
+    pub gas_used: u64,
+    /// The price per gas paid by the transaction.
+    pub effective_gas_price: u128,
+    /// The L1 data fee paid by the transaction, if the chain charges one and the upstream reports
+    /// it.
+    pub l1_fee: Option<u128>,
+    /// The number of the block the transaction was included in.
+    pub block_number: u64,
+}
+
+impl TransactionOutcome {
+    /// Returns the share of the outcome attributed to each of the given number of accounts a
+    /// transaction was sponsored for: the gas used and the L1 data fee are split evenly, rounding
+    /// down.
+    pub fn attributed(self, accounts: usize) -> Self {
+        let accounts = accounts.max(1);
+        Self {
+            gas_used: self.gas_used / accounts as u64,
+            l1_fee: self.l1_fee.map(|fee| fee / accounts as u128),
+            ..self
+        }
+    }
+}
+
+/// Configuration for the [`Reputation`] tracker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReputationConfig {
+    /// The minimum number of recorded outcomes before an account can be banned.
+    pub min_samples: u64,
+    /// The maximum ratio of reverted transactions, between 0 and 1, before an account is banned.
+    pub max_revert_rate: f64,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        Self { min_samples: DEFAULT_MIN_SAMPLES, max_revert_rate: DEFAULT_MAX_REVERT_RATE }
+    }
+}
+
+/// The recorded sponsorship outcomes of an account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountReputation {
+    /// The account.
+    pub address: Address,
+    /// The number of sponsored transactions that succeeded.
+    pub succeeded: u64,
+    /// The number of sponsored transactions that reverted.
+    pub reverted: u64,
+    /// The total gas used by reverted sponsored transactions.
+    pub gas_wasted: u64,
+    /// The total gas used by sponsored transactions.
+    pub gas_used: u64,
+    /// The total gas that was estimated for sponsored transactions but not used, and was credited
+    /// back to the account.
+    pub gas_refunded: u64,
+    /// Whether the account is banned from sponsorship.
+    pub banned: bool,
+}
+
+impl AccountReputation {
+    /// Returns the total number of recorded outcomes.
+    pub const fn total(&self) -> u64 {
+        self.succeeded + self.reverted
+    }
+
+    /// Returns the ratio of reverted transactions.
+    pub fn revert_rate(&self) -> f64 {
+        if self.total() == 0 {
+            return 0.0;
+        }
+        self.reverted as f64 / self.total() as f64
+    }
+}
+
+/// Tracks the outcomes of sponsored transactions per account and bans misbehaving accounts.
+#[derive(Debug, Clone, Default)]
+pub struct Reputation {
+    inner: Arc<ReputationInner>,
+}
+
+#[derive(Debug, Default)]
+struct ReputationInner {
+    config: ReputationConfig,
+    accounts: RwLock<HashMap<Address, TrackedAccount>>,
+    /// The sequence number of the next update, used to find the least recently updated accounts.
+    next_update: AtomicU64,
+}
+
+/// The reputation of an account, and the sequence number of its last update.
+#[derive(Debug)]
+struct TrackedAccount {
+    reputation: AccountReputation,
+    updated: u64,
+}
+
+impl Reputation {
+    /// Creates a new reputation tracker with the given configuration.
+    pub fn new(config: ReputationConfig) -> Self {
+        Self {
+            inner: Arc::new(ReputationInner {
+                config,
+                accounts: Default::default(),
+                next_update: Default::default(),
+            }),
+        }
+    }
+
+    /// Returns `true` if the account is banned from sponsorship.
+    pub fn is_banned(&self, address: &Address) -> bool {
+        self.inner.accounts.read().get(address).is_some_and(|account| account.reputation.banned)
+    }
+
+    /// Returns the reputation of the account, if any outcomes were recorded for it.
+    pub fn get(&self, address: &Address) -> Option<AccountReputation> {
+        self.inner.accounts.read().get(address).map(|account| account.reputation)
+    }
+
+    /// Records the outcome of a sponsored transaction for the account, banning it if its revert
+    /// rate exceeds the configured maximum.
+    pub fn record(&self, address: Address, outcome: TransactionOutcome) {
+        let config = self.inner.config;
+        let mut accounts = self.inner.accounts.write();
+        let account = self.update(&mut accounts, address);
+
+        account.gas_used = account.gas_used.saturating_add(outcome.gas_used);
+        if outcome.success {
+            account.succeeded += 1;
+        } else {
+            account.reverted += 1;
+            account.gas_wasted = account.gas_wasted.saturating_add(outcome.gas_used);
+        }
+
+        if !account.banned
+            && account.total() >= config.min_samples
+            && account.revert_rate() > config.max_revert_rate
+        {
+            warn!(
+                target: "rpc::wallet",
+                %address,
+                reverted = account.reverted,
+                succeeded = account.succeeded,
+                "Banning account from sponsorship"
+            );
+            account.banned = true;
+        }
+    }
+
+    /// Records the outcome of a transaction that was sponsored for the given accounts, and credits
+    /// the gas it did not use of its gas limit.
+    ///
+    /// The destination is charged the full outcome, so it can not dilute its reputation by adding
+    /// authorizations of fresh accounts, and the other authorities share the outcome evenly.
+    pub fn record_sponsored(
+        &self,
+        destination: Option<Address>,
+        authorities: &[Address],
+        outcome: TransactionOutcome,
+        unused_gas: u64,
+    ) {
+        for authority in authorities {
+            if Some(*authority) == destination {
+                self.record(*authority, outcome);
+                self.refund(*authority, unused_gas);
+            } else {
+                self.record(*authority, outcome.attributed(authorities.len()));
+                self.refund(*authority, unused_gas / authorities.len() as u64);
+            }
+        }
+    }
+
+    /// Credits gas that was estimated for a sponsored transaction of the account but not used.
+    pub fn refund(&self, address: Address, gas: u64) {
+        let mut accounts = self.inner.accounts.write();
+        let account = self.update(&mut accounts, address);
+        account.gas_refunded = account.gas_refunded.saturating_add(gas);
+    }
+
+    /// Returns the reputation of the account to update, tracking the account if it is not tracked
+    /// yet.
+    fn update<'a>(
+        &self,
+        accounts: &'a mut HashMap<Address, TrackedAccount>,
+        address: Address,
+    ) -> &'a mut AccountReputation {
+        let updated = self.inner.next_update.fetch_add(1, Ordering::Relaxed);
+        if !accounts.contains_key(&address) && accounts.len() >= MAX_TRACKED_ACCOUNTS {
+            Self::evict(accounts);
+        }
+        let account = accounts.entry(address).or_insert_with(|| TrackedAccount {
+            reputation: AccountReputation { address, ..Default::default() },
+            updated,
+        });
+        account.updated = updated;
+        &mut account.reputation
+    }
+
+    /// Forgets the least recently updated accounts until a batch of accounts is free.
+    ///
+    /// Accounts that are not banned are forgotten first, and the oldest bans are only lifted if
+    /// there are not enough of them.
+    fn evict(accounts: &mut HashMap<Address, TrackedAccount>) {
+        let keep = MAX_TRACKED_ACCOUNTS - MAX_TRACKED_ACCOUNTS.div_ceil(EVICTION_BATCH_DIVISOR);
+        let evicted = accounts.len().saturating_sub(keep);
+        if evicted == 0 {
+            return;
+        }
+        // banned accounts are ordered after every account that is not banned
+        let mut order: Vec<_> =
+            accounts.values().map(|account| (account.reputation.banned, account.updated)).collect();
+        let (_, &mut cutoff, _) = order.select_nth_unstable(evicted - 1);
+        accounts.retain(|_, account| (account.reputation.banned, account.updated) > cutoff);
+    }
+
+    /// Returns all banned accounts.
+    pub fn banned(&self) -> Vec<AccountReputation> {
+        self.inner
+            .accounts
+            .read()
+            .values()
+            .filter(|account| account.reputation.banned)
+            .map(|account| account.reputation)
+            .collect()
+    }
+
+    /// Lifts the ban of the account and resets its recorded outcomes.
+    ///
+    /// Returns `true` if the account was banned.
+    pub fn unban(&self, address: &Address) -> bool {
+        self.inner.accounts.write().remove(address).is_some_and(|account| account.reputation.banned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REVERTED: TransactionOutcome = TransactionOutcome {
+        success: false,
+        gas_used: 21_000,
+        effective_gas_price: 1,
+        l1_fee: None,
+        block_number: 1,
+    };
+    const SUCCEEDED: TransactionOutcome = TransactionOutcome {
+        success: true,
+        gas_used: 21_000,
+        effective_gas_price: 1,
+        l1_fee: None,
+        block_number: 1,
+    };
+
+    #[test]
+    fn bans_after_min_samples() {
+        let reputation = Reputation::new(ReputationConfig { min_samples: 3, max_revert_rate: 0.5 });
+        let account = Address::with_last_byte(1);
+
+        reputation.record(account, REVERTED);
+        reputation.record(account, REVERTED);
+        assert!(!reputation.is_banned(&account));
+
+        reputation.record(account, REVERTED);
+        assert!(reputation.is_banned(&account));
+        assert_eq!(reputation.get(&account).unwrap().gas_wasted, 63_000);
+        assert_eq!(REVERTED.attributed(2).gas_used, 10_500);
+        assert_eq!(REVERTED.attributed(0), REVERTED);
+        assert_eq!(reputation.banned().len(), 1);
+    }
+
+    #[test]
+    fn does_not_ban_below_revert_rate() {
+        let reputation = Reputation::new(ReputationConfig { min_samples: 2, max_revert_rate: 0.5 });
+        let account = Address::with_last_byte(1);
+
+        reputation.record(account, SUCCEEDED);
+        reputation.record(account, REVERTED);
+        reputation.record(account, SUCCEEDED);
+        reputation.record(account, REVERTED);
+        assert!(!reputation.is_banned(&account));
+        assert_eq!(reputation.get(&account).unwrap().gas_wasted, 42_000);
+        assert_eq!(reputation.get(&account).unwrap().gas_used, 84_000);
+    }
+
+    #[test]
+    fn charges_destination_of_batches() {
+        let reputation = Reputation::new(ReputationConfig { min_samples: 2, max_revert_rate: 0.5 });
+        let destination = Address::with_last_byte(1);
+        let fresh: Vec<_> = (2..=10).map(Address::with_last_byte).collect();
+        let authorities: Vec<_> = fresh.iter().copied().chain([destination]).collect();
+
+        // a reverting batch with authorizations of fresh accounts still bans the destination
+        reputation.record_sponsored(Some(destination), &authorities, REVERTED, 10_000);
+        reputation.record_sponsored(Some(destination), &authorities, REVERTED, 10_000);
+        let recorded = reputation.get(&destination).unwrap();
+        assert!(recorded.banned);
+        assert_eq!(recorded.gas_wasted, 42_000);
+        assert_eq!(recorded.gas_refunded, 20_000);
+
+        let recorded = reputation.get(&fresh[0]).unwrap();
+        assert_eq!(recorded.gas_wasted, 4_200);
+        assert_eq!(recorded.gas_refunded, 2_000);
+    }
+
+    #[test]
+    fn records_refunds() {
+        let reputation = Reputation::default();
+        let account = Address::with_last_byte(1);
+
+        reputation.record(account, SUCCEEDED);
+        reputation.refund(account, 9_000);
+        reputation.refund(account, 1_000);
+
+        let recorded = reputation.get(&account).unwrap();
+        assert_eq!(recorded.gas_used, 21_000);
+        assert_eq!(recorded.gas_refunded, 10_000);
+        assert!(!recorded.banned);
+    }
+
+    #[test]
+    fn forgets_least_recently_updated_accounts() {
+        let reputation = Reputation::new(ReputationConfig { min_samples: 1, max_revert_rate: 0.0 });
+        let banned = Address::with_last_byte(1);
+        reputation.record(banned, REVERTED);
+
+        let accounts: Vec<_> = (0..MAX_TRACKED_ACCOUNTS as u64)
+            .map(|i| Address::left_padding_from(&(i + 2).to_be_bytes()))
+            .collect();
+        for account in &accounts {
+            reputation.record(*account, SUCCEEDED);
+        }
+        // the least recently updated accounts are forgotten, but banned accounts are kept
+        let tracked = reputation.inner.accounts.read().len();
+        assert!(tracked <= MAX_TRACKED_ACCOUNTS);
+        assert!(reputation.is_banned(&banned));
+        assert!(reputation.get(&accounts[0]).is_none());
+        assert!(reputation.get(accounts.last().unwrap()).is_some());
+    }
+
+    #[test]
+    fn forgets_oldest_bans() {
+        let reputation = Reputation::new(ReputationConfig { min_samples: 1, max_revert_rate: 0.0 });
+        let accounts: Vec<_> = (0..=MAX_TRACKED_ACCOUNTS as u64)
+            .map(|i| Address::left_padding_from(&(i + 1).to_be_bytes()))
+            .collect();
+        for account in &accounts {
+            reputation.record(*account, REVERTED);
+        }
+
+        // banned accounts are bounded as well, lifting the oldest bans first
+        assert!(reputation.banned().len() <= MAX_TRACKED_ACCOUNTS);
+        assert!(!reputation.is_banned(&accounts[0]));
+        assert!(reputation.is_banned(accounts.last().unwrap()));
+    }
+
+    #[test]
+    fn unban_resets_account() {
+        let reputation = Reputation::new(ReputationConfig { min_samples: 1, max_revert_rate: 0.0 });
+        let account = Address::with_last_byte(1);
+
+        reputation.record(account, REVERTED);
+        assert!(reputation.is_banned(&account));
+
+        assert!(reputation.unban(&account));
+        assert!(!reputation.is_banned(&account));
+        assert!(reputation.get(&account).is_none());
+        assert!(!reputation.unban(&account));
+    }
+}
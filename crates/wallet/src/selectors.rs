@@ -0,0 +1,194 @@
+//! Allowlist of the functions that are sponsored per implementation.
+//!
+//! Permissive delegation contracts expose functions the sponsor should not pay for. If a
+//! [`SelectorAllowlist`] is configured, requests are only sponsored if the account delegates to an
+//! implementation on the list, and the selector of the call is allowed for that implementation.
+//! An implementation can also allow any call with the `*` wildcard.
+//!
+//! Rules are given as `<implementation>:<selector>`, where the selector is either the hex-encoded
+//! 4-byte selector, a function signature such as `execute(bytes32,bytes)`, or `*`.
+
+use crate::OdysseyWalletError;
+use alloy_primitives::{keccak256, Address, Selector};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+/// A function that is allowed for an implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectorRule {
+    /// The implementation the rule applies to.
+    pub implementation: Address,
+    /// The allowed selector, or `None` if any call is allowed.
+    pub selector: Option<Selector>,
+}
+
+impl FromStr for SelectorRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (implementation, selector) = s.split_once(':').ok_or_else(|| {
+            format!("invalid selector rule {s}, expected <implementation>:<selector>")
+        })?;
+        let implementation = implementation
+            .parse()
+            .map_err(|err| format!("invalid implementation {implementation}: {err}"))?;
+        let selector = match selector {
+            "*" => None,
+            selector => Some(parse_selector(selector)?),
+        };
+        Ok(Self { implementation, selector })
+    }
+}
+
+/// Parses a hex-encoded 4-byte selector, or the selector of a function signature such as
+/// `execute(bytes32,bytes)`.
+pub(crate) fn parse_selector(selector: &str) -> Result<Selector, String> {
+    if selector.contains('(') {
+        return Ok(Selector::from_slice(&keccak256(selector.as_bytes())[..4]));
+    }
+    selector.parse().map_err(|err| format!("invalid selector {selector}: {err}"))
+}
+
+/// The functions that are allowed for an implementation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AllowedSelectors {
+    /// Any call is allowed.
+    Any,
+    /// Only calls with the given selectors are allowed.
+    Only(HashSet<Selector>),
+}
+
+/// The functions that are sponsored per implementation.
+///
+/// An empty allowlist sponsors any call to any implementation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelectorAllowlist {
+    implementations: HashMap<Address, AllowedSelectors>,
+}
+
+impl SelectorAllowlist {
+    /// Allows the function of the rule.
+    pub fn allow(&mut self, rule: SelectorRule) {
+        let allowed = self
+            .implementations
+            .entry(rule.implementation)
+            .or_insert_with(|| AllowedSelectors::Only(HashSet::new()));
+        match (rule.selector, allowed) {
+            (None, allowed) => *allowed = AllowedSelectors::Any,
+            (Some(selector), AllowedSelectors::Only(selectors)) => {
+                selectors.insert(selector);
+            }
+            (Some(_), AllowedSelectors::Any) => {}
+        }
+    }
+
+    /// Returns whether the allowlist is empty, in which case every call is sponsored.
+    pub fn is_empty(&self) -> bool {
+        self.implementations.is_empty()
+    }
+
+    /// Checks that a call with the given input to an account delegating to the implementation is
+    /// allowed.
+    ///
+    /// Calls with less than 4 bytes of input have no selector, and are only allowed by a wildcard.
+    pub(crate) fn check(
+        &self,
+        implementation: Address,
+        input: &[u8],
+    ) -> Result<(), OdysseyWalletError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let selector = input.get(..4).map(Selector::from_slice);
+        match self.implementations.get(&implementation) {
+            None => Err(OdysseyWalletError::ImplementationNotAllowed(implementation)),
+            Some(AllowedSelectors::Any) => Ok(()),
+            Some(AllowedSelectors::Only(selectors)) => match selector {
+                Some(selector) if selectors.contains(&selector) => Ok(()),
+                _ => Err(OdysseyWalletError::SelectorNotAllowed { implementation, selector }),
+            },
+        }
+    }
+
+    /// Checks that accounts delegating to the implementation can be sponsored, i.e. that some
+    /// function is allowed for it.
+    pub(crate) fn check_implementation(
+        &self,
+        implementation: Address,
+    ) -> Result<(), OdysseyWalletError> {
+        if self.is_empty() || self.implementations.contains_key(&implementation) {
+            return Ok(());
+        }
+        Err(OdysseyWalletError::ImplementationNotAllowed(implementation))
+    }
+}
+
+impl FromIterator<SelectorRule> for SelectorAllowlist {
+    fn from_iter<I: IntoIterator<Item = SelectorRule>>(iter: I) -> Self {
+        let mut allowlist = Self::default();
+        for rule in iter {
+            allowlist.allow(rule);
+        }
+        allowlist
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, hex};
+
+    const IMPLEMENTATION: Address = address!("0000000000000000000000000000000000000001");
+    const WILDCARD: Address = address!("0000000000000000000000000000000000000002");
+
+    #[test]
+    fn parses_rules() {
+        let rule: SelectorRule =
+            "0x0000000000000000000000000000000000000001:transfer(address,uint256)".parse().unwrap();
+        assert_eq!(rule.implementation, IMPLEMENTATION);
+        assert_eq!(rule.selector, Some(Selector::from(hex!("a9059cbb"))));
+
+        let rule: SelectorRule =
+            "0x0000000000000000000000000000000000000001:0xa9059cbb".parse().unwrap();
+        assert_eq!(rule.selector, Some(Selector::from(hex!("a9059cbb"))));
+
+        let rule: SelectorRule = "0x0000000000000000000000000000000000000001:*".parse().unwrap();
+        assert_eq!(rule.selector, None);
+
+        assert!("0x0000000000000000000000000000000000000001".parse::<SelectorRule>().is_err());
+        assert!("0x01:0xa9059cbb".parse::<SelectorRule>().is_err());
+        assert!("0x0000000000000000000000000000000000000001:0xa9".parse::<SelectorRule>().is_err());
+    }
+
+    #[test]
+    fn checks_selectors() {
+        assert!(SelectorAllowlist::default().check(IMPLEMENTATION, &[]).is_ok());
+
+        let allowlist = SelectorAllowlist::from_iter([
+            SelectorRule {
+                implementation: IMPLEMENTATION,
+                selector: Some(Selector::from(hex!("a9059cbb"))),
+            },
+            SelectorRule { implementation: WILDCARD, selector: None },
+        ]);
+
+        assert!(allowlist.check(IMPLEMENTATION, &hex!("a9059cbb00")).is_ok());
+        assert!(matches!(
+            allowlist.check(IMPLEMENTATION, &hex!("095ea7b3")),
+            Err(OdysseyWalletError::SelectorNotAllowed { selector: Some(_), .. })
+        ));
+        assert!(matches!(
+            allowlist.check(IMPLEMENTATION, &hex!("a905")),
+            Err(OdysseyWalletError::SelectorNotAllowed { selector: None, .. })
+        ));
+        assert!(allowlist.check(WILDCARD, &[]).is_ok());
+        assert!(matches!(
+            allowlist.check(Address::ZERO, &hex!("a9059cbb")),
+            Err(OdysseyWalletError::ImplementationNotAllowed(Address::ZERO))
+        ));
+        assert!(allowlist.check_implementation(WILDCARD).is_ok());
+        assert!(allowlist.check_implementation(Address::ZERO).is_err());
+    }
+}
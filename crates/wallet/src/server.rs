@@ -0,0 +1,148 @@
+//! Standalone HTTP and WebSocket server for the wallet RPC.
+//!
+//! This is used by the relay, and by the node if the wallet is served separately from the node's
+//! RPC server. WebSocket connections are served on the same port as HTTP, if enabled.
+
+use crate::{
+    ExperimentGateLayer, ExperimentGates, LoadShedConfig, LoadShedLayer, RequestValidationLayer,
+    RpcDebugLogConfig, RpcDebugLogLayer, TraceContextLayer,
+};
+use hyper::{header::HeaderValue, Method};
+use jsonrpsee::{
+    server::{RpcServiceBuilder, Server, ServerHandle},
+    Methods,
+};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use tower::ServiceBuilder;
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{AllowOrigin, Any, CorsLayer},
+};
+use tracing::info;
+
+/// The default port of the wallet server.
+pub const DEFAULT_WALLET_HTTP_PORT: u16 = 9119;
+
+/// The default maximum size of a request body in megabytes.
+pub const DEFAULT_MAX_REQUEST_SIZE_MB: u32 = 15;
+
+/// The default maximum size of a response body in megabytes.
+pub const DEFAULT_MAX_RESPONSE_SIZE_MB: u32 = 160;
+
+/// The default maximum number of concurrent connections.
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 500;
+
+/// Configuration for the standalone wallet server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletServerConfig {
+    /// The address to serve the RPC on.
+    pub addr: SocketAddr,
+    /// Comma separated list of origins that are allowed to make cross-origin requests, or `*` to
+    /// allow any origin.
+    pub cors_domains: String,
+    /// The maximum size of a request body in megabytes.
+    pub max_request_size_mb: u32,
+    /// The maximum size of a response body in megabytes.
+    pub max_response_size_mb: u32,
+    /// The maximum number of concurrent connections.
+    pub max_connections: u32,
+    /// Whether to compress responses with gzip or brotli.
+    pub compression: bool,
+    /// The thresholds at which calls are shed under overload.
+    pub load_shed: LoadShedConfig,
+    /// Whether to accept WebSocket connections, which are required for subscriptions.
+    pub ws: bool,
+    /// The methods that are gated behind experiment flags.
+    pub experiments: ExperimentGates,
+    /// The logging of raw requests and responses for debugging, disabled by default.
+    pub debug_log: RpcDebugLogConfig,
+}
+
+impl WalletServerConfig {
+    /// Starts the server, serving the given methods.
+    ///
+    /// The address is bound before this returns, so callers can fail their startup if it is not
+    /// available. This must be called from within a tokio runtime.
+    pub fn start(self, methods: impl Into<Methods>) -> eyre::Result<ServerHandle> {
+        let listener = TcpListener::bind(self.addr)?;
+        listener.set_nonblocking(true)?;
+
+        let cors = CorsLayer::new()
+            .allow_methods([Method::POST])
+            .allow_origin(cors_origin(&self.cors_domains)?)
+            .allow_headers([hyper::header::CONTENT_TYPE]);
+        let compression = CompressionLayer::new().gzip(self.compression).br(self.compression);
+
+        let builder = Server::builder();
+        let builder = if self.ws { builder } else { builder.http_only() };
+        let server = builder
+            .max_request_body_size(self.max_request_size_mb.saturating_mul(1024 * 1024))
+            .max_response_body_size(self.max_response_size_mb.saturating_mul(1024 * 1024))
+            .max_connections(self.max_connections)
+            .set_rpc_middleware(
+                RpcServiceBuilder::new()
+                    .layer(RpcDebugLogLayer::new(self.debug_log))
+                    .layer(ExperimentGateLayer::new(self.experiments))
+                    .layer(LoadShedLayer::new(self.load_shed))
+                    .layer(RequestValidationLayer),
+            )
+            .set_http_middleware(
+                ServiceBuilder::new().layer(TraceContextLayer).layer(cors).layer(compression),
+            )
+            .build_from_tcp(listener)?;
+        info!(
+            target: "rpc::wallet",
+            addr = ?server.local_addr()?,
+            ws = self.ws,
+            "Started wallet server"
+        );
+
+        Ok(server.start(methods))
+    }
+}
+
+impl Default for WalletServerConfig {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), DEFAULT_WALLET_HTTP_PORT),
+            cors_domains: "*".to_string(),
+            max_request_size_mb: DEFAULT_MAX_REQUEST_SIZE_MB,
+            max_response_size_mb: DEFAULT_MAX_RESPONSE_SIZE_MB,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            compression: true,
+            load_shed: LoadShedConfig::default(),
+            ws: false,
+            experiments: ExperimentGates::default(),
+            debug_log: RpcDebugLogConfig::default(),
+        }
+    }
+}
+
+/// Parses a comma separated list of allowed origins.
+fn cors_origin(domains: &str) -> eyre::Result<AllowOrigin> {
+    let domains: Vec<_> = domains.split(',').map(str::trim).collect();
+    if domains.contains(&"*") {
+        if domains.len() > 1 {
+            eyre::bail!("wildcard origin (`*`) cannot be passed as part of a list: {domains:?}");
+        }
+        return Ok(Any.into());
+    }
+
+    let origins = domains
+        .into_iter()
+        .map(|domain| domain.parse::<HeaderValue>())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(AllowOrigin::list(origins))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cors_origins() {
+        assert!(cors_origin("*").is_ok());
+        assert!(cors_origin("https://ithaca.xyz, https://odyssey.ithaca.xyz").is_ok());
+        assert!(cors_origin("*,https://ithaca.xyz").is_err());
+    }
+}
@@ -0,0 +1,346 @@
+//! Load shedding for the wallet server and the RPC server of the node.
+//!
+//! Under overload, requests are shed by priority instead of every request suffering from tail
+//! latency:
+//!
+//! - Read-only status calls are rejected first, once half of the capacity is in use or the average
+//!   latency exceeds the threshold.
+//! - Calls that send transactions wait for capacity until a deadline.
+//! - Other calls are rejected once the capacity is in use.
+//! - Engine API calls are never shed.
+//!
+//! The average latency is measured over the handlers of normal and read-only calls, as calls that
+//! send transactions spend most of their time waiting for the sponsor. It decays while no calls are
+//! measured, so read-only calls are served again once the server recovers.
+//!
+//! Rejected calls fail with the JSON-RPC "server is busy" error, so clients can retry later.
+
+use futures::future::BoxFuture;
+use jsonrpsee::{
+    server::{middleware::rpc::RpcServiceT, MethodResponse},
+    types::{
+        error::{SERVER_IS_BUSY_CODE, SERVER_IS_BUSY_MSG},
+        ErrorObject, Request,
+    },
+};
+use odyssey_common::{Clock, SystemClock};
+use parking_lot::Mutex;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tower::Layer;
+use tracing::debug;
+
+/// The default maximum number of requests that are processed at once.
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 512;
+
+/// The default average latency above which read-only calls are shed.
+pub const DEFAULT_MAX_LATENCY: Duration = Duration::from_secs(2);
+
+/// The default time calls that send transactions wait for capacity.
+pub const DEFAULT_SEND_DEADLINE: Duration = Duration::from_secs(5);
+
+/// The weight of a new sample in the moving average of the latency.
+const LATENCY_WEIGHT: f64 = 0.1;
+
+/// The time after which the average latency has halved if no calls are measured.
+const LATENCY_HALF_LIFE: Duration = Duration::from_secs(5);
+
+/// Read-only status calls, which are shed first.
+const READ_ONLY_METHODS: &[&str] = &[
+    "odyssey_getWallTimeData",
+    "odyssey_feeHistory",
+    "wallet_feeHistory",
+    "wallet_getInclusionStats",
+    "wallet_getSponsorFeeStats",
+    "odyssey_getSponsorFeeStats",
+    "wallet_getTokenFeeStats",
+    "wallet_getDelegators",
+    "wallet_getCapabilities",
+    "wallet_pendingSponsoredTransactions",
+    "wallet_getTransactionStatus",
+    "eth_chainId",
+    "net_version",
+    "web3_clientVersion",
+];
+
+/// The priority class of a method, deciding how calls are shed under overload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodClass {
+    /// Engine API calls, which are never shed.
+    Critical,
+    /// Calls that send transactions, which wait for capacity until a deadline.
+    Send,
+    /// Calls that are shed once the capacity is in use.
+    Normal,
+    /// Read-only status calls, which are shed first.
+    ReadOnly,
+}
+
+impl MethodClass {
+    /// Returns the class of the method with the given name.
+    pub fn of(method: &str) -> Self {
+        if method.starts_with("engine_") {
+            Self::Critical
+        } else if method.ends_with("_sendTransaction") || method.starts_with("wallet_send") {
+            Self::Send
+        } else if READ_ONLY_METHODS.contains(&method) {
+            Self::ReadOnly
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+/// Configuration of the load shedding thresholds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadShedConfig {
+    /// The maximum number of requests that are processed at once.
+    pub max_in_flight: usize,
+    /// The average latency above which read-only calls are shed.
+    pub max_latency: Duration,
+    /// The time calls that send transactions wait for capacity.
+    pub send_deadline: Duration,
+}
+
+impl Default for LoadShedConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            max_latency: DEFAULT_MAX_LATENCY,
+            send_deadline: DEFAULT_SEND_DEADLINE,
+        }
+    }
+}
+
+/// RPC middleware that sheds calls by priority under overload.
+#[derive(Debug, Clone)]
+pub struct LoadShedLayer {
+    state: Arc<LoadShedState>,
+}
+
+impl LoadShedLayer {
+    /// Creates a new middleware with the given thresholds.
+    pub fn new(config: LoadShedConfig) -> Self {
+        Self::with_clock(config, Arc::new(SystemClock))
+    }
+
+    /// Creates a new middleware with the given thresholds, measuring latency with the given
+    /// [`Clock`].
+    pub fn with_clock(config: LoadShedConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            state: Arc::new(LoadShedState {
+                permits: Arc::new(Semaphore::new(config.max_in_flight)),
+                latency: Default::default(),
+                clock,
+                config,
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for LoadShedLayer {
+    type Service = LoadShed<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        LoadShed { service, state: self.state.clone() }
+    }
+}
+
+/// The service created by [`LoadShedLayer`].
+#[derive(Debug, Clone)]
+pub struct LoadShed<S> {
+    service: S,
+    state: Arc<LoadShedState>,
+}
+
+impl<'a, S> RpcServiceT<'a> for LoadShed<S>
+where
+    S: RpcServiceT<'a> + Clone + Send + Sync + 'a,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let service = self.service.clone();
+        let state = self.state.clone();
+
+        Box::pin(async move {
+            let class = MethodClass::of(request.method_name());
+            if class == MethodClass::Critical {
+                return service.call(request).await;
+            }
+
+            let Some(_permit) = state.admit(class).await else {
+                debug!(target: "rpc::wallet", method = request.method_name(), "Shedding call");
+                return MethodResponse::error(
+                    request.id,
+                    ErrorObject::borrowed(SERVER_IS_BUSY_CODE, SERVER_IS_BUSY_MSG, None),
+                );
+            };
+
+            if class == MethodClass::Send {
+                return service.call(request).await;
+            }
+            let start = state.clock.now();
+            let response = service.call(request).await;
+            state.record_latency(state.clock.now().saturating_duration_since(start));
+            response
+        })
+    }
+}
+
+#[derive(Debug)]
+struct LoadShedState {
+    config: LoadShedConfig,
+    /// A permit per call that can be processed at once.
+    permits: Arc<Semaphore>,
+    /// The moving average of the latency of the handlers of admitted calls.
+    latency: Mutex<LatencyAverage>,
+    clock: Arc<dyn Clock>,
+}
+
+/// A moving average of the latency, and when it was last updated.
+#[derive(Debug, Default)]
+struct LatencyAverage {
+    average: Duration,
+    updated_at: Option<Instant>,
+}
+
+impl LatencyAverage {
+    /// Returns the average, halved for every [`LATENCY_HALF_LIFE`] since it was last updated.
+    fn decayed(&self, now: Instant) -> Duration {
+        let Some(updated_at) = self.updated_at else { return self.average };
+        let half_lives = now.saturating_duration_since(updated_at).as_secs_f64()
+            / LATENCY_HALF_LIFE.as_secs_f64();
+        self.average.mul_f64(0.5f64.powf(half_lives))
+    }
+}
+
+impl LoadShedState {
+    /// Waits for capacity to process a call of the given class, or returns `None` if the call
+    /// should be shed.
+    async fn admit(&self, class: MethodClass) -> Option<OwnedSemaphorePermit> {
+        match class {
+            MethodClass::Critical | MethodClass::Normal => {
+                self.permits.clone().try_acquire_owned().ok()
+            }
+            MethodClass::Send => tokio::time::timeout(
+                self.config.send_deadline,
+                self.permits.clone().acquire_owned(),
+            )
+            .await
+            .ok()?
+            .ok(),
+            MethodClass::ReadOnly => {
+                if self.permits.available_permits() <= self.config.max_in_flight / 2
+                    || self.latency() > self.config.max_latency
+                {
+                    return None;
+                }
+                self.permits.clone().try_acquire_owned().ok()
+            }
+        }
+    }
+
+    /// Returns the moving average of the latency of admitted calls.
+    fn latency(&self) -> Duration {
+        self.latency.lock().decayed(self.clock.now())
+    }
+
+    /// Records the latency of the handler of an admitted call.
+    fn record_latency(&self, elapsed: Duration) {
+        let now = self.clock.now();
+        let mut latency = self.latency.lock();
+        let average = latency.decayed(now);
+        latency.average = if latency.updated_at.is_none() {
+            elapsed
+        } else {
+            average.mul_f64(1.0 - LATENCY_WEIGHT) + elapsed.mul_f64(LATENCY_WEIGHT)
+        };
+        latency.updated_at = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odyssey_common::MockClock;
+
+    fn state(max_in_flight: usize) -> Arc<LoadShedState> {
+        state_with_clock(max_in_flight, Arc::new(SystemClock))
+    }
+
+    fn state_with_clock(max_in_flight: usize, clock: Arc<dyn Clock>) -> Arc<LoadShedState> {
+        LoadShedLayer::with_clock(
+            LoadShedConfig {
+                max_in_flight,
+                max_latency: Duration::from_millis(100),
+                send_deadline: Duration::from_millis(50),
+            },
+            clock,
+        )
+        .state
+    }
+
+    #[test]
+    fn classifies_methods() {
+        assert_eq!(MethodClass::of("engine_getPayloadV3"), MethodClass::Critical);
+        assert_eq!(MethodClass::of("odyssey_sendTransaction"), MethodClass::Send);
+        assert_eq!(MethodClass::of("wallet_sendPreparedCalls"), MethodClass::Send);
+        assert_eq!(MethodClass::of("odyssey_getWallTimeData"), MethodClass::ReadOnly);
+        assert_eq!(MethodClass::of("wallet_prepareCalls"), MethodClass::Normal);
+    }
+
+    #[tokio::test]
+    async fn sheds_read_only_calls_first() {
+        let state = state(4);
+
+        let _a = state.admit(MethodClass::Normal).await.unwrap();
+        assert!(state.admit(MethodClass::ReadOnly).await.is_some());
+
+        // half of the capacity is in use
+        let _b = state.admit(MethodClass::Normal).await.unwrap();
+        assert!(state.admit(MethodClass::ReadOnly).await.is_none());
+        let _c = state.admit(MethodClass::Normal).await.unwrap();
+        let _d = state.admit(MethodClass::Normal).await.unwrap();
+
+        // the capacity is in use
+        assert!(state.admit(MethodClass::Normal).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn sheds_read_only_calls_on_latency() {
+        let clock = Arc::new(MockClock::default());
+        let state = state_with_clock(4, clock.clone());
+        assert!(state.admit(MethodClass::ReadOnly).await.is_some());
+
+        state.record_latency(Duration::from_millis(300));
+        assert!(state.admit(MethodClass::ReadOnly).await.is_none());
+        assert!(state.admit(MethodClass::Normal).await.is_some());
+
+        // the average decays while read-only calls are shed
+        clock.advance(LATENCY_HALF_LIFE);
+        assert!(state.admit(MethodClass::ReadOnly).await.is_none());
+        clock.advance(LATENCY_HALF_LIFE);
+        assert!(state.admit(MethodClass::ReadOnly).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn queues_send_calls() {
+        let state = state(1);
+        let permit = state.admit(MethodClass::Normal).await.unwrap();
+
+        // the deadline passes
+        assert!(state.admit(MethodClass::Send).await.is_none());
+
+        // capacity frees up before the deadline
+        let waiting = tokio::spawn({
+            let state = state.clone();
+            async move { state.admit(MethodClass::Send).await.is_some() }
+        });
+        drop(permit);
+        assert!(waiting.await.unwrap());
+    }
+}
@@ -0,0 +1,86 @@
+//! Simulation of sponsored transactions.
+//!
+//! `wallet_simulateSponsoredTransaction` runs a request through the same validation as
+//! `wallet_sendTransaction`, and executes it against the latest state with optional state
+//! overrides, e.g. to pretend the account already delegates to a contract under development. The
+//! request is never signed or sent, and does not count against the queue or the budget of the
+//! sponsor.
+
+use alloy_primitives::{Address, Bytes, U256};
+use alloy_rpc_types::TransactionRequest;
+use serde::{Deserialize, Serialize};
+
+/// The result of executing a transaction request as a call.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedCall {
+    /// Whether the call succeeded.
+    pub success: bool,
+    /// The return data of the call, or its revert data if it failed.
+    pub output: Bytes,
+    /// The estimated gas usage of the call, if it succeeded.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "alloy_rpc_types::serde_helpers::quantity::opt"
+    )]
+    pub gas: Option<u64>,
+    /// The reason the call failed, if it did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SimulatedCall {
+    /// Returns the result of a call that succeeded with the given output and gas estimate.
+    pub const fn succeeded(output: Bytes, gas: u64) -> Self {
+        Self { success: true, output, gas: Some(gas), error: None }
+    }
+
+    /// Returns the result of a call that failed for the given reason, with the given revert data.
+    pub fn failed(error: impl Into<String>, output: Bytes) -> Self {
+        Self { success: false, output, gas: None, error: Some(error.into()) }
+    }
+}
+
+/// The result of `wallet_simulateSponsoredTransaction`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SponsoredSimulation {
+    /// The account the transaction would be sponsored for, if any.
+    pub account: Option<Address>,
+    /// The transaction the sponsor would sign, without a nonce.
+    ///
+    /// The gas limit and fees are only set if the call succeeded.
+    pub request: TransactionRequest,
+    /// The result of executing the transaction.
+    #[serde(flatten)]
+    pub call: SimulatedCall,
+    /// The L1 data fee of the transaction, if the call succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub l1_fee: Option<U256>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::bytes;
+
+    #[test]
+    fn serializes_simulation() {
+        let simulation = SponsoredSimulation {
+            account: Some(Address::with_last_byte(1)),
+            request: TransactionRequest::default(),
+            call: SimulatedCall::failed("execution reverted", bytes!("08c379a0")),
+            l1_fee: None,
+        };
+        let json = serde_json::to_value(&simulation).unwrap();
+        assert_eq!(json["success"], false);
+        assert_eq!(json["output"], "0x08c379a0");
+        assert_eq!(json["error"], "execution reverted");
+        assert!(json.get("gas").is_none());
+        assert_eq!(serde_json::from_value::<SponsoredSimulation>(json).unwrap(), simulation);
+
+        let call = SimulatedCall::succeeded(Bytes::new(), 21_000);
+        assert_eq!(serde_json::to_value(&call).unwrap()["gas"], "0x5208");
+    }
+}
@@ -0,0 +1,245 @@
+//! Status updates of sponsored transactions.
+//!
+//! The wallet publishes an update when a sponsored transaction is sent, and when it is included
+//! in a block or dropped. Wallet UIs subscribe to the updates of the transactions of an authority
+//! with `wallet_subscribeMyTransactions` over WebSocket, instead of polling for receipts.
+//!
+//! Included transactions can still be reorged out until their block is finalized.
+//! `wallet_getTransactionStatus` returns the status of a transaction along with its confirmation
+//! depth, and whether its block is at or below the safe and finalized heads of the fork choice, so
+//! clients can tell included transactions from irreversible ones.
+
+use alloy_primitives::{Address, TxHash};
+use jsonrpsee::{core::SubscriptionResult, PendingSubscriptionSink, SubscriptionMessage};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::debug;
+
+/// The default number of status updates that are buffered for slow subscribers.
+pub const DEFAULT_STATUS_CHANNEL_CAPACITY: usize = 1_024;
+
+/// The status of a sponsored transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransactionStatus {
+    /// The transaction was sent and is waiting for inclusion.
+    Pending,
+    /// The transaction was included in a block and succeeded.
+    Succeeded,
+    /// The transaction was included in a block and reverted.
+    Reverted,
+    /// The transaction was not included in time.
+    Dropped,
+}
+
+/// An update of the status of a sponsored transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionStatusUpdate {
+    /// The hash of the transaction.
+    pub transaction_hash: TxHash,
+    /// The authority the transaction was sponsored for.
+    pub authority: Address,
+    /// The new status of the transaction.
+    pub status: TransactionStatus,
+    /// The gas used by the transaction, if it was included.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "alloy_rpc_types::serde_helpers::quantity::opt"
+    )]
+    pub gas_used: Option<u64>,
+}
+
+/// The latest, safe and finalized block numbers of the chain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChainHeads {
+    /// The number of the latest block.
+    pub latest: u64,
+    /// The number of the safe block, if the fork choice has one.
+    pub safe: Option<u64>,
+    /// The number of the finalized block, if the fork choice has one.
+    pub finalized: Option<u64>,
+}
+
+/// The status of a sponsored transaction returned by `wallet_getTransactionStatus`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SponsoredTransactionStatus {
+    /// The hash of the transaction.
+    pub transaction_hash: TxHash,
+    /// The status of the transaction.
+    pub status: TransactionStatus,
+    /// The number of the block the transaction was included in, if it was included.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "alloy_rpc_types::serde_helpers::quantity::opt"
+    )]
+    pub block_number: Option<u64>,
+    /// The number of blocks including the transaction's block on the canonical chain, or 0 if it
+    /// was not included.
+    #[serde(with = "alloy_rpc_types::serde_helpers::quantity")]
+    pub confirmations: u64,
+    /// Whether the block of the transaction is at or below the safe head.
+    pub safe: bool,
+    /// Whether the block of the transaction is finalized, so the transaction can no longer be
+    /// reorged out.
+    pub finalized: bool,
+}
+
+impl SponsoredTransactionStatus {
+    /// Returns the status of a transaction that was not included yet.
+    pub const fn pending(transaction_hash: TxHash) -> Self {
+        Self {
+            transaction_hash,
+            status: TransactionStatus::Pending,
+            block_number: None,
+            confirmations: 0,
+            safe: false,
+            finalized: false,
+        }
+    }
+
+    /// Returns the status of a transaction that was included in the given block, relative to the
+    /// heads of the chain.
+    pub fn included(
+        transaction_hash: TxHash,
+        success: bool,
+        block_number: u64,
+        heads: ChainHeads,
+    ) -> Self {
+        let at_or_below = |head: Option<u64>| head.is_some_and(|head| block_number <= head);
+        Self {
+            transaction_hash,
+            status: if success {
+                TransactionStatus::Succeeded
+            } else {
+                TransactionStatus::Reverted
+            },
+            block_number: Some(block_number),
+            confirmations: (heads.latest + 1).saturating_sub(block_number),
+            safe: at_or_below(heads.safe),
+            finalized: at_or_below(heads.finalized),
+        }
+    }
+}
+
+/// Broadcasts status updates of sponsored transactions to subscribers.
+#[derive(Debug, Clone)]
+pub struct TransactionStatusFeed {
+    sender: broadcast::Sender<TransactionStatusUpdate>,
+}
+
+impl Default for TransactionStatusFeed {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATUS_CHANNEL_CAPACITY)
+    }
+}
+
+impl TransactionStatusFeed {
+    /// Creates a new feed that buffers the given number of updates for slow subscribers.
+    pub fn new(capacity: usize) -> Self {
+        Self { sender: broadcast::channel(capacity).0 }
+    }
+
+    /// Publishes a status update to all subscribers.
+    pub fn publish(&self, update: TransactionStatusUpdate) {
+        // sending only fails if there are no subscribers
+        let _ = self.sender.send(update);
+    }
+
+    /// Subscribes to the status updates of all sponsored transactions.
+    pub fn subscribe(&self) -> broadcast::Receiver<TransactionStatusUpdate> {
+        self.sender.subscribe()
+    }
+
+    /// Accepts the subscription, and sends the status updates of the transactions of the
+    /// authority to the subscriber until it unsubscribes.
+    pub(crate) async fn pipe(
+        &self,
+        pending: PendingSubscriptionSink,
+        authority: Address,
+    ) -> SubscriptionResult {
+        // subscribe before accepting, so no update is missed
+        let mut updates = self.subscribe();
+        let sink = pending.accept().await?;
+
+        loop {
+            tokio::select! {
+                _ = sink.closed() => break,
+                update = updates.recv() => match update {
+                    Ok(update) if update.authority == authority => {
+                        sink.send(SubscriptionMessage::from_json(&update)?).await?;
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        debug!(target: "rpc::wallet", %authority, skipped, "Subscriber lagged behind");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_updates() {
+        let update = TransactionStatusUpdate {
+            transaction_hash: TxHash::with_last_byte(1),
+            authority: Address::with_last_byte(2),
+            status: TransactionStatus::Succeeded,
+            gas_used: Some(21_000),
+        };
+        let json = serde_json::to_value(&update).unwrap();
+        assert_eq!(json["status"], "succeeded");
+        assert_eq!(json["gasUsed"], "0x5208");
+
+        let pending = TransactionStatusUpdate {
+            status: TransactionStatus::Pending,
+            gas_used: None,
+            ..update
+        };
+        let json = serde_json::to_value(&pending).unwrap();
+        assert!(json.get("gasUsed").is_none());
+    }
+
+    #[test]
+    fn derives_confirmations_from_heads() {
+        let tx_hash = TxHash::with_last_byte(1);
+        let heads = ChainHeads { latest: 12, safe: Some(10), finalized: Some(8) };
+
+        let status = SponsoredTransactionStatus::included(tx_hash, true, 10, heads);
+        assert_eq!(status.confirmations, 3);
+        assert!(status.safe);
+        assert!(!status.finalized);
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["status"], "succeeded");
+        assert_eq!(json["blockNumber"], "0xa");
+        assert_eq!(json["confirmations"], "0x3");
+
+        let status = SponsoredTransactionStatus::included(tx_hash, false, 8, heads);
+        assert_eq!(status.status, TransactionStatus::Reverted);
+        assert!(status.safe && status.finalized);
+
+        // without safe and finalized heads, nothing is safe
+        let status = SponsoredTransactionStatus::included(
+            tx_hash,
+            true,
+            1,
+            ChainHeads { latest: 12, ..Default::default() },
+        );
+        assert_eq!(status.confirmations, 12);
+        assert!(!status.safe && !status.finalized);
+
+        let status = SponsoredTransactionStatus::pending(tx_hash);
+        assert_eq!(status.confirmations, 0);
+        assert!(serde_json::to_value(&status).unwrap().get("blockNumber").is_none());
+    }
+}
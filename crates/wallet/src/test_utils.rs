@@ -0,0 +1,240 @@
+//! Test utilities of the wallet.
+
+use crate::{
+    ChainHeads, OdysseyWalletError, PendingSponsoredTransaction, SimulatedCall, TransactionOutcome,
+    Upstream,
+};
+use alloy_primitives::{keccak256, Address, Bytes, PrimitiveSignature, TxHash, B256};
+use alloy_provider::utils::Eip1559Estimation;
+use alloy_rpc_types::{
+    simulate::SimCallResult, state::StateOverride, BlockId, BlockNumberOrTag, FeeHistory,
+    TransactionRequest,
+};
+use alloy_signer::SignerSync;
+use alloy_signer_local::PrivateKeySigner;
+use jsonrpsee::core::async_trait;
+use odyssey_common::delegation_designator;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::Semaphore;
+
+/// The gas estimate of every request.
+pub(crate) const MOCK_GAS_ESTIMATE: u64 = 100_000;
+
+/// The gas used by every included transaction.
+pub(crate) const MOCK_GAS_USED: u64 = 60_000;
+
+/// The max fee per gas of every sponsored transaction.
+pub(crate) const MOCK_MAX_FEE_PER_GAS: u128 = 2;
+
+/// An [`Upstream`] that serves a chain held in memory.
+///
+/// Sent transactions wait in the pool of the sponsor until they are included with
+/// [`MockUpstream::include`], or dropped with [`MockUpstream::drop_pending`].
+#[derive(Debug)]
+pub(crate) struct MockUpstream {
+    sponsor: PrivateKeySigner,
+    state: Mutex<MockState>,
+    /// Holds sends until permits are added, if set.
+    sends: Option<Semaphore>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    code: HashMap<Address, Bytes>,
+    /// The nonce of the next transaction of the sponsor that is included.
+    nonce: u64,
+    /// The transactions of the sponsor that are waiting in the pool.
+    pool: Vec<(TxHash, TransactionRequest)>,
+    /// Every transaction that was sent, in order.
+    sent: Vec<TransactionRequest>,
+    outcomes: HashMap<TxHash, TransactionOutcome>,
+    /// The errors returned by the next sends.
+    send_errors: VecDeque<OdysseyWalletError>,
+    /// The number of times the next nonce of the sponsor was requested.
+    nonce_requests: usize,
+}
+
+impl MockUpstream {
+    /// Creates a new upstream with a random sponsor.
+    pub(crate) fn new() -> Self {
+        Self { sponsor: PrivateKeySigner::random(), state: Default::default(), sends: None }
+    }
+
+    /// Creates a new upstream that holds every send until it is released with
+    /// [`MockUpstream::release_sends`].
+    pub(crate) fn holding_sends() -> Self {
+        Self { sends: Some(Semaphore::new(0)), ..Self::new() }
+    }
+
+    /// Lets the given number of held sends through.
+    pub(crate) fn release_sends(&self, sends: usize) {
+        self.sends.as_ref().expect("sends are held").add_permits(sends);
+    }
+
+    /// Sets the code of the account to a delegation to the implementation.
+    pub(crate) fn delegate(&self, account: Address, implementation: Address) {
+        self.state.lock().code.insert(account, delegation_designator(implementation));
+    }
+
+    /// Fails the next send with the given error.
+    pub(crate) fn fail_next_send(&self, error: OdysseyWalletError) {
+        self.state.lock().send_errors.push_back(error);
+    }
+
+    /// Returns every transaction that was sent, in order.
+    pub(crate) fn sent(&self) -> Vec<TransactionRequest> {
+        self.state.lock().sent.clone()
+    }
+
+    /// Returns the number of times the next nonce of the sponsor was requested.
+    pub(crate) fn nonce_requests(&self) -> usize {
+        self.state.lock().nonce_requests
+    }
+
+    /// Includes a pending transaction in a block.
+    pub(crate) fn include(&self, tx_hash: TxHash, success: bool) {
+        let mut state = self.state.lock();
+        let index = state.pool.iter().position(|(hash, _)| *hash == tx_hash).expect("pending");
+        let (_, tx) = state.pool.remove(index);
+        state.nonce = tx.nonce.expect("nonce is set") + 1;
+        state.outcomes.insert(
+            tx_hash,
+            TransactionOutcome {
+                success,
+                gas_used: MOCK_GAS_USED,
+                effective_gas_price: MOCK_MAX_FEE_PER_GAS,
+                l1_fee: None,
+                block_number: 1,
+            },
+        );
+    }
+
+    /// Drops every pending transaction from the pool.
+    pub(crate) fn drop_pending(&self) {
+        self.state.lock().pool.clear();
+    }
+}
+
+#[async_trait]
+impl Upstream for MockUpstream {
+    fn default_signer_address(&self) -> Address {
+        self.sponsor.address()
+    }
+
+    async fn get_code(&self, address: Address) -> Result<Bytes, OdysseyWalletError> {
+        Ok(self.state.lock().code.get(&address).cloned().unwrap_or_default())
+    }
+
+    async fn next_nonce(&self) -> Result<u64, OdysseyWalletError> {
+        let mut state = self.state.lock();
+        state.nonce_requests += 1;
+        let pending = state.pool.iter().filter_map(|(_, tx)| tx.nonce).map(|nonce| nonce + 1);
+        Ok(pending.fold(state.nonce, u64::max))
+    }
+
+    async fn estimate_gas(
+        &self,
+        _: &TransactionRequest,
+        _: &StateOverride,
+        _: BlockId,
+    ) -> Result<u64, OdysseyWalletError> {
+        Ok(MOCK_GAS_ESTIMATE)
+    }
+
+    async fn estimate_fees(&self) -> Result<Eip1559Estimation, OdysseyWalletError> {
+        Ok(Eip1559Estimation { max_fee_per_gas: MOCK_MAX_FEE_PER_GAS, max_priority_fee_per_gas: 1 })
+    }
+
+    async fn call(&self, _: &TransactionRequest, _: BlockId) -> Result<Bytes, OdysseyWalletError> {
+        // there is no gas price oracle, so the L1 data fee is zero
+        Ok(Bytes::new())
+    }
+
+    async fn simulate(
+        &self,
+        _: &TransactionRequest,
+        _: &StateOverride,
+        _: BlockId,
+    ) -> Result<SimulatedCall, OdysseyWalletError> {
+        Ok(SimulatedCall::succeeded(Bytes::new(), MOCK_GAS_ESTIMATE))
+    }
+
+    async fn simulate_logs(
+        &self,
+        _: &TransactionRequest,
+        _: BlockId,
+    ) -> Result<SimCallResult, OdysseyWalletError> {
+        Ok(SimCallResult {
+            return_data: Bytes::new(),
+            logs: Vec::new(),
+            gas_used: MOCK_GAS_ESTIMATE,
+            status: true,
+            error: None,
+        })
+    }
+
+    async fn fee_history(
+        &self,
+        _: u64,
+        _: BlockNumberOrTag,
+        _: Option<Vec<f64>>,
+    ) -> Result<FeeHistory, OdysseyWalletError> {
+        Ok(FeeHistory::default())
+    }
+
+    async fn sign_hash(&self, hash: &B256) -> Result<PrimitiveSignature, OdysseyWalletError> {
+        self.sponsor
+            .sign_hash_sync(hash)
+            .map_err(|err| OdysseyWalletError::InternalError(err.into()))
+    }
+
+    async fn sign_and_send(&self, tx: TransactionRequest) -> Result<TxHash, OdysseyWalletError> {
+        if let Some(sends) = &self.sends {
+            sends.acquire().await.expect("not closed").forget();
+        }
+
+        let mut state = self.state.lock();
+        if let Some(error) = state.send_errors.pop_front() {
+            return Err(error);
+        }
+        let nonce = tx.nonce.expect("nonce is set");
+        if nonce < state.nonce || state.pool.iter().any(|(_, pending)| pending.nonce == tx.nonce) {
+            return Err(OdysseyWalletError::InternalError(eyre::eyre!("nonce too low")));
+        }
+        let tx_hash = keccak256(serde_json::to_vec(&tx).expect("serializable"));
+        state.sent.push(tx.clone());
+        state.pool.push((tx_hash, tx));
+        Ok(tx_hash)
+    }
+
+    async fn get_transaction_outcome(
+        &self,
+        tx_hash: TxHash,
+    ) -> Result<Option<TransactionOutcome>, OdysseyWalletError> {
+        Ok(self.state.lock().outcomes.get(&tx_hash).copied())
+    }
+
+    async fn pending_transactions(
+        &self,
+    ) -> Result<Vec<PendingSponsoredTransaction>, OdysseyWalletError> {
+        Ok(self
+            .state
+            .lock()
+            .pool
+            .iter()
+            .map(|(hash, tx)| PendingSponsoredTransaction {
+                hash: *hash,
+                nonce: tx.nonce.unwrap_or_default(),
+                to: tx.to.and_then(|to| to.to().copied()),
+                max_fee_per_gas: tx.max_fee_per_gas.unwrap_or_default(),
+                max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+                age_ms: None,
+            })
+            .collect())
+    }
+
+    async fn chain_heads(&self) -> Result<ChainHeads, OdysseyWalletError> {
+        Ok(ChainHeads::default())
+    }
+}
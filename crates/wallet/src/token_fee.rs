@@ -0,0 +1,263 @@
+//! Payment of sponsored transactions in an ERC-20 token.
+//!
+//! This is an experiment that turns the wallet into a paymaster: if it is enabled, accounts
+//! without ETH compensate the sponsor in a token instead. Every sponsored call has to transfer the
+//! price of the transaction in the token to the sponsor, e.g. by batching a `transfer` with the
+//! calls of the account. Before the transaction is sent, it is simulated against the latest state,
+//! and it is only sent if the `Transfer` events of the token in the simulation pay the sponsor at
+//! least the price.
+//!
+//! The price is charged per ETH of the maximum cost of the transaction, including the L1 data fee,
+//! so the sponsor is compensated even if the fees rise until the transaction is included. Payments
+//! are only collected once the transaction is included and succeeds, as the transfer is reverted
+//! along with the rest of the call otherwise.
+
+use crate::OdysseyWalletError;
+use alloy_primitives::{Address, TxHash, U256};
+use alloy_rpc_types::Log;
+use alloy_sol_types::{sol, SolEvent};
+use metrics::Counter;
+use metrics_derive::Metrics;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+
+/// The number of wei in one ETH.
+const WEI_PER_ETH: U256 = U256::from_limbs([1_000_000_000_000_000_000, 0, 0, 0]);
+
+sol! {
+    /// The event of an ERC-20 transfer.
+    event Transfer(address indexed from, address indexed to, uint256 value);
+}
+
+/// Configuration of the payment of sponsored transactions in an ERC-20 token.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenFeeConfig {
+    /// The token sponsored transactions are paid in.
+    pub token: Address,
+    /// The amount of the token, in its smallest unit, charged per ETH of the maximum cost of a
+    /// sponsored transaction.
+    pub price: U256,
+    /// The minimum amount of the token, in its smallest unit, charged per sponsored transaction.
+    pub min_amount: U256,
+}
+
+impl TokenFeeConfig {
+    /// Returns the amount of the token the sponsor has to be paid for a transaction with the given
+    /// maximum cost in wei.
+    ///
+    /// The amount is rounded up, so the sponsor is never paid less than the price.
+    pub fn required_payment(&self, cost: U256) -> U256 {
+        cost.saturating_mul(self.price).div_ceil(WEI_PER_ETH).max(self.min_amount)
+    }
+}
+
+/// Returns the net amount of the token transferred to the recipient by the logs.
+pub(crate) fn paid_to(logs: &[Log], token: Address, recipient: Address) -> U256 {
+    let (received, sent) = logs
+        .iter()
+        .filter(|log| log.address() == token)
+        .filter_map(|log| Transfer::decode_log_data(log.data(), true).ok())
+        .fold((U256::ZERO, U256::ZERO), |(received, sent), transfer| {
+            if transfer.to == recipient && transfer.from != recipient {
+                (received.saturating_add(transfer.value), sent)
+            } else if transfer.from == recipient && transfer.to != recipient {
+                (received, sent.saturating_add(transfer.value))
+            } else {
+                (received, sent)
+            }
+        });
+    received.saturating_sub(sent)
+}
+
+/// The price of sponsored transactions in the token, and statistics about their payments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenFeeStats {
+    /// The token and price sponsored transactions are paid with.
+    #[serde(flatten)]
+    pub config: TokenFeeConfig,
+    /// The number of payments that were collected.
+    pub payments: u64,
+    /// The amount of the token collected from included transactions.
+    pub collected: U256,
+    /// The amount of the token paid by sent transactions that were not included yet.
+    pub pending: U256,
+}
+
+/// Verifies the payments of sponsored transactions in the token, and accounts for the payments
+/// that were collected.
+#[derive(Debug, Clone)]
+pub struct TokenFeeLedger {
+    inner: Arc<TokenFeeLedgerInner>,
+}
+
+#[derive(Debug)]
+struct TokenFeeLedgerInner {
+    config: TokenFeeConfig,
+    state: Mutex<TokenFeeLedgerState>,
+    metrics: TokenFeeMetrics,
+}
+
+#[derive(Debug, Default)]
+struct TokenFeeLedgerState {
+    /// The payments of sent transactions, until they are included or dropped.
+    pending: HashMap<TxHash, U256>,
+    /// The number of payments that were collected.
+    payments: u64,
+    /// The amount of the token collected from included transactions.
+    collected: U256,
+}
+
+impl TokenFeeLedger {
+    /// Creates a new ledger of the payments in the configured token.
+    pub fn new(config: TokenFeeConfig) -> Self {
+        Self {
+            inner: Arc::new(TokenFeeLedgerInner {
+                config,
+                state: Default::default(),
+                metrics: Default::default(),
+            }),
+        }
+    }
+
+    /// Returns the configuration of the payments.
+    pub fn config(&self) -> &TokenFeeConfig {
+        &self.inner.config
+    }
+
+    /// Verifies that the logs of the simulated transaction pay the sponsor for a transaction with
+    /// the given maximum cost in wei, and returns the paid amount.
+    pub(crate) fn verify(
+        &self,
+        logs: &[Log],
+        sponsor: Address,
+        cost: U256,
+    ) -> Result<U256, OdysseyWalletError> {
+        let required = self.inner.config.required_payment(cost);
+        let paid = paid_to(logs, self.inner.config.token, sponsor);
+        if paid < required {
+            self.inner.metrics.rejected_token_fee_payments.increment(1);
+            return Err(OdysseyWalletError::TokenFeeNotPaid {
+                token: self.inner.config.token,
+                required,
+                paid,
+            });
+        }
+        Ok(paid)
+    }
+
+    /// Records the payment of a sent transaction until it is included or dropped.
+    pub(crate) fn submitted(&self, tx_hash: TxHash, amount: U256) {
+        self.inner.state.lock().pending.insert(tx_hash, amount);
+    }
+
+    /// Collects the payment of a transaction that was included and succeeded, or discards it if
+    /// the transaction reverted or was dropped.
+    pub(crate) fn settle(&self, tx_hash: &TxHash, success: bool) {
+        let mut state = self.inner.state.lock();
+        let Some(amount) = state.pending.remove(tx_hash) else { return };
+        if success {
+            state.payments += 1;
+            state.collected = state.collected.saturating_add(amount);
+            self.inner.metrics.token_fee_payments.increment(1);
+        }
+    }
+
+    /// Returns the price of sponsored transactions in the token, and statistics about their
+    /// payments.
+    pub fn stats(&self) -> TokenFeeStats {
+        let state = self.inner.state.lock();
+        TokenFeeStats {
+            config: self.inner.config,
+            payments: state.payments,
+            collected: state.collected,
+            pending: state
+                .pending
+                .values()
+                .fold(U256::ZERO, |pending, amount| pending.saturating_add(*amount)),
+        }
+    }
+}
+
+/// Metrics for the payments of sponsored transactions in the token.
+#[derive(Metrics)]
+#[metrics(scope = "wallet")]
+struct TokenFeeMetrics {
+    /// Number of token payments collected from included sponsored transactions
+    token_fee_payments: Counter,
+    /// Number of requests rejected because they did not pay the sponsor enough of the token
+    rejected_token_fee_payments: Counter,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: Address = Address::with_last_byte(1);
+    const SPONSOR: Address = Address::with_last_byte(2);
+    const ACCOUNT: Address = Address::with_last_byte(3);
+
+    fn transfer(token: Address, from: Address, to: Address, value: u64) -> Log {
+        let event = Transfer { from, to, value: U256::from(value) };
+        Log {
+            inner: alloy_primitives::Log { address: token, data: event.encode_log_data() },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn prices_transactions() {
+        let config =
+            TokenFeeConfig { token: TOKEN, price: U256::from(2_000), min_amount: U256::from(10) };
+        // 0.5 ETH
+        assert_eq!(config.required_payment(WEI_PER_ETH / U256::from(2)), U256::from(1_000));
+        // rounded up
+        assert_eq!(config.required_payment(WEI_PER_ETH + U256::from(1)), U256::from(2_001));
+        assert_eq!(config.required_payment(U256::ZERO), U256::from(10));
+    }
+
+    #[test]
+    fn sums_transfers_to_the_sponsor() {
+        let logs = [
+            transfer(TOKEN, ACCOUNT, SPONSOR, 100),
+            // transfers of other tokens and to other accounts do not count
+            transfer(Address::ZERO, ACCOUNT, SPONSOR, 1_000),
+            transfer(TOKEN, ACCOUNT, Address::ZERO, 1_000),
+            // transfers from the sponsor are deducted
+            transfer(TOKEN, SPONSOR, ACCOUNT, 30),
+            transfer(TOKEN, ACCOUNT, SPONSOR, 20),
+        ];
+        assert_eq!(paid_to(&logs, TOKEN, SPONSOR), U256::from(90));
+        assert_eq!(paid_to(&logs[3..4], TOKEN, SPONSOR), U256::ZERO);
+    }
+
+    #[test]
+    fn accounts_for_payments() {
+        let config = TokenFeeConfig { token: TOKEN, price: U256::ZERO, min_amount: U256::from(50) };
+        let ledger = TokenFeeLedger::new(config);
+        assert!(matches!(
+            ledger.verify(&[transfer(TOKEN, ACCOUNT, SPONSOR, 49)], SPONSOR, U256::ZERO),
+            Err(OdysseyWalletError::TokenFeeNotPaid { paid, .. }) if paid == U256::from(49)
+        ));
+        let paid =
+            ledger.verify(&[transfer(TOKEN, ACCOUNT, SPONSOR, 60)], SPONSOR, U256::ZERO).unwrap();
+        assert_eq!(paid, U256::from(60));
+
+        let (included, reverted) = (TxHash::with_last_byte(1), TxHash::with_last_byte(2));
+        ledger.submitted(included, paid);
+        ledger.submitted(reverted, paid);
+        assert_eq!(ledger.stats().pending, U256::from(120));
+
+        ledger.settle(&included, true);
+        ledger.settle(&reverted, false);
+        assert_eq!(
+            ledger.stats(),
+            TokenFeeStats { config, payments: 1, collected: U256::from(60), pending: U256::ZERO }
+        );
+        let json = serde_json::to_value(ledger.stats()).unwrap();
+        assert_eq!(json["minAmount"], "0x32");
+        assert_eq!(json["collected"], "0x3c");
+    }
+}
@@ -0,0 +1,87 @@
+//! Usage metrics of delegation implementations.
+//!
+//! The sponsored transactions and the gas they used are exported per implementation the
+//! sponsored account delegates to, so the owners of an implementation can follow its usage
+//! without indexing the chain. Only the implementations passed to [`ImplementationUsage::new`]
+//! are labeled, which bounds the cardinality of the metrics.
+
+use alloy_primitives::Address;
+use metrics::{Counter, Gauge};
+use metrics_derive::Metrics;
+use std::collections::HashMap;
+
+/// The usage metrics of the tracked delegation implementations.
+#[derive(Debug, Default)]
+pub(crate) struct ImplementationUsage {
+    metrics: HashMap<Address, ImplementationUsageMetrics>,
+}
+
+impl ImplementationUsage {
+    /// Creates the usage metrics of the given implementations.
+    pub(crate) fn new(implementations: impl IntoIterator<Item = Address>) -> Self {
+        let metrics = implementations
+            .into_iter()
+            .map(|implementation| {
+                let labels = [("implementation", implementation.to_string())];
+                (implementation, ImplementationUsageMetrics::new_with_labels(&labels))
+            })
+            .collect();
+        Self { metrics }
+    }
+
+    /// Returns `true` if no implementation is tracked.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+
+    /// Returns `true` if the usage of the implementation is tracked.
+    pub(crate) fn tracks(&self, implementation: &Address) -> bool {
+        self.metrics.contains_key(implementation)
+    }
+
+    /// Records a transaction sponsored for an account delegating to the implementation.
+    pub(crate) fn sponsored(&self, implementation: &Address) {
+        if let Some(metrics) = self.metrics.get(implementation) {
+            metrics.implementation_sponsored_transactions.increment(1);
+        }
+    }
+
+    /// Records the gas used by an included transaction sponsored for an account delegating to the
+    /// implementation.
+    pub(crate) fn included(&self, implementation: &Address, gas_used: u64) {
+        if let Some(metrics) = self.metrics.get(implementation) {
+            metrics.implementation_sponsored_gas.increment(gas_used as f64);
+        }
+    }
+}
+
+/// Metrics for the usage of a delegation implementation.
+#[derive(Metrics)]
+#[metrics(scope = "wallet")]
+struct ImplementationUsageMetrics {
+    /// Number of transactions sponsored for accounts delegating to the implementation
+    implementation_sponsored_transactions: Counter,
+    /// Gas used by included transactions sponsored for accounts delegating to the implementation
+    implementation_sponsored_gas: Gauge,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_listed_implementations() {
+        let (tracked, other) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        assert!(ImplementationUsage::default().is_empty());
+
+        let usage = ImplementationUsage::new([tracked]);
+        assert!(!usage.is_empty());
+        assert!(usage.tracks(&tracked));
+        assert!(!usage.tracks(&other));
+
+        // untracked implementations are not labeled
+        usage.sponsored(&other);
+        usage.included(&other, 21_000);
+        assert_eq!(usage.metrics.len(), 1);
+    }
+}
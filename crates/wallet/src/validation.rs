@@ -0,0 +1,304 @@
+//! Strict validation of transaction requests.
+//!
+//! Common mistakes in the encoding of [EIP-7702][eip-7702] requests, such as authorizations with
+//! both `yParity` and `v`, authorizations without a `chainId`, or addresses of the wrong size,
+//! fail deserialization with opaque errors like "duplicate field". The [`RequestValidationLayer`]
+//! checks the transaction request of calls to the methods that take one before they are
+//! deserialized, and rejects invalid requests with an error that names the offending field.
+//!
+//! [eip-7702]: https://eips.ethereum.org/EIPS/eip-7702
+
+use crate::layer_methods;
+use futures::future::{BoxFuture, FutureExt};
+use jsonrpsee::{
+    server::{middleware::rpc::RpcServiceT, MethodResponse},
+    types::{error::INVALID_PARAMS_CODE, ErrorObject, Request},
+    Methods,
+};
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::value::RawValue;
+use std::{collections::HashSet, fmt};
+use tower::Layer;
+use tracing::debug;
+
+/// Methods whose first parameter is a transaction request.
+const VALIDATED_METHODS: &[&str] = &[
+    "wallet_sendTransaction",
+    "odyssey_sendTransaction",
+    "wallet_prepareCalls",
+    "wallet_getQuote",
+    "wallet_sendQuotedTransaction",
+    "wallet_simulateSponsoredTransaction",
+    "odyssey_callAsDelegated",
+    "eth_sendTransaction",
+    "eth_call",
+    "eth_estimateGas",
+];
+
+/// The data of the error of calls with an invalid transaction request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvalidField {
+    /// The path of the invalid field, e.g. `authorizationList[0].chainId`.
+    pub field: String,
+    /// Why the field is invalid.
+    pub reason: String,
+}
+
+impl InvalidField {
+    fn new(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self { field: field.into(), reason: reason.into() }
+    }
+}
+
+/// A JSON object with its fields in order, including duplicate fields.
+#[derive(Debug, Default)]
+struct Fields(Vec<(String, Box<RawValue>)>);
+
+impl Fields {
+    /// Returns the first value of the field, if any.
+    fn get(&self, key: &str) -> Option<&RawValue> {
+        self.0.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_ref())
+    }
+
+    /// Returns the first field that is set more than once, if any.
+    fn duplicate(&self) -> Option<&str> {
+        let mut seen = HashSet::with_capacity(self.0.len());
+        self.0.iter().map(|(name, _)| name.as_str()).find(|name| !seen.insert(*name))
+    }
+}
+
+impl<'de> Deserialize<'de> for Fields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldsVisitor;
+
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = Fields;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(field) = map.next_entry()? {
+                    fields.push(field);
+                }
+                Ok(Fields(fields))
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor)
+    }
+}
+
+/// Checks the transaction request in the given JSON-RPC parameters.
+///
+/// Parameters that are not well-formed JSON, or requests that are not objects, are left to the
+/// method to reject.
+pub fn validate_request_params(params: &str) -> Result<(), InvalidField> {
+    let request = match serde_json::from_str::<Vec<Box<RawValue>>>(params) {
+        Ok(params) => params.into_iter().next(),
+        // named parameters
+        Err(_) => serde_json::from_str::<Fields>(params)
+            .ok()
+            .and_then(|params| params.get("request").map(ToOwned::to_owned)),
+    };
+    let Some(request) = request.and_then(|request| serde_json::from_str(request.get()).ok()) else {
+        return Ok(());
+    };
+    validate_request(&request)
+}
+
+/// Checks the fields of a transaction request.
+fn validate_request(request: &Fields) -> Result<(), InvalidField> {
+    if let Some(field) = request.duplicate() {
+        return Err(InvalidField::new(field, "field is set more than once"));
+    }
+    for field in ["from", "to"] {
+        if let Some(value) = request.get(field) {
+            validate_address(field, value, true)?;
+        }
+    }
+
+    let Some(authorizations) = request.get("authorizationList") else { return Ok(()) };
+    let Ok(authorizations) =
+        serde_json::from_str::<Option<Vec<Box<RawValue>>>>(authorizations.get())
+    else {
+        return Err(InvalidField::new("authorizationList", "expected an array of authorizations"));
+    };
+    for (index, authorization) in authorizations.iter().flatten().enumerate() {
+        let path = |field: &str| format!("authorizationList[{index}].{field}");
+        let Ok(authorization) = serde_json::from_str::<Fields>(authorization.get()) else {
+            return Err(InvalidField::new(
+                format!("authorizationList[{index}]"),
+                "expected an authorization object",
+            ));
+        };
+        if let Some(field) = authorization.duplicate() {
+            return Err(InvalidField::new(path(field), "field is set more than once"));
+        }
+        if authorization.get("yParity").is_some() && authorization.get("v").is_some() {
+            return Err(InvalidField::new(
+                path("v"),
+                "both `yParity` and `v` are set, only `yParity` should be set",
+            ));
+        }
+        if authorization.get("chainId").is_none() {
+            return Err(InvalidField::new(
+                path("chainId"),
+                "missing field, use 0x0 to authorize the delegation on every chain",
+            ));
+        }
+        match authorization.get("address") {
+            Some(address) => validate_address(&path("address"), address, false)?,
+            None => return Err(InvalidField::new(path("address"), "missing field")),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the value is a hex-encoded 20-byte address, or `null` if it is optional.
+fn validate_address(field: &str, value: &RawValue, optional: bool) -> Result<(), InvalidField> {
+    let address = match serde_json::from_str::<Option<String>>(value.get()) {
+        Ok(Some(address)) => address,
+        Ok(None) if optional => return Ok(()),
+        _ => return Err(InvalidField::new(field, "expected a hex-encoded address")),
+    };
+    let hex = address.strip_prefix("0x").unwrap_or(&address);
+    if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(InvalidField::new(field, "expected a hex-encoded address"));
+    }
+    if hex.len() != 40 {
+        return Err(InvalidField::new(
+            field,
+            format!("expected a 20-byte address, got {} hex digits", hex.len()),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the methods that take a transaction request, with the [`RequestValidationLayer`]
+/// applied.
+///
+/// This is used to validate the requests of methods that are served by the RPC server of the node,
+/// e.g. `eth_call`, see [`layer_methods`].
+pub fn validated_methods(methods: impl Into<Methods>) -> Methods {
+    let methods = methods.into();
+    let mut validated = Methods::new();
+    for name in methods.method_names().filter(|name| VALIDATED_METHODS.contains(name)) {
+        if let Some(callback) = methods.method(name) {
+            validated.verify_and_insert(name, callback.clone()).expect("method names are unique");
+        }
+    }
+    layer_methods(validated, RequestValidationLayer)
+}
+
+/// RPC middleware that rejects calls with invalid transaction requests, naming the invalid field.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestValidationLayer;
+
+impl<S> Layer<S> for RequestValidationLayer {
+    type Service = RequestValidation<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        RequestValidation { service }
+    }
+}
+
+/// The service created by [`RequestValidationLayer`].
+#[derive(Debug, Clone)]
+pub struct RequestValidation<S> {
+    service: S,
+}
+
+impl<'a, S> RpcServiceT<'a> for RequestValidation<S>
+where
+    S: RpcServiceT<'a> + Send + Sync + 'a,
+    S::Future: Send,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        if !VALIDATED_METHODS.contains(&request.method_name()) {
+            return self.service.call(request).boxed();
+        }
+        let Err(invalid) = request.params().as_str().map_or(Ok(()), validate_request_params) else {
+            return self.service.call(request).boxed();
+        };
+
+        debug!(
+            target: "rpc::wallet",
+            method = request.method_name(),
+            field = %invalid.field,
+            reason = %invalid.reason,
+            "Rejecting invalid transaction request"
+        );
+        let response = MethodResponse::error(
+            request.id,
+            ErrorObject::owned(
+                INVALID_PARAMS_CODE,
+                format!("invalid {}: {}", invalid.field, invalid.reason),
+                Some(invalid),
+            ),
+        );
+        futures::future::ready(response).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUTHORIZATION: &str = r#""address":"0x0000000000000000000000000000000000000001","nonce":"0x0","r":"0x1","s":"0x1""#;
+
+    fn validate(request: &str) -> Result<(), InvalidField> {
+        validate_request_params(&format!("[{request}]"))
+    }
+
+    #[test]
+    fn accepts_valid_requests() {
+        assert!(validate_request_params("[]").is_ok());
+        assert!(validate(r#"{"to":"0x0000000000000000000000000000000000000001"}"#).is_ok());
+        assert!(validate(r#"{"to":null,"data":"0x"}"#).is_ok());
+        assert!(validate(&format!(
+            r#"{{"authorizationList":[{{"chainId":"0x0","yParity":"0x1",{AUTHORIZATION}}}]}}"#
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn rejects_7702_mistakes() {
+        let err = validate(&format!(
+            r#"{{"authorizationList":[{{"chainId":"0x1","yParity":"0x1","v":"0x1",{AUTHORIZATION}}}]}}"#
+        ))
+        .unwrap_err();
+        assert_eq!(err.field, "authorizationList[0].v");
+
+        let err = validate(&format!(
+            r#"{{"authorizationList":[{{"chainId":"0x1","yParity":"0x1",{AUTHORIZATION}}},{{"yParity":"0x1",{AUTHORIZATION}}}]}}"#
+        ))
+        .unwrap_err();
+        assert_eq!(err.field, "authorizationList[1].chainId");
+
+        let err = validate(
+            r#"{"authorizationList":[{"chainId":"0x1","yParity":"0x1","address":"0x0001"}]}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.field, "authorizationList[0].address");
+        assert_eq!(err.reason, "expected a 20-byte address, got 4 hex digits");
+
+        let err = validate(
+            r#"{"to":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000002"}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.field, "to");
+        assert_eq!(err.reason, "field is set more than once");
+
+        // named parameters
+        let err = validate_request_params(r#"{"request":{"from":"0x01"}}"#).unwrap_err();
+        assert_eq!(err.field, "from");
+    }
+}
@@ -0,0 +1,156 @@
+//! Sponsorship of authorizations that are valid on every chain.
+//!
+//! EIP-7702 authorizations with a chain ID of 0 are valid on every chain, so anyone can replay
+//! them on another chain where the authority has the same nonce. By default they are sponsored
+//! like authorizations bound to the chain, but a [`WildcardAuthorizationPolicy`] can reject them,
+//! or only sponsor them if they delegate to implementations that are deployed at the same address
+//! on every chain.
+
+use crate::OdysseyWalletError;
+use alloy_primitives::Address;
+use alloy_rpc_types::SignedAuthorization;
+use metrics::Counter;
+use metrics_derive::Metrics;
+use std::{collections::HashSet, str::FromStr};
+
+/// How authorizations with a chain ID of 0 are sponsored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WildcardAuthorizationMode {
+    /// Wildcard authorizations are sponsored like authorizations bound to the chain.
+    #[default]
+    Accept,
+    /// Requests with wildcard authorizations are rejected.
+    Reject,
+    /// Wildcard authorizations are only sponsored if they delegate to one of the implementations
+    /// of the policy.
+    Restrict,
+}
+
+impl FromStr for WildcardAuthorizationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "accept" => Ok(Self::Accept),
+            "reject" => Ok(Self::Reject),
+            "restrict" => Ok(Self::Restrict),
+            _ => Err(format!(
+                "unknown wildcard authorization mode {s}, expected accept, reject or restrict"
+            )),
+        }
+    }
+}
+
+/// The policy for sponsoring authorizations with a chain ID of 0.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WildcardAuthorizationPolicy {
+    /// How wildcard authorizations are sponsored.
+    pub mode: WildcardAuthorizationMode,
+    /// The implementations wildcard authorizations may delegate to in
+    /// [`WildcardAuthorizationMode::Restrict`] mode.
+    pub implementations: HashSet<Address>,
+}
+
+impl WildcardAuthorizationPolicy {
+    /// Creates a new policy with the given mode, and the implementations wildcard authorizations
+    /// may delegate to if they are restricted.
+    pub fn new(
+        mode: WildcardAuthorizationMode,
+        implementations: impl IntoIterator<Item = Address>,
+    ) -> Self {
+        Self { mode, implementations: implementations.into_iter().collect() }
+    }
+
+    /// Checks that every wildcard authorization of the list is sponsored.
+    pub fn check(&self, authorizations: &[SignedAuthorization]) -> Result<(), OdysseyWalletError> {
+        let rejected = authorizations
+            .iter()
+            .filter(|authorization| authorization.chain_id == 0)
+            .find(|authorization| match self.mode {
+                WildcardAuthorizationMode::Accept => false,
+                WildcardAuthorizationMode::Reject => true,
+                WildcardAuthorizationMode::Restrict => {
+                    !self.implementations.contains(&authorization.address)
+                }
+            });
+        match rejected {
+            Some(authorization) => {
+                Err(OdysseyWalletError::WildcardAuthorizationNotAllowed(authorization.address))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Metrics for the authorizations of sponsorship requests.
+#[derive(Metrics)]
+#[metrics(scope = "wallet")]
+pub(crate) struct AuthorizationMetrics {
+    /// Number of authorizations bound to the chain in sponsorship requests
+    chain_bound_authorizations: Counter,
+    /// Number of authorizations valid on every chain in sponsorship requests
+    wildcard_authorizations: Counter,
+    /// Number of sponsorship requests rejected because of an authorization valid on every chain
+    rejected_wildcard_requests: Counter,
+}
+
+impl AuthorizationMetrics {
+    /// Records the authorizations of a request, and whether it was rejected by the policy.
+    pub(crate) fn record(&self, authorizations: &[SignedAuthorization], rejected: bool) {
+        let wildcard =
+            authorizations.iter().filter(|authorization| authorization.chain_id == 0).count();
+        self.wildcard_authorizations.increment(wildcard as u64);
+        self.chain_bound_authorizations.increment((authorizations.len() - wildcard) as u64);
+        if rejected {
+            self.rejected_wildcard_requests.increment(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{PrimitiveSignature, U256};
+    use alloy_rpc_types::Authorization;
+
+    fn authorization(chain_id: u64, address: Address) -> SignedAuthorization {
+        Authorization { chain_id, address, nonce: 0 }.into_signed(PrimitiveSignature::new(
+            U256::ZERO,
+            U256::ZERO,
+            false,
+        ))
+    }
+
+    #[test]
+    fn checks_wildcard_authorizations() {
+        let (trusted, other) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        let bound = [authorization(1, other)];
+        let wildcard = [authorization(1, other), authorization(0, trusted)];
+        let untrusted = [authorization(0, trusted), authorization(0, other)];
+
+        let accept = WildcardAuthorizationPolicy::default();
+        assert!(accept.check(&untrusted).is_ok());
+
+        let reject = WildcardAuthorizationPolicy::new(WildcardAuthorizationMode::Reject, []);
+        assert!(reject.check(&bound).is_ok());
+        assert!(matches!(
+            reject.check(&wildcard),
+            Err(OdysseyWalletError::WildcardAuthorizationNotAllowed(address)) if address == trusted
+        ));
+
+        let restrict =
+            WildcardAuthorizationPolicy::new(WildcardAuthorizationMode::Restrict, [trusted]);
+        assert!(restrict.check(&bound).is_ok());
+        assert!(restrict.check(&wildcard).is_ok());
+        assert!(matches!(
+            restrict.check(&untrusted),
+            Err(OdysseyWalletError::WildcardAuthorizationNotAllowed(address)) if address == other
+        ));
+    }
+
+    #[test]
+    fn parses_mode() {
+        assert_eq!("restrict".parse(), Ok(WildcardAuthorizationMode::Restrict));
+        assert!("any".parse::<WildcardAuthorizationMode>().is_err());
+    }
+}
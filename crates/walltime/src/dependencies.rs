@@ -0,0 +1,216 @@
+//! Tips of the chains the node depends on.
+//!
+//! In interop experiments, the node depends on other chains. Their tips are polled from their RPC
+//! endpoints, and reported by `odyssey_getChainTips` next to the tip of the local chain, so the
+//! liveness of every chain can be monitored through a single endpoint.
+
+use jsonrpsee::{
+    core::client::ClientT,
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+};
+use odyssey_common::Clock;
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, sync::Arc, time::Duration};
+use tokio::sync::RwLock;
+use tracing::debug;
+
+/// The default interval at which the tips of dependency chains are polled.
+pub const DEFAULT_DEPENDENCY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A chain the node depends on, given as `<name>=<url>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyChain {
+    /// The name the chain is reported as.
+    pub name: String,
+    /// The RPC endpoint of the chain.
+    pub url: String,
+}
+
+impl FromStr for DependencyChain {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, url) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid dependency chain {s}, expected <name>=<url>"))?;
+        if name.is_empty() || url.is_empty() {
+            return Err(format!("invalid dependency chain {s}, expected <name>=<url>"));
+        }
+        Ok(Self { name: name.to_string(), url: url.to_string() })
+    }
+}
+
+/// The tip of a dependency chain.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DependencyTip {
+    /// Name of the chain
+    name: String,
+    /// Number of last block, if any was observed
+    last_block_number: Option<u64>,
+    /// Wall time at which the last block was first observed
+    last_block_wall_time_ms: Option<u64>,
+    /// Timestamp of last block (chain time)
+    last_block_timestamp: Option<u64>,
+    /// Wall time of the last poll of the chain
+    last_poll_wall_time_ms: Option<u64>,
+    /// The error of the last poll, if it failed
+    error: Option<String>,
+}
+
+/// The number and timestamp of a block, as returned by `eth_getBlockByNumber`.
+#[derive(Debug, Deserialize)]
+struct RpcHeader {
+    number: String,
+    timestamp: String,
+}
+
+/// Polls the tips of the dependency chains.
+#[derive(Debug, Clone)]
+pub(crate) struct DependencyTracker {
+    tips: Arc<RwLock<Vec<DependencyTip>>>,
+}
+
+impl DependencyTracker {
+    /// Spawns a task per dependency chain that polls its tip at the given interval.
+    pub(crate) fn spawn(
+        dependencies: Vec<DependencyChain>,
+        interval: Duration,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let mut tips = Vec::with_capacity(dependencies.len());
+        let mut clients = Vec::with_capacity(dependencies.len());
+        for chain in dependencies {
+            let mut tip = DependencyTip { name: chain.name, ..Default::default() };
+            match HttpClientBuilder::default().request_timeout(interval).build(&chain.url) {
+                Ok(client) => clients.push((tips.len(), client)),
+                Err(err) => {
+                    debug!(
+                        target: "rpc::walltime",
+                        ?err,
+                        name = %tip.name,
+                        "Invalid dependency chain"
+                    );
+                    tip.error = Some(err.to_string());
+                }
+            }
+            tips.push(tip);
+        }
+
+        let tips = Arc::new(RwLock::new(tips));
+        for (index, client) in clients {
+            // the task stops once the tracker is dropped
+            let (tips, clock) = (Arc::downgrade(&tips), clock.clone());
+            tokio::task::spawn(async move {
+                let mut interval = tokio::time::interval(interval);
+                loop {
+                    interval.tick().await;
+                    let result = latest_header(&client).await;
+                    let now = clock.unix_epoch_ms();
+                    let Some(tips) = tips.upgrade() else { break };
+                    let mut tips = tips.write().await;
+                    let tip = &mut tips[index];
+                    tip.last_poll_wall_time_ms = Some(now);
+                    match result {
+                        Ok((number, timestamp)) => {
+                            if tip.last_block_number != Some(number)
+                                || tip.last_block_timestamp != Some(timestamp)
+                            {
+                                tip.last_block_number = Some(number);
+                                tip.last_block_timestamp = Some(timestamp);
+                                tip.last_block_wall_time_ms = Some(now);
+                            }
+                            tip.error = None;
+                        }
+                        Err(err) => {
+                            debug!(
+                                target: "rpc::walltime",
+                                %err,
+                                name = %tip.name,
+                                "Failed to poll dependency chain"
+                            );
+                            tip.error = Some(err.to_string());
+                        }
+                    }
+                }
+            });
+        }
+        Self { tips }
+    }
+
+    /// Returns the tips of the dependency chains, in the order they were configured.
+    pub(crate) async fn tips(&self) -> Vec<DependencyTip> {
+        self.tips.read().await.clone()
+    }
+}
+
+/// Returns the number and timestamp of the latest block of the chain.
+async fn latest_header(client: &HttpClient) -> Result<(u64, u64), jsonrpsee::core::ClientError> {
+    let header: RpcHeader =
+        client.request("eth_getBlockByNumber", rpc_params!["latest", false]).await?;
+    let parse = |quantity: &str| {
+        u64::from_str_radix(quantity.trim_start_matches("0x"), 16).map_err(|err| {
+            jsonrpsee::core::ClientError::Custom(format!("invalid quantity {quantity}: {err}"))
+        })
+    };
+    Ok((parse(&header.number)?, parse(&header.timestamp)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpsee::{server::ServerBuilder, RpcModule};
+    use odyssey_common::MockClock;
+
+    #[test]
+    fn parses_dependency_chains() {
+        let chain: DependencyChain = "op=http://localhost:8545".parse().unwrap();
+        assert_eq!(chain.name, "op");
+        assert_eq!(chain.url, "http://localhost:8545");
+
+        assert!("http://localhost:8545".parse::<DependencyChain>().is_err());
+        assert!("=http://localhost:8545".parse::<DependencyChain>().is_err());
+    }
+
+    #[tokio::test]
+    async fn polls_dependency_tips() {
+        let mut module = RpcModule::new(());
+        module
+            .register_method(
+                "eth_getBlockByNumber",
+                |_, _, _| serde_json::json!({ "number": "0x2a", "timestamp": "0x64" }),
+            )
+            .unwrap();
+        let server = ServerBuilder::default().build("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.start(module);
+
+        let tracker = DependencyTracker::spawn(
+            vec![
+                DependencyChain { name: "a".to_string(), url: format!("http://{addr}") },
+                DependencyChain { name: "b".to_string(), url: "http://127.0.0.1:1".to_string() },
+            ],
+            Duration::from_millis(50),
+            Arc::new(MockClock::new(1_000)),
+        );
+
+        let mut tips = tracker.tips().await;
+        for _ in 0..100 {
+            if tips.iter().all(|tip| tip.last_poll_wall_time_ms.is_some()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            tips = tracker.tips().await;
+        }
+        assert_eq!(tips[0].name, "a");
+        assert_eq!(tips[0].last_block_number, Some(42));
+        assert_eq!(tips[0].last_block_timestamp, Some(100));
+        assert_eq!(tips[0].last_block_wall_time_ms, Some(1_000));
+        assert_eq!(tips[0].error, None);
+        assert_eq!(tips[1].name, "b");
+        assert_eq!(tips[1].last_block_number, None);
+        assert!(tips[1].error.is_some());
+
+        handle.stop().unwrap();
+    }
+}
@@ -1,6 +1,22 @@
 //! # Odyssey walltime
 //!
 //! Returns the current walltime and the chain's tip timestamps.
+//!
+//! The block time of the tip is initialized from the latest block at startup, and can optionally
+//! be persisted to disk, so the endpoint is immediately useful after a restart, even if the chain
+//! is quiet.
+//!
+//! The wall times at which recent blocks arrived are kept in a ring buffer, and summarized by
+//! `odyssey_getBlockIntervalStats`, so dashboards can visualize the regularity of the sequencer.
+//!
+//! On a reorg, the tip is replaced by the tip of the new chain, which arrived at the time of the
+//! reorg. The samples of reorged blocks are dropped, and the interval to the new tip is not
+//! counted, since the slot of the replaced block was already counted. Reorgs are counted in the
+//! `walltime_reorgs` metric.
+//!
+//! The tips of chains the node depends on, e.g. in interop experiments, can be tracked as well by
+//! polling their RPC endpoints, and are reported together with the local tip by
+//! `odyssey_getChainTips`.
 
 #![cfg_attr(not(test), warn(unused_crate_dependencies))]
 
@@ -10,10 +26,31 @@ use jsonrpsee::{
     proc_macros::rpc,
     types::{error::INTERNAL_ERROR_CODE, ErrorObject},
 };
+use metrics::Counter;
+use metrics_derive::Metrics;
+use odyssey_common::{Clock, SystemClock};
 use reth_chain_state::CanonStateNotification;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use tokio::sync::RwLock;
+use tracing::debug;
+
+pub use odyssey_common::unix_epoch_ms;
+
+mod dependencies;
+use dependencies::DependencyTracker;
+pub use dependencies::{DependencyChain, DependencyTip, DEFAULT_DEPENDENCY_POLL_INTERVAL};
+
+/// The default number of recent blocks the interval statistics are computed from.
+pub const DEFAULT_BLOCK_HISTORY: usize = 256;
+
+/// The default expected interval between blocks, matching the block time of the rollup config.
+pub const DEFAULT_BLOCK_INTERVAL: Duration = Duration::from_secs(2);
 
 /// The odyssey walltime endpoint.
 #[derive(Debug, Clone)]
@@ -23,19 +60,84 @@ pub struct OdysseyWallTime {
 
 impl OdysseyWallTime {
     /// Creates a new instance with the connected stream.
-    pub fn spawn<St>(mut st: St) -> Self
+    pub fn spawn<St>(st: St) -> Self
     where
         St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
     {
-        let walltime = Self { inner: Default::default() };
+        Self::spawn_with_clock(st, Arc::new(SystemClock))
+    }
+
+    /// Creates a new instance with the connected stream that reads the time from the given
+    /// [`Clock`].
+    pub fn spawn_with_clock<St>(st: St, clock: Arc<dyn Clock>) -> Self
+    where
+        St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
+    {
+        Self::spawn_with_config(st, WallTimeConfig { clock, ..Default::default() })
+    }
+
+    /// Creates a new instance with the connected stream and the given [`WallTimeConfig`].
+    pub fn spawn_with_config<St>(mut st: St, config: WallTimeConfig) -> Self
+    where
+        St: Stream<Item = CanonStateNotification> + Send + Unpin + 'static,
+    {
+        let block_time_data = RwLock::new(config.initial_block_time());
+        let WallTimeConfig {
+            clock,
+            persistence_path,
+            history,
+            block_interval,
+            dependencies,
+            dependency_poll_interval,
+            ..
+        } = config;
+        let dependencies =
+            DependencyTracker::spawn(dependencies, dependency_poll_interval, clock.clone());
+
+        let walltime = Self {
+            inner: Arc::new(OdysseyWallTimeInner {
+                block_time_data,
+                clock,
+                history: RwLock::new(BlockHistory::new(history)),
+                block_interval,
+                dependencies,
+                metrics: WallTimeMetrics::default(),
+            }),
+        };
         let listener = walltime.clone();
         tokio::task::spawn(async move {
             while let Some(notification) = st.next().await {
+                // the tip of the new chain arrived now, even if it replaces a tip with a later
+                // timestamp
                 let tip = BlockTimeData {
-                    wall_time_ms: unix_epoch_ms(),
+                    wall_time_ms: listener.inner.clock.unix_epoch_ms(),
                     block_timestamp: notification.tip().timestamp,
                 };
                 *listener.inner.block_time_data.write().await = Some(tip);
+
+                let mut history = listener.inner.history.write().await;
+                if let Some(reverted) = notification.reverted() {
+                    debug!(
+                        target: "rpc::walltime",
+                        depth = reverted.len(),
+                        tip = notification.tip().number,
+                        "Chain reorged"
+                    );
+                    listener.inner.metrics.reorgs.increment(1);
+                    history.revert(reverted.first().number);
+                }
+                history.push(BlockSample {
+                    number: notification.tip().number,
+                    wall_time_ms: tip.wall_time_ms,
+                    reorged: notification.reverted().is_some(),
+                });
+                drop(history);
+
+                if let Some(path) = &persistence_path {
+                    if let Err(err) = persist_block_time(path, tip).await {
+                        debug!(target: "rpc::walltime", ?err, ?path, "Failed to persist block time");
+                    }
+                }
             }
         });
         walltime
@@ -47,11 +149,85 @@ impl OdysseyWallTime {
     }
 }
 
+/// Configuration for [`OdysseyWallTime`].
+#[derive(Debug, Clone)]
+pub struct WallTimeConfig {
+    /// The clock used to read the wall time.
+    pub clock: Arc<dyn Clock>,
+    /// The timestamp of the latest block at startup, if any.
+    ///
+    /// This is used to initialize the tracked block time before the first block arrives.
+    pub latest_block_timestamp: Option<u64>,
+    /// The file to persist the last observed block time to, if any.
+    ///
+    /// If the persisted block time belongs to the latest block at startup, it is used to
+    /// initialize the tracked block time.
+    pub persistence_path: Option<PathBuf>,
+    /// The number of recent blocks the interval statistics are computed from.
+    pub history: usize,
+    /// The expected interval between blocks, used to detect missed slots.
+    pub block_interval: Duration,
+    /// The chains the node depends on, whose tips are reported by `odyssey_getChainTips`.
+    pub dependencies: Vec<DependencyChain>,
+    /// The interval at which the tips of the dependency chains are polled.
+    pub dependency_poll_interval: Duration,
+}
+
+impl WallTimeConfig {
+    /// Returns the block time to initialize the tracked block time with, if any.
+    fn initial_block_time(&self) -> Option<BlockTimeData> {
+        let persisted = self.persistence_path.as_deref().and_then(load_block_time);
+        match (self.latest_block_timestamp, persisted) {
+            (Some(timestamp), Some(persisted)) if persisted.block_timestamp == timestamp => {
+                Some(persisted)
+            }
+            // we have not observed the latest block, so we assume it arrived on time
+            (Some(timestamp), _) => Some(BlockTimeData {
+                wall_time_ms: timestamp.saturating_mul(1000),
+                block_timestamp: timestamp,
+            }),
+            (None, persisted) => persisted,
+        }
+    }
+}
+
+impl Default for WallTimeConfig {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            latest_block_timestamp: None,
+            persistence_path: None,
+            history: DEFAULT_BLOCK_HISTORY,
+            block_interval: DEFAULT_BLOCK_INTERVAL,
+            dependencies: Vec::new(),
+            dependency_poll_interval: DEFAULT_DEPENDENCY_POLL_INTERVAL,
+        }
+    }
+}
+
 /// Implementation of the Odyssey `odyssey_getWallTimeData` endpoint.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 struct OdysseyWallTimeInner {
     /// Tracks the recent blocktime data
     block_time_data: RwLock<Option<BlockTimeData>>,
+    /// The clock used to read the wall time
+    clock: Arc<dyn Clock>,
+    /// The wall times at which recent blocks arrived
+    history: RwLock<BlockHistory>,
+    /// The expected interval between blocks
+    block_interval: Duration,
+    /// Polls the tips of the dependency chains
+    dependencies: DependencyTracker,
+    /// The metrics of the tracked chain
+    metrics: WallTimeMetrics,
+}
+
+/// Metrics of the chain tracked by [`OdysseyWallTime`].
+#[derive(Metrics)]
+#[metrics(scope = "walltime")]
+struct WallTimeMetrics {
+    /// Number of reorgs of the canonical chain
+    reorgs: Counter,
 }
 
 /// Data about the current time and the last block's.
@@ -65,13 +241,47 @@ pub struct WallTimeData {
     last_block_timestamp: u64,
 }
 
+impl WallTimeData {
+    /// Returns the wall time in milliseconds when the data was returned.
+    pub const fn current_wall_time_ms(&self) -> u64 {
+        self.current_wall_time_ms
+    }
+
+    /// Returns the wall time in milliseconds when the last block arrived.
+    pub const fn last_block_wall_time_ms(&self) -> u64 {
+        self.last_block_wall_time_ms
+    }
+
+    /// Returns the timestamp of the last block.
+    pub const fn last_block_timestamp(&self) -> u64 {
+        self.last_block_timestamp
+    }
+}
+
+/// The tips of the local chain and of the chains it depends on.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ChainTips {
+    /// Wall time and tip of the local chain, if synced
+    local: Option<WallTimeData>,
+    /// Tips of the dependency chains
+    dependencies: Vec<DependencyTip>,
+}
+
 /// Rpc endpoints
-#[cfg_attr(not(test), rpc(server, namespace = "odyssey"))]
-#[cfg_attr(test, rpc(server, client, namespace = "odyssey"))]
+#[cfg_attr(not(any(test, feature = "client")), rpc(server, namespace = "odyssey"))]
+#[cfg_attr(any(test, feature = "client"), rpc(server, client, namespace = "odyssey"))]
 pub trait OdysseyWallTimeRpcApi {
     /// Return the wall time and block timestamp of the latest block.
     #[method(name = "getWallTimeData")]
     async fn get_timedata(&self) -> RpcResult<WallTimeData>;
+
+    /// Return statistics about the wall time intervals between recent blocks.
+    #[method(name = "getBlockIntervalStats")]
+    async fn get_block_interval_stats(&self) -> RpcResult<BlockIntervalStats>;
+
+    /// Return the tips of the local chain and of the chains it depends on.
+    #[method(name = "getChainTips")]
+    async fn get_chain_tips(&self) -> RpcResult<ChainTips>;
 }
 
 #[async_trait]
@@ -81,11 +291,119 @@ impl OdysseyWallTimeRpcApiServer for OdysseyWallTime {
             return Err(ErrorObject::owned(INTERNAL_ERROR_CODE, "node is not synced", None::<()>));
         };
         Ok(WallTimeData {
-            current_wall_time_ms: unix_epoch_ms(),
+            current_wall_time_ms: self.inner.clock.unix_epoch_ms(),
             last_block_wall_time_ms: current.wall_time_ms,
             last_block_timestamp: current.block_timestamp,
         })
     }
+
+    async fn get_block_interval_stats(&self) -> RpcResult<BlockIntervalStats> {
+        Ok(self.inner.history.read().await.stats(self.inner.block_interval))
+    }
+
+    async fn get_chain_tips(&self) -> RpcResult<ChainTips> {
+        Ok(ChainTips {
+            local: self.get_timedata().await.ok(),
+            dependencies: self.inner.dependencies.tips().await,
+        })
+    }
+}
+
+/// Statistics about the wall time intervals between recent blocks.
+///
+/// Only intervals between consecutive blocks are included, blocks that arrive together, e.g.
+/// while syncing, are skipped.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct BlockIntervalStats {
+    /// Number of intervals the statistics are computed from
+    samples: u64,
+    /// Mean interval in milliseconds
+    mean_interval_ms: f64,
+    /// Variance of the intervals in milliseconds squared
+    interval_variance_ms2: f64,
+    /// Longest interval in milliseconds
+    max_interval_ms: u64,
+    /// Number of slots in which no block arrived, based on the expected block interval
+    missed_slots: u64,
+}
+
+/// The wall time at which a block arrived.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct BlockSample {
+    /// Number of the block
+    number: u64,
+    /// Wall time of the block
+    wall_time_ms: u64,
+    /// Whether the block replaced a reorged block
+    reorged: bool,
+}
+
+/// Ring buffer of the most recent [`BlockSample`]s.
+#[derive(Debug)]
+struct BlockHistory {
+    samples: VecDeque<BlockSample>,
+    capacity: usize,
+}
+
+impl BlockHistory {
+    fn new(capacity: usize) -> Self {
+        Self { samples: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// Drops the samples of blocks from the given block number on, which were reorged out.
+    fn revert(&mut self, first_reverted: u64) {
+        while self.samples.back().is_some_and(|last| last.number >= first_reverted) {
+            self.samples.pop_back();
+        }
+    }
+
+    /// Records a new tip, dropping samples of blocks that were reorged out.
+    fn push(&mut self, sample: BlockSample) {
+        while self.samples.back().is_some_and(|last| last.number >= sample.number) {
+            self.samples.pop_back();
+        }
+        if self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Computes the statistics of the intervals between consecutive blocks.
+    fn stats(&self, block_interval: Duration) -> BlockIntervalStats {
+        let block_interval_ms = (block_interval.as_millis() as u64).max(1);
+        let intervals: Vec<u64> = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .filter(|(parent, block)| !block.reorged && block.number == parent.number + 1)
+            .map(|(parent, block)| block.wall_time_ms.saturating_sub(parent.wall_time_ms))
+            .collect();
+        if intervals.is_empty() {
+            return BlockIntervalStats::default();
+        }
+
+        let samples = intervals.len() as f64;
+        let mean = intervals.iter().sum::<u64>() as f64 / samples;
+        let variance =
+            intervals.iter().map(|&interval| (interval as f64 - mean).powi(2)).sum::<f64>()
+                / samples;
+        // an interval spans the slots of the expected number of blocks, rounded to the nearest
+        // slot, so jitter is not counted as a missed slot
+        let missed_slots = intervals
+            .iter()
+            .map(|&interval| {
+                ((interval + block_interval_ms / 2) / block_interval_ms).saturating_sub(1)
+            })
+            .sum();
+
+        BlockIntervalStats {
+            samples: intervals.len() as u64,
+            mean_interval_ms: mean,
+            interval_variance_ms2: variance,
+            max_interval_ms: intervals.iter().copied().max().unwrap_or_default(),
+            missed_slots,
+        }
+    }
 }
 
 /// Time data about the last block.
@@ -97,11 +415,117 @@ pub struct BlockTimeData {
     block_timestamp: u64,
 }
 
-/// Returns the current unix epoch in milliseconds.
-pub fn unix_epoch_ms() -> u64 {
-    use std::time::SystemTime;
-    let now = SystemTime::now();
-    now.duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap_or_else(|err| panic!("Current time {now:?} is invalid: {err:?}"))
-        .as_millis() as u64
+/// Loads the persisted [`BlockTimeData`] from the given file, if it exists and is valid.
+fn load_block_time(path: &Path) -> Option<BlockTimeData> {
+    let data = std::fs::read(path).ok()?;
+    serde_json::from_slice(&data)
+        .inspect_err(
+            |err| debug!(target: "rpc::walltime", ?err, ?path, "Invalid persisted block time"),
+        )
+        .ok()
+}
+
+/// Persists the [`BlockTimeData`] to the given file.
+async fn persist_block_time(path: &Path, data: BlockTimeData) -> std::io::Result<()> {
+    // write to a temporary file first, so the persisted data is never partially written
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec(&data)?).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odyssey_common::MockClock;
+
+    #[tokio::test]
+    async fn initializes_from_latest_block() {
+        let clock = Arc::new(MockClock::new(10_000));
+        let walltime = OdysseyWallTime::spawn_with_config(
+            futures::stream::pending(),
+            WallTimeConfig { clock, latest_block_timestamp: Some(9), ..Default::default() },
+        );
+
+        let data = walltime.get_timedata().await.unwrap();
+        assert_eq!(
+            data,
+            WallTimeData {
+                current_wall_time_ms: 10_000,
+                last_block_wall_time_ms: 9_000,
+                last_block_timestamp: 9
+            }
+        );
+    }
+
+    #[test]
+    fn computes_block_interval_stats() {
+        let mut history = BlockHistory::new(4);
+        assert_eq!(history.stats(DEFAULT_BLOCK_INTERVAL), BlockIntervalStats::default());
+
+        for (number, wall_time_ms) in [(1, 0), (2, 2_000), (3, 4_100), (4, 9_900)] {
+            history.push(BlockSample { number, wall_time_ms, reorged: false });
+        }
+        let stats = history.stats(DEFAULT_BLOCK_INTERVAL);
+        assert_eq!(stats.samples, 3);
+        assert_eq!(stats.mean_interval_ms, 3_300.0);
+        assert_eq!(stats.max_interval_ms, 5_800);
+        // the last block arrived two slots late
+        assert_eq!(stats.missed_slots, 2);
+
+        // a reorg replaces the reorged blocks, and the oldest block is evicted
+        history.push(BlockSample { number: 4, wall_time_ms: 6_000, reorged: false });
+        history.push(BlockSample { number: 5, wall_time_ms: 8_000, reorged: false });
+        let stats = history.stats(DEFAULT_BLOCK_INTERVAL);
+        assert_eq!(stats.samples, 3);
+        assert_eq!(stats.max_interval_ms, 2_100);
+        assert_eq!(stats.missed_slots, 0);
+    }
+
+    #[test]
+    fn skips_reorged_slots() {
+        let mut history = BlockHistory::new(8);
+        for (number, wall_time_ms) in [(1, 0), (2, 2_000), (3, 4_000)] {
+            history.push(BlockSample { number, wall_time_ms, reorged: false });
+        }
+
+        // blocks 2 and 3 are replaced by a new block 2, whose slot was already counted
+        history.revert(2);
+        history.push(BlockSample { number: 2, wall_time_ms: 5_000, reorged: true });
+        let stats = history.stats(DEFAULT_BLOCK_INTERVAL);
+        assert_eq!(stats.samples, 0);
+        assert_eq!(stats.missed_slots, 0);
+
+        // the following blocks are counted as usual
+        history.push(BlockSample { number: 3, wall_time_ms: 7_000, reorged: false });
+        let stats = history.stats(DEFAULT_BLOCK_INTERVAL);
+        assert_eq!(stats.samples, 1);
+        assert_eq!(stats.max_interval_ms, 2_000);
+    }
+
+    #[tokio::test]
+    async fn initializes_from_persisted_block_time() {
+        let path =
+            std::env::temp_dir().join(format!("odyssey-walltime-{}.json", std::process::id()));
+        let persisted = BlockTimeData { wall_time_ms: 9_500, block_timestamp: 9 };
+        persist_block_time(&path, persisted).await.unwrap();
+
+        // the persisted block time is only used if it belongs to the latest block
+        let config = WallTimeConfig {
+            latest_block_timestamp: Some(9),
+            persistence_path: Some(path.clone()),
+            ..Default::default()
+        };
+        assert_eq!(config.initial_block_time(), Some(persisted));
+
+        let config = WallTimeConfig { latest_block_timestamp: Some(11), ..config };
+        assert_eq!(
+            config.initial_block_time(),
+            Some(BlockTimeData { wall_time_ms: 11_000, block_timestamp: 11 })
+        );
+
+        let config = WallTimeConfig { latest_block_timestamp: None, ..config };
+        assert_eq!(config.initial_block_time(), Some(persisted));
+
+        std::fs::remove_file(path).unwrap();
+    }
 }